@@ -0,0 +1,18 @@
+//! Decode and evaluate arbitrary bytes as a compiled `AST`.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rhai::{Dynamic, Engine};
+
+fuzz_target!(|data: &[u8]| {
+    let mut engine = Engine::new();
+
+    engine.set_max_operations(10_000);
+    engine.set_max_call_levels(32);
+    engine.set_max_string_size(1_000);
+    engine.set_max_array_size(100);
+    engine.set_max_map_size(100);
+
+    if let Ok(ast) = engine.ast_from_bytes(data) {
+        let _ = engine.eval_ast::<Dynamic>(&ast);
+    }
+});
@@ -0,0 +1,31 @@
+//! Evaluate arbitrary text under tight resource limits.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rhai::{Dynamic, Engine, Scope, INT};
+use std::time::Duration;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(script) = std::str::from_utf8(data) {
+        let mut engine = Engine::new();
+
+        engine.set_max_operations(10_000);
+        engine.set_max_duration(Duration::from_secs(1));
+        engine.set_max_call_levels(32);
+        engine.set_max_expr_depth(32);
+        engine.set_max_stmt_depth(32);
+        engine.set_max_modules(4);
+        engine.set_max_string_size(1_000);
+        engine.set_max_array_size(100);
+        engine.set_max_map_size(100);
+        engine.set_max_data_size(100_000);
+        engine.set_max_eval_operations(1_000);
+
+        // Do not touch the file system
+        engine.set_module_resolver(None::<rhai::module_resolvers::StaticModuleResolver>);
+
+        let mut scope = Scope::new();
+        scope.push("x", 42 as INT);
+
+        let _ = engine.eval_with_scope::<Dynamic>(&mut scope, script);
+    }
+});
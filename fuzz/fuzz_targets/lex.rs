@@ -0,0 +1,14 @@
+//! Tokenize arbitrary text.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rhai::lex;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(script) = std::str::from_utf8(data) {
+        // The token stream ends with an endless run of EOF tokens
+        lex(&[script])
+            .take_while(|(token, _)| !token.is_eof())
+            .take(script.len() + 1)
+            .for_each(drop);
+    }
+});
@@ -0,0 +1,12 @@
+//! Compile arbitrary text, with all nesting limits in place.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rhai::Engine;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(script) = std::str::from_utf8(data) {
+        let engine = Engine::new();
+        let _ = engine.compile(script);
+        let _ = engine.compile_expression(script);
+    }
+});
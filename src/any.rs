@@ -15,11 +15,15 @@ use crate::engine::Array;
 #[cfg(not(feature = "no_object"))]
 use crate::engine::Map;
 
+#[cfg(not(feature = "no_function"))]
+use crate::engine::Generator;
+
 use crate::stdlib::{
     any::{type_name, Any, TypeId},
     boxed::Box,
     collections::HashMap,
     fmt,
+    ops::{Range, RangeInclusive},
     string::String,
     vec::Vec,
 };
@@ -219,6 +223,10 @@ impl Dynamic {
 
             #[cfg(not(feature = "no_std"))]
             Union::Variant(value) if value.is::<Instant>() => "timestamp",
+            Union::Variant(value) if value.is::<Range<INT>>() => "range",
+            Union::Variant(value) if value.is::<RangeInclusive<INT>>() => "range_inclusive",
+            #[cfg(not(feature = "no_function"))]
+            Union::Variant(value) if value.is::<Generator>() => "generator",
             Union::Variant(value) => (***value).type_name(),
         }
     }
@@ -243,7 +251,17 @@ impl fmt::Display for Dynamic {
 
             #[cfg(not(feature = "no_std"))]
             Union::Variant(value) if value.is::<Instant>() => write!(f, "<timestamp>"),
-            Union::Variant(_) => write!(f, "?"),
+            Union::Variant(value) => {
+                if let Some(range) = (***value).as_any().downcast_ref::<Range<INT>>() {
+                    write!(f, "{:?}", range)
+                } else if let Some(range) =
+                    (***value).as_any().downcast_ref::<RangeInclusive<INT>>()
+                {
+                    write!(f, "{:?}", range)
+                } else {
+                    write!(f, "?")
+                }
+            }
         }
     }
 }
@@ -267,7 +285,17 @@ impl fmt::Debug for Dynamic {
 
             #[cfg(not(feature = "no_std"))]
             Union::Variant(value) if value.is::<Instant>() => write!(f, "<timestamp>"),
-            Union::Variant(_) => write!(f, "<dynamic>"),
+            Union::Variant(value) => {
+                if let Some(range) = (***value).as_any().downcast_ref::<Range<INT>>() {
+                    write!(f, "{:?}", range)
+                } else if let Some(range) =
+                    (***value).as_any().downcast_ref::<RangeInclusive<INT>>()
+                {
+                    write!(f, "{:?}", range)
+                } else {
+                    write!(f, "<dynamic>")
+                }
+            }
         }
     }
 }
@@ -1,23 +1,28 @@
 //! Module that defines the extern API of `Engine`.
 
 use crate::any::{Dynamic, Variant};
-use crate::engine::{make_getter, make_setter, Engine, State, FUNC_INDEXER};
+use crate::engine::{make_getter, make_setter, Engine, State, Usage, FUNC_INDEXER};
 use crate::error::ParseError;
 use crate::fn_call::FuncArgs;
 use crate::fn_native::{
-    IteratorCallback, ObjectGetCallback, ObjectIndexerCallback, ObjectSetCallback,
+    shared_make_mut, IteratorCallback, ObjectGetCallback, ObjectIndexerCallback, ObjectSetCallback,
+    Shared,
 };
 use crate::fn_register::RegisterFn;
 use crate::optimize::{optimize_into_ast, OptimizationLevel};
 use crate::parser::{parse, parse_global_expr, AST};
 use crate::result::EvalAltResult;
 use crate::scope::Scope;
+use crate::serialize::deserialize_ast;
 use crate::token::{lex, Position};
 use crate::utils::StaticVec;
 
 #[cfg(not(feature = "no_object"))]
 use crate::engine::Map;
 
+#[cfg(not(feature = "no_function"))]
+use crate::engine::{Generator, GeneratorIter};
+
 use crate::stdlib::{
     any::{type_name, TypeId},
     boxed::Box,
@@ -117,14 +122,49 @@ impl Engine {
     #[cfg(not(feature = "no_object"))]
     pub fn register_type_with_name<T: Variant + Clone>(&mut self, name: &str) {
         // Add the pretty-print type name into the map
-        self.type_names
+        shared_make_mut(&mut self.type_names)
             .insert(type_name::<T>().to_string(), name.to_string());
     }
 
     /// Register an iterator adapter for a type with the `Engine`.
     /// This is an advanced feature.
     pub fn register_iterator<T: Variant + Clone, F: IteratorCallback>(&mut self, f: F) {
-        self.global_module.set_iter(TypeId::of::<T>(), Box::new(f));
+        shared_make_mut(&mut self.global_module).set_iter(TypeId::of::<T>(), Box::new(f));
+    }
+
+    /// Mark all registered functions of a particular name as pure or not.
+    ///
+    /// A pure function always returns the same result for the same arguments and has no side effects.
+    /// Under `OptimizationLevel::Full`, calls to pure functions with constant arguments are evaluated
+    /// during optimization and replaced by their results.  If such a call fails, it is left alone
+    /// so the error is raised when the script is run.
+    ///
+    /// Only functions already registered are affected.  Registered functions are not pure by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<rhai::EvalAltResult>> {
+    /// # #[cfg(not(feature = "no_optimize"))]
+    /// # {
+    /// use rhai::{Engine, RegisterFn, OptimizationLevel};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_optimization_level(OptimizationLevel::Full);
+    ///
+    /// engine.register_fn("double", |x: i64| x * 2);
+    /// engine.set_fn_pure("double", true);
+    ///
+    /// // 'double(21)' is replaced by '42' during compilation
+    /// let ast = engine.compile("double(21)")?;
+    ///
+    /// assert_eq!(engine.eval_ast::<i64>(&ast)?, 42);
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_fn_pure(&mut self, name: &str, pure: bool) {
+        shared_make_mut(&mut self.global_module).set_fns_pure_by_name(name, pure);
     }
 
     /// Register a getter function for a member of a registered type with the `Engine`.
@@ -443,13 +483,13 @@ impl Engine {
         scripts: &[&str],
         optimization_level: OptimizationLevel,
     ) -> Result<AST, Box<ParseError>> {
-        let stream = lex(scripts);
+        let stream = lex(scripts).disable_symbols(&self.disabled_symbols);
         parse(&mut stream.peekable(), self, scope, optimization_level)
     }
 
     /// Read the contents of a file into a string.
     #[cfg(not(feature = "no_std"))]
-    fn read_file(path: PathBuf) -> Result<String, Box<EvalAltResult>> {
+    pub(crate) fn read_file(path: PathBuf) -> Result<String, Box<EvalAltResult>> {
         let mut f = File::open(path.clone()).map_err(|err| {
             Box::new(EvalAltResult::ErrorReadingScriptFile(
                 path.clone(),
@@ -565,7 +605,7 @@ impl Engine {
 
         // Trims the JSON string and add a '#' in front
         let scripts = ["#", json.trim()];
-        let stream = lex(&scripts);
+        let stream = lex(&scripts).disable_symbols(&self.disabled_symbols);
         let ast = parse_global_expr(
             &mut stream.peekable(),
             self,
@@ -650,7 +690,7 @@ impl Engine {
         script: &str,
     ) -> Result<AST, Box<ParseError>> {
         let scripts = [script];
-        let stream = lex(&scripts);
+        let stream = lex(&scripts).disable_symbols(&self.disabled_symbols);
 
         {
             let mut peekable = stream.peekable();
@@ -658,6 +698,41 @@ impl Engine {
         }
     }
 
+    /// Decode an `AST` previously encoded by `AST::to_bytes`, rejecting any `AST` nested deeper
+    /// than scripts compiled under the nesting depth limits of this `Engine` can be.
+    ///
+    /// Use this instead of `AST::from_bytes`, which trusts the nesting depth recorded in the data,
+    /// to load compiled scripts from untrusted sources.
+    ///
+    /// Flat chains of operators and `else if` ladders are not limited in length by the parser,
+    /// so an `AST` with an extremely long one may be nested too deeply to load this way.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<rhai::EvalAltResult>> {
+    /// use rhai::Engine;
+    ///
+    /// let engine = Engine::new();
+    ///
+    /// let bytes = engine.compile("40 + 2")?.to_bytes();
+    /// let ast = engine.ast_from_bytes(&bytes)?;
+    ///
+    /// assert_eq!(engine.eval_ast::<i64>(&ast)?, 42);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ast_from_bytes(&self, bytes: &[u8]) -> Result<AST, Box<ParseError>> {
+        // Every nesting level of the parser may add a few levels of statement or expression,
+        // with some room left for operator chains and `else if` ladders
+        let max_depth = self
+            .max_expr_depth
+            .saturating_add(self.max_stmt_depth)
+            .saturating_mul(4);
+
+        deserialize_ast(bytes, Some(max_depth))
+    }
+
     /// Evaluate a script file.
     ///
     /// # Example
@@ -751,12 +826,42 @@ impl Engine {
         scope: &mut Scope,
         script: &str,
     ) -> Result<T, Box<EvalAltResult>> {
-        let ast = self.compile_with_scope_and_optimization_level(
+        self.eval_with_usage(scope, script).0
+    }
+
+    /// Evaluate a string with own scope, also returning the resources used.
+    ///
+    /// The `Usage` is returned even when evaluation fails, in which case it covers
+    /// all the work done up to the point of the error (none if the script fails to compile).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<rhai::EvalAltResult>> {
+    /// use rhai::{Engine, Scope};
+    ///
+    /// let engine = Engine::new();
+    ///
+    /// let (result, usage) = engine.eval_with_usage::<i64>(&mut Scope::new(), "let x = 40; x + 2");
+    ///
+    /// assert_eq!(result?, 42);
+    /// assert!(usage.operations > 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn eval_with_usage<T: Variant + Clone>(
+        &self,
+        scope: &mut Scope,
+        script: &str,
+    ) -> (Result<T, Box<EvalAltResult>>, Usage) {
+        match self.compile_with_scope_and_optimization_level(
             scope,
             &[script],
             self.optimization_level,
-        )?;
-        self.eval_ast_with_scope(scope, &ast)
+        ) {
+            Ok(ast) => self.eval_ast_with_usage(scope, &ast),
+            Err(err) => (Err(err.into()), Default::default()),
+        }
     }
 
     /// Evaluate a string containing an expression.
@@ -804,7 +909,7 @@ impl Engine {
         script: &str,
     ) -> Result<T, Box<EvalAltResult>> {
         let scripts = [script];
-        let stream = lex(&scripts);
+        let stream = lex(&scripts).disable_symbols(&self.disabled_symbols);
         // Since the AST will be thrown away afterwards, don't bother to optimize it
         let ast = parse_global_expr(&mut stream.peekable(), self, scope, OptimizationLevel::None)?;
         self.eval_ast_with_scope(scope, &ast)
@@ -866,26 +971,65 @@ impl Engine {
         scope: &mut Scope,
         ast: &AST,
     ) -> Result<T, Box<EvalAltResult>> {
-        let (result, _) = self.eval_ast_with_scope_raw(scope, ast)?;
+        self.eval_ast_with_usage(scope, ast).0
+    }
+
+    /// Evaluate an `AST` with own scope, also returning the resources used.
+    ///
+    /// The `Usage` is returned even when evaluation fails, in which case it covers
+    /// all the work done up to the point of the error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<rhai::EvalAltResult>> {
+    /// use rhai::{Engine, Scope};
+    ///
+    /// let engine = Engine::new();
+    ///
+    /// let ast = engine.compile("let x = 40; x + 2")?;
+    ///
+    /// let (result, usage) = engine.eval_ast_with_usage::<i64>(&mut Scope::new(), &ast);
+    ///
+    /// assert_eq!(result?, 42);
+    /// assert!(usage.operations > 0);
+    /// assert_eq!(usage.call_depth, 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn eval_ast_with_usage<T: Variant + Clone>(
+        &self,
+        scope: &mut Scope,
+        ast: &AST,
+    ) -> (Result<T, Box<EvalAltResult>>, Usage) {
+        let (result, usage) = self.eval_ast_with_scope_raw(scope, ast, Default::default());
 
-        let return_type = self.map_type_name(result.type_name());
+        let result = result.and_then(|result| {
+            let return_type = self.map_type_name(result.type_name());
 
-        return result.try_cast::<T>().ok_or_else(|| {
-            Box::new(EvalAltResult::ErrorMismatchOutputType(
-                return_type.into(),
-                Position::none(),
-            ))
+            result.try_cast::<T>().ok_or_else(|| {
+                Box::new(EvalAltResult::ErrorMismatchOutputType(
+                    return_type.into(),
+                    Position::none(),
+                ))
+            })
         });
+
+        (result, usage)
     }
 
+    /// Evaluate an `AST` with own scope, continuing from the resources already used in `usage`.
     pub(crate) fn eval_ast_with_scope_raw(
         &self,
         scope: &mut Scope,
         ast: &AST,
-    ) -> Result<(Dynamic, u64), Box<EvalAltResult>> {
+        usage: Usage,
+    ) -> (Result<Dynamic, Box<EvalAltResult>>, Usage) {
         let mut state = State::new(ast.fn_lib());
+        state.set_usage(&usage);
 
-        ast.statements()
+        let result = ast
+            .statements()
             .iter()
             .try_fold(().into(), |_, stmt| {
                 self.eval_stmt(scope, &mut state, stmt, 0)
@@ -893,8 +1037,9 @@ impl Engine {
             .or_else(|err| match *err {
                 EvalAltResult::Return(out, _) => Ok(out),
                 _ => Err(err),
-            })
-            .map(|v| (v, state.operations))
+            });
+
+        (result, state.usage())
     }
 
     /// Evaluate a file, but throw away the result and only return error (if any).
@@ -928,12 +1073,29 @@ impl Engine {
         scope: &mut Scope,
         script: &str,
     ) -> Result<(), Box<EvalAltResult>> {
+        self.consume_with_usage(scope, script).0
+    }
+
+    /// Evaluate a string with own scope, but throw away the result and only return error (if any),
+    /// together with the resources used.
+    ///
+    /// The `Usage` is returned even when evaluation fails, in which case it covers
+    /// all the work done up to the point of the error (none if the script fails to compile).
+    pub fn consume_with_usage(
+        &self,
+        scope: &mut Scope,
+        script: &str,
+    ) -> (Result<(), Box<EvalAltResult>>, Usage) {
         let scripts = [script];
-        let stream = lex(&scripts);
+        let stream = lex(&scripts).disable_symbols(&self.disabled_symbols);
 
         // Since the AST will be thrown away afterwards, don't bother to optimize it
-        let ast = parse(&mut stream.peekable(), self, scope, OptimizationLevel::None)?;
-        self.consume_ast_with_scope(scope, &ast)
+        let ast = parse(&mut stream.peekable(), self, scope, OptimizationLevel::None);
+
+        match ast {
+            Ok(ast) => self.consume_ast_with_usage(scope, &ast),
+            Err(err) => (Err(err.into()), Default::default()),
+        }
     }
 
     /// Evaluate an AST, but throw away the result and only return error (if any).
@@ -949,20 +1111,21 @@ impl Engine {
         scope: &mut Scope,
         ast: &AST,
     ) -> Result<(), Box<EvalAltResult>> {
-        let mut state = State::new(ast.fn_lib());
+        self.consume_ast_with_usage(scope, ast).0
+    }
 
-        ast.statements()
-            .iter()
-            .try_fold(().into(), |_, stmt| {
-                self.eval_stmt(scope, &mut state, stmt, 0)
-            })
-            .map_or_else(
-                |err| match *err {
-                    EvalAltResult::Return(_, _) => Ok(()),
-                    err => Err(Box::new(err)),
-                },
-                |_| Ok(()),
-            )
+    /// Evaluate an `AST` with own scope, but throw away the result and only return error (if any),
+    /// together with the resources used.
+    ///
+    /// The `Usage` is returned even when evaluation fails, in which case it covers
+    /// all the work done up to the point of the error.
+    pub fn consume_ast_with_usage(
+        &self,
+        scope: &mut Scope,
+        ast: &AST,
+    ) -> (Result<(), Box<EvalAltResult>>, Usage) {
+        let (result, usage) = self.eval_ast_with_scope_raw(scope, ast, Default::default());
+        (result.map(|_| ()), usage)
     }
 
     /// Call a script function defined in an `AST` with multiple arguments.
@@ -1008,28 +1171,112 @@ impl Engine {
         name: &str,
         args: A,
     ) -> Result<T, Box<EvalAltResult>> {
+        self.call_fn_with_usage(scope, ast, name, args).0
+    }
+
+    /// Call a script function defined in an `AST` with multiple arguments, also returning the resources used.
+    ///
+    /// The `Usage` is returned even when the call fails, in which case it covers
+    /// all the work done up to the point of the error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<rhai::EvalAltResult>> {
+    /// # #[cfg(not(feature = "no_function"))]
+    /// # {
+    /// use rhai::{Engine, Scope};
+    ///
+    /// let engine = Engine::new();
+    ///
+    /// let ast = engine.compile("fn add(x, y) { x + y }")?;
+    ///
+    /// let (result, usage) = engine.call_fn_with_usage::<_, i64>(&mut Scope::new(), &ast, "add", (40_i64, 2_i64));
+    ///
+    /// assert_eq!(result?, 42);
+    /// assert_eq!(usage.call_depth, 1);
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(feature = "no_function"))]
+    pub fn call_fn_with_usage<A: FuncArgs, T: Variant + Clone>(
+        &self,
+        scope: &mut Scope,
+        ast: &AST,
+        name: &str,
+        args: A,
+    ) -> (Result<T, Box<EvalAltResult>>, Usage) {
         let mut arg_values = args.into_vec();
         let mut args: StaticVec<_> = arg_values.iter_mut().collect();
         let fn_lib = ast.fn_lib();
         let pos = Position::none();
 
-        let fn_def = fn_lib
-            .get_function_by_signature(name, args.len(), true)
-            .ok_or_else(|| Box::new(EvalAltResult::ErrorFunctionNotFound(name.into(), pos)))?;
+        let fn_def = match fn_lib.get_function_by_signature(name, args.len(), true) {
+            Some(fn_def) => fn_def,
+            None => {
+                let err = EvalAltResult::ErrorFunctionNotFound(name.into(), pos);
+                return (Err(Box::new(err)), Default::default());
+            }
+        };
 
-        let state = State::new(fn_lib);
+        let mut state = State::new(fn_lib);
         let args = args.as_mut();
 
-        let (result, _) = self.call_script_fn(Some(scope), state, name, fn_def, args, pos, 0)?;
+        let result = self
+            .call_script_fn(Some(scope), &mut state, name, fn_def, args, pos, 0)
+            .and_then(|result| {
+                let return_type = self.map_type_name(result.type_name());
 
-        let return_type = self.map_type_name(result.type_name());
+                result.try_cast().ok_or_else(|| {
+                    Box::new(EvalAltResult::ErrorMismatchOutputType(
+                        return_type.into(),
+                        pos,
+                    ))
+                })
+            });
 
-        return result.try_cast().ok_or_else(|| {
-            Box::new(EvalAltResult::ErrorMismatchOutputType(
-                return_type.into(),
-                pos,
-            ))
-        });
+        (result, state.usage())
+    }
+
+    /// Iterate the values yielded by a generator returned from a script.
+    ///
+    /// The body of the generator function runs up to each `yield` in turn as the iterator is advanced,
+    /// with the script-defined functions in the `AST` that created the generator.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<rhai::EvalAltResult>> {
+    /// # #[cfg(not(feature = "no_function"))]
+    /// # {
+    /// use rhai::{Engine, Generator, Scope};
+    ///
+    /// let engine = Engine::new();
+    ///
+    /// let ast = engine.compile(r"
+    ///     fn naturals(from) {
+    ///         let n = from;
+    ///         loop { yield n; n += 1; }
+    ///     }
+    /// ")?;
+    ///
+    /// let generator: Generator = engine.call_fn(&mut Scope::new(), &ast, "naturals", (1_i64,))?;
+    ///
+    /// let values = engine
+    ///     .iter_generator(&ast, generator)
+    ///     .take(3)
+    ///     .map(|v| v.map(|v| v.cast::<i64>()))
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    ///
+    /// assert_eq!(values, [1, 2, 3]);
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(feature = "no_function"))]
+    pub fn iter_generator<'e>(&'e self, ast: &'e AST, generator: Generator) -> GeneratorIter<'e> {
+        GeneratorIter::new(self, ast.fn_lib(), generator)
     }
 
     /// Optimize the `AST` with constants defined in an external Scope.
@@ -1096,7 +1343,7 @@ impl Engine {
     /// ```
     #[cfg(feature = "sync")]
     pub fn on_progress(&mut self, callback: impl Fn(u64) -> bool + Send + Sync + 'static) {
-        self.progress = Some(Box::new(callback));
+        self.progress = Some(Shared::new(callback));
     }
 
     /// Register a callback for script evaluation progress.
@@ -1135,7 +1382,7 @@ impl Engine {
     /// ```
     #[cfg(not(feature = "sync"))]
     pub fn on_progress(&mut self, callback: impl Fn(u64) -> bool + 'static) {
-        self.progress = Some(Box::new(callback));
+        self.progress = Some(Shared::new(callback));
     }
 
     /// Override default action of `print` (print to stdout using `println!`)
@@ -1164,7 +1411,7 @@ impl Engine {
     /// ```
     #[cfg(feature = "sync")]
     pub fn on_print(&mut self, callback: impl Fn(&str) + Send + Sync + 'static) {
-        self.print = Box::new(callback);
+        self.print = Shared::new(callback);
     }
     /// Override default action of `print` (print to stdout using `println!`)
     ///
@@ -1192,7 +1439,7 @@ impl Engine {
     /// ```
     #[cfg(not(feature = "sync"))]
     pub fn on_print(&mut self, callback: impl Fn(&str) + 'static) {
-        self.print = Box::new(callback);
+        self.print = Shared::new(callback);
     }
 
     /// Override default action of `debug` (print to stdout using `println!`)
@@ -1221,7 +1468,7 @@ impl Engine {
     /// ```
     #[cfg(feature = "sync")]
     pub fn on_debug(&mut self, callback: impl Fn(&str) + Send + Sync + 'static) {
-        self.debug = Box::new(callback);
+        self.debug = Shared::new(callback);
     }
     /// Override default action of `debug` (print to stdout using `println!`)
     ///
@@ -1249,6 +1496,6 @@ impl Engine {
     /// ```
     #[cfg(not(feature = "sync"))]
     pub fn on_debug(&mut self, callback: impl Fn(&str) + 'static) {
-        self.debug = Box::new(callback);
+        self.debug = Shared::new(callback);
     }
 }
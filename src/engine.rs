@@ -3,17 +3,25 @@
 use crate::any::{Dynamic, Union};
 use crate::calc_fn_hash;
 use crate::error::ParseErrorType;
-use crate::fn_native::{FnCallArgs, NativeFunctionABI, PrintCallback, ProgressCallback};
+use crate::fn_native::{
+    CallContext, FnCallArgs, NativeFunctionABI, PrintCallback, ProgressCallback, Shared,
+    SharedNativeFunction,
+};
 use crate::module::Module;
 use crate::optimize::OptimizationLevel;
 use crate::packages::{CorePackage, Package, PackageLibrary, PackagesCollection, StandardPackage};
-use crate::parser::{Expr, FnAccess, FnDef, ReturnType, SharedFnDef, Stmt, AST};
+use crate::parser::{
+    Expr, FnAccess, FnDef, Pattern, ReturnType, SharedFnDef, Stmt, Type, AST, INT,
+};
 use crate::r#unsafe::unsafe_cast_var_name_to_lifetime;
 use crate::result::EvalAltResult;
 use crate::scope::{EntryType as ScopeEntryType, Scope};
 use crate::token::Position;
 use crate::utils::{StaticVec, EMPTY_TYPE_ID};
 
+#[cfg(feature = "internals")]
+use crate::optimize::OptimizerPass;
+
 #[cfg(not(feature = "no_module"))]
 use crate::module::{resolvers, ModuleRef, ModuleResolver};
 
@@ -23,18 +31,26 @@ use crate::parser::ModuleRef;
 use crate::stdlib::{
     any::TypeId,
     boxed::Box,
-    collections::HashMap,
-    format,
+    collections::{HashMap, HashSet},
+    fmt, format,
+    hash::{Hash, Hasher},
     iter::{empty, once, repeat},
     mem,
     num::{NonZeroU64, NonZeroUsize},
-    ops::{Deref, DerefMut},
+    ops::{Deref, DerefMut, Range, RangeInclusive},
     rc::Rc,
     string::{String, ToString},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     vec::Vec,
 };
 
+#[cfg(not(feature = "unchecked"))]
+#[cfg(not(feature = "no_std"))]
+use crate::stdlib::time::{Duration, Instant};
+
 /// Variable-sized array of `Dynamic` values.
 ///
 /// Not available under the `no_index` feature.
@@ -58,6 +74,18 @@ pub const MAX_CALL_STACK_DEPTH: usize = 256;
 #[cfg(feature = "unchecked")]
 pub const MAX_CALL_STACK_DEPTH: usize = usize::MAX;
 
+#[cfg(not(feature = "unchecked"))]
+pub const MAX_EXPR_DEPTH: usize = 64;
+
+#[cfg(feature = "unchecked")]
+pub const MAX_EXPR_DEPTH: usize = usize::MAX;
+
+#[cfg(not(feature = "unchecked"))]
+pub const MAX_STMT_DEPTH: usize = 64;
+
+#[cfg(feature = "unchecked")]
+pub const MAX_STMT_DEPTH: usize = usize::MAX;
+
 pub const KEYWORD_PRINT: &str = "print";
 pub const KEYWORD_DEBUG: &str = "debug";
 pub const KEYWORD_TYPE_OF: &str = "type_of";
@@ -67,6 +95,20 @@ pub const FUNC_GETTER: &str = "get$";
 pub const FUNC_SETTER: &str = "set$";
 pub const FUNC_INDEXER: &str = "$index$";
 
+/// How calls to `eval` are handled by an `Engine`.
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub enum EvalMode {
+    /// `eval` runs in the caller's scope and can call script-defined functions.
+    /// Variables defined in the evaluated script stay in the caller's scope.  This is the default.
+    Shared,
+    /// `eval` runs in a new, empty scope without access to the caller's variables
+    /// or script-defined functions.  All changes are discarded when the evaluated script ends.
+    Isolated,
+    /// `eval` is not available, and calling it fails with `EvalAltResult::ErrorFunctionNotFound`
+    /// unless a function named `eval` is registered or defined in script.
+    Disabled,
+}
+
 /// A type that encapsulates a mutation target for an expression with side effects.
 enum Target<'a> {
     /// The target is a mutable reference to a `Dynamic` value somewhere.
@@ -76,6 +118,9 @@ enum Target<'a> {
     /// The target is a character inside a String.
     /// This is necessary because directly pointing to a char inside a String is impossible.
     StringChar(&'a mut Dynamic, usize, Dynamic),
+    /// The target is a slice of an Array, held as a copy of the items in the slice.
+    #[cfg(not(feature = "no_index"))]
+    ArraySlice(&'a mut Dynamic, Range<usize>, Dynamic),
 }
 
 impl Target<'_> {
@@ -84,6 +129,8 @@ impl Target<'_> {
         match self {
             Target::Ref(_) => true,
             Target::Value(_) | Target::StringChar(_, _, _) => false,
+            #[cfg(not(feature = "no_index"))]
+            Target::ArraySlice(_, _, _) => false,
         }
     }
 
@@ -93,6 +140,8 @@ impl Target<'_> {
             Target::Ref(r) => r.clone(),        // Referenced value is cloned
             Target::Value(v) => v,              // Owned value is simply taken
             Target::StringChar(_, _, ch) => ch, // Character is taken
+            #[cfg(not(feature = "no_index"))]
+            Target::ArraySlice(_, _, items) => items, // Slice is taken
         }
     }
 
@@ -102,6 +151,8 @@ impl Target<'_> {
             Target::Ref(r) => *r,
             Target::Value(ref mut r) => r,
             Target::StringChar(_, _, ref mut r) => r,
+            #[cfg(not(feature = "no_index"))]
+            Target::ArraySlice(_, _, ref mut r) => r,
         }
     }
 
@@ -128,7 +179,28 @@ impl Target<'_> {
                     chars.iter().for_each(|&ch| s.push(ch));
                 }
             }
-            _ => unreachable!(),
+            Target::StringChar(_, _, _) => {
+                return Err(Box::new(EvalAltResult::ErrorInternal(
+                    "character target is not a string".into(),
+                    pos,
+                )))
+            }
+            #[cfg(not(feature = "no_index"))]
+            Target::ArraySlice(Dynamic(Union::Array(arr)), range, _) => {
+                // Replace the items in the slice
+                let items = new_val
+                    .try_cast::<Array>()
+                    .ok_or_else(|| EvalAltResult::ErrorArrayMismatch(pos))?;
+
+                arr.splice(range.clone(), items);
+            }
+            #[cfg(not(feature = "no_index"))]
+            Target::ArraySlice(_, _, _) => {
+                return Err(Box::new(EvalAltResult::ErrorInternal(
+                    "slice target is not an array".into(),
+                    pos,
+                )))
+            }
         }
 
         Ok(())
@@ -146,13 +218,77 @@ impl<T: Into<Dynamic>> From<T> for Target<'_> {
     }
 }
 
+/// The resolved target of a function call.
+#[derive(Clone)]
+pub enum CallTarget<'a> {
+    /// A script-defined function.
+    Script(&'a FnDef),
+    /// A native Rust function, either registered with the `Engine` or loaded from a package.
+    Native(SharedNativeFunction),
+    /// No function matches the call.
+    NotFound,
+}
+
+impl fmt::Debug for CallTarget<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Script(fn_def) => write!(f, "<script fn {}>", fn_def.name),
+            Self::Native(_) => write!(f, "<native fn>"),
+            Self::NotFound => write!(f, "<not found>"),
+        }
+    }
+}
+
+/// Maximum number of arrays and object maps whose estimated sizes are kept in a `State`.
+#[cfg(not(feature = "unchecked"))]
+const MAX_DATA_SIZES: usize = 8;
+
+/// Estimated total size of an array or object map that has been changed in place.
+///
+/// Walking a large value after every change to check `Engine::set_max_data_size` would make
+/// building it up piece by piece take quadratic time.  Instead, the size of the data added
+/// is added to the estimate, and the value is walked again only when the estimate exceeds
+/// the limit or has doubled since the last walk.
+#[cfg(not(feature = "unchecked"))]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DataSize {
+    /// Identity of the value - the addresses of its storage, its capacity and its length.
+    key: (usize, usize, usize, usize),
+    /// Estimated total size in bytes.
+    size: usize,
+    /// Exact total size in bytes when the value was last walked.
+    measured: usize,
+}
+
+/// Get the identity of an array or object map for its `DataSize`.
+#[cfg(not(feature = "unchecked"))]
+fn data_size_key(value: &Dynamic) -> Option<(usize, usize, usize, usize)> {
+    match &value.0 {
+        #[cfg(not(feature = "no_index"))]
+        Union::Array(arr) => Some((
+            arr.as_ref() as *const Array as usize,
+            arr.as_ptr() as usize,
+            arr.capacity(),
+            arr.len(),
+        )),
+        #[cfg(not(feature = "no_object"))]
+        Union::Map(map) => Some((
+            map.as_ref() as *const Map as usize,
+            0,
+            map.capacity(),
+            map.len(),
+        )),
+        _ => None,
+    }
+}
+
 /// A type that holds all the current states of the Engine.
 ///
 /// # Safety
 ///
 /// This type uses some unsafe code, mainly for avoiding cloning of local variable names via
 /// direct lifetime casting.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct State<'a> {
     /// Global script-defined functions.
     pub fn_lib: &'a FunctionsLib,
@@ -171,6 +307,33 @@ pub struct State<'a> {
 
     /// Number of modules loaded.
     pub modules: u64,
+
+    /// Maximum depth of nested calls to script-defined functions reached.
+    pub call_depth: usize,
+
+    /// Time after which the script run is terminated, set when the first operation is performed.
+    #[cfg(not(feature = "unchecked"))]
+    #[cfg(not(feature = "no_std"))]
+    pub deadline: Option<Instant>,
+
+    /// Number of operations after which the current `eval` is terminated, if it has its own budget.
+    #[cfg(not(feature = "unchecked"))]
+    pub eval_operations_limit: Option<u64>,
+
+    /// Cache of resolved function calls, keyed by the pair of function hashes of the call.
+    ///
+    /// The native function hash is calculated from the actual argument types, so a call made
+    /// with a different set of argument types never hits a stale entry.
+    pub fn_resolution_cache: HashMap<(u64, u64), CallTarget<'a>>,
+
+    /// Estimated sizes of the arrays and object maps most recently changed in place, latest first.
+    #[cfg(not(feature = "unchecked"))]
+    pub(crate) data_sizes: Vec<DataSize>,
+
+    /// Number of bytes of data added to the value at the root of the dot/index chain being evaluated,
+    /// or `None` if it is not known.
+    #[cfg(not(feature = "unchecked"))]
+    pub(crate) data_growth: Option<usize>,
 }
 
 impl<'a> State<'a> {
@@ -182,15 +345,119 @@ impl<'a> State<'a> {
             scope_level: 0,
             operations: 0,
             modules: 0,
+            call_depth: 0,
+            #[cfg(not(feature = "unchecked"))]
+            #[cfg(not(feature = "no_std"))]
+            deadline: None,
+            #[cfg(not(feature = "unchecked"))]
+            eval_operations_limit: None,
+            fn_resolution_cache: Default::default(),
+            #[cfg(not(feature = "unchecked"))]
+            data_sizes: Vec::new(),
+            #[cfg(not(feature = "unchecked"))]
+            data_growth: None,
+        }
+    }
+    /// Get a report of the resources used so far.
+    pub fn usage(&self) -> Usage {
+        Usage {
+            operations: self.operations,
+            call_depth: self.call_depth,
+            modules: self.modules,
+            #[cfg(not(feature = "unchecked"))]
+            #[cfg(not(feature = "no_std"))]
+            deadline: self.deadline,
+            #[cfg(not(feature = "unchecked"))]
+            eval_operations_limit: self.eval_operations_limit,
+        }
+    }
+    /// Continue from the resources used (and the limits) in a `Usage`.
+    pub(crate) fn set_usage(&mut self, usage: &Usage) {
+        self.operations = usage.operations;
+        self.call_depth = usage.call_depth;
+        self.modules = usage.modules;
+
+        #[cfg(not(feature = "unchecked"))]
+        {
+            #[cfg(not(feature = "no_std"))]
+            {
+                self.deadline = usage.deadline;
+            }
+            self.eval_operations_limit = usage.eval_operations_limit;
         }
     }
+    /// Add to the data added to the value at the root of the dot/index chain being evaluated.
+    #[cfg(not(feature = "unchecked"))]
+    pub(crate) fn add_data_growth(&mut self, size: Option<usize>) {
+        self.data_growth = match (self.data_growth, size) {
+            (Some(growth), Some(size)) => Some(growth.saturating_add(size)),
+            _ => None,
+        };
+    }
+    /// Remove the estimated size of an array or object map, if any.
+    #[cfg(not(feature = "unchecked"))]
+    pub(crate) fn take_data_size(&mut self, value: &Dynamic) -> Option<DataSize> {
+        let key = data_size_key(value)?;
+        let index = self.data_sizes.iter().position(|entry| entry.key == key)?;
+        Some(self.data_sizes.remove(index))
+    }
+    /// Keep the estimated size of an array or object map, forgetting the oldest one if there are too many.
+    #[cfg(not(feature = "unchecked"))]
+    pub(crate) fn keep_data_size(&mut self, entry: DataSize) {
+        self.data_sizes.insert(0, entry);
+        self.data_sizes.truncate(MAX_DATA_SIZES);
+    }
     /// Does a certain script-defined function exist in the `State`?
     pub fn has_function(&self, hash: u64) -> bool {
         self.fn_lib.contains_key(&hash)
     }
     /// Get a script-defined function definition from the `State`.
-    pub fn get_function(&self, hash: u64) -> Option<&FnDef> {
-        self.fn_lib.get(&hash).map(|fn_def| fn_def.as_ref())
+    pub fn get_function(&self, hash: u64) -> Option<&'a FnDef> {
+        let fn_lib: &'a FunctionsLib = self.fn_lib;
+        fn_lib.get_function(hash)
+    }
+    /// Resolve a function call to its target, consulting the resolution cache first.
+    ///
+    /// Script-defined functions take precedence over native Rust functions, which in turn take
+    /// precedence over script-defined functions that need default values for some parameters.
+    pub fn resolve_fn(
+        &mut self,
+        engine: &Engine,
+        fn_name: &str,
+        num_args: usize,
+        hashes: (u64, u64),
+    ) -> CallTarget<'a> {
+        if let Some(target) = self.fn_resolution_cache.get(&hashes) {
+            return target.clone();
+        }
+
+        let fn_lib: &'a FunctionsLib = self.fn_lib;
+
+        let target = if hashes.1 > 0 {
+            self.get_function(hashes.1).map(CallTarget::Script)
+        } else {
+            None
+        }
+        .or_else(|| {
+            engine
+                .global_module
+                .get_shared_fn(hashes.0)
+                .or_else(|| engine.packages.get_shared_fn(hashes.0))
+                .map(CallTarget::Native)
+        })
+        .or_else(|| {
+            if hashes.1 > 0 {
+                fn_lib
+                    .get_function_by_signature(fn_name, num_args, false)
+                    .map(CallTarget::Script)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(CallTarget::NotFound);
+
+        self.fn_resolution_cache.insert(hashes, target.clone());
+        target
     }
 }
 
@@ -238,7 +505,12 @@ impl FunctionsLib {
     pub fn get_function(&self, hash_fn_def: u64) -> Option<&FnDef> {
         self.get(&hash_fn_def).map(|fn_def| fn_def.as_ref())
     }
-    /// Get a function definition from the `FunctionsLib`.
+    /// Get a function definition from the `FunctionsLib` that can be called with a certain
+    /// number of arguments.
+    ///
+    /// A function taking exactly that number of parameters is preferred.  Otherwise, a function
+    /// is matched if default values are available for all the parameters not passed, or if it has
+    /// a rest parameter to collect all extra arguments.
     pub fn get_function_by_signature(
         &self,
         name: &str,
@@ -247,7 +519,12 @@ impl FunctionsLib {
     ) -> Option<&FnDef> {
         // Qualifiers (none) + function name + placeholders (one for each parameter).
         let hash_fn_def = calc_fn_hash(empty(), name, repeat(EMPTY_TYPE_ID()).take(params));
-        let fn_def = self.get_function(hash_fn_def);
+        let fn_def = self.get_function(hash_fn_def).or_else(|| {
+            self.values()
+                .map(|fn_def| fn_def.as_ref())
+                .filter(|fn_def| fn_def.name == name && fn_def.accepts_args(params))
+                .min_by_key(|fn_def| (fn_def.rest_param.is_some(), fn_def.params.len()))
+        });
 
         match fn_def.as_ref().map(|f| f.access) {
             None => None,
@@ -298,6 +575,212 @@ impl DerefMut for FunctionsLib {
     }
 }
 
+/// A generator, returned by calling a script-defined function that contains `yield` statements.
+///
+/// The body of the function does not run until the generator is iterated by a `for` loop,
+/// which then runs it up to each `yield` in turn.  Iterating a generator again runs the body again
+/// from the beginning.
+#[cfg(not(feature = "no_function"))]
+#[derive(Debug, Clone)]
+pub struct Generator {
+    /// The generator function.
+    fn_def: SharedFnDef,
+    /// Parameters of the function, bound to the arguments of the call.
+    params: Scope<'static>,
+    /// Position of the function call.
+    pos: Position,
+}
+
+#[cfg(not(feature = "no_function"))]
+impl Generator {
+    /// Start running the body of the generator function.
+    fn start(self) -> GeneratorRun {
+        GeneratorRun {
+            fn_def: self.fn_def,
+            scope: self.params,
+            frames: Vec::new(),
+            pos: self.pos,
+            always_search: false,
+            finished: false,
+        }
+    }
+}
+
+/// An iterator over the values yielded by a `Generator`, returned by `Engine::iter_generator`.
+///
+/// Each item runs the body of the generator function up to the next `yield`.  The iterator ends
+/// when the body finishes running or after an error.
+#[cfg(not(feature = "no_function"))]
+pub struct GeneratorIter<'e> {
+    /// The `Engine` running the generator.
+    engine: &'e Engine,
+    /// Evaluation state, with the script-defined functions the generator may call.
+    state: State<'e>,
+    /// The running body of the generator function.
+    run: GeneratorRun,
+}
+
+#[cfg(not(feature = "no_function"))]
+impl<'e> GeneratorIter<'e> {
+    /// Create a new `GeneratorIter` running a generator with the functions of a `FunctionsLib`.
+    pub(crate) fn new(engine: &'e Engine, fn_lib: &'e FunctionsLib, generator: Generator) -> Self {
+        Self {
+            engine,
+            state: State::new(fn_lib),
+            run: generator.start(),
+        }
+    }
+}
+
+#[cfg(not(feature = "no_function"))]
+impl Iterator for GeneratorIter<'_> {
+    type Item = Result<Dynamic, Box<EvalAltResult>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.engine
+            .resume_generator(&mut self.state, &mut self.run, 0)
+            .transpose()
+    }
+}
+
+/// The body of a generator function which is running, suspended between `yield` statements.
+#[cfg(not(feature = "no_function"))]
+struct GeneratorRun {
+    /// The generator function.
+    fn_def: SharedFnDef,
+    /// Variables of the function.
+    scope: Scope<'static>,
+    /// Where the function body is suspended, with one frame for each statement nested in the
+    /// statement before it, starting from the function body and ending with a `yield` statement.
+    frames: Vec<GeneratorFrame>,
+    /// Position of the function call.
+    pos: Position,
+    /// Must variables be searched by name, e.g. after running `eval` in the function body?
+    always_search: bool,
+    /// Has the function body finished running?
+    finished: bool,
+}
+
+/// A statement in which the body of a generator function is suspended.
+#[cfg(not(feature = "no_function"))]
+enum GeneratorFrame {
+    /// In a statement block, at the statement with a particular index.
+    /// Also holds the size of the `Scope` before the block.
+    Block(usize, usize),
+    /// In the `if` (`true`) or `else` (`false`) branch of an `if` statement.
+    Branch(bool),
+    /// In the body of a `while`, `do` or `loop` statement.
+    Loop,
+    /// In the body of a `for` loop, with the remaining items to iterate.
+    /// Also holds the size of the `Scope` before the loop.
+    For(GeneratorLoopItems, usize),
+    /// At a `yield` statement.
+    Yield,
+}
+
+/// Items iterated by a `for` loop in the body of a generator function.
+#[cfg(not(feature = "no_function"))]
+enum GeneratorLoopItems {
+    /// Items returned by a registered iterator.
+    Iter(Box<dyn Iterator<Item = Dynamic>>),
+    /// Properties of an object map.
+    #[cfg(not(feature = "no_object"))]
+    Pairs(Box<dyn Iterator<Item = (String, Dynamic)>>),
+    /// Values yielded by another generator.
+    Generator(Box<GeneratorRun>),
+}
+
+/// Wrap an error raised inside a script-defined function, adding the function to the call chain.
+fn wrap_fn_call_error(fn_name: &str, err: Box<EvalAltResult>, pos: Position) -> Box<EvalAltResult> {
+    match *err {
+        EvalAltResult::ErrorInFunctionCall(name, err, _) => Box::new(
+            EvalAltResult::ErrorInFunctionCall(format!("{} > {}", fn_name, name), err, pos),
+        ),
+        _ => Box::new(EvalAltResult::ErrorInFunctionCall(
+            fn_name.to_string(),
+            err,
+            pos,
+        )),
+    }
+}
+
+/// Get a shared reference to a script-defined function, cloning the function definition
+/// if it is not in the library of the current script (e.g. if it is defined in a module).
+#[cfg(not(feature = "no_function"))]
+fn share_fn_def(state: &State, fn_def: &FnDef) -> SharedFnDef {
+    state
+        .fn_lib
+        .values()
+        .find(|f| crate::stdlib::ptr::eq(f.as_ref(), fn_def))
+        .cloned()
+        .unwrap_or_else(|| fn_def.clone().into())
+}
+
+/// A token to terminate scripts while they are running, usually from another thread.
+///
+/// Clones of a `CancellationToken` share the same state, so cancelling one cancels them all.
+/// When an `Engine` holding a cancelled token runs a script, the script is terminated at the next operation
+/// with `EvalAltResult::ErrorTerminated`.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Create a new `CancellationToken`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Cancel all scripts run by `Engine`'s holding this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+    /// Has this token been cancelled?
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+    /// Reset this token so that scripts can run again.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Resources used by a script run.
+///
+/// The numbers are the same ones checked against the limits set on the `Engine`,
+/// e.g. via `Engine::set_max_operations`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Usage {
+    /// Number of operations performed.
+    pub operations: u64,
+    /// Maximum depth of nested calls to script-defined functions reached (zero if none is called).
+    pub call_depth: usize,
+    /// Number of modules loaded.
+    pub modules: u64,
+
+    /// Time after which the script run is terminated, carried into the evaluation of imported modules.
+    #[cfg(not(feature = "unchecked"))]
+    #[cfg(not(feature = "no_std"))]
+    pub(crate) deadline: Option<Instant>,
+
+    /// Number of operations after which the current `eval` is terminated, carried into the evaluation
+    /// of imported modules.
+    #[cfg(not(feature = "unchecked"))]
+    pub(crate) eval_operations_limit: Option<u64>,
+}
+
+// The limits carried along are not resources used, so they are not compared
+impl PartialEq for Usage {
+    fn eq(&self, other: &Self) -> bool {
+        (self.operations, self.call_depth, self.modules)
+            == (other.operations, other.call_depth, other.modules)
+    }
+}
+impl Eq for Usage {}
+impl Hash for Usage {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.operations, self.call_depth, self.modules).hash(state);
+    }
+}
+
 /// Rhai main scripting engine.
 ///
 /// ```
@@ -313,26 +796,31 @@ impl DerefMut for FunctionsLib {
 /// # }
 /// ```
 ///
+/// Cloning an `Engine` is cheap: the registered functions, type names, packages and module resolver
+/// are shared with the clone (and only copied when a new function or type is registered into one of them),
+/// while callbacks and limits can be changed on each clone independently.
+///
 /// Currently, `Engine` is neither `Send` nor `Sync`. Turn on the `sync` feature to make it `Send + Sync`.
+#[derive(Clone)]
 pub struct Engine {
     /// A module containing all functions directly loaded into the Engine.
-    pub(crate) global_module: Module,
+    pub(crate) global_module: Shared<Module>,
     /// A collection of all library packages loaded into the Engine.
     pub(crate) packages: PackagesCollection,
 
     /// A module resolution service.
     #[cfg(not(feature = "no_module"))]
-    pub(crate) module_resolver: Option<Box<dyn ModuleResolver>>,
+    pub(crate) module_resolver: Option<Shared<dyn ModuleResolver>>,
 
     /// A hashmap mapping type names to pretty-print names.
-    pub(crate) type_names: HashMap<String, String>,
+    pub(crate) type_names: Shared<HashMap<String, String>>,
 
     /// Closure for implementing the `print` command.
-    pub(crate) print: Box<PrintCallback>,
+    pub(crate) print: Shared<PrintCallback>,
     /// Closure for implementing the `debug` command.
-    pub(crate) debug: Box<PrintCallback>,
+    pub(crate) debug: Shared<PrintCallback>,
     /// Closure for progress reporting.
-    pub(crate) progress: Option<Box<ProgressCallback>>,
+    pub(crate) progress: Option<Shared<ProgressCallback>>,
 
     /// Optimize the AST after compilation.
     pub(crate) optimization_level: OptimizationLevel,
@@ -340,10 +828,45 @@ pub struct Engine {
     ///
     /// Defaults to 28 for debug builds and 256 for non-debug builds.
     pub(crate) max_call_stack_depth: usize,
+    /// Maximum nesting depth of expressions, checked during parsing.
+    ///
+    /// Defaults to 64.
+    pub(crate) max_expr_depth: usize,
+    /// Maximum nesting depth of statement blocks, checked during parsing.
+    ///
+    /// Defaults to 64.
+    pub(crate) max_stmt_depth: usize,
     /// Maximum number of operations allowed to run.
     pub(crate) max_operations: Option<NonZeroU64>,
+    /// Maximum number of operations allowed to run within each call to `eval`.
+    pub(crate) max_eval_operations: Option<NonZeroU64>,
     /// Maximum number of modules allowed to load.
     pub(crate) max_modules: Option<NonZeroU64>,
+    /// Maximum wall-clock time allowed for a script run.
+    #[cfg(not(feature = "unchecked"))]
+    #[cfg(not(feature = "no_std"))]
+    pub(crate) max_duration: Option<Duration>,
+    /// Token to terminate running scripts, possibly from another thread.
+    pub(crate) cancellation_token: Option<CancellationToken>,
+    /// Maximum length of a string.
+    pub(crate) max_string_size: Option<NonZeroUsize>,
+    /// Maximum number of items in an array.
+    pub(crate) max_array_size: Option<NonZeroUsize>,
+    /// Maximum number of properties in an object map.
+    pub(crate) max_map_size: Option<NonZeroUsize>,
+    /// Maximum estimated size of a data value, in bytes.
+    pub(crate) max_data_size: Option<NonZeroUsize>,
+
+    /// Keywords, operators and functions disabled in scripts.
+    pub(crate) disabled_symbols: HashSet<String>,
+    /// How calls to `eval` are handled.
+    pub(crate) eval_mode: EvalMode,
+    /// Check the types of values in a script during compilation.
+    pub(crate) type_checking: bool,
+
+    /// Custom passes run by the optimizer.
+    #[cfg(feature = "internals")]
+    pub(crate) optimizer_passes: Vec<Shared<dyn OptimizerPass>>,
 }
 
 impl Default for Engine {
@@ -355,7 +878,7 @@ impl Default for Engine {
 
             #[cfg(not(feature = "no_module"))]
             #[cfg(not(feature = "no_std"))]
-            module_resolver: Some(Box::new(resolvers::FileModuleResolver::new())),
+            module_resolver: Some(Shared::new(resolvers::FileModuleResolver::new())),
             #[cfg(not(feature = "no_module"))]
             #[cfg(feature = "no_std")]
             module_resolver: None,
@@ -363,8 +886,8 @@ impl Default for Engine {
             type_names: Default::default(),
 
             // default print/debug implementations
-            print: Box::new(default_print),
-            debug: Box::new(default_print),
+            print: Shared::new(default_print),
+            debug: Shared::new(default_print),
 
             // progress callback
             progress: None,
@@ -382,8 +905,26 @@ impl Default for Engine {
             optimization_level: OptimizationLevel::Full,
 
             max_call_stack_depth: MAX_CALL_STACK_DEPTH,
+            max_expr_depth: MAX_EXPR_DEPTH,
+            max_stmt_depth: MAX_STMT_DEPTH,
             max_operations: None,
+            max_eval_operations: None,
             max_modules: None,
+            max_string_size: None,
+            max_array_size: None,
+            max_map_size: None,
+            max_data_size: None,
+            #[cfg(not(feature = "unchecked"))]
+            #[cfg(not(feature = "no_std"))]
+            max_duration: None,
+            cancellation_token: None,
+
+            disabled_symbols: Default::default(),
+            eval_mode: EvalMode::Shared,
+            type_checking: false,
+
+            #[cfg(feature = "internals")]
+            optimizer_passes: Vec::new(),
         };
 
         #[cfg(feature = "no_stdlib")]
@@ -438,15 +979,135 @@ fn extract_prop_from_setter(fn_name: &str) -> Option<&str> {
     }
 }
 
+/// Calculate the sizes of a data value, including all the values nested inside.
+///
+/// Returns the maximum length of a string, the maximum number of items in an array,
+/// the maximum number of properties in an object map, and the estimated total size in bytes.
+pub(crate) fn calc_data_sizes(value: &Dynamic) -> (usize, usize, usize, usize) {
+    match &value.0 {
+        Union::Str(s) => (s.len(), 0, 0, mem::size_of::<Dynamic>() + s.len()),
+        #[cfg(not(feature = "no_index"))]
+        Union::Array(arr) => arr.iter().map(calc_data_sizes).fold(
+            (0, arr.len(), 0, mem::size_of::<Dynamic>()),
+            |(s, a, m, d), (s2, a2, m2, d2)| (s.max(s2), a.max(a2), m.max(m2), d + d2),
+        ),
+        #[cfg(not(feature = "no_object"))]
+        Union::Map(map) => map
+            .iter()
+            .map(|(key, value)| {
+                let (s, a, m, d) = calc_data_sizes(value);
+                (s, a, m, d + key.len())
+            })
+            .fold(
+                (0, 0, map.len(), mem::size_of::<Dynamic>()),
+                |(s, a, m, d), (s2, a2, m2, d2)| (s.max(s2), a.max(a2), m.max(m2), d + d2),
+            ),
+        _ => (0, 0, 0, mem::size_of::<Dynamic>()),
+    }
+}
+
+/// Check a size against a size limit.
+pub(crate) fn check_size_limit(
+    typ: &str,
+    max: Option<NonZeroUsize>,
+    size: usize,
+    pos: Position,
+) -> Result<(), Box<EvalAltResult>> {
+    match max {
+        Some(max) if size > max.get() => Err(Box::new(EvalAltResult::ErrorDataTooLarge(
+            typ.into(),
+            max.get(),
+            size,
+            pos,
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Calculate the offset of an index into `len` items, with negative indices counting from the end.
+#[cfg(not(feature = "no_index"))]
+fn calc_offset(len: usize, index: INT) -> i128 {
+    if index < 0 {
+        len as i128 + index as i128
+    } else {
+        index as i128
+    }
+}
+
+/// Calculate the position of an index into `len` items.
+///
+/// Returns `None` if the index is out of bounds.
+#[cfg(not(feature = "no_index"))]
+fn calc_index(len: usize, index: INT) -> Option<usize> {
+    match calc_offset(len, index) {
+        offset if offset >= 0 && offset < len as i128 => Some(offset as usize),
+        _ => None,
+    }
+}
+
+/// Calculate the positions of a slice of `len` items selected by a range index.
+///
+/// Both ends are clamped to the items available.  Returns `None` if the index is not a range.
+#[cfg(not(feature = "no_index"))]
+fn calc_slice(len: usize, index: &Dynamic) -> Option<Range<usize>> {
+    let (start, end) = if let Some(range) = index.downcast_ref::<Range<INT>>() {
+        (calc_offset(len, range.start), calc_offset(len, range.end))
+    } else if let Some(range) = index.downcast_ref::<RangeInclusive<INT>>() {
+        (
+            calc_offset(len, *range.start()),
+            calc_offset(len, *range.end()) + 1,
+        )
+    } else {
+        return None;
+    };
+
+    let clamp = |offset: i128| offset.max(0).min(len as i128) as usize;
+    let (start, end) = (clamp(start), clamp(end));
+
+    Some(start..end.max(start))
+}
+
 /// Print/debug to stdout
 fn default_print(s: &str) {
     #[cfg(not(feature = "no_std"))]
     println!("{}", s);
 }
 
+/// Search for the root module of a module-qualified name within the scope
+#[cfg(not(feature = "no_module"))]
+fn search_module<'a>(
+    scope: &'a mut Scope,
+    state: &State,
+    modules: &ModuleRef,
+) -> Result<&'a mut Module, Box<EvalAltResult>> {
+    let (id, root_pos) = modules.get(0);
+
+    // Search by name if the offset is stale or does not point to a module
+    let index = if state.always_search {
+        None
+    } else {
+        modules
+            .index()
+            .and_then(|index| scope.len().checked_sub(index.get()))
+            .filter(|&index| match scope.get_mut(index) {
+                Some((v, _)) => v.is::<Module>(),
+                None => false,
+            })
+    };
+
+    match index {
+        Some(index) => scope
+            .get_mut(index)
+            .and_then(|(v, _)| v.downcast_mut::<Module>()),
+        None => scope.find_module(id),
+    }
+    .ok_or_else(|| Box::new(EvalAltResult::ErrorModuleNotFound(id.into(), *root_pos)))
+}
+
 /// Search for a variable within the scope
 fn search_scope<'a>(
     scope: &'a mut Scope,
+    state: &State,
     name: &str,
     #[cfg(not(feature = "no_module"))] modules: Option<(&Box<ModuleRef>, u64)>,
     #[cfg(feature = "no_module")] _: Option<(&ModuleRef, u64)>,
@@ -456,19 +1117,7 @@ fn search_scope<'a>(
     #[cfg(not(feature = "no_module"))]
     {
         if let Some((modules, hash_var)) = modules {
-            let module = if let Some(index) = modules.index() {
-                scope
-                    .get_mut(scope.len() - index.get())
-                    .0
-                    .downcast_mut::<Module>()
-                    .unwrap()
-            } else {
-                let (id, root_pos) = modules.get(0);
-
-                scope.find_module(id).ok_or_else(|| {
-                    Box::new(EvalAltResult::ErrorModuleNotFound(id.into(), *root_pos))
-                })?
-            };
+            let module = search_module(scope, state, modules)?;
 
             return Ok((
                 module.get_qualified_var_mut(name, hash_var, pos)?,
@@ -478,16 +1127,36 @@ fn search_scope<'a>(
         }
     }
 
-    let index = if let Some(index) = index {
-        scope.len() - index.get()
-    } else {
-        scope
-            .get_index(name)
-            .ok_or_else(|| Box::new(EvalAltResult::ErrorVariableNotFound(name.into(), pos)))?
-            .0
+    let index = if state.always_search { None } else { index };
+
+    // Search by name if the offset is out of range
+    let index = match index.and_then(|index| scope.len().checked_sub(index.get())) {
+        Some(index) => index,
+        None => {
+            scope
+                .get_index(name)
+                .ok_or_else(|| Box::new(EvalAltResult::ErrorVariableNotFound(name.into(), pos)))?
+                .0
+        }
     };
 
-    Ok(scope.get_mut(index))
+    scope.get_mut(index).ok_or_else(|| invalid_scope_index(pos))
+}
+
+/// Make the error for a generator resumed without the frame for a suspended statement.
+fn missing_generator_frame(pos: Position) -> Box<EvalAltResult> {
+    Box::new(EvalAltResult::ErrorInternal(
+        "missing generator frame".into(),
+        pos,
+    ))
+}
+
+/// Make the error for an index into the scope that is out of range.
+fn invalid_scope_index(pos: Position) -> Box<EvalAltResult> {
+    Box::new(EvalAltResult::ErrorInternal(
+        "invalid index in Scope".into(),
+        pos,
+    ))
 }
 
 impl Engine {
@@ -507,8 +1176,8 @@ impl Engine {
             module_resolver: None,
 
             type_names: Default::default(),
-            print: Box::new(|_| {}),
-            debug: Box::new(|_| {}),
+            print: Shared::new(|_| {}),
+            debug: Shared::new(|_| {}),
             progress: None,
 
             #[cfg(feature = "no_optimize")]
@@ -523,8 +1192,26 @@ impl Engine {
             optimization_level: OptimizationLevel::Full,
 
             max_call_stack_depth: MAX_CALL_STACK_DEPTH,
+            max_expr_depth: MAX_EXPR_DEPTH,
+            max_stmt_depth: MAX_STMT_DEPTH,
             max_operations: None,
+            max_eval_operations: None,
             max_modules: None,
+            max_string_size: None,
+            max_array_size: None,
+            max_map_size: None,
+            max_data_size: None,
+            #[cfg(not(feature = "unchecked"))]
+            #[cfg(not(feature = "no_std"))]
+            max_duration: None,
+            cancellation_token: None,
+
+            disabled_symbols: Default::default(),
+            eval_mode: EvalMode::Shared,
+            type_checking: false,
+
+            #[cfg(feature = "internals")]
+            optimizer_passes: Vec::new(),
         }
     }
 
@@ -554,6 +1241,17 @@ impl Engine {
         self.optimization_level = optimization_level
     }
 
+    /// Add a custom pass to be run by the optimizer on every statement and expression.
+    ///
+    /// Passes run in the order they are added.
+    ///
+    /// Only available under the `internals` feature.  Not available under the `no_optimize` feature.
+    #[cfg(feature = "internals")]
+    #[cfg(not(feature = "no_optimize"))]
+    pub fn add_optimizer_pass(&mut self, pass: impl OptimizerPass + 'static) {
+        self.optimizer_passes.push(Shared::new(pass));
+    }
+
     /// Set the maximum levels of function calls allowed for a script in order to avoid
     /// infinite recursion and stack overflows.
     #[cfg(not(feature = "unchecked"))]
@@ -561,6 +1259,24 @@ impl Engine {
         self.max_call_stack_depth = levels
     }
 
+    /// Set the maximum nesting depth of expressions allowed in a script (0 for unlimited),
+    /// in order to avoid stack overflows when parsing and evaluating deeply-nested expressions.
+    ///
+    /// The limit also applies to the bodies of script-defined functions.
+    #[cfg(not(feature = "unchecked"))]
+    pub fn set_max_expr_depth(&mut self, depth: usize) {
+        self.max_expr_depth = if depth == 0 { usize::MAX } else { depth };
+    }
+
+    /// Set the maximum nesting depth of statement blocks allowed in a script (0 for unlimited),
+    /// in order to avoid stack overflows when parsing and evaluating deeply-nested statements.
+    ///
+    /// The limit also applies to the bodies of script-defined functions.
+    #[cfg(not(feature = "unchecked"))]
+    pub fn set_max_stmt_depth(&mut self, depth: usize) {
+        self.max_stmt_depth = if depth == 0 { usize::MAX } else { depth };
+    }
+
     /// Set the maximum number of operations allowed for a script to run to avoid
     /// consuming too much resources (0 for unlimited).
     #[cfg(not(feature = "unchecked"))]
@@ -568,33 +1284,205 @@ impl Engine {
         self.max_operations = NonZeroU64::new(operations);
     }
 
+    /// Set the maximum number of operations allowed for each call to `eval` (0 for unlimited).
+    ///
+    /// Operations inside `eval` still count towards the limit set by `set_max_operations`.
+    #[cfg(not(feature = "unchecked"))]
+    pub fn set_max_eval_operations(&mut self, operations: u64) {
+        self.max_eval_operations = NonZeroU64::new(operations);
+    }
+
     /// Set the maximum number of imported modules allowed for a script (0 for unlimited).
     #[cfg(not(feature = "unchecked"))]
     pub fn set_max_modules(&mut self, modules: u64) {
         self.max_modules = NonZeroU64::new(modules);
     }
 
-    /// Set the module resolution service used by the `Engine`.
+    /// Set the maximum length of strings (0 for unlimited).
+    #[cfg(not(feature = "unchecked"))]
+    pub fn set_max_string_size(&mut self, max_size: usize) {
+        self.max_string_size = NonZeroUsize::new(max_size);
+    }
+
+    /// Set the maximum number of items in arrays (0 for unlimited).
     ///
-    /// Not available under the `no_module` feature.
-    #[cfg(not(feature = "no_module"))]
-    pub fn set_module_resolver(&mut self, resolver: Option<impl ModuleResolver + 'static>) {
-        self.module_resolver = resolver.map(|f| Box::new(f) as Box<dyn ModuleResolver>);
+    /// Not available under the `no_index` feature.
+    #[cfg(not(feature = "unchecked"))]
+    #[cfg(not(feature = "no_index"))]
+    pub fn set_max_array_size(&mut self, max_size: usize) {
+        self.max_array_size = NonZeroUsize::new(max_size);
     }
 
-    /// Universal method for calling functions either registered with the `Engine` or written in Rhai.
+    /// Set the maximum number of properties in object maps (0 for unlimited).
     ///
-    /// ## WARNING
+    /// Not available under the `no_object` feature.
+    #[cfg(not(feature = "unchecked"))]
+    #[cfg(not(feature = "no_object"))]
+    pub fn set_max_map_size(&mut self, max_size: usize) {
+        self.max_map_size = NonZeroUsize::new(max_size);
+    }
+
+    /// Set the maximum estimated size, in bytes, of any single data value (0 for unlimited).
     ///
-    /// Function call arguments may be _consumed_ when the function requires them to be passed by value.
-    /// All function arguments not in the first position are always passed by value and thus consumed.
-    /// **DO NOT** reuse the argument values unless for the first `&mut` argument - all others are silently replaced by `()`!
-    pub(crate) fn call_fn_raw(
-        &self,
-        scope: Option<&mut Scope>,
-        state: &mut State,
-        fn_name: &str,
-        hashes: (u64, u64),
+    /// The size of a value includes all the strings, arrays and object maps nested inside it.
+    #[cfg(not(feature = "unchecked"))]
+    pub fn set_max_data_size(&mut self, max_size: usize) {
+        self.max_data_size = NonZeroUsize::new(max_size);
+    }
+
+    /// Set the maximum wall-clock time allowed for each script run (zero for unlimited).
+    ///
+    /// The time is checked before every operation, so a single long-running native function call
+    /// is not interrupted - the script terminates at the next operation after the time is up.
+    ///
+    /// Not available under the `no_std` feature.
+    #[cfg(not(feature = "unchecked"))]
+    #[cfg(not(feature = "no_std"))]
+    pub fn set_max_duration(&mut self, duration: Duration) {
+        self.max_duration = if duration.as_nanos() == 0 {
+            None
+        } else {
+            Some(duration)
+        };
+    }
+
+    /// Set a `CancellationToken` to terminate scripts run by the `Engine`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<rhai::EvalAltResult>> {
+    /// use rhai::{CancellationToken, Engine, EvalAltResult};
+    ///
+    /// let token = CancellationToken::new();
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_cancellation_token(Some(token.clone()));
+    ///
+    /// // Typically called from another thread
+    /// token.cancel();
+    ///
+    /// let result = engine.eval::<()>("loop {}").expect_err("should error");
+    /// assert!(matches!(*result, EvalAltResult::ErrorTerminated(_)));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_cancellation_token(&mut self, token: Option<CancellationToken>) {
+        self.cancellation_token = token;
+    }
+
+    /// Set the module resolution service used by the `Engine`.
+    ///
+    /// Not available under the `no_module` feature.
+    #[cfg(not(feature = "no_module"))]
+    pub fn set_module_resolver(&mut self, resolver: Option<impl ModuleResolver + 'static>) {
+        self.module_resolver = resolver.map(|f| Shared::new(f) as Shared<dyn ModuleResolver>);
+    }
+
+    /// Disable a particular keyword, operator or function in all scripts compiled by this `Engine`.
+    ///
+    /// Symbols are matched against each token of a script, so a keyword (e.g. `while`), an operator (e.g. `+`),
+    /// or any identifier (e.g. the `eval` function) can be disabled.  Using a disabled symbol fails compilation
+    /// with `ParseErrorType::DisabledSymbol`.
+    ///
+    /// Disabling an operator also disables its compound assignment (e.g. disabling `+` disables `+=`),
+    /// but a compound assignment can be disabled on its own.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<rhai::EvalAltResult>> {
+    /// use rhai::{Engine, ParseErrorType};
+    ///
+    /// let mut engine = Engine::new();
+    ///
+    /// engine.disable_symbol("while");
+    /// engine.disable_symbol("eval");
+    ///
+    /// let err = engine.compile("let x = 0; while x < 10 { x += 1; }").expect_err("should error");
+    /// assert_eq!(*err.error_type(), ParseErrorType::DisabledSymbol("while".into()));
+    ///
+    /// assert!(engine.compile(r#"eval("40 + 2")"#).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn disable_symbol(&mut self, symbol: &str) {
+        self.disabled_symbols.insert(symbol.into());
+    }
+
+    /// Control how scripts can use `eval`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<rhai::EvalAltResult>> {
+    /// use rhai::{Engine, EvalMode, Scope, INT};
+    ///
+    /// let mut engine = Engine::new();
+    /// let mut scope = Scope::new();
+    ///
+    /// engine.set_eval_mode(EvalMode::Isolated);
+    ///
+    /// // The evaluated script cannot see or modify 'x'
+    /// let result = engine.eval_with_scope::<INT>(&mut scope, r#"
+    ///                 let x = 40;
+    ///                 eval("let x = 1; x + 1") + x
+    ///             "#)?;
+    ///
+    /// assert_eq!(result, 42);
+    ///
+    /// engine.set_eval_mode(EvalMode::Disabled);
+    /// assert!(engine.eval::<INT>(r#"eval("40 + 2")"#).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_eval_mode(&mut self, mode: EvalMode) {
+        self.eval_mode = mode;
+    }
+
+    /// Control whether the `Engine` checks the types of values in a script when compiling it.
+    ///
+    /// The type checker follows the types of literals, type annotations and the return values of
+    /// registered Rust functions, and reports values that can never match a type annotation, or
+    /// function calls whose arguments match none of the functions registered at the time of compilation.
+    /// Types that are not known until runtime are never reported.  The default is `false`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<rhai::EvalAltResult>> {
+    /// use rhai::Engine;
+    ///
+    /// let mut engine = Engine::new();
+    ///
+    /// // Without type checking, the errors are only found when the script is run
+    /// assert!(engine.compile(r#"let x: int = "42";"#).is_ok());
+    /// assert!(engine.compile(r#"abs("42")"#).is_ok());
+    ///
+    /// engine.set_type_checking(true);
+    ///
+    /// assert!(engine.compile(r#"let x: int = "42";"#).is_err());
+    /// assert!(engine.compile(r#"abs("42")"#).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_type_checking(&mut self, enable: bool) {
+        self.type_checking = enable;
+    }
+
+    /// Universal method for calling functions either registered with the `Engine` or written in Rhai.
+    ///
+    /// ## WARNING
+    ///
+    /// Function call arguments may be _consumed_ when the function requires them to be passed by value.
+    /// All function arguments not in the first position are always passed by value and thus consumed.
+    /// **DO NOT** reuse the argument values unless for the first `&mut` argument - all others are silently replaced by `()`!
+    pub(crate) fn call_fn_raw(
+        &self,
+        scope: Option<&mut Scope>,
+        state: &mut State,
+        fn_name: &str,
+        hashes: (u64, u64),
         args: &mut FnCallArgs,
         is_ref: bool,
         def_val: Option<&Dynamic>,
@@ -608,22 +1496,25 @@ impl Engine {
             return Err(Box::new(EvalAltResult::ErrorStackOverflow(pos)));
         }
 
-        // First search in script-defined functions (can override built-in)
-        if hashes.1 > 0 {
-            if let Some(fn_def) = state.get_function(hashes.1) {
-                let (result, state2) =
-                    self.call_script_fn(scope, *state, fn_name, fn_def, args, pos, level)?;
-                *state = state2;
+        // Search script-defined functions first (can override built-in),
+        // then built-in's and external functions
+        let func = match state.resolve_fn(self, fn_name, args.len(), hashes) {
+            CallTarget::Script(fn_def) => {
+                // The first argument is consumed, so whatever it is replaced with is not known
+                #[cfg(not(feature = "unchecked"))]
+                if is_ref {
+                    state.add_data_growth(None);
+                }
+
+                let result =
+                    self.call_script_fn(scope, state, fn_name, fn_def, args, pos, level)?;
                 return Ok((result, false));
             }
-        }
+            CallTarget::Native(func) => Some(func),
+            CallTarget::NotFound => None,
+        };
 
-        // Search built-in's and external functions
-        if let Some(func) = self
-            .global_module
-            .get_fn(hashes.0)
-            .or_else(|| self.packages.get_fn(hashes.0))
-        {
+        if let Some(func) = func {
             let mut backup: Dynamic = Default::default();
 
             let (updated, restore) = match func.abi() {
@@ -638,8 +1529,16 @@ impl Engine {
                 NativeFunctionABI::Method => (true, false),
             };
 
+            // Length of a string changed in place, to tell how much it grows
+            #[cfg(not(feature = "unchecked"))]
+            let string_len = match args.first() {
+                Some(arg) if updated => arg.as_str().ok().map(str::len),
+                _ => None,
+            };
+
             // Run external function
-            let result = match func.call(args) {
+            let mut context = self.call_context();
+            let result = match func.call_with_context(&mut context, args) {
                 Ok(r) => {
                     // Restore the backup value for the first argument since it has been consumed!
                     if restore {
@@ -674,7 +1573,28 @@ impl Engine {
                     .into(),
                     false,
                 ),
-                _ => (result, updated),
+                _ => {
+                    #[cfg(not(feature = "unchecked"))]
+                    {
+                        self.check_data_size(&result, pos)?;
+
+                        if updated {
+                            self.check_changed_data_size(args[0], pos)?;
+
+                            if self.max_data_size.is_some() {
+                                let growth = self.calc_data_growth(
+                                    hashes.0,
+                                    args[0],
+                                    string_len,
+                                    context.added_data_size,
+                                );
+                                state.add_data_growth(growth);
+                            }
+                        }
+                    }
+
+                    (result, updated)
+                }
             });
         }
 
@@ -726,106 +1646,243 @@ impl Engine {
     /// Function call arguments may be _consumed_ when the function requires them to be passed by value.
     /// All function arguments not in the first position are always passed by value and thus consumed.
     /// **DO NOT** reuse the argument values unless for the first `&mut` argument - all others are silently replaced by `()`!
-    pub(crate) fn call_script_fn<'s>(
+    pub(crate) fn call_script_fn(
         &self,
         scope: Option<&mut Scope>,
-        mut state: State<'s>,
+        state: &mut State,
         fn_name: &str,
         fn_def: &FnDef,
         args: &mut FnCallArgs,
         pos: Position,
         level: usize,
-    ) -> Result<(Dynamic, State<'s>), Box<EvalAltResult>> {
+    ) -> Result<Dynamic, Box<EvalAltResult>> {
+        // Actually consume the arguments instead of cloning them
+        let args = args.iter_mut().map(|v| Some(mem::take(*v)));
+        self.call_script_fn_with_args(scope, state, fn_name, fn_def, args, pos, level)
+    }
+
+    /// Call a script-defined function with a list of optional arguments, one for each parameter.
+    ///
+    /// Parameters without an argument take their default values, which are evaluated inside
+    /// the function after all the previous parameters are set.
+    fn call_script_fn_with_args(
+        &self,
+        scope: Option<&mut Scope>,
+        state: &mut State,
+        fn_name: &str,
+        fn_def: &FnDef,
+        args: impl IntoIterator<Item = Option<Dynamic>>,
+        pos: Position,
+        level: usize,
+    ) -> Result<Dynamic, Box<EvalAltResult>> {
         let orig_scope_level = state.scope_level;
         state.scope_level += 1;
+        state.call_depth = state.call_depth.max(level + 1);
 
-        match scope {
-            // Extern scope passed in which is not empty
-            Some(scope) if scope.len() > 0 => {
-                let scope_len = scope.len();
+        // Use the extern scope passed in if it is not empty, otherwise create an internal scope
+        let mut local_scope = Scope::new();
 
-                // Put arguments into scope as variables
-                scope.extend(
-                    fn_def
-                        .params
-                        .iter()
-                        .zip(
-                            // Actually consume the arguments instead of cloning them
-                            args.into_iter().map(|v| mem::take(*v)),
-                        )
-                        .map(|(name, value)| {
-                            let var_name =
-                                unsafe_cast_var_name_to_lifetime(name.as_str(), &mut state);
-                            (var_name, ScopeEntryType::Normal, value)
-                        }),
-                );
+        let scope = match scope {
+            Some(scope) if scope.len() > 0 => scope,
+            _ => &mut local_scope,
+        };
 
-                // Evaluate the function at one higher level of call depth
-                let result = self
-                    .eval_stmt(scope, &mut state, &fn_def.body, level + 1)
-                    .or_else(|err| match *err {
-                        // Convert return statement to return value
-                        EvalAltResult::Return(x, _) => Ok(x),
-                        EvalAltResult::ErrorInFunctionCall(name, err, _) => {
-                            Err(Box::new(EvalAltResult::ErrorInFunctionCall(
-                                format!("{} > {}", fn_name, name),
-                                err,
-                                pos,
-                            )))
-                        }
-                        _ => Err(Box::new(EvalAltResult::ErrorInFunctionCall(
-                            fn_name.to_string(),
-                            err,
+        let scope_len = scope.len();
+
+        // Put arguments into scope as variables, then
+        // evaluate the function at one higher level of call depth
+        let result = self
+            .push_fn_params(scope, state, fn_name, fn_def, args, pos, level)
+            .and_then(|_| {
+                // Calling a generator only binds the parameters - the body runs when it is iterated
+                #[cfg(not(feature = "no_function"))]
+                {
+                    if fn_def.is_generator {
+                        let generator = Generator {
+                            fn_def: share_fn_def(state, fn_def),
+                            params: scope.split_off(scope_len),
                             pos,
-                        ))),
-                    });
+                        };
+                        return Ok((Dynamic::from(generator), pos));
+                    }
+                }
+
+                self.eval_stmt(scope, state, &fn_def.body, level + 1)
+                    .map(|value| (value, fn_def.result_position()))
+            })
+            .or_else(|err| match *err {
+                // Convert return statement to return value
+                EvalAltResult::Return(x, return_pos) => Ok((x, return_pos)),
+                _ => Err(err),
+            })
+            .and_then(|(value, value_pos)| match &fn_def.return_type {
+                // The return type of a generator is the type of the values it yields
+                Some(typ) if !fn_def.is_generator => self.cast_to_type(value, typ, value_pos),
+                _ => Ok(value),
+            })
+            .map_err(|err| wrap_fn_call_error(fn_name, err, pos));
+
+        // Remove all local variables
+        scope.rewind(scope_len);
+        state.scope_level = orig_scope_level;
+
+        result
+    }
+
+    /// Push the parameters of a script-defined function into a `Scope`,
+    /// evaluating default values for those without an argument.
+    fn push_fn_params<'s>(
+        &self,
+        scope: &mut Scope<'s>,
+        state: &mut State,
+        fn_name: &str,
+        fn_def: &FnDef,
+        args: impl IntoIterator<Item = Option<Dynamic>>,
+        pos: Position,
+        level: usize,
+    ) -> Result<(), Box<EvalAltResult>> {
+        let required = fn_def.required_params();
+        let mut args = args.into_iter();
+
+        for (index, name) in fn_def.params.iter().enumerate() {
+            let value = match args.next().and_then(|v| v) {
+                Some(value) => value,
+                None if index >= required => {
+                    let expr = &fn_def.defaults[index - required];
+                    self.eval_expr(scope, state, expr, level + 1)?
+                }
+                None => {
+                    return Err(Box::new(EvalAltResult::ErrorFunctionArgsMismatch(
+                        fn_name.into(),
+                        required,
+                        index,
+                        pos,
+                    )))
+                }
+            };
+
+            let value = match &fn_def.param_types[index] {
+                Some(typ) => self.cast_to_type(value, typ, pos)?,
+                None => value,
+            };
 
-                // Remove all local variables
-                scope.rewind(scope_len);
-                state.scope_level = orig_scope_level;
+            let var_name = unsafe_cast_var_name_to_lifetime(name.as_str(), state);
+            scope.push_dynamic_value(var_name, ScopeEntryType::Normal, value, false);
+        }
 
-                return result.map(|v| (v, state));
+        // Collect all extra arguments into the rest parameter
+        #[cfg(not(feature = "no_index"))]
+        {
+            if let Some(name) = &fn_def.rest_param {
+                let rest = Dynamic(Union::Array(Box::new(args.flatten().collect())));
+                let var_name = unsafe_cast_var_name_to_lifetime(name.as_str(), state);
+                scope.push_dynamic_value(var_name, ScopeEntryType::Normal, rest, false);
             }
-            // No new scope - create internal scope
-            _ => {
-                let mut scope = Scope::new();
+        }
+
+        Ok(())
+    }
+
+    /// Call a script-defined function with named arguments.
+    ///
+    /// The trailing arguments are matched to parameters by name, and parameters without an
+    /// argument take their default values.
+    fn call_fn_with_named_args(
+        &self,
+        state: &mut State,
+        fn_name: &str,
+        fn_def: Option<&FnDef>,
+        arg_values: StaticVec<Dynamic>,
+        named_args: &[(String, Position)],
+        pos: Position,
+        level: usize,
+    ) -> Result<Dynamic, Box<EvalAltResult>> {
+        self.inc_operations(state, pos)?;
+
+        // Check for stack overflow
+        if level > self.max_call_stack_depth {
+            return Err(Box::new(EvalAltResult::ErrorStackOverflow(pos)));
+        }
 
-                // Put arguments into scope as variables
-                scope.extend(
-                    fn_def
+        let num_positional = arg_values.len() - named_args.len();
+
+        // Named arguments are only supported by script-defined functions
+        let fn_def = match fn_def {
+            Some(fn_def) => fn_def,
+            None => {
+                let types_list: Vec<_> = arg_values
+                    .iter()
+                    .enumerate()
+                    .map(|(index, value)| {
+                        let typ = self.map_type_name(value.type_name());
+
+                        match index.checked_sub(num_positional) {
+                            Some(n) => format!("{}: {}", named_args[n].0, typ),
+                            None => typ.to_string(),
+                        }
+                    })
+                    .collect();
+
+                return Err(Box::new(EvalAltResult::ErrorFunctionNotFound(
+                    format!("{} ({})", fn_name, types_list.join(", ")),
+                    pos,
+                )));
+            }
+        };
+
+        // Match the arguments to the parameters - extra positional arguments go to the rest parameter
+        let mut args: StaticVec<Option<Dynamic>> = (0..fn_def.params.len().max(num_positional))
+            .map(|_| None)
+            .collect();
+
+        for (index, value) in arg_values.into_iter().enumerate() {
+            let (param, pos) = match index.checked_sub(num_positional) {
+                None => (index, pos),
+                Some(n) => {
+                    let (name, pos) = &named_args[n];
+
+                    let param = fn_def
                         .params
                         .iter()
-                        .zip(
-                            // Actually consume the arguments instead of cloning them
-                            args.into_iter().map(|v| mem::take(*v)),
-                        )
-                        .map(|(name, value)| (name, ScopeEntryType::Normal, value)),
-                );
+                        .position(|p| p == name)
+                        .ok_or_else(|| {
+                            Box::new(EvalAltResult::ErrorNamedArgument(
+                                format!("Function '{}' has no parameter named '{}'", fn_name, name),
+                                *pos,
+                            ))
+                        })?;
 
-                // Evaluate the function at one higher level of call depth
-                let result = self
-                    .eval_stmt(&mut scope, &mut state, &fn_def.body, level + 1)
-                    .or_else(|err| match *err {
-                        // Convert return statement to return value
-                        EvalAltResult::Return(x, _) => Ok(x),
-                        EvalAltResult::ErrorInFunctionCall(name, err, _) => {
-                            Err(Box::new(EvalAltResult::ErrorInFunctionCall(
-                                format!("{} > {}", fn_name, name),
-                                err,
-                                pos,
-                            )))
-                        }
-                        _ => Err(Box::new(EvalAltResult::ErrorInFunctionCall(
-                            fn_name.to_string(),
-                            err,
-                            pos,
-                        ))),
-                    });
+                    (param, *pos)
+                }
+            };
 
-                state.scope_level = orig_scope_level;
-                return result.map(|v| (v, state));
+            if args[param].is_some() {
+                return Err(Box::new(EvalAltResult::ErrorNamedArgument(
+                    format!(
+                        "Parameter '{}' of function '{}' is passed more than once",
+                        fn_def.params[param], fn_name
+                    ),
+                    pos,
+                )));
             }
+
+            args[param] = Some(value);
+        }
+
+        // All parameters without default values must be passed
+        let required = fn_def.required_params();
+
+        if let Some(param) = args.iter().take(required).position(Option::is_none) {
+            return Err(Box::new(EvalAltResult::ErrorNamedArgument(
+                format!(
+                    "Parameter '{}' of function '{}' is not passed",
+                    fn_def.params[param], fn_name
+                ),
+                pos,
+            )));
         }
+
+        self.call_script_fn_with_args(None, state, fn_name, fn_def, args.into_iter(), pos, level)
     }
 
     // Has a system function an override?
@@ -868,7 +1925,11 @@ impl Engine {
             )),
 
             // eval - reaching this point it must be a method-style call
-            KEYWORD_EVAL if args.len() == 1 && !self.has_override(state, hashes) => {
+            KEYWORD_EVAL
+                if args.len() == 1
+                    && self.eval_mode != EvalMode::Disabled
+                    && !self.has_override(state, hashes) =>
+            {
                 Err(Box::new(EvalAltResult::ErrorRuntime(
                     "'eval' should not be called in method style. Try eval(...);".into(),
                     pos,
@@ -889,6 +1950,7 @@ impl Engine {
         state: &mut State,
         script: &Dynamic,
         pos: Position,
+        level: usize,
     ) -> Result<Dynamic, Box<EvalAltResult>> {
         let script = script
             .as_str()
@@ -896,28 +1958,94 @@ impl Engine {
 
         // Compile the script text
         // No optimizations because we only run it once
-        let mut ast = self.compile_with_scope_and_optimization_level(
-            &Scope::new(),
-            &[script],
-            OptimizationLevel::None,
-        )?;
+        let mut ast = self
+            .compile_with_scope_and_optimization_level(
+                &Scope::new(),
+                &[script],
+                OptimizationLevel::None,
+            )
+            .map_err(|err| {
+                // Keep the position of the error inside the script text
+                EvalAltResult::ErrorInFunctionCall(
+                    KEYWORD_EVAL.into(),
+                    Box::new(EvalAltResult::ErrorParsing(err)),
+                    pos,
+                )
+            })?;
 
         // If new functions are defined within the eval string, it is an error
-        if ast.fn_lib().len() > 0 {
-            return Err(Box::new(EvalAltResult::ErrorParsing(
-                ParseErrorType::WrongFnDefinition.into_err(pos),
+        if let Some((_, fn_def)) = ast.fn_lib().iter().next() {
+            return Err(Box::new(EvalAltResult::ErrorInFunctionCall(
+                KEYWORD_EVAL.into(),
+                Box::new(EvalAltResult::ErrorParsing(
+                    ParseErrorType::WrongFnDefinition.into_err(fn_def.pos),
+                )),
+                pos,
             )));
         }
 
+        let isolated = self.eval_mode == EvalMode::Isolated;
+
         let statements = mem::take(ast.statements_mut());
-        let ast = AST::new(statements, state.fn_lib.clone());
+        let fn_lib = if isolated {
+            Default::default()
+        } else {
+            state.fn_lib.clone()
+        };
+        let ast = AST::new(statements, fn_lib);
+
+        // Usage inside eval continues the count of the caller
+        let mut eval_state = State::new(ast.fn_lib());
+        eval_state.operations = state.operations;
+        eval_state.modules = state.modules;
+        eval_state.call_depth = state.call_depth;
+
+        #[cfg(not(feature = "unchecked"))]
+        {
+            #[cfg(not(feature = "no_std"))]
+            {
+                eval_state.deadline = state.deadline;
+            }
+
+            // Each eval has its own budget of operations, within the budget of any enclosing eval
+            let limit = self
+                .max_eval_operations
+                .map(|max| state.operations.saturating_add(max.get()));
+
+            eval_state.eval_operations_limit = match (state.eval_operations_limit, limit) {
+                (Some(outer), Some(inner)) => Some(outer.min(inner)),
+                (outer, None) => outer,
+                (None, inner) => inner,
+            };
+        }
+
+        let mut isolated_scope = Scope::new();
+        let scope = if isolated { &mut isolated_scope } else { scope };
 
         // Evaluate the AST
-        let (result, operations) = self
-            .eval_ast_with_scope_raw(scope, &ast)
-            .map_err(|err| err.new_position(pos))?;
+        let result = ast
+            .statements()
+            .iter()
+            .try_fold(().into(), |_, stmt| {
+                self.eval_stmt(scope, &mut eval_state, stmt, level)
+            })
+            .or_else(|err| match *err {
+                EvalAltResult::Return(out, _) => Ok(out),
+                _ => Err(err),
+            })
+            .map_err(|err| err.new_position(pos));
+
+        state.operations = eval_state.operations;
+        state.modules = eval_state.modules;
+        state.call_depth = eval_state.call_depth;
+
+        #[cfg(not(feature = "unchecked"))]
+        #[cfg(not(feature = "no_std"))]
+        {
+            state.deadline = eval_state.deadline;
+        }
 
-        state.operations += operations;
+        let result = result?;
         self.inc_operations(state, pos)?;
 
         return Ok(result);
@@ -931,6 +2059,7 @@ impl Engine {
         rhs: &Expr,
         idx_values: &mut StaticVec<Dynamic>,
         is_index: bool,
+        is_optional: bool,
         op_pos: Position,
         level: usize,
         mut new_val: Option<Dynamic>,
@@ -940,6 +2069,11 @@ impl Engine {
         // Get a reference to the mutation target Dynamic
         let obj = target.as_mut();
 
+        // ()?.rhs or ()?[rhs] - short-circuit the rest of the chain
+        if is_optional && obj.is::<()>() {
+            return Ok((Default::default(), false));
+        }
+
         // Pop the last index value
         let mut idx_val = idx_values.pop();
 
@@ -953,16 +2087,26 @@ impl Engine {
                         .get_indexed_mut(state, obj, is_ref, idx_val, pos, op_pos, false)?;
 
                     self.eval_dot_index_chain_helper(
-                        state, this_ptr, &x.1, idx_values, is_idx, x.2, level, new_val,
+                        state, this_ptr, &x.1, idx_values, is_idx, x.3, x.2, level, new_val,
                     )
                 }
                 // xxx[rhs] = new_val
                 _ if new_val.is_some() => {
                     let pos = rhs.position();
+                    let new_val = new_val.unwrap();
+
+                    #[cfg(not(feature = "unchecked"))]
+                    self.check_assigned_data_size(state, &new_val, idx_val.as_str().ok(), pos)?;
+
                     let this_ptr = &mut self
                         .get_indexed_mut(state, obj, is_ref, idx_val, pos, op_pos, true)?;
 
-                    this_ptr.set_value(new_val.unwrap(), rhs.position())?;
+                    this_ptr.set_value(new_val, rhs.position())?;
+
+                    // Assigning may add a property to an object map, or change the length of a string
+                    #[cfg(not(feature = "unchecked"))]
+                    self.check_changed_data_size(obj, pos)?;
+
                     Ok((Default::default(), true))
                 }
                 // xxx[rhs]
@@ -974,32 +2118,56 @@ impl Engine {
             match rhs {
                 // xxx.fn_name(arg_expr_list)
                 Expr::FnCall(x) if x.1.is_none() => {
-                    let ((name, pos), _, hash_fn_def, _, def_val) = x.as_ref();
+                    let ((name, pos), _, hash_fn_def, args_expr, def_val, _) = x.as_ref();
                     let def_val = def_val.as_ref();
 
-                    let mut arg_values: StaticVec<_> = once(obj)
-                        .chain(
-                            idx_val
-                                .downcast_mut::<StaticVec<Dynamic>>()
-                                .unwrap()
-                                .iter_mut(),
-                        )
-                        .collect();
+                    let idx_args =
+                        idx_val
+                            .downcast_mut::<StaticVec<Dynamic>>()
+                            .ok_or_else(|| {
+                                Box::new(EvalAltResult::ErrorInternal(
+                                    "method call arguments are not a list".into(),
+                                    *pos,
+                                ))
+                            })?;
+                    let mut arg_values: StaticVec<_> =
+                        once(obj).chain(idx_args.iter_mut()).collect();
                     let args = arg_values.as_mut();
 
-                    self.exec_fn_call(state, name, *hash_fn_def, args, is_ref, def_val, *pos, 0)
+                    // Spread arguments change the number of arguments
+                    let hash_fn_def = if args_expr.iter().any(Expr::is_spread) {
+                        let args_iter = repeat(EMPTY_TYPE_ID()).take(args.len());
+                        calc_fn_hash(empty(), name, args_iter)
+                    } else {
+                        *hash_fn_def
+                    };
+
+                    self.exec_fn_call(state, name, hash_fn_def, args, is_ref, def_val, *pos, 0)
                 }
                 // xxx.module::fn_name(...) - syntax error
-                Expr::FnCall(_) => unreachable!(),
+                Expr::FnCall(x) => Err(Box::new(EvalAltResult::ErrorDotExpr(
+                    "- module-qualified function call".into(),
+                    (x.0).1,
+                ))),
                 // {xxx:map}.id = ???
                 #[cfg(not(feature = "no_object"))]
                 Expr::Property(x) if obj.is::<Map>() && new_val.is_some() => {
                     let ((prop, _, _), pos) = x.as_ref();
+                    let new_val = new_val.unwrap();
+
+                    #[cfg(not(feature = "unchecked"))]
+                    self.check_assigned_data_size(state, &new_val, Some(prop), *pos)?;
+
                     let index = prop.clone().into();
                     let mut val =
                         self.get_indexed_mut(state, obj, is_ref, index, *pos, op_pos, true)?;
 
-                    val.set_value(new_val.unwrap(), rhs.position())?;
+                    val.set_value(new_val, rhs.position())?;
+
+                    // Assigning may add a property to the object map
+                    #[cfg(not(feature = "unchecked"))]
+                    self.check_changed_data_size(obj, *pos)?;
+
                     Ok((Default::default(), true))
                 }
                 // {xxx:map}.id
@@ -1044,7 +2212,7 @@ impl Engine {
                     };
 
                     self.eval_dot_index_chain_helper(
-                        state, &mut val, &x.1, idx_values, is_idx, x.2, level, new_val,
+                        state, &mut val, &x.1, idx_values, is_idx, x.3, x.2, level, new_val,
                     )
                 }
                 // xxx.idx_lhs[idx_expr] | xxx.dot_lhs.rhs
@@ -1066,7 +2234,7 @@ impl Engine {
                     let target = &mut val.into();
 
                     let (result, may_be_changed) = self.eval_dot_index_chain_helper(
-                        state, target, &x.1, idx_values, is_idx, x.2, level, new_val,
+                        state, target, &x.1, idx_values, is_idx, x.3, x.2, level, new_val,
                     )?;
 
                     // Feed the value back via a setter just in case it has been updated
@@ -1103,40 +2271,73 @@ impl Engine {
         dot_lhs: &Expr,
         dot_rhs: &Expr,
         is_index: bool,
+        is_optional: bool,
         op_pos: Position,
         level: usize,
         new_val: Option<Dynamic>,
     ) -> Result<Dynamic, Box<EvalAltResult>> {
         let idx_values = &mut StaticVec::new();
 
-        self.eval_indexed_chain(scope, state, dot_rhs, idx_values, 0, level)?;
+        self.eval_indexed_chain(scope, state, dot_rhs, idx_values, is_index, 0, level)?;
 
         match dot_lhs {
             // id.??? or id[???]
             Expr::Variable(x) => {
                 let ((name, pos), modules, hash_var, index) = x.as_ref();
-                let index = if state.always_search { None } else { *index };
                 let mod_and_hash = modules.as_ref().map(|m| (m, *hash_var));
-                let (target, typ) = search_scope(scope, &name, mod_and_hash, index, *pos)?;
+                let (target, typ) = search_scope(scope, state, &name, mod_and_hash, *index, *pos)?;
                 self.inc_operations(state, *pos)?;
 
-                // Constants cannot be modified
+                // Constants and modules cannot be modified
                 match typ {
-                    ScopeEntryType::Module => unreachable!(),
-                    ScopeEntryType::Constant if new_val.is_some() => {
+                    ScopeEntryType::Constant | ScopeEntryType::Module if new_val.is_some() => {
                         return Err(Box::new(EvalAltResult::ErrorAssignmentToConstant(
                             name.clone(),
                             *pos,
                         )));
                     }
-                    ScopeEntryType::Constant | ScopeEntryType::Normal => (),
+                    _ => (),
                 }
 
+                // Count the data added to the value by the chain
+                #[cfg(not(feature = "unchecked"))]
+                let (last_size, outer_growth) = (
+                    self.max_data_size
+                        .and_then(|_| state.take_data_size(target)),
+                    state.data_growth.replace(0),
+                );
+
                 let this_ptr = &mut target.into();
-                self.eval_dot_index_chain_helper(
-                    state, this_ptr, dot_rhs, idx_values, is_index, op_pos, level, new_val,
-                )
-                .map(|(v, _)| v)
+                let result = self
+                    .eval_dot_index_chain_helper(
+                        state,
+                        this_ptr,
+                        dot_rhs,
+                        idx_values,
+                        is_index,
+                        is_optional,
+                        op_pos,
+                        level,
+                        new_val,
+                    )
+                    .map(|(v, _)| v);
+
+                // Changing a value in place may grow it beyond the limits
+                #[cfg(not(feature = "unchecked"))]
+                {
+                    let growth = mem::replace(&mut state.data_growth, outer_growth);
+                    let result = result?;
+                    self.check_chain_data_size(
+                        state,
+                        this_ptr.as_mut(),
+                        last_size,
+                        growth,
+                        op_pos,
+                    )?;
+                    Ok(result)
+                }
+                #[cfg(feature = "unchecked")]
+                result
             }
             // {expr}.??? = ??? or {expr}[???] = ???
             expr if new_val.is_some() => {
@@ -1149,7 +2350,15 @@ impl Engine {
                 let val = self.eval_expr(scope, state, expr, level)?;
                 let this_ptr = &mut val.into();
                 self.eval_dot_index_chain_helper(
-                    state, this_ptr, dot_rhs, idx_values, is_index, op_pos, level, new_val,
+                    state,
+                    this_ptr,
+                    dot_rhs,
+                    idx_values,
+                    is_index,
+                    is_optional,
+                    op_pos,
+                    level,
+                    new_val,
                 )
                 .map(|(v, _)| v)
             }
@@ -1167,21 +2376,24 @@ impl Engine {
         state: &mut State,
         expr: &Expr,
         idx_values: &mut StaticVec<Dynamic>,
+        is_index: bool,
         size: usize,
         level: usize,
     ) -> Result<(), Box<EvalAltResult>> {
         self.inc_operations(state, expr.position())?;
 
         match expr {
-            Expr::FnCall(x) if x.1.is_none() => {
-                let arg_values =
-                    x.3.iter()
-                        .map(|arg_expr| self.eval_expr(scope, state, arg_expr, level))
-                        .collect::<Result<StaticVec<Dynamic>, _>>()?;
-
+            // Function calls in indices (e.g. `x[foo(1)]`) are evaluated normally
+            Expr::FnCall(x) if !is_index && x.1.is_none() => {
+                let arg_values = self.eval_args(scope, state, x.3.as_ref(), level)?;
                 idx_values.push(Dynamic::from(arg_values));
             }
-            Expr::FnCall(_) => unreachable!(),
+            Expr::FnCall(x) if !is_index => {
+                return Err(Box::new(EvalAltResult::ErrorDotExpr(
+                    "- module-qualified function call".into(),
+                    (x.0).1,
+                )))
+            }
             Expr::Property(_) => idx_values.push(()), // Store a placeholder - no need to copy the property name
             Expr::Index(x) | Expr::Dot(x) => {
                 // Evaluate in left-to-right order
@@ -1191,7 +2403,8 @@ impl Engine {
                 };
 
                 // Push in reverse order
-                self.eval_indexed_chain(scope, state, &x.1, idx_values, size, level)?;
+                let is_idx = matches!(expr, Expr::Index(_));
+                self.eval_indexed_chain(scope, state, &x.1, idx_values, is_idx, size, level)?;
 
                 idx_values.push(lhs_val);
             }
@@ -1214,27 +2427,36 @@ impl Engine {
     ) -> Result<Target<'a>, Box<EvalAltResult>> {
         self.inc_operations(state, op_pos)?;
 
+        // val_array[range]
+        #[cfg(not(feature = "no_index"))]
+        {
+            let slice = match val {
+                Dynamic(Union::Array(arr)) => {
+                    calc_slice(arr.len(), &idx).map(|range| (arr[range.clone()].to_vec(), range))
+                }
+                _ => None,
+            };
+
+            if let Some((items, range)) = slice {
+                return Ok(Target::ArraySlice(val, range, items.into()));
+            }
+        }
+
         match val {
             #[cfg(not(feature = "no_index"))]
             Dynamic(Union::Array(arr)) => {
+                let arr_len = arr.len();
+
                 // val_array[idx]
                 let index = idx
                     .as_int()
                     .map_err(|_| EvalAltResult::ErrorNumericIndexExpr(idx_pos))?;
 
-                let arr_len = arr.len();
-
-                if index >= 0 {
-                    arr.get_mut(index as usize)
-                        .map(Target::from)
-                        .ok_or_else(|| {
-                            Box::new(EvalAltResult::ErrorArrayBounds(arr_len, index, idx_pos))
-                        })
-                } else {
-                    Err(Box::new(EvalAltResult::ErrorArrayBounds(
-                        arr_len, index, idx_pos,
-                    )))
-                }
+                calc_index(arr_len, index)
+                    .map(move |offset| Target::from(&mut arr[offset]))
+                    .ok_or_else(|| {
+                        Box::new(EvalAltResult::ErrorArrayBounds(arr_len, index, idx_pos))
+                    })
             }
 
             #[cfg(not(feature = "no_object"))]
@@ -1255,23 +2477,24 @@ impl Engine {
 
             #[cfg(not(feature = "no_index"))]
             Dynamic(Union::Str(s)) => {
-                // val_string[idx]
                 let chars_len = s.chars().count();
-                let index = idx
-                    .as_int()
-                    .map_err(|_| EvalAltResult::ErrorNumericIndexExpr(idx_pos))?;
 
-                if index >= 0 {
-                    let offset = index as usize;
-                    let ch = s.chars().nth(offset).ok_or_else(|| {
-                        Box::new(EvalAltResult::ErrorStringBounds(chars_len, index, idx_pos))
-                    })?;
-                    Ok(Target::StringChar(val, offset, ch.into()))
-                } else {
-                    Err(Box::new(EvalAltResult::ErrorStringBounds(
-                        chars_len, index, idx_pos,
-                    )))
+                // val_string[range]
+                if let Some(range) = calc_slice(chars_len, &idx) {
+                    let sub: String = s.chars().skip(range.start).take(range.len()).collect();
+                    return Ok(sub.into());
                 }
+
+                // val_string[idx]
+                let index = idx
+                    .as_int()
+                    .map_err(|_| EvalAltResult::ErrorNumericIndexExpr(idx_pos))?;
+
+                let offset = calc_index(chars_len, index).ok_or_else(|| {
+                    Box::new(EvalAltResult::ErrorStringBounds(chars_len, index, idx_pos))
+                })?;
+                let ch = s.chars().nth(offset).unwrap_or_default();
+                Ok(Target::StringChar(val, offset, ch.into()))
             }
 
             _ => {
@@ -1300,6 +2523,20 @@ impl Engine {
         let mut lhs_value = self.eval_expr(scope, state, lhs, level)?;
         let rhs_value = self.eval_expr(scope, state, rhs, level)?;
 
+        // Only allows INT in ranges
+        if let Some(range) = rhs_value.downcast_ref::<Range<INT>>() {
+            return match lhs_value {
+                Dynamic(Union::Int(n)) => Ok(range.contains(&n).into()),
+                _ => Err(Box::new(EvalAltResult::ErrorInExpr(lhs.position()))),
+            };
+        }
+        if let Some(range) = rhs_value.downcast_ref::<RangeInclusive<INT>>() {
+            return match lhs_value {
+                Dynamic(Union::Int(n)) => Ok(range.contains(&n).into()),
+                _ => Err(Box::new(EvalAltResult::ErrorInExpr(lhs.position()))),
+            };
+        }
+
         match rhs_value {
             #[cfg(not(feature = "no_index"))]
             Dynamic(Union::Array(mut rhs_value)) => {
@@ -1343,6 +2580,30 @@ impl Engine {
         }
     }
 
+    /// Evaluate the arguments of a function call, expanding spread arguments (i.e. `..expr`).
+    fn eval_args(
+        &self,
+        scope: &mut Scope,
+        state: &mut State,
+        args_expr: &[Expr],
+        level: usize,
+    ) -> Result<StaticVec<Dynamic>, Box<EvalAltResult>> {
+        let mut arg_values = StaticVec::new();
+
+        for expr in args_expr {
+            match expr {
+                #[cfg(not(feature = "no_index"))]
+                Expr::Spread(x) => match self.eval_expr(scope, state, &x.0, level)? {
+                    Dynamic(Union::Array(arr)) => arr.into_iter().for_each(|v| arg_values.push(v)),
+                    _ => return Err(Box::new(EvalAltResult::ErrorArrayMismatch(x.0.position()))),
+                },
+                expr => arg_values.push(self.eval_expr(scope, state, expr, level)?),
+            }
+        }
+
+        Ok(arg_values)
+    }
+
     /// Evaluate an expression
     fn eval_expr(
         &self,
@@ -1357,16 +2618,34 @@ impl Engine {
             Expr::IntegerConstant(x) => Ok(x.0.into()),
             #[cfg(not(feature = "no_float"))]
             Expr::FloatConstant(x) => Ok(x.0.into()),
-            Expr::StringConstant(x) => Ok(x.0.to_string().into()),
+            Expr::StringConstant(x) => {
+                #[cfg(not(feature = "unchecked"))]
+                {
+                    if let Some(max) = self.max_string_size {
+                        if x.0.len() > max.get() {
+                            return Err(Box::new(EvalAltResult::ErrorDataTooLarge(
+                                "Length of string".into(),
+                                max.get(),
+                                x.0.len(),
+                                x.1,
+                            )));
+                        }
+                    }
+                }
+
+                Ok(x.0.to_string().into())
+            }
             Expr::CharConstant(x) => Ok(x.0.into()),
             Expr::Variable(x) => {
                 let ((name, pos), modules, hash_var, index) = x.as_ref();
-                let index = if state.always_search { None } else { *index };
                 let mod_and_hash = modules.as_ref().map(|m| (m, *hash_var));
-                let (val, _) = search_scope(scope, name, mod_and_hash, index, *pos)?;
+                let (val, _) = search_scope(scope, state, name, mod_and_hash, *index, *pos)?;
                 Ok(val.clone())
             }
-            Expr::Property(_) => unreachable!(),
+            Expr::Property(x) => Err(Box::new(EvalAltResult::ErrorInternal(
+                "property access without an object".into(),
+                x.1,
+            ))),
 
             // Statement block
             Expr::Stmt(stmt) => self.eval_stmt(scope, state, &stmt.0, level),
@@ -1380,21 +2659,24 @@ impl Engine {
                     // name = rhs
                     Expr::Variable(x) => {
                         let ((name, pos), modules, hash_var, index) = x.as_ref();
-                        let index = if state.always_search { None } else { *index };
                         let mod_and_hash = modules.as_ref().map(|m| (m, *hash_var));
-                        let (lhs_ptr, typ) = search_scope(scope, name, mod_and_hash, index, *pos)?;
+                        let (lhs_ptr, typ) =
+                            search_scope(scope, state, name, mod_and_hash, *index, *pos)?;
                         self.inc_operations(state, *pos)?;
 
                         match typ {
-                            ScopeEntryType::Constant => Err(Box::new(
+                            // Modules cannot be assigned to either
+                            ScopeEntryType::Constant | ScopeEntryType::Module => Err(Box::new(
                                 EvalAltResult::ErrorAssignmentToConstant(name.clone(), *pos),
                             )),
                             ScopeEntryType::Normal => {
+                                // Forget the estimated size of the value replaced
+                                #[cfg(not(feature = "unchecked"))]
+                                state.take_data_size(lhs_ptr);
+
                                 *lhs_ptr = rhs_val;
                                 Ok(Default::default())
                             }
-                            // End variable cannot be a module
-                            ScopeEntryType::Module => unreachable!(),
                         }
                     }
                     // idx_lhs[idx_expr] = rhs
@@ -1402,7 +2684,7 @@ impl Engine {
                     Expr::Index(x) => {
                         let new_val = Some(rhs_val);
                         self.eval_dot_index_chain(
-                            scope, state, &x.0, &x.1, true, x.2, level, new_val,
+                            scope, state, &x.0, &x.1, true, x.3, x.2, level, new_val,
                         )
                     }
                     // dot_lhs.dot_rhs = rhs
@@ -1410,7 +2692,7 @@ impl Engine {
                     Expr::Dot(x) => {
                         let new_val = Some(rhs_val);
                         self.eval_dot_index_chain(
-                            scope, state, &x.0, &x.1, false, op_pos, level, new_val,
+                            scope, state, &x.0, &x.1, false, x.3, op_pos, level, new_val,
                         )
                     }
                     // Error assignment to constant
@@ -1430,54 +2712,85 @@ impl Engine {
             // lhs[idx_expr]
             #[cfg(not(feature = "no_index"))]
             Expr::Index(x) => {
-                self.eval_dot_index_chain(scope, state, &x.0, &x.1, true, x.2, level, None)
+                self.eval_dot_index_chain(scope, state, &x.0, &x.1, true, x.3, x.2, level, None)
             }
 
             // lhs.dot_rhs
             #[cfg(not(feature = "no_object"))]
             Expr::Dot(x) => {
-                self.eval_dot_index_chain(scope, state, &x.0, &x.1, false, x.2, level, None)
+                self.eval_dot_index_chain(scope, state, &x.0, &x.1, false, x.3, x.2, level, None)
             }
 
             #[cfg(not(feature = "no_index"))]
-            Expr::Array(x) => Ok(Dynamic(Union::Array(Box::new(
-                x.0.iter()
-                    .map(|item| self.eval_expr(scope, state, item, level))
-                    .collect::<Result<Vec<_>, _>>()?,
-            )))),
+            Expr::Array(x) => {
+                let arr = Dynamic(Union::Array(Box::new(
+                    x.0.iter()
+                        .map(|item| self.eval_expr(scope, state, item, level))
+                        .collect::<Result<Vec<_>, _>>()?,
+                )));
+
+                #[cfg(not(feature = "unchecked"))]
+                self.check_data_size(&arr, x.1)?;
+
+                Ok(arr)
+            }
 
             #[cfg(not(feature = "no_object"))]
-            Expr::Map(x) => Ok(Dynamic(Union::Map(Box::new(
-                x.0.iter()
-                    .map(|((key, _), expr)| {
-                        self.eval_expr(scope, state, expr, level)
-                            .map(|val| (key.clone(), val))
-                    })
-                    .collect::<Result<HashMap<_, _>, _>>()?,
-            )))),
+            Expr::Map(x) => {
+                let map = Dynamic(Union::Map(Box::new(
+                    x.0.iter()
+                        .map(|((key, _), expr)| {
+                            self.eval_expr(scope, state, expr, level)
+                                .map(|val| (key.clone(), val))
+                        })
+                        .collect::<Result<HashMap<_, _>, _>>()?,
+                )));
+
+                #[cfg(not(feature = "unchecked"))]
+                self.check_data_size(&map, x.1)?;
+
+                Ok(map)
+            }
 
             // Normal function call
             Expr::FnCall(x) if x.1.is_none() => {
-                let ((name, pos), _, hash_fn_def, args_expr, def_val) = x.as_ref();
+                let ((name, pos), _, hash_fn_def, args_expr, def_val, named_args) = x.as_ref();
                 let def_val = def_val.as_ref();
 
-                let mut arg_values = args_expr
-                    .iter()
-                    .map(|expr| self.eval_expr(scope, state, expr, level))
-                    .collect::<Result<StaticVec<_>, _>>()?;
+                let mut arg_values = self.eval_args(scope, state, args_expr.as_ref(), level)?;
+
+                // Spread arguments change the number of arguments
+                let hash_fn_def = if args_expr.iter().any(Expr::is_spread) {
+                    let args_iter = repeat(EMPTY_TYPE_ID()).take(arg_values.len());
+                    calc_fn_hash(empty(), name, args_iter)
+                } else {
+                    *hash_fn_def
+                };
+
+                if !named_args.is_empty() {
+                    let fn_lib = state.fn_lib;
+                    let fn_def = fn_lib.get_function_by_signature(name, arg_values.len(), false);
+                    return self.call_fn_with_named_args(
+                        state, name, fn_def, arg_values, named_args, *pos, level,
+                    );
+                }
 
                 let mut args: StaticVec<_> = arg_values.iter_mut().collect();
 
-                if name == KEYWORD_EVAL && args.len() == 1 && args.get(0).is::<String>() {
+                if name == KEYWORD_EVAL
+                    && self.eval_mode != EvalMode::Disabled
+                    && args.len() == 1
+                    && args.get(0).is::<String>()
+                {
                     let hash_fn = calc_fn_hash(empty(), name, once(TypeId::of::<String>()));
 
-                    if !self.has_override(state, (hash_fn, *hash_fn_def)) {
+                    if !self.has_override(state, (hash_fn, hash_fn_def)) {
                         // eval - only in function call style
                         let prev_len = scope.len();
                         let pos = args_expr.get(0).position();
 
                         // Evaluate the text string as a script
-                        let result = self.eval_script_expr(scope, state, args.pop(), pos);
+                        let result = self.eval_script_expr(scope, state, args.pop(), pos, level);
 
                         if scope.len() != prev_len {
                             // IMPORTANT! If the eval defines new variables in the current scope,
@@ -1491,44 +2804,56 @@ impl Engine {
 
                 // Normal function call - except for eval (handled above)
                 let args = args.as_mut();
-                self.exec_fn_call(state, name, *hash_fn_def, args, false, def_val, *pos, level)
+                self.exec_fn_call(state, name, hash_fn_def, args, false, def_val, *pos, level)
                     .map(|(v, _)| v)
             }
 
             // Module-qualified function call
             #[cfg(not(feature = "no_module"))]
             Expr::FnCall(x) if x.1.is_some() => {
-                let ((name, pos), modules, hash_fn_def, args_expr, def_val) = x.as_ref();
-                let modules = modules.as_ref().unwrap();
+                let ((name, pos), modules, hash_fn_def, args_expr, def_val, named_args) =
+                    x.as_ref();
+                let modules = modules.as_ref().ok_or_else(|| {
+                    Box::new(EvalAltResult::ErrorInternal(
+                        "module-qualified call has no modules".into(),
+                        *pos,
+                    ))
+                })?;
+
+                let mut arg_values = self.eval_args(scope, state, args_expr.as_ref(), level)?;
+
+                // Spread arguments change the number of arguments
+                let hash_fn_def = if args_expr.iter().any(Expr::is_spread) {
+                    let qualifiers = modules.iter().map(|(m, _)| m.as_str());
+                    let args_iter = repeat(EMPTY_TYPE_ID()).take(arg_values.len());
+                    calc_fn_hash(qualifiers, name, args_iter)
+                } else {
+                    *hash_fn_def
+                };
 
-                let mut arg_values = args_expr
-                    .iter()
-                    .map(|expr| self.eval_expr(scope, state, expr, level))
-                    .collect::<Result<StaticVec<_>, _>>()?;
+                let module = search_module(scope, state, modules)?;
 
-                let mut args: StaticVec<_> = arg_values.iter_mut().collect();
+                // Fall back to variadic functions
+                #[cfg(not(feature = "no_index"))]
+                let fn_def = match module.get_qualified_scripted_fn(hash_fn_def) {
+                    Some(fn_def) => Some(fn_def),
+                    None => module.get_qualified_variadic_fn(modules, name, arg_values.len()),
+                };
+                #[cfg(feature = "no_index")]
+                let fn_def = module.get_qualified_scripted_fn(hash_fn_def);
 
-                let (id, root_pos) = modules.get(0); // First module
+                if !named_args.is_empty() {
+                    return self.call_fn_with_named_args(
+                        state, name, fn_def, arg_values, named_args, *pos, level,
+                    );
+                }
 
-                let module = if let Some(index) = modules.index() {
-                    scope
-                        .get_mut(scope.len() - index.get())
-                        .0
-                        .downcast_mut::<Module>()
-                        .unwrap()
-                } else {
-                    scope.find_module(id).ok_or_else(|| {
-                        Box::new(EvalAltResult::ErrorModuleNotFound(id.into(), *root_pos))
-                    })?
-                };
+                let mut args: StaticVec<_> = arg_values.iter_mut().collect();
 
                 // First search in script-defined functions (can override built-in)
-                if let Some(fn_def) = module.get_qualified_scripted_fn(*hash_fn_def) {
+                if let Some(fn_def) = fn_def {
                     let args = args.as_mut();
-                    let (result, state2) =
-                        self.call_script_fn(None, *state, name, fn_def, args, *pos, level)?;
-                    *state = state2;
-                    Ok(result)
+                    self.call_script_fn(None, state, name, fn_def, args, *pos, level)
                 } else {
                     // Then search in Rust functions
                     self.inc_operations(state, *pos)?;
@@ -1540,11 +2865,11 @@ impl Engine {
                     //    the actual list of parameter `TypeId`'.s
                     let hash_fn_args = calc_fn_hash(empty(), "", args.iter().map(|a| a.type_id()));
                     // 3) The final hash is the XOR of the two hashes.
-                    let hash_fn_native = *hash_fn_def ^ hash_fn_args;
+                    let hash_fn_native = hash_fn_def ^ hash_fn_args;
 
                     match module.get_qualified_fn(name, hash_fn_native) {
                         Ok(func) => func
-                            .call(args.as_mut())
+                            .call_with_context(&mut self.call_context(), args.as_mut())
                             .map_err(|err| err.new_position(*pos)),
                         Err(_) if def_val.is_some() => Ok(def_val.clone().unwrap()),
                         Err(err) => Err(err),
@@ -1590,11 +2915,108 @@ impl Engine {
                 .into())
             }
 
+            Expr::Coalesce(x) => {
+                let (lhs, rhs, _) = x.as_ref();
+                let value = self.eval_expr(scope, state, lhs, level)?;
+
+                // Short-circuit unless the left-hand side is ()
+                if value.is::<()>() {
+                    self.eval_expr(scope, state, rhs, level)
+                } else {
+                    Ok(value)
+                }
+            }
+
+            Expr::Cast(x) => {
+                let (expr, typ, pos) = x.as_ref();
+                let value = self.eval_expr(scope, state, expr, level)?;
+                self.cast_to_type(value, typ, *pos)
+            }
+
             Expr::True(_) => Ok(true.into()),
             Expr::False(_) => Ok(false.into()),
             Expr::Unit(_) => Ok(().into()),
 
-            _ => unreachable!(),
+            _ => Err(Box::new(EvalAltResult::ErrorInternal(
+                "unexpected expression".into(),
+                expr.position(),
+            ))),
+        }
+    }
+
+    /// Bind the variables in a destructuring pattern to the parts of a value,
+    /// pushing them onto the `Scope` in the same order as `Pattern::for_each_name`.
+    ///
+    /// Missing items or properties bind to `()`.
+    fn bind_pattern<'s>(
+        &self,
+        scope: &mut Scope<'s>,
+        state: &State,
+        pattern: &Pattern,
+        value: Dynamic,
+    ) -> Result<(), Box<EvalAltResult>> {
+        match pattern {
+            Pattern::Variable(x) => {
+                let var_name = unsafe_cast_var_name_to_lifetime(&x.0, state);
+                scope.push_dynamic_value(var_name, ScopeEntryType::Normal, value, false);
+                Ok(())
+            }
+
+            #[cfg(not(feature = "no_index"))]
+            Pattern::Array(x) => {
+                let (items, rest, pos) = x.as_ref();
+
+                let typ = value.type_name();
+                let arr = value.try_cast::<Array>().ok_or_else(|| {
+                    Box::new(EvalAltResult::ErrorPatternMismatch(
+                        "Array".into(),
+                        self.map_type_name(typ).into(),
+                        *pos,
+                    ))
+                })?;
+                let mut values = arr.into_iter();
+
+                for item in items.iter() {
+                    let value = values.next().unwrap_or_default();
+                    self.bind_pattern(scope, state, item, value)?;
+                }
+
+                if let Some((name, _)) = rest {
+                    let var_name = unsafe_cast_var_name_to_lifetime(name, state);
+                    scope.push(var_name, values.collect::<Array>());
+                }
+
+                Ok(())
+            }
+
+            #[cfg(not(feature = "no_object"))]
+            Pattern::Map(x) => {
+                let (props, pos) = x.as_ref();
+
+                let typ = value.type_name();
+                let map = value.try_cast::<Map>().ok_or_else(|| {
+                    Box::new(EvalAltResult::ErrorPatternMismatch(
+                        "Object map".into(),
+                        self.map_type_name(typ).into(),
+                        *pos,
+                    ))
+                })?;
+
+                for ((prop, _), item) in props.iter() {
+                    let value = map.get(prop).cloned().unwrap_or_default();
+                    self.bind_pattern(scope, state, item, value)?;
+                }
+
+                Ok(())
+            }
+
+            // Key/value pairs only appear in `for` loops over object maps
+            #[cfg(not(feature = "no_object"))]
+            Pattern::Pair(x) => Err(Box::new(EvalAltResult::ErrorPatternMismatch(
+                "Key/value pair".into(),
+                self.map_type_name(value.type_name()).into(),
+                x.2,
+            ))),
         }
     }
 
@@ -1680,6 +3102,28 @@ impl Engine {
                 }
             },
 
+            // Do loop
+            Stmt::Do(x) => loop {
+                let (body, expr, is_until) = x.as_ref();
+
+                match self.eval_stmt(scope, state, body, level) {
+                    Ok(_) => (),
+                    Err(err) => match *err {
+                        EvalAltResult::ErrorLoopBreak(false, _) => (),
+                        EvalAltResult::ErrorLoopBreak(true, _) => return Ok(Default::default()),
+                        _ => return Err(err),
+                    },
+                }
+
+                match self.eval_expr(scope, state, expr, level)?.as_bool() {
+                    Ok(guard) if guard != *is_until => (),
+                    Ok(_) => return Ok(Default::default()),
+                    Err(_) => {
+                        return Err(Box::new(EvalAltResult::ErrorLogicGuard(expr.position())))
+                    }
+                }
+            },
+
             // Loop statement
             Stmt::Loop(body) => loop {
                 match self.eval_stmt(scope, state, body, level) {
@@ -1692,25 +3136,123 @@ impl Engine {
                 }
             },
 
+            // For loop over the property names and values of an object map
+            #[cfg(not(feature = "no_object"))]
+            Stmt::For(x) if matches!(x.0, Pattern::Pair(_)) => {
+                let (pattern, expr, stmt) = x.as_ref();
+                let ((key_name, _), (value_name, _), pos) = match pattern {
+                    Pattern::Pair(p) => p.as_ref(),
+                    _ => {
+                        return Err(Box::new(EvalAltResult::ErrorInternal(
+                            "for loop pattern is not a key/value pair".into(),
+                            expr.position(),
+                        )))
+                    }
+                };
+                let map = self.eval_expr(scope, state, expr, level)?;
+                let typ = map.type_name();
+                let map = map.try_cast::<Map>().ok_or_else(|| {
+                    Box::new(EvalAltResult::ErrorPatternMismatch(
+                        "Key/value pair".into(),
+                        self.map_type_name(typ).into(),
+                        *pos,
+                    ))
+                })?;
+
+                // Add the loop variables
+                let key_name = unsafe_cast_var_name_to_lifetime(key_name, state);
+                let value_name = unsafe_cast_var_name_to_lifetime(value_name, state);
+                scope.push(key_name, ());
+                scope.push(value_name, ());
+                let index = scope.len() - 2;
+                state.scope_level += 1;
+
+                for (key, value) in map {
+                    let (key_var, _) = scope
+                        .get_mut(index)
+                        .ok_or_else(|| invalid_scope_index(stmt.position()))?;
+                    *key_var = key.into();
+
+                    let (value_var, _) = scope
+                        .get_mut(index + 1)
+                        .ok_or_else(|| invalid_scope_index(stmt.position()))?;
+                    *value_var = value;
+
+                    self.inc_operations(state, stmt.position())?;
+
+                    match self.eval_stmt(scope, state, stmt, level) {
+                        Ok(_) => (),
+                        Err(err) => match *err {
+                            EvalAltResult::ErrorLoopBreak(false, _) => (),
+                            EvalAltResult::ErrorLoopBreak(true, _) => break,
+                            _ => return Err(err),
+                        },
+                    }
+                }
+
+                scope.rewind(index);
+                state.scope_level -= 1;
+                Ok(Default::default())
+            }
+
             // For loop
             Stmt::For(x) => {
-                let (name, expr, stmt) = x.as_ref();
+                let (pattern, expr, stmt) = x.as_ref();
                 let iter_type = self.eval_expr(scope, state, expr, level)?;
                 let tid = iter_type.type_id();
 
+                // Run the body of a generator up to each `yield` in turn
+                #[cfg(not(feature = "no_function"))]
+                {
+                    if iter_type.is::<Generator>() {
+                        let mut generator = iter_type
+                            .try_cast::<Generator>()
+                            .ok_or_else(|| {
+                                Box::new(EvalAltResult::ErrorInternal(
+                                    "generator value is not a generator".into(),
+                                    expr.position(),
+                                ))
+                            })?
+                            .start();
+                        let prev_len = scope.len();
+                        state.scope_level += 1;
+
+                        while let Some(loop_var) =
+                            self.resume_generator(state, &mut generator, level)?
+                        {
+                            // Bind the loop variables afresh for each item
+                            scope.rewind(prev_len);
+                            self.bind_pattern(scope, state, pattern, loop_var)?;
+                            self.inc_operations(state, stmt.position())?;
+
+                            match self.eval_stmt(scope, state, stmt, level) {
+                                Ok(_) => (),
+                                Err(err) => match *err {
+                                    EvalAltResult::ErrorLoopBreak(false, _) => (),
+                                    EvalAltResult::ErrorLoopBreak(true, _) => break,
+                                    _ => return Err(err),
+                                },
+                            }
+                        }
+
+                        scope.rewind(prev_len);
+                        state.scope_level -= 1;
+                        return Ok(Default::default());
+                    }
+                }
+
                 if let Some(iter_fn) = self
                     .global_module
                     .get_iter(tid)
                     .or_else(|| self.packages.get_iter(tid))
                 {
-                    // Add the loop variable
-                    let var_name = unsafe_cast_var_name_to_lifetime(name, &state);
-                    scope.push(var_name, ());
-                    let index = scope.len() - 1;
+                    let prev_len = scope.len();
                     state.scope_level += 1;
 
                     for loop_var in iter_fn(iter_type) {
-                        *scope.get_mut(index).0 = loop_var;
+                        // Bind the loop variables afresh for each item
+                        scope.rewind(prev_len);
+                        self.bind_pattern(scope, state, pattern, loop_var)?;
                         self.inc_operations(state, stmt.position())?;
 
                         match self.eval_stmt(scope, state, stmt, level) {
@@ -1723,7 +3265,7 @@ impl Engine {
                         }
                     }
 
-                    scope.rewind(scope.len() - 1);
+                    scope.rewind(prev_len);
                     state.scope_level -= 1;
                     Ok(Default::default())
                 } else {
@@ -1760,11 +3302,15 @@ impl Engine {
             }
 
             // Empty throw
-            Stmt::ReturnWithVal(x) if (x.0).0 == ReturnType::Exception => {
+            Stmt::ReturnWithVal(x) => {
                 Err(Box::new(EvalAltResult::ErrorRuntime("".into(), (x.0).1)))
             }
 
-            Stmt::ReturnWithVal(_) => unreachable!(),
+            // Yield statements only run in the bodies of generators, via `Engine::resume_generator`
+            Stmt::Yield(x) => Err(Box::new(EvalAltResult::ErrorInternal(
+                "yield outside a generator".into(),
+                x.1,
+            ))),
 
             // Let statement
             Stmt::Let(x) if x.1.is_some() => {
@@ -1782,6 +3328,14 @@ impl Engine {
                 Ok(Default::default())
             }
 
+            // Let statement with a destructuring pattern
+            Stmt::LetPattern(x) => {
+                let (pattern, expr) = x.as_ref();
+                let val = self.eval_expr(scope, state, expr, level)?;
+                self.bind_pattern(scope, state, pattern, val)?;
+                Ok(Default::default())
+            }
+
             // Const statement
             Stmt::Const(x) if x.1.is_constant() => {
                 let ((var_name, _), expr) = x.as_ref();
@@ -1792,12 +3346,18 @@ impl Engine {
             }
 
             // Const expression not constant
-            Stmt::Const(_) => unreachable!(),
+            Stmt::Const(x) => Err(Box::new(EvalAltResult::ErrorInternal(
+                "constant value is not a constant expression".into(),
+                (x.0).1,
+            ))),
 
             // Import statement
             Stmt::Import(x) => {
                 #[cfg(feature = "no_module")]
-                unreachable!();
+                return Err(Box::new(EvalAltResult::ErrorInternal(
+                    "import statement with modules disabled".into(),
+                    (x.1).1,
+                )));
 
                 #[cfg(not(feature = "no_module"))]
                 {
@@ -1815,14 +3375,23 @@ impl Engine {
                         .try_cast::<String>()
                     {
                         if let Some(resolver) = &self.module_resolver {
-                            // Use an empty scope to create a module
-                            let module =
-                                resolver.resolve(self, Scope::new(), &path, expr.position())?;
+                            state.modules += 1;
 
-                            let mod_name = unsafe_cast_var_name_to_lifetime(name, &state);
-                            scope.push_module(mod_name, module);
+                            // Use an empty scope to create a module, adding the resources used
+                            // to evaluate it to the current usage - even if it fails.
+                            // The module runs within the time limit and `eval` budget of the caller.
+                            let mut usage = state.usage();
+                            let result = resolver.resolve_with_usage(
+                                self,
+                                Scope::new(),
+                                &path,
+                                expr.position(),
+                                &mut usage,
+                            );
+                            state.set_usage(&usage);
 
-                            state.modules += 1;
+                            let mod_name = unsafe_cast_var_name_to_lifetime(name, &state);
+                            scope.push_module(mod_name, result?);
 
                             Ok(Default::default())
                         } else {
@@ -1851,7 +3420,9 @@ impl Engine {
                             .map(|(n, _)| n.clone())
                             .unwrap_or_else(|| id.clone());
 
-                        scope.set_entry_alias(index, alias);
+                        scope
+                            .set_entry_alias(index, alias)
+                            .ok_or_else(|| invalid_scope_index(*id_pos))?;
                     } else {
                         return Err(Box::new(EvalAltResult::ErrorVariableNotFound(
                             id.into(),
@@ -1864,6 +3435,381 @@ impl Engine {
         }
     }
 
+    /// Run the body of a generator up to the next `yield` statement, returning the value yielded.
+    ///
+    /// Returns `None` when the body finishes running.
+    #[cfg(not(feature = "no_function"))]
+    fn resume_generator(
+        &self,
+        state: &mut State,
+        generator: &mut GeneratorRun,
+        level: usize,
+    ) -> Result<Option<Dynamic>, Box<EvalAltResult>> {
+        if generator.finished {
+            return Ok(None);
+        }
+
+        // Check for stack overflow, e.g. a generator iterating over itself
+        if level > self.max_call_stack_depth {
+            return Err(Box::new(EvalAltResult::ErrorStackOverflow(generator.pos)));
+        }
+
+        let fn_def = generator.fn_def.clone();
+        let orig_scope_level = state.scope_level;
+        let orig_always_search = mem::replace(&mut state.always_search, generator.always_search);
+        state.scope_level += 1;
+        state.call_depth = state.call_depth.max(level + 1);
+
+        let result = self.resume_stmt(state, generator, &fn_def.body, 0, level + 1);
+
+        generator.always_search = mem::replace(&mut state.always_search, orig_always_search);
+        state.scope_level = orig_scope_level;
+
+        match result {
+            Ok(Some(value)) => Ok(Some(value)),
+            Ok(None) => {
+                generator.finished = true;
+                Ok(None)
+            }
+            Err(err) => {
+                generator.finished = true;
+
+                match *err {
+                    // A return statement ends the generator
+                    EvalAltResult::Return(_, _) => Ok(None),
+                    _ => Err(wrap_fn_call_error(&fn_def.name, err, generator.pos)),
+                }
+            }
+        }
+    }
+
+    /// Run a statement in the body of a generator up to the next `yield` statement,
+    /// returning the value yielded, or `None` if the statement finishes running.
+    ///
+    /// If the generator is suspended inside the statement, i.e. there is a frame at `depth`,
+    /// it carries on from where it is suspended.
+    #[cfg(not(feature = "no_function"))]
+    fn resume_stmt(
+        &self,
+        state: &mut State,
+        generator: &mut GeneratorRun,
+        stmt: &Stmt,
+        depth: usize,
+        level: usize,
+    ) -> Result<Option<Dynamic>, Box<EvalAltResult>> {
+        let resuming = generator.frames.len() > depth;
+
+        if !resuming {
+            // Statements without yield run as usual
+            if !stmt.contains_yield() {
+                self.eval_stmt(&mut generator.scope, state, stmt, level)?;
+                return Ok(None);
+            }
+
+            self.inc_operations(state, stmt.position())?;
+        }
+
+        match stmt {
+            // Yield statement
+            Stmt::Yield(x) => {
+                // Carry on after the yield statement
+                if resuming {
+                    generator.frames.truncate(depth);
+                    return Ok(None);
+                }
+
+                let value = self.eval_expr(&mut generator.scope, state, &x.0, level)?;
+
+                // The return type of a generator is the type of the values it yields
+                let value = match &generator.fn_def.return_type {
+                    Some(typ) => self.cast_to_type(value, typ, x.0.position())?,
+                    None => value,
+                };
+
+                generator.frames.push(GeneratorFrame::Yield);
+                Ok(Some(value))
+            }
+
+            // Block scope
+            Stmt::Block(x) => {
+                let (mut index, scope_len) = match generator.frames.get(depth) {
+                    Some(GeneratorFrame::Block(index, scope_len)) => (*index, *scope_len),
+                    _ => {
+                        let scope_len = generator.scope.len();
+                        generator.frames.push(GeneratorFrame::Block(0, scope_len));
+                        (0, scope_len)
+                    }
+                };
+
+                let result = loop {
+                    if index >= x.0.len() {
+                        break Ok(None);
+                    }
+
+                    *generator
+                        .frames
+                        .get_mut(depth)
+                        .ok_or_else(|| missing_generator_frame(stmt.position()))? =
+                        GeneratorFrame::Block(index, scope_len);
+
+                    match self.resume_stmt(state, generator, x.0.get(index), depth + 1, level) {
+                        Ok(None) => index += 1,
+                        result => break result,
+                    }
+                };
+
+                if let Ok(Some(_)) = result {
+                    return result;
+                }
+
+                generator.scope.rewind(scope_len);
+                generator.frames.truncate(depth);
+                state.always_search = false;
+                result
+            }
+
+            // If-else statement
+            Stmt::IfThenElse(x) => {
+                let (expr, if_block, else_block) = x.as_ref();
+
+                let guard_val = match generator.frames.get(depth) {
+                    Some(GeneratorFrame::Branch(guard_val)) => *guard_val,
+                    _ => {
+                        let guard_val = self
+                            .eval_expr(&mut generator.scope, state, expr, level)?
+                            .as_bool()
+                            .map_err(|_| {
+                                Box::new(EvalAltResult::ErrorLogicGuard(expr.position()))
+                            })?;
+                        generator.frames.push(GeneratorFrame::Branch(guard_val));
+                        guard_val
+                    }
+                };
+
+                let result = match (guard_val, else_block) {
+                    (true, _) => self.resume_stmt(state, generator, if_block, depth + 1, level),
+                    (false, Some(stmt)) => {
+                        self.resume_stmt(state, generator, stmt, depth + 1, level)
+                    }
+                    (false, None) => Ok(None),
+                };
+
+                if let Ok(Some(_)) = result {
+                    return result;
+                }
+
+                generator.frames.truncate(depth);
+                result
+            }
+
+            // While, do and loop statements
+            Stmt::While(_) | Stmt::Do(_) | Stmt::Loop(_) => {
+                if !resuming {
+                    generator.frames.push(GeneratorFrame::Loop);
+                }
+
+                // Carry on inside the body if suspended there
+                let mut in_body = resuming;
+
+                loop {
+                    let body = match stmt {
+                        Stmt::While(x) if !in_body => {
+                            let expr = &x.0;
+
+                            match self
+                                .eval_expr(&mut generator.scope, state, expr, level)?
+                                .as_bool()
+                            {
+                                Ok(true) => &x.1,
+                                Ok(false) => break,
+                                Err(_) => {
+                                    return Err(Box::new(EvalAltResult::ErrorLogicGuard(
+                                        expr.position(),
+                                    )))
+                                }
+                            }
+                        }
+                        Stmt::While(x) => &x.1,
+                        Stmt::Do(x) => &x.0,
+                        Stmt::Loop(body) => body.as_ref(),
+                        _ => {
+                            return Err(Box::new(EvalAltResult::ErrorInternal(
+                                "statement is not a loop".into(),
+                                stmt.position(),
+                            )))
+                        }
+                    };
+
+                    match self.resume_stmt(state, generator, body, depth + 1, level) {
+                        Ok(Some(value)) => return Ok(Some(value)),
+                        Ok(None) => (),
+                        Err(err) => match *err {
+                            EvalAltResult::ErrorLoopBreak(false, _) => (),
+                            EvalAltResult::ErrorLoopBreak(true, _) => break,
+                            _ => return Err(err),
+                        },
+                    }
+
+                    generator.frames.truncate(depth + 1);
+                    in_body = false;
+
+                    if let Stmt::Do(x) = stmt {
+                        let (_, expr, is_until) = x.as_ref();
+
+                        match self
+                            .eval_expr(&mut generator.scope, state, expr, level)?
+                            .as_bool()
+                        {
+                            Ok(guard) if guard != *is_until => (),
+                            Ok(_) => break,
+                            Err(_) => {
+                                return Err(Box::new(EvalAltResult::ErrorLogicGuard(
+                                    expr.position(),
+                                )))
+                            }
+                        }
+                    }
+                }
+
+                generator.frames.truncate(depth);
+                Ok(None)
+            }
+
+            // For loop
+            Stmt::For(x) => {
+                let (pattern, expr, body) = x.as_ref();
+
+                if !resuming {
+                    let iter_type = self.eval_expr(&mut generator.scope, state, expr, level)?;
+                    let tid = iter_type.type_id();
+
+                    let items = match pattern {
+                        #[cfg(not(feature = "no_object"))]
+                        Pattern::Pair(p) => {
+                            let typ = iter_type.type_name();
+                            let map = iter_type.try_cast::<Map>().ok_or_else(|| {
+                                Box::new(EvalAltResult::ErrorPatternMismatch(
+                                    "Key/value pair".into(),
+                                    self.map_type_name(typ).into(),
+                                    p.2,
+                                ))
+                            })?;
+                            GeneratorLoopItems::Pairs(Box::new(map.into_iter()))
+                        }
+                        _ if iter_type.is::<Generator>() => {
+                            let inner = iter_type.try_cast::<Generator>().ok_or_else(|| {
+                                Box::new(EvalAltResult::ErrorInternal(
+                                    "generator value is not a generator".into(),
+                                    expr.position(),
+                                ))
+                            })?;
+                            GeneratorLoopItems::Generator(Box::new(inner.start()))
+                        }
+                        _ => match self
+                            .global_module
+                            .get_iter(tid)
+                            .or_else(|| self.packages.get_iter(tid))
+                        {
+                            Some(iter_fn) => GeneratorLoopItems::Iter(iter_fn(iter_type)),
+                            None => return Err(Box::new(EvalAltResult::ErrorFor(expr.position()))),
+                        },
+                    };
+
+                    let scope_len = generator.scope.len();
+                    generator.frames.push(GeneratorFrame::For(items, scope_len));
+                }
+
+                let scope_len = match generator.frames.get(depth) {
+                    Some(GeneratorFrame::For(_, scope_len)) => *scope_len,
+                    _ => {
+                        return Err(Box::new(EvalAltResult::ErrorInternal(
+                            "generator frame is not a for loop".into(),
+                            stmt.position(),
+                        )))
+                    }
+                };
+
+                // Carry on inside the body if suspended there
+                let mut in_body = resuming;
+
+                loop {
+                    if !in_body {
+                        // Bind the loop variables afresh for each item
+                        generator.scope.rewind(scope_len);
+
+                        let frame = generator
+                            .frames
+                            .get_mut(depth)
+                            .ok_or_else(|| missing_generator_frame(stmt.position()))?;
+
+                        let loop_var = match frame {
+                            GeneratorFrame::For(GeneratorLoopItems::Iter(iter), _) => iter.next(),
+                            #[cfg(not(feature = "no_object"))]
+                            GeneratorFrame::For(GeneratorLoopItems::Pairs(iter), _) => {
+                                match (iter.next(), pattern) {
+                                    (Some((key, value)), Pattern::Pair(p)) => {
+                                        let ((key_name, _), (value_name, _), _) = p.as_ref();
+                                        let key_name =
+                                            unsafe_cast_var_name_to_lifetime(key_name, state);
+                                        let value_name =
+                                            unsafe_cast_var_name_to_lifetime(value_name, state);
+                                        generator.scope.push(key_name, key);
+                                        generator.scope.push_dynamic(value_name, value);
+                                        Some(Default::default())
+                                    }
+                                    _ => None,
+                                }
+                            }
+                            GeneratorFrame::For(GeneratorLoopItems::Generator(inner), _) => {
+                                self.resume_generator(state, inner, level)?
+                            }
+                            _ => {
+                                return Err(Box::new(EvalAltResult::ErrorInternal(
+                                    "generator frame is not a for loop".into(),
+                                    stmt.position(),
+                                )))
+                            }
+                        };
+
+                        match (loop_var, pattern) {
+                            (None, _) => break,
+                            #[cfg(not(feature = "no_object"))]
+                            (Some(_), Pattern::Pair(_)) => (),
+                            (Some(loop_var), _) => {
+                                self.bind_pattern(&mut generator.scope, state, pattern, loop_var)?
+                            }
+                        }
+
+                        self.inc_operations(state, body.position())?;
+                    }
+
+                    match self.resume_stmt(state, generator, body, depth + 1, level) {
+                        Ok(Some(value)) => return Ok(Some(value)),
+                        Ok(None) => (),
+                        Err(err) => match *err {
+                            EvalAltResult::ErrorLoopBreak(false, _) => (),
+                            EvalAltResult::ErrorLoopBreak(true, _) => break,
+                            _ => return Err(err),
+                        },
+                    }
+
+                    generator.frames.truncate(depth + 1);
+                    in_body = false;
+                }
+
+                generator.scope.rewind(scope_len);
+                generator.frames.truncate(depth);
+                Ok(None)
+            }
+
+            // No other statements can contain yield statements
+            _ => Err(Box::new(EvalAltResult::ErrorInternal(
+                "statement cannot contain yield".into(),
+                stmt.position(),
+            ))),
+        }
+    }
+
     /// Check if the number of operations stay within limit.
     fn inc_operations(&self, state: &mut State, pos: Position) -> Result<(), Box<EvalAltResult>> {
         state.operations += 1;
@@ -1876,6 +3822,37 @@ impl Engine {
                     return Err(Box::new(EvalAltResult::ErrorTooManyOperations(pos)));
                 }
             }
+
+            // Guard against too many operations inside eval
+            if let Some(limit) = state.eval_operations_limit {
+                if state.operations > limit {
+                    return Err(Box::new(EvalAltResult::ErrorTooManyOperations(pos)));
+                }
+            }
+        }
+
+        // Terminate script if cancelled
+        if let Some(token) = &self.cancellation_token {
+            if token.is_cancelled() {
+                return Err(Box::new(EvalAltResult::ErrorTerminated(pos)));
+            }
+        }
+
+        #[cfg(not(feature = "unchecked"))]
+        #[cfg(not(feature = "no_std"))]
+        {
+            // Guard against running out of time
+            if let Some(duration) = self.max_duration {
+                let now = Instant::now();
+
+                match state.deadline {
+                    Some(deadline) if now > deadline => {
+                        return Err(Box::new(EvalAltResult::ErrorTimeout(pos)));
+                    }
+                    Some(_) => (),
+                    None => state.deadline = Some(now + duration),
+                }
+            }
         }
 
         // Report progress - only in steps
@@ -1889,6 +3866,206 @@ impl Engine {
         Ok(())
     }
 
+    /// Check if a data value, including all the values nested inside, stays within the size limits.
+    #[cfg(not(feature = "unchecked"))]
+    fn check_data_size(&self, value: &Dynamic, pos: Position) -> Result<(), Box<EvalAltResult>> {
+        if self.max_string_size.is_none()
+            && self.max_array_size.is_none()
+            && self.max_map_size.is_none()
+            && self.max_data_size.is_none()
+        {
+            return Ok(());
+        }
+
+        let (string_size, array_size, map_size, data_size) = calc_data_sizes(value);
+
+        self.check_sizes(string_size, array_size, map_size, data_size, pos)
+    }
+
+    /// Check if a data value changed in place stays within the size limits.
+    ///
+    /// Only the size of the value itself is checked, because the values nested inside
+    /// are checked when they are created.  Walking all of them after every change would make
+    /// building up a large value take quadratic time.  The total size of the data is checked
+    /// at the end of the dot/index chain instead (see `DataSize`).
+    #[cfg(not(feature = "unchecked"))]
+    fn check_changed_data_size(
+        &self,
+        value: &Dynamic,
+        pos: Position,
+    ) -> Result<(), Box<EvalAltResult>> {
+        let (string_size, array_size, map_size) = match &value.0 {
+            Union::Str(s) => (s.len(), 0, 0),
+            #[cfg(not(feature = "no_index"))]
+            Union::Array(arr) => (0, arr.len(), 0),
+            #[cfg(not(feature = "no_object"))]
+            Union::Map(map) => (0, 0, map.len()),
+            _ => (0, 0, 0),
+        };
+
+        self.check_sizes(string_size, array_size, map_size, 0, pos)
+    }
+
+    /// Calculate the number of bytes of data added to the first argument of a native Rust function
+    /// that changes it in place, or `None` if it is not known.
+    #[cfg(not(feature = "unchecked"))]
+    fn calc_data_growth(
+        &self,
+        hash_fn: u64,
+        value: &Dynamic,
+        string_len: Option<usize>,
+        added_data_size: Option<usize>,
+    ) -> Option<usize> {
+        // Pure functions do not change their arguments
+        if self.global_module.is_fn_pure(hash_fn) || self.packages.is_fn_pure(hash_fn) {
+            return Some(0);
+        }
+
+        match &value.0 {
+            Union::Str(s) => string_len.map(|len| s.len().saturating_sub(len)),
+            #[cfg(not(feature = "no_index"))]
+            Union::Array(_) => added_data_size,
+            #[cfg(not(feature = "no_object"))]
+            Union::Map(_) => added_data_size,
+            _ => Some(0),
+        }
+    }
+
+    /// Check a value about to be assigned inside a data value, and count its size, together with
+    /// the name of the property it is assigned to (if any), as data added to the value at the root
+    /// of the dot/index chain.
+    #[cfg(not(feature = "unchecked"))]
+    fn check_assigned_data_size(
+        &self,
+        state: &mut State,
+        value: &Dynamic,
+        prop: Option<&str>,
+        pos: Position,
+    ) -> Result<(), Box<EvalAltResult>> {
+        if self.max_string_size.is_none()
+            && self.max_array_size.is_none()
+            && self.max_map_size.is_none()
+            && self.max_data_size.is_none()
+        {
+            return Ok(());
+        }
+
+        let (string_size, array_size, map_size, data_size) = calc_data_sizes(value);
+        state.add_data_growth(Some(data_size + prop.map_or(0, str::len)));
+
+        self.check_sizes(string_size, array_size, map_size, data_size, pos)
+    }
+
+    /// Check the total size of the data value at the root of a dot/index chain after the chain
+    /// has been evaluated, given its estimated size before and the data added by the chain.
+    #[cfg(not(feature = "unchecked"))]
+    fn check_chain_data_size(
+        &self,
+        state: &mut State,
+        value: &Dynamic,
+        last: Option<DataSize>,
+        growth: Option<usize>,
+        pos: Position,
+    ) -> Result<(), Box<EvalAltResult>> {
+        let max = match self.max_data_size {
+            Some(max) => max.get(),
+            None => return Ok(()),
+        };
+
+        let key = match data_size_key(value) {
+            Some(key) => key,
+            // Other values are cheap to measure
+            None if growth == Some(0) => return Ok(()),
+            None => {
+                let size = calc_data_sizes(value).3;
+                return check_size_limit("Size of data", self.max_data_size, size, pos);
+            }
+        };
+
+        let estimate = match (last, growth) {
+            // Nothing added - the value was checked before
+            (None, Some(0)) => return Ok(()),
+            (Some(last), Some(0)) => Some(DataSize { key, ..last }),
+            (Some(last), Some(added)) => {
+                let size = last.size.saturating_add(added);
+
+                if size <= max && size <= last.measured.saturating_mul(2) {
+                    Some(DataSize { key, size, ..last })
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+
+        let entry = match estimate {
+            Some(entry) => entry,
+            None => {
+                let size = calc_data_sizes(value).3;
+                check_size_limit("Size of data", self.max_data_size, size, pos)?;
+                DataSize {
+                    key,
+                    size,
+                    measured: size,
+                }
+            }
+        };
+
+        state.keep_data_size(entry);
+        Ok(())
+    }
+
+    /// Check sizes of data against the size limits.
+    #[cfg(not(feature = "unchecked"))]
+    fn check_sizes(
+        &self,
+        string_size: usize,
+        array_size: usize,
+        map_size: usize,
+        data_size: usize,
+        pos: Position,
+    ) -> Result<(), Box<EvalAltResult>> {
+        let limits = [
+            ("Length of string", self.max_string_size, string_size),
+            ("Size of array", self.max_array_size, array_size),
+            ("Size of object map", self.max_map_size, map_size),
+            ("Size of data", self.max_data_size, data_size),
+        ];
+
+        for &(typ, max, size) in limits.iter() {
+            check_size_limit(typ, max, size, pos)?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the context of a call to a native Rust function, holding the size limits of the `Engine`.
+    pub(crate) fn call_context(&self) -> CallContext {
+        CallContext {
+            max_string_size: self.max_string_size,
+            max_array_size: self.max_array_size,
+            max_map_size: self.max_map_size,
+            max_data_size: self.max_data_size,
+            added_data_size: None,
+        }
+    }
+
+    /// Check a value against a type annotation, converting it if necessary.
+    pub(crate) fn cast_to_type(
+        &self,
+        value: Dynamic,
+        typ: &Type,
+        pos: Position,
+    ) -> Result<Dynamic, Box<EvalAltResult>> {
+        typ.cast(self, value).map_err(|value| {
+            Box::new(EvalAltResult::ErrorMismatchDataType(
+                typ.to_string(),
+                self.map_type_name(value.type_name()).into(),
+                pos,
+            ))
+        })
+    }
+
     /// Map a type_name into a pretty-print name
     pub(crate) fn map_type_name<'a>(&'a self, name: &'a str) -> &'a str {
         self.type_names
@@ -21,10 +21,22 @@ pub enum LexError {
     MalformedIdentifier(String),
     /// Bad keyword encountered when tokenizing the script text.
     ImproperKeyword(String),
+    /// A keyword, operator or identifier that has been disabled is encountered.
+    DisabledSymbol(String),
 }
 
 impl Error for LexError {}
 
+impl LexError {
+    /// Convert the `LexError` into a `ParseErrorType`.
+    pub(crate) fn to_parse_error_type(&self) -> ParseErrorType {
+        match self {
+            Self::DisabledSymbol(s) => ParseErrorType::DisabledSymbol(s.clone()),
+            err => ParseErrorType::BadInput(err.to_string()),
+        }
+    }
+}
+
 impl fmt::Display for LexError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -35,6 +47,7 @@ impl fmt::Display for LexError {
             Self::MalformedIdentifier(s) => write!(f, "Variable name is not proper: '{}'", s),
             Self::UnterminatedString => write!(f, "Open string is not terminated"),
             Self::ImproperKeyword(s) => write!(f, "{}", s),
+            Self::DisabledSymbol(s) => write!(f, "'{}' is disabled", s),
         }
     }
 }
@@ -78,6 +91,17 @@ pub enum ParseErrorType {
     VariableExpected,
     /// Missing an expression. Wrapped value is the expression type.
     ExprExpected(String),
+    /// Missing a type name in a type annotation.
+    TypeExpected,
+    /// A type annotation names a type that is neither built-in nor registered with the `Engine`.
+    /// Wrapped value is the type name.
+    UnknownType(String),
+    /// A value does not match a type annotation, found by the type checker.
+    /// Wrapped values are the expected type and the actual type.
+    MismatchedType(String, String),
+    /// No function accepts the types of the arguments in a function call, found by the type checker.
+    /// Wrapped value is the function signature with the argument types.
+    FnArgTypesMismatch(String),
     /// Defining a function `fn` in an appropriate place (e.g. inside another function).
     ///
     /// Never appears under the `no_function` feature.
@@ -94,10 +118,19 @@ pub enum ParseErrorType {
     ///
     /// Never appears under the `no_function` feature.
     FnDuplicatedParam(String, String),
+    /// A function definition has a parameter without a default value following a parameter with one.
+    /// Wrapped values are the function name and parameter name.
+    ///
+    /// Never appears under the `no_function` feature.
+    FnMissingParamDefault(String, String),
     /// A function definition is missing the body. Wrapped value is the function name.
     ///
     /// Never appears under the `no_function` feature.
     FnMissingBody(String),
+    /// A `yield` statement outside the body of a function, or inside a statement expression.
+    ///
+    /// Never appears under the `no_function` feature.
+    WrongYield,
     /// An export statement has duplicated names.
     ///
     /// Never appears under the `no_module` feature.
@@ -112,6 +145,12 @@ pub enum ParseErrorType {
     AssignmentToConstant(String),
     /// Break statement not inside a loop.
     LoopBreak,
+    /// A keyword, operator or function that has been disabled is used. Wrapped value is the symbol.
+    DisabledSymbol(String),
+    /// An expression is nested too deeply.
+    ExprTooDeep,
+    /// A statement block is nested too deeply.
+    StmtTooDeep,
 }
 
 impl ParseErrorType {
@@ -150,16 +189,25 @@ impl ParseError {
             ParseErrorType::PropertyExpected => "Expecting name of a property",
             ParseErrorType::VariableExpected => "Expecting name of a variable",
             ParseErrorType::ExprExpected(_) => "Expecting an expression",
+            ParseErrorType::TypeExpected => "Expecting name of a type",
+            ParseErrorType::UnknownType(_) => "Unknown type",
+            ParseErrorType::MismatchedType(_, _) => "Value does not match the type annotation",
+            ParseErrorType::FnArgTypesMismatch(_) => "No function accepts the types of the arguments",
             ParseErrorType::FnMissingName => "Expecting name in function declaration",
             ParseErrorType::FnMissingParams(_) => "Expecting parameters in function declaration",
             ParseErrorType::FnDuplicatedParam(_,_) => "Duplicated parameters in function declaration",
+            ParseErrorType::FnMissingParamDefault(_,_) => "Expecting default value for parameter in function declaration",
             ParseErrorType::FnMissingBody(_) => "Expecting body statement block for function declaration",
             ParseErrorType::WrongFnDefinition => "Function definitions must be at global level and cannot be inside a block or another function",
+            ParseErrorType::WrongYield => "Yield statement can only appear in the body of a function",
             ParseErrorType::DuplicatedExport(_) => "Duplicated variable/function in export statement",
             ParseErrorType::WrongExport => "Export statement can only appear at global level",
             ParseErrorType::AssignmentToCopy => "Only a copy of the value is change with this assignment",
             ParseErrorType::AssignmentToConstant(_) => "Cannot assign to a constant value.",
-            ParseErrorType::LoopBreak => "Break statement should only be used inside a loop"
+            ParseErrorType::LoopBreak => "Break statement should only be used inside a loop",
+            ParseErrorType::DisabledSymbol(_) => "Use of a disabled symbol",
+            ParseErrorType::ExprTooDeep => "Expression exceeds maximum nesting depth",
+            ParseErrorType::StmtTooDeep => "Statement block exceeds maximum nesting depth"
         }
     }
 }
@@ -191,6 +239,18 @@ impl fmt::Display for ParseError {
 
             ParseErrorType::ExprExpected(s) => write!(f, "Expecting {} expression", s)?,
 
+            ParseErrorType::UnknownType(s) => write!(f, "Unknown type '{}'", s)?,
+
+            ParseErrorType::MismatchedType(expected, actual) => write!(
+                f,
+                "Expecting a value of type '{}' but found '{}'",
+                expected, actual
+            )?,
+
+            ParseErrorType::FnArgTypesMismatch(s) => {
+                write!(f, "No function matches the argument types of '{}'", s)?
+            }
+
             ParseErrorType::FnMissingParams(s) => {
                 write!(f, "Expecting parameters for function '{}'", s)?
             }
@@ -203,6 +263,12 @@ impl fmt::Display for ParseError {
                 write!(f, "Duplicated parameter '{}' for function '{}'", arg, s)?
             }
 
+            ParseErrorType::FnMissingParamDefault(s, arg) => write!(
+                f,
+                "Parameter '{}' for function '{}' must have a default value because it follows an optional parameter",
+                arg, s
+            )?,
+
             ParseErrorType::DuplicatedExport(s) => write!(
                 f,
                 "Duplicated variable/function '{}' in export statement",
@@ -211,6 +277,8 @@ impl fmt::Display for ParseError {
 
             ParseErrorType::MissingToken(token, s) => write!(f, "Expecting '{}' {}", token, s)?,
 
+            ParseErrorType::DisabledSymbol(s) => write!(f, "'{}' is disabled", s)?,
+
             ParseErrorType::AssignmentToConstant(s) if s.is_empty() => {
                 write!(f, "{}", self.desc())?
             }
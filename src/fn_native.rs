@@ -1,7 +1,9 @@
 use crate::any::Dynamic;
+use crate::engine::check_size_limit;
 use crate::result::EvalAltResult;
+use crate::token::Position;
 
-use crate::stdlib::{boxed::Box, rc::Rc, sync::Arc};
+use crate::stdlib::{boxed::Box, num::NonZeroUsize, rc::Rc, sync::Arc};
 
 pub type FnCallArgs<'a> = [&'a mut Dynamic];
 
@@ -10,6 +12,13 @@ pub type FnAny = dyn Fn(&mut FnCallArgs) -> Result<Dynamic, Box<EvalAltResult>>
 #[cfg(not(feature = "sync"))]
 pub type FnAny = dyn Fn(&mut FnCallArgs) -> Result<Dynamic, Box<EvalAltResult>>;
 
+#[cfg(feature = "sync")]
+pub(crate) type FnWithContext =
+    dyn Fn(&mut CallContext, &mut FnCallArgs) -> Result<Dynamic, Box<EvalAltResult>> + Send + Sync;
+#[cfg(not(feature = "sync"))]
+pub(crate) type FnWithContext =
+    dyn Fn(&mut CallContext, &mut FnCallArgs) -> Result<Dynamic, Box<EvalAltResult>>;
+
 #[cfg(feature = "sync")]
 pub type IteratorFn = dyn Fn(Dynamic) -> Box<dyn Iterator<Item = Dynamic>> + Send + Sync;
 #[cfg(not(feature = "sync"))]
@@ -82,6 +91,79 @@ pub enum NativeFunctionABI {
     Method,
 }
 
+/// The context of a call to a native Rust function, holding the size limits of the calling `Engine`.
+///
+/// Only the functions in the built-in packages use it, to check sizes before growing strings,
+/// arrays and object maps.  It is not exported, so it is invisible to `NativeCallable` implementations
+/// outside this crate.
+#[derive(Debug, Clone, Default)]
+pub struct CallContext {
+    /// Maximum length of a string.
+    pub(crate) max_string_size: Option<NonZeroUsize>,
+    /// Maximum number of items in an array.
+    pub(crate) max_array_size: Option<NonZeroUsize>,
+    /// Maximum number of properties in an object map.
+    pub(crate) max_map_size: Option<NonZeroUsize>,
+    /// Maximum estimated size of a data value, in bytes.
+    pub(crate) max_data_size: Option<NonZeroUsize>,
+    /// Number of bytes of data added to the first argument by the function, if known.
+    pub(crate) added_data_size: Option<usize>,
+}
+
+impl CallContext {
+    /// Check if a string about to grow to `size` bytes stays within the size limit.
+    ///
+    /// Functions that grow strings, arrays or object maps check the new size before
+    /// allocating, so that oversized data is never built.
+    pub(crate) fn check_string_size(&self, size: usize) -> Result<(), Box<EvalAltResult>> {
+        check_size_limit(
+            "Length of string",
+            self.max_string_size,
+            size,
+            Position::none(),
+        )
+    }
+
+    /// Check if an array about to grow to `size` items stays within the size limit.
+    #[cfg(not(feature = "no_index"))]
+    pub(crate) fn check_array_size(&self, size: usize) -> Result<(), Box<EvalAltResult>> {
+        check_size_limit("Size of array", self.max_array_size, size, Position::none())
+    }
+
+    /// Check if an object map about to grow to `size` properties stays within the size limit.
+    #[cfg(not(feature = "no_object"))]
+    pub(crate) fn check_map_size(&self, size: usize) -> Result<(), Box<EvalAltResult>> {
+        check_size_limit(
+            "Size of object map",
+            self.max_map_size,
+            size,
+            Position::none(),
+        )
+    }
+
+    /// Check if adding data to the first argument stays within the limit on the total size of data,
+    /// and record the size added for the `Engine`.
+    ///
+    /// The size is calculated by `calc_size` only when the limit is set.  Only the size of the data
+    /// added is checked here; the `Engine` adds it to its estimate of the total size after the
+    /// function returns.
+    pub(crate) fn check_added_data_size(
+        &mut self,
+        calc_size: impl FnOnce() -> usize,
+    ) -> Result<(), Box<EvalAltResult>> {
+        if self.max_data_size.is_none() {
+            return Ok(());
+        }
+
+        let size = self
+            .added_data_size
+            .unwrap_or(0)
+            .saturating_add(calc_size());
+        self.added_data_size = Some(size);
+        check_size_limit("Size of data", self.max_data_size, size, Position::none())
+    }
+}
+
 /// Trait implemented by all native Rust functions that are callable by Rhai.
 #[cfg(not(feature = "sync"))]
 pub trait NativeCallable {
@@ -89,6 +171,15 @@ pub trait NativeCallable {
     fn abi(&self) -> NativeFunctionABI;
     /// Call a native Rust function.
     fn call(&self, args: &mut FnCallArgs) -> Result<Dynamic, Box<EvalAltResult>>;
+    /// Call a native Rust function with the context of the call.  Used by the `Engine`.
+    #[doc(hidden)]
+    fn call_with_context(
+        &self,
+        _context: &mut CallContext,
+        args: &mut FnCallArgs,
+    ) -> Result<Dynamic, Box<EvalAltResult>> {
+        self.call(args)
+    }
 }
 
 /// Trait implemented by all native Rust functions that are callable by Rhai.
@@ -98,17 +189,55 @@ pub trait NativeCallable: Send + Sync {
     fn abi(&self) -> NativeFunctionABI;
     /// Call a native Rust function.
     fn call(&self, args: &mut FnCallArgs) -> Result<Dynamic, Box<EvalAltResult>>;
+    /// Call a native Rust function with the context of the call.  Used by the `Engine`.
+    #[doc(hidden)]
+    fn call_with_context(
+        &self,
+        _context: &mut CallContext,
+        args: &mut FnCallArgs,
+    ) -> Result<Dynamic, Box<EvalAltResult>> {
+        self.call(args)
+    }
+}
+
+/// The Rust function wrapped by a `NativeFunction`.
+enum NativeFunctionBody {
+    /// A function taking only the arguments.
+    Args(Box<FnAny>),
+    /// A function of the built-in packages, also taking the context of the call.
+    WithContext(Box<FnWithContext>),
 }
 
 /// A type encapsulating a native Rust function callable by Rhai.
-pub struct NativeFunction(Box<FnAny>, NativeFunctionABI);
+pub struct NativeFunction(NativeFunctionBody, NativeFunctionABI);
 
 impl NativeCallable for NativeFunction {
     fn abi(&self) -> NativeFunctionABI {
         self.1
     }
     fn call(&self, args: &mut FnCallArgs) -> Result<Dynamic, Box<EvalAltResult>> {
-        (self.0)(args)
+        match &self.0 {
+            NativeFunctionBody::Args(func) => func(args),
+            // Called without an `Engine`, so there are no limits
+            NativeFunctionBody::WithContext(func) => func(&mut Default::default(), args),
+        }
+    }
+    fn call_with_context(
+        &self,
+        context: &mut CallContext,
+        args: &mut FnCallArgs,
+    ) -> Result<Dynamic, Box<EvalAltResult>> {
+        match &self.0 {
+            NativeFunctionBody::Args(func) => {
+                context.added_data_size = None;
+                func(args)
+            }
+            // Functions taking a context report all the data they add
+            NativeFunctionBody::WithContext(func) => {
+                context.added_data_size = Some(0);
+                func(context, args)
+            }
+        }
     }
 }
 
@@ -120,7 +249,11 @@ impl From<(Box<FnAny>, NativeFunctionABI)> for NativeFunction {
 impl NativeFunction {
     /// Create a new `NativeFunction`.
     pub fn new(func: Box<FnAny>, abi: NativeFunctionABI) -> Self {
-        Self(func, abi)
+        Self(NativeFunctionBody::Args(func), abi)
+    }
+    /// Create a new `NativeFunction` which takes the context of the call.
+    pub(crate) fn new_with_context(func: Box<FnWithContext>, abi: NativeFunctionABI) -> Self {
+        Self(NativeFunctionBody::WithContext(func), abi)
     }
 }
 
@@ -131,6 +264,21 @@ pub type SharedNativeFunction = Rc<Box<dyn NativeCallable>>;
 #[cfg(feature = "sync")]
 pub type SharedNativeFunction = Arc<Box<dyn NativeCallable>>;
 
+/// A reference-counted shared value.
+#[cfg(not(feature = "sync"))]
+pub type Shared<T> = Rc<T>;
+/// A reference-counted shared value.
+#[cfg(feature = "sync")]
+pub type Shared<T> = Arc<T>;
+
+/// Get a mutable reference to a shared value, cloning the value first if it is shared.
+pub fn shared_make_mut<T: Clone>(value: &mut Shared<T>) -> &mut T {
+    #[cfg(not(feature = "sync"))]
+    return Rc::make_mut(value);
+    #[cfg(feature = "sync")]
+    return Arc::make_mut(value);
+}
+
 /// A type iterator function.
 #[cfg(not(feature = "sync"))]
 pub type SharedIteratorFunction = Rc<Box<IteratorFn>>;
@@ -4,7 +4,7 @@
 
 use crate::any::{Dynamic, Variant};
 use crate::engine::Engine;
-use crate::fn_native::{FnCallArgs, NativeFunctionABI::*};
+use crate::fn_native::{shared_make_mut, FnCallArgs, NativeFunctionABI::*};
 use crate::parser::FnAccess;
 use crate::result::EvalAltResult;
 
@@ -202,7 +202,7 @@ macro_rules! def_register {
         > RegisterFn<FN, ($($mark,)*), RET> for Engine
         {
             fn register_fn(&mut self, name: &str, f: FN) {
-                self.global_module.set_fn(name.to_string(), $abi, FnAccess::Public,
+                shared_make_mut(&mut self.global_module).set_fn_returning::<RET>(name.to_string(), $abi, FnAccess::Public,
                     &[$(TypeId::of::<$par>()),*],
                     make_func!(f : map_dynamic ; $($par => $clone),*)
                 );
@@ -220,7 +220,7 @@ macro_rules! def_register {
         > RegisterDynamicFn<FN, ($($mark,)*)> for Engine
         {
             fn register_dynamic_fn(&mut self, name: &str, f: FN) {
-                self.global_module.set_fn(name.to_string(), $abi, FnAccess::Public,
+                shared_make_mut(&mut self.global_module).set_fn(name.to_string(), $abi, FnAccess::Public,
                     &[$(TypeId::of::<$par>()),*],
                     make_func!(f : map_identity ; $($par => $clone),*)
                 );
@@ -239,7 +239,7 @@ macro_rules! def_register {
         > RegisterResultFn<FN, ($($mark,)*), RET> for Engine
         {
             fn register_result_fn(&mut self, name: &str, f: FN) {
-                self.global_module.set_fn(name.to_string(), $abi, FnAccess::Public,
+                shared_make_mut(&mut self.global_module).set_fn_returning::<RET>(name.to_string(), $abi, FnAccess::Public,
                     &[$(TypeId::of::<$par>()),*],
                     make_func!(f : map_result ; $($par => $clone),*)
                 );
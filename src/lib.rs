@@ -60,6 +60,7 @@
 //! | `only_i32`    | Set the system integer type to `i32` and disable all other integer types. `INT` is set to `i32`.                                                         |
 //! | `only_i64`    | Set the system integer type to `i64` and disable all other integer types. `INT` is set to `i64`.                                                         |
 //! | `no_std`      | Build for `no-std`. Notice that additional dependencies will be pulled in to replace `std` features.                                                     |
+//! | `internals`   | Expose internal data structures (e.g. `AST` nodes) and custom optimizer passes. Beware that these are unstable and may change between versions.          |
 //! | `sync`        | Restrict all values types to those that are `Send + Sync`. Under this feature, `Engine`, `Scope` and `AST` are all `Send + Sync`.                        |
 //!
 //! [Check out the README on GitHub for details on the Rhai language!](https://github.com/jonathandturner/rhai)
@@ -83,23 +84,28 @@ pub mod packages;
 mod parser;
 mod result;
 mod scope;
+mod serialize;
 mod stdlib;
 mod token;
+mod typecheck;
 mod r#unsafe;
 mod utils;
 
 pub use any::Dynamic;
-pub use engine::Engine;
-pub use error::{ParseError, ParseErrorType};
+pub use engine::{CancellationToken, Engine, EvalMode, Usage};
+pub use error::{LexError, ParseError, ParseErrorType};
 pub use fn_native::NativeCallable;
 pub use fn_register::{RegisterDynamicFn, RegisterFn, RegisterResultFn};
 pub use module::Module;
 pub use parser::{AST, INT};
 pub use result::EvalAltResult;
 pub use scope::Scope;
-pub use token::Position;
+pub use token::{lex, Position, Span, Token, TokenIterator};
 pub use utils::calc_fn_spec as calc_fn_hash;
 
+#[cfg(not(feature = "no_function"))]
+pub use engine::{Generator, GeneratorIter};
+
 #[cfg(not(feature = "no_function"))]
 pub use fn_func::Func;
 
@@ -123,3 +129,15 @@ pub mod module_resolvers {
 
 #[cfg(not(feature = "no_optimize"))]
 pub use optimize::OptimizationLevel;
+
+// Expose internal data structures.
+
+#[cfg(feature = "internals")]
+pub use parser::{Expr, Pattern, ReturnType, Stmt, Type};
+
+#[cfg(feature = "internals")]
+pub use utils::StaticVec;
+
+#[cfg(feature = "internals")]
+#[cfg(not(feature = "no_optimize"))]
+pub use optimize::OptimizerPass;
@@ -2,9 +2,9 @@
 
 use crate::any::{Dynamic, Variant};
 use crate::calc_fn_hash;
-use crate::engine::{Engine, FunctionsLib};
+use crate::engine::{Engine, FunctionsLib, Usage};
 use crate::fn_native::{
-    FnAny, FnCallArgs, IteratorFn, NativeCallable, NativeFunction, NativeFunctionABI,
+    CallContext, FnAny, FnCallArgs, IteratorFn, NativeCallable, NativeFunction, NativeFunctionABI,
     NativeFunctionABI::*, SharedIteratorFunction, SharedNativeFunction,
 };
 use crate::parser::{FnAccess, FnDef, SharedFnDef, AST};
@@ -16,12 +16,13 @@ use crate::utils::{StaticVec, EMPTY_TYPE_ID};
 use crate::stdlib::{
     any::TypeId,
     boxed::Box,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt,
     iter::{empty, repeat},
     mem,
     num::NonZeroUsize,
     ops::{Deref, DerefMut},
+    ptr,
     rc::Rc,
     string::{String, ToString},
     sync::Arc,
@@ -29,6 +30,9 @@ use crate::stdlib::{
     vec::Vec,
 };
 
+#[cfg(not(feature = "no_index"))]
+use crate::{engine::Array, stdlib::iter::once};
+
 /// Default function access mode.
 const DEF_ACCESS: FnAccess = FnAccess::Public;
 
@@ -56,6 +60,15 @@ pub struct Module {
     /// Flattened collection of all external Rust functions, including those in sub-modules.
     all_functions: HashMap<u64, SharedNativeFunction>,
 
+    /// Hash keys of all external Rust functions that are pure.
+    pure_functions: HashSet<u64>,
+
+    /// Types of the return values of external Rust functions, where known.
+    return_types: HashMap<u64, TypeId>,
+
+    /// Are Rust functions set into the module marked pure?
+    pure_by_default: bool,
+
     /// Script-defined functions.
     fn_lib: FunctionsLib,
 
@@ -272,9 +285,90 @@ impl Module {
         self.functions.contains_key(&hash_fn)
     }
 
+    /// Is a Rust function pure?
+    ///
+    /// The `u64` hash is calculated by the function `crate::calc_fn_hash`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rhai::Module;
+    ///
+    /// let mut module = Module::new();
+    /// let hash = module.set_fn_1("calc", |x: i64| Ok(x + 1));
+    /// assert!(!module.is_fn_pure(hash));
+    /// ```
+    pub fn is_fn_pure(&self, hash_fn: u64) -> bool {
+        self.pure_functions.contains(&hash_fn)
+    }
+
+    /// Mark a Rust function as pure or not.
+    ///
+    /// A pure function always returns the same result for the same arguments and has no side effects.
+    /// Under `OptimizationLevel::Full`, calls to pure functions with constant arguments are evaluated
+    /// during optimization.
+    ///
+    /// The `u64` hash is calculated by the function `crate::calc_fn_hash`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rhai::Module;
+    ///
+    /// let mut module = Module::new();
+    /// let hash = module.set_fn_1("calc", |x: i64| Ok(x + 1));
+    /// module.set_fn_pure(hash, true);
+    /// assert!(module.is_fn_pure(hash));
+    /// ```
+    pub fn set_fn_pure(&mut self, hash_fn: u64, pure: bool) {
+        if pure && self.functions.contains_key(&hash_fn) {
+            self.pure_functions.insert(hash_fn);
+        } else {
+            self.pure_functions.remove(&hash_fn);
+        }
+    }
+
+    /// Mark all Rust functions of a particular name as pure or not.
+    pub(crate) fn set_fns_pure_by_name(&mut self, name: &str, pure: bool) {
+        let hashes: StaticVec<_> = self
+            .functions
+            .iter()
+            .filter(|(_, (fn_name, _, _, _))| fn_name == name)
+            .map(|(&hash_fn, _)| hash_fn)
+            .collect();
+
+        hashes
+            .into_iter()
+            .for_each(|hash_fn| self.set_fn_pure(hash_fn, pure));
+    }
+
+    /// Are Rust functions marked pure when they are set into the module?
+    pub fn is_pure_by_default(&self) -> bool {
+        self.pure_by_default
+    }
+
+    /// Mark all Rust functions subsequently set into the module as pure or not.
+    ///
+    /// This is useful for registering a batch of pure functions.  The default is `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rhai::Module;
+    ///
+    /// let mut module = Module::new();
+    /// module.set_pure_by_default(true);
+    /// let hash = module.set_fn_1("calc", |x: i64| Ok(x + 1));
+    /// assert!(module.is_fn_pure(hash));
+    /// ```
+    pub fn set_pure_by_default(&mut self, pure: bool) {
+        self.pure_by_default = pure;
+    }
+
     /// Set a Rust function into the module, returning a hash key.
     ///
     /// If there is an existing Rust function of the same hash, it is replaced.
+    /// The function is marked pure only if the module is set to be pure by default.
     pub fn set_fn(
         &mut self,
         name: String,
@@ -282,10 +376,21 @@ impl Module {
         access: FnAccess,
         params: &[TypeId],
         func: Box<FnAny>,
+    ) -> u64 {
+        self.set_native_fn(name, access, params, NativeFunction::new(func, abi))
+    }
+
+    /// Set a `NativeFunction` into the module, returning a hash key.
+    fn set_native_fn(
+        &mut self,
+        name: String,
+        access: FnAccess,
+        params: &[TypeId],
+        func: NativeFunction,
     ) -> u64 {
         let hash_fn = calc_fn_hash(empty(), &name, params.iter().cloned());
 
-        let f = Box::new(NativeFunction::from((func, abi))) as Box<dyn NativeCallable>;
+        let f = Box::new(func) as Box<dyn NativeCallable>;
 
         #[cfg(not(feature = "sync"))]
         let func = Rc::new(f);
@@ -295,10 +400,51 @@ impl Module {
         let params = params.into_iter().cloned().collect();
 
         self.functions.insert(hash_fn, (name, access, params, func));
+        self.set_fn_pure(hash_fn, self.pure_by_default);
+        self.return_types.remove(&hash_fn);
 
         hash_fn
     }
 
+    /// Set a Rust function into the module, returning a hash key.
+    ///
+    /// The type of the return value is recorded for the type checker, unless it is `Dynamic`.
+    pub(crate) fn set_fn_returning<T: Variant>(
+        &mut self,
+        name: String,
+        abi: NativeFunctionABI,
+        access: FnAccess,
+        params: &[TypeId],
+        func: Box<FnAny>,
+    ) -> u64 {
+        let hash_fn = self.set_fn(name, abi, access, params, func);
+        self.set_return_type::<T>(hash_fn);
+        hash_fn
+    }
+
+    /// Record the type of the return value of a Rust function for the type checker, unless it is `Dynamic`.
+    fn set_return_type<T: Variant>(&mut self, hash_fn: u64) {
+        if TypeId::of::<T>() != TypeId::of::<Dynamic>() {
+            self.return_types.insert(hash_fn, TypeId::of::<T>());
+        }
+    }
+
+    /// Get an iterator over the signatures of all Rust functions in the module,
+    /// i.e. the name, the parameter types and the return type (if known) of each function.
+    pub(crate) fn iter_fn_signatures(
+        &self,
+    ) -> impl Iterator<Item = (&str, &[TypeId], Option<TypeId>)> {
+        self.functions
+            .iter()
+            .map(move |(hash_fn, (name, _, params, _))| {
+                (
+                    name.as_str(),
+                    params.as_ref(),
+                    self.return_types.get(hash_fn).cloned(),
+                )
+            })
+    }
+
     /// Set a Rust function taking no parameters into the module, returning a hash key.
     ///
     /// If there is a similar existing Rust function, it is replaced.
@@ -320,7 +466,7 @@ impl Module {
     ) -> u64 {
         let f = move |_: &mut FnCallArgs| func().map(Dynamic::from);
         let arg_types = [];
-        self.set_fn(name.into(), Pure, DEF_ACCESS, &arg_types, Box::new(f))
+        self.set_fn_returning::<T>(name.into(), Pure, DEF_ACCESS, &arg_types, Box::new(f))
     }
 
     /// Set a Rust function taking one parameter into the module, returning a hash key.
@@ -345,7 +491,7 @@ impl Module {
         let f =
             move |args: &mut FnCallArgs| func(mem::take(args[0]).cast::<A>()).map(Dynamic::from);
         let arg_types = [TypeId::of::<A>()];
-        self.set_fn(name.into(), Pure, DEF_ACCESS, &arg_types, Box::new(f))
+        self.set_fn_returning::<T>(name.into(), Pure, DEF_ACCESS, &arg_types, Box::new(f))
     }
 
     /// Set a Rust function taking one mutable parameter into the module, returning a hash key.
@@ -371,7 +517,7 @@ impl Module {
             func(args[0].downcast_mut::<A>().unwrap()).map(Dynamic::from)
         };
         let arg_types = [TypeId::of::<A>()];
-        self.set_fn(name.into(), Method, DEF_ACCESS, &arg_types, Box::new(f))
+        self.set_fn_returning::<T>(name.into(), Method, DEF_ACCESS, &arg_types, Box::new(f))
     }
 
     /// Set a Rust function taking two parameters into the module, returning a hash key.
@@ -402,7 +548,7 @@ impl Module {
             func(a, b).map(Dynamic::from)
         };
         let arg_types = [TypeId::of::<A>(), TypeId::of::<B>()];
-        self.set_fn(name.into(), Pure, DEF_ACCESS, &arg_types, Box::new(f))
+        self.set_fn_returning::<T>(name.into(), Pure, DEF_ACCESS, &arg_types, Box::new(f))
     }
 
     /// Set a Rust function taking two parameters (the first one mutable) into the module,
@@ -437,7 +583,7 @@ impl Module {
             func(a, b).map(Dynamic::from)
         };
         let arg_types = [TypeId::of::<A>(), TypeId::of::<B>()];
-        self.set_fn(name.into(), Method, DEF_ACCESS, &arg_types, Box::new(f))
+        self.set_fn_returning::<T>(name.into(), Method, DEF_ACCESS, &arg_types, Box::new(f))
     }
 
     /// Set a Rust function taking three parameters into the module, returning a hash key.
@@ -475,7 +621,7 @@ impl Module {
             func(a, b, c).map(Dynamic::from)
         };
         let arg_types = [TypeId::of::<A>(), TypeId::of::<B>(), TypeId::of::<C>()];
-        self.set_fn(name.into(), Pure, DEF_ACCESS, &arg_types, Box::new(f))
+        self.set_fn_returning::<T>(name.into(), Pure, DEF_ACCESS, &arg_types, Box::new(f))
     }
 
     /// Set a Rust function taking three parameters (the first one mutable) into the module,
@@ -514,7 +660,35 @@ impl Module {
             func(a, b, c).map(Dynamic::from)
         };
         let arg_types = [TypeId::of::<A>(), TypeId::of::<B>(), TypeId::of::<C>()];
-        self.set_fn(name.into(), Method, DEF_ACCESS, &arg_types, Box::new(f))
+        self.set_fn_returning::<T>(name.into(), Method, DEF_ACCESS, &arg_types, Box::new(f))
+    }
+
+    /// Set a Rust function taking the context of the call and a list of arguments into the module,
+    /// returning a hash key.
+    ///
+    /// This is used by the built-in packages for functions that check the size limits of the `Engine`.
+    /// The first argument is passed by reference and may be changed, while the other arguments
+    /// can be consumed (e.g. via `std::mem::take`) because they are not used again.
+    ///
+    /// If there is a similar existing Rust function, it is replaced.
+    pub(crate) fn set_fn_with_context<K: Into<String>, T: Variant + Clone>(
+        &mut self,
+        name: K,
+        arg_types: &[TypeId],
+        #[cfg(not(feature = "sync"))] func: impl Fn(&mut CallContext, &mut FnCallArgs) -> FuncReturn<T>
+            + 'static,
+        #[cfg(feature = "sync")] func: impl Fn(&mut CallContext, &mut FnCallArgs) -> FuncReturn<T>
+            + Send
+            + Sync
+            + 'static,
+    ) -> u64 {
+        let f = move |context: &mut CallContext, args: &mut FnCallArgs| {
+            func(context, args).map(Dynamic::from)
+        };
+        let func = NativeFunction::new_with_context(Box::new(f), Method);
+        let hash_fn = self.set_native_fn(name.into(), DEF_ACCESS, arg_types, func);
+        self.set_return_type::<T>(hash_fn);
+        hash_fn
     }
 
     /// Get a Rust function.
@@ -535,6 +709,13 @@ impl Module {
         self.functions.get(&hash_fn).map(|(_, _, _, v)| v.as_ref())
     }
 
+    /// Get a shared reference-counted handle to a Rust function.
+    ///
+    /// The `u64` hash is calculated by the function `crate::calc_fn_hash`.
+    pub(crate) fn get_shared_fn(&self, hash_fn: u64) -> Option<SharedNativeFunction> {
+        self.functions.get(&hash_fn).map(|(_, _, _, v)| v.clone())
+    }
+
     /// Get a modules-qualified function.
     ///
     /// The `u64` hash is calculated by the function `crate::calc_fn_hash`.
@@ -562,6 +743,25 @@ impl Module {
         self.all_fn_lib.get_function(hash_fn_def)
     }
 
+    /// Get a modules-qualified variadic script-defined function that can be called with
+    /// a certain number of arguments.
+    #[cfg(not(feature = "no_index"))]
+    pub(crate) fn get_qualified_variadic_fn(
+        &self,
+        modules: &ModuleRef,
+        name: &str,
+        num_args: usize,
+    ) -> Option<&FnDef> {
+        let fn_lib = &self.all_fn_lib;
+
+        // Try the largest number of fixed parameters first
+        (0..=num_args).rev().find_map(|num_params| {
+            let qualifiers = modules.iter().map(|(m, _)| m.as_str());
+            let hash_fn_def = calc_variadic_fn_hash(qualifiers, name, num_params);
+            fn_lib.get_function(hash_fn_def)
+        })
+    }
+
     /// Create a new `Module` by evaluating an `AST`.
     ///
     /// # Examples
@@ -579,33 +779,72 @@ impl Module {
     /// # }
     /// ```
     #[cfg(not(feature = "no_module"))]
-    pub fn eval_ast_as_new(mut scope: Scope, ast: &AST, engine: &Engine) -> FuncReturn<Self> {
+    pub fn eval_ast_as_new(scope: Scope, ast: &AST, engine: &Engine) -> FuncReturn<Self> {
+        Self::eval_ast_as_new_with_usage(scope, ast, engine, &mut Default::default())
+    }
+
+    /// Create a new `Module` by evaluating an `AST`, adding the resources used to a `Usage`.
+    ///
+    /// The evaluation continues from the numbers in `usage`, so limits set on the `Engine`
+    /// (e.g. via `Engine::set_max_operations`) apply to the total.  A `Usage` passed to
+    /// `ModuleResolver::resolve_with_usage` by a running script also carries the script's
+    /// time limit (see `Engine::set_max_duration`), so the module is evaluated within it.
+    /// The `Usage` is updated even when evaluation fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<rhai::EvalAltResult>> {
+    /// use rhai::{Engine, Module, Scope, Usage};
+    ///
+    /// let engine = Engine::new();
+    /// let ast = engine.compile("let answer = 42; export answer;")?;
+    ///
+    /// let mut usage = Usage::default();
+    /// let module = Module::eval_ast_as_new_with_usage(Scope::new(), &ast, &engine, &mut usage)?;
+    /// assert!(module.contains_var("answer"));
+    /// assert!(usage.operations > 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(feature = "no_module"))]
+    pub fn eval_ast_as_new_with_usage(
+        mut scope: Scope,
+        ast: &AST,
+        engine: &Engine,
+        usage: &mut Usage,
+    ) -> FuncReturn<Self> {
         // Run the script
-        engine.eval_ast_with_scope_raw(&mut scope, &ast)?;
+        let (result, new_usage) = engine.eval_ast_with_scope_raw(&mut scope, &ast, *usage);
+        *usage = new_usage;
+        result?;
 
         // Create new module
         let mut module = Module::new();
 
-        scope.into_iter().for_each(
-            |ScopeEntry {
-                 typ, value, alias, ..
-             }| {
-                match typ {
-                    // Variables with an alias left in the scope become module variables
-                    ScopeEntryType::Normal | ScopeEntryType::Constant if alias.is_some() => {
-                        module.variables.insert(*alias.unwrap(), value);
-                    }
-                    // Modules left in the scope become sub-modules
-                    ScopeEntryType::Module if alias.is_some() => {
-                        module
-                            .modules
-                            .insert(*alias.unwrap(), value.cast::<Module>());
-                    }
-                    // Variables and modules with no alias are private and not exported
-                    _ => (),
+        for ScopeEntry {
+            typ, value, alias, ..
+        } in scope.into_iter()
+        {
+            match typ {
+                // Variables with an alias left in the scope become module variables
+                ScopeEntryType::Normal | ScopeEntryType::Constant if alias.is_some() => {
+                    module.variables.insert(*alias.unwrap(), value);
                 }
-            },
-        );
+                // Modules left in the scope become sub-modules
+                ScopeEntryType::Module if alias.is_some() => {
+                    let sub_module = value.try_cast::<Module>().ok_or_else(|| {
+                        Box::new(EvalAltResult::ErrorInternal(
+                            "module entry in scope is not a module".into(),
+                            Position::none(),
+                        ))
+                    })?;
+                    module.modules.insert(*alias.unwrap(), sub_module);
+                }
+                // Variables and modules with no alias are private and not exported
+                _ => (),
+            }
+        }
 
         module.fn_lib = module.fn_lib.merge(ast.fn_lib());
 
@@ -666,13 +905,40 @@ impl Module {
                     FnAccess::Private => continue,
                     DEF_ACCESS => (),
                 }
-                // Qualifiers + function name + placeholders (one for each parameter)
-                let hash_fn_def = calc_fn_hash(
-                    qualifiers.iter().map(|&v| v),
-                    &fn_def.name,
-                    repeat(EMPTY_TYPE_ID()).take(fn_def.params.len()),
-                );
-                fn_lib.push((hash_fn_def, fn_def.clone()));
+                // Index the function once for each number of arguments it can be called with,
+                // unless another function takes precedence for that number of arguments
+                for num_args in fn_def.required_params()..=fn_def.params.len() {
+                    let target =
+                        module
+                            .fn_lib
+                            .get_function_by_signature(&fn_def.name, num_args, false);
+
+                    match target {
+                        Some(f) if ptr::eq(f, fn_def.as_ref()) => (),
+                        _ => continue,
+                    }
+
+                    // Qualifiers + function name + placeholders (one for each argument)
+                    let hash_fn_def = calc_fn_hash(
+                        qualifiers.iter().map(|&v| v),
+                        &fn_def.name,
+                        repeat(EMPTY_TYPE_ID()).take(num_args),
+                    );
+                    fn_lib.push((hash_fn_def, fn_def.clone()));
+                }
+
+                // Index variadic functions once more for any number of extra arguments
+                #[cfg(not(feature = "no_index"))]
+                {
+                    if fn_def.rest_param.is_some() {
+                        let hash_fn_def = calc_variadic_fn_hash(
+                            qualifiers.iter().map(|&v| v),
+                            &fn_def.name,
+                            fn_def.params.len(),
+                        );
+                        fn_lib.push((hash_fn_def, fn_def.clone()));
+                    }
+                }
             }
         }
 
@@ -712,6 +978,22 @@ impl Module {
     }
 }
 
+/// Calculate the hash of a variadic script-defined function, with a dummy `Array` parameter type
+/// placed after the fixed parameters in place of the rest parameter.
+#[cfg(not(feature = "no_index"))]
+fn calc_variadic_fn_hash<'a>(
+    qualifiers: impl Iterator<Item = &'a str>,
+    fn_name: &str,
+    num_params: usize,
+) -> u64 {
+    let params = repeat(EMPTY_TYPE_ID()).take(num_params);
+    calc_fn_hash(
+        qualifiers,
+        fn_name,
+        params.chain(once(TypeId::of::<Array>())),
+    )
+}
+
 /// A chain of module names to qualify a variable or function call.
 /// A `u64` hash key is kept for quick search purposes.
 ///
@@ -782,6 +1064,22 @@ pub trait ModuleResolver {
         path: &str,
         pos: Position,
     ) -> Result<Module, Box<EvalAltResult>>;
+
+    /// Resolve a module based on a path string, adding the resources used to evaluate it (if any)
+    /// to a `Usage`, even when the resolution fails.
+    ///
+    /// Resolvers that evaluate scripts should override this, e.g. via `Module::eval_ast_as_new_with_usage`.
+    /// The default implementation simply calls `resolve`.
+    fn resolve_with_usage(
+        &self,
+        engine: &Engine,
+        scope: Scope,
+        path: &str,
+        pos: Position,
+        _usage: &mut Usage,
+    ) -> Result<Module, Box<EvalAltResult>> {
+        self.resolve(engine, scope, path, pos)
+    }
 }
 
 /// Trait that encapsulates a module resolution service.
@@ -796,6 +1094,22 @@ pub trait ModuleResolver: Send + Sync {
         path: &str,
         pos: Position,
     ) -> Result<Module, Box<EvalAltResult>>;
+
+    /// Resolve a module based on a path string, adding the resources used to evaluate it (if any)
+    /// to a `Usage`, even when the resolution fails.
+    ///
+    /// Resolvers that evaluate scripts should override this, e.g. via `Module::eval_ast_as_new_with_usage`.
+    /// The default implementation simply calls `resolve`.
+    fn resolve_with_usage(
+        &self,
+        engine: &Engine,
+        scope: Scope,
+        path: &str,
+        pos: Position,
+        _usage: &mut Usage,
+    ) -> Result<Module, Box<EvalAltResult>> {
+        self.resolve(engine, scope, path, pos)
+    }
 }
 
 /// Re-export module resolvers.
@@ -811,7 +1125,67 @@ pub mod resolvers {
 #[cfg(not(feature = "no_std"))]
 mod file {
     use super::*;
-    use crate::stdlib::path::PathBuf;
+    use crate::optimize::OptimizationLevel;
+    use crate::parser::AST;
+    use crate::stdlib::{
+        collections::hash_map::DefaultHasher,
+        fs,
+        hash::{Hash, Hasher},
+        path::{Path, PathBuf},
+    };
+
+    /// Get the optimization level of the compiled modules kept in the cache.
+    ///
+    /// Under `OptimizationLevel::Full`, calls to native Rust functions with constant arguments
+    /// are evaluated during compilation and replaced by their results.  The functions can change
+    /// without the settings of the `Engine` changing, so the cache keeps modules optimized at
+    /// `OptimizationLevel::Simple` and the full optimizations are run again on loading.
+    fn cache_optimization_level(engine: &Engine) -> OptimizationLevel {
+        match engine.optimization_level {
+            OptimizationLevel::Full => OptimizationLevel::Simple,
+            level => level,
+        }
+    }
+
+    /// Calculate a fingerprint of the settings of an `Engine` that affect how scripts are compiled.
+    ///
+    /// A compiled module is only valid for an `Engine` with the same fingerprint.
+    fn settings_fingerprint(engine: &Engine) -> u64 {
+        let mut s = DefaultHasher::new();
+
+        cache_optimization_level(engine).hash(&mut s);
+        engine.max_expr_depth.hash(&mut s);
+        engine.max_stmt_depth.hash(&mut s);
+
+        let mut disabled: Vec<_> = engine.disabled_symbols.iter().collect();
+        disabled.sort();
+        disabled.hash(&mut s);
+
+        // Type annotations must name built-in or registered types
+        let mut type_names: Vec<_> = engine.type_names.iter().collect();
+        type_names.sort();
+        type_names.hash(&mut s);
+
+        // Type checking depends on the signatures of registered functions
+        engine.type_checking.hash(&mut s);
+        if engine.type_checking {
+            let mut signatures: Vec<_> = engine
+                .global_module
+                .iter_fn_signatures()
+                .chain(engine.packages.iter_fn_signatures())
+                .collect();
+            signatures.sort();
+            signatures.hash(&mut s);
+        }
+
+        #[cfg(feature = "internals")]
+        engine
+            .optimizer_passes
+            .iter()
+            .for_each(|pass| pass.name().hash(&mut s));
+
+        s.finish()
+    }
 
     /// Module resolution service that loads module script files from the file system.
     ///
@@ -833,10 +1207,24 @@ mod file {
     /// let mut engine = Engine::new();
     /// engine.set_module_resolver(Some(resolver));
     /// ```
+    ///
+    /// # Compiled module cache
+    ///
+    /// When enabled via `enable_cache`, each script file is compiled only once and the compiled
+    /// `AST` is saved next to it, with the extension suffixed by `c` (e.g. `my_module.rhaic`).
+    /// The compiled file is used as long as it was compiled from the same contents of the script file,
+    /// by the same build with the same compilation settings of the `Engine` (optimization level,
+    /// nesting depth limits, disabled symbols, type checking and custom optimizer passes).
+    /// Otherwise the script file is recompiled and the compiled file replaced.
+    ///
+    /// Under `OptimizationLevel::Full`, the compiled file holds the module optimized at
+    /// `OptimizationLevel::Simple`, and the full optimizations (which call native Rust functions)
+    /// are run each time it is loaded.
     #[derive(Debug, Eq, PartialEq, PartialOrd, Ord, Clone, Hash)]
     pub struct FileModuleResolver {
         path: PathBuf,
         extension: String,
+        cache: bool,
     }
 
     impl Default for FileModuleResolver {
@@ -889,6 +1277,7 @@ mod file {
             Self {
                 path: path.into(),
                 extension: extension.into(),
+                cache: false,
             }
         }
 
@@ -911,6 +1300,89 @@ mod file {
             Default::default()
         }
 
+        /// Enable/disable the use of cached compiled modules saved next to the script files.
+        ///
+        /// The cache is disabled by default.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use rhai::Engine;
+        /// use rhai::module_resolvers::FileModuleResolver;
+        ///
+        /// // Compiled modules are saved as '.rhaic' files next to the '.rhai' scripts
+        /// let mut resolver = FileModuleResolver::new_with_path("./scripts");
+        /// resolver.enable_cache(true);
+        ///
+        /// let mut engine = Engine::new();
+        /// engine.set_module_resolver(Some(resolver));
+        /// ```
+        pub fn enable_cache(&mut self, enable: bool) {
+            self.cache = enable;
+        }
+
+        /// Load the compiled `AST` of a script file from the cache, if it is up to date.
+        ///
+        /// The compiled file starts with a header holding the fingerprint of the settings it was
+        /// compiled with and a hash of the script it was compiled from.
+        fn load_cached(engine: &Engine, cache_path: &Path, header: &[u8]) -> Option<AST> {
+            let bytes = fs::read(cache_path).ok()?;
+
+            if !bytes.starts_with(header) {
+                return None;
+            }
+
+            engine.ast_from_bytes(&bytes[header.len()..]).ok()
+        }
+
+        /// Compile a script file, going through the cache if it is enabled.
+        fn compile(&self, engine: &Engine, file_path: PathBuf) -> Result<AST, Box<EvalAltResult>> {
+            if !self.cache {
+                return engine.compile_file(file_path);
+            }
+
+            let mut cache_path = file_path.clone();
+            cache_path.set_extension(format!("{}c", self.extension));
+
+            let script = Engine::read_file(file_path)?;
+
+            let mut s = DefaultHasher::new();
+            script.hash(&mut s);
+
+            let mut header = settings_fingerprint(engine).to_le_bytes().to_vec();
+            header.extend_from_slice(&s.finish().to_le_bytes());
+
+            let ast = match Self::load_cached(engine, &cache_path, &header) {
+                Some(ast) => ast,
+                None => {
+                    let level = cache_optimization_level(engine);
+                    let ast = engine.compile_with_scope_and_optimization_level(
+                        &Scope::new(),
+                        &[&script],
+                        level,
+                    )?;
+
+                    let mut bytes = header;
+                    bytes.extend(ast.to_bytes());
+
+                    // The cache is only an optimization, so failing to write it is not an error
+                    let _ = fs::write(cache_path, bytes);
+
+                    ast
+                }
+            };
+
+            // Run the optimizations not kept in the cache
+            #[cfg(not(feature = "no_optimize"))]
+            {
+                if engine.optimization_level == OptimizationLevel::Full {
+                    return Ok(engine.optimize_ast(&Scope::new(), ast, OptimizationLevel::Full));
+                }
+            }
+
+            Ok(ast)
+        }
+
         /// Create a `Module` from a file path.
         pub fn create_module<P: Into<PathBuf>>(
             &self,
@@ -929,6 +1401,17 @@ mod file {
             scope: Scope,
             path: &str,
             pos: Position,
+        ) -> Result<Module, Box<EvalAltResult>> {
+            self.resolve_with_usage(engine, scope, path, pos, &mut Default::default())
+        }
+
+        fn resolve_with_usage(
+            &self,
+            engine: &Engine,
+            scope: Scope,
+            path: &str,
+            pos: Position,
+            usage: &mut Usage,
         ) -> Result<Module, Box<EvalAltResult>> {
             // Construct the script file path
             let mut file_path = self.path.clone();
@@ -936,11 +1419,12 @@ mod file {
             file_path.set_extension(&self.extension); // Force extension
 
             // Compile it
-            let ast = engine
-                .compile_file(file_path)
+            let ast = self
+                .compile(engine, file_path)
                 .map_err(|err| err.new_position(pos))?;
 
-            Module::eval_ast_as_new(scope, &ast, engine).map_err(|err| err.new_position(pos))
+            Module::eval_ast_as_new_with_usage(scope, &ast, engine, usage)
+                .map_err(|err| err.new_position(pos))
         }
     }
 }
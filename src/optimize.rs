@@ -4,16 +4,14 @@ use crate::engine::{
     Engine, FunctionsLib, KEYWORD_DEBUG, KEYWORD_EVAL, KEYWORD_PRINT, KEYWORD_TYPE_OF,
 };
 use crate::fn_native::FnCallArgs;
-use crate::module::Module;
-use crate::packages::PackagesCollection;
 use crate::parser::{map_dynamic_to_expr, Expr, FnDef, ReturnType, Stmt, AST};
-use crate::result::EvalAltResult;
 use crate::scope::{Entry as ScopeEntry, EntryType as ScopeEntryType, Scope};
 use crate::token::Position;
 use crate::utils::StaticVec;
 
 use crate::stdlib::{
     boxed::Box,
+    format,
     iter::empty,
     mem,
     string::{String, ToString},
@@ -21,6 +19,24 @@ use crate::stdlib::{
     vec::Vec,
 };
 
+#[cfg(not(feature = "no_function"))]
+use crate::stdlib::{collections::HashSet, iter::repeat};
+
+#[cfg(feature = "internals")]
+use crate::stdlib::any::type_name;
+
+#[cfg(not(feature = "no_function"))]
+use crate::parser::FnAccess;
+
+#[cfg(not(feature = "no_function"))]
+use crate::utils::EMPTY_TYPE_ID;
+
+/// Maximum number of times the optimizer runs over a list of statements.
+///
+/// Optimization normally stops as soon as a round changes nothing, but a custom optimizer pass
+/// that keeps replacing a node, or two passes that undo each other, never reach that point.
+const MAX_OPTIMIZATION_ROUNDS: usize = 100;
+
 /// Level of optimization performed.
 ///
 /// Not available under the `no_optimize` feature.
@@ -30,8 +46,9 @@ pub enum OptimizationLevel {
     None,
     /// Only perform simple optimizations without evaluating functions.
     Simple,
-    /// Full optimizations performed, including evaluating functions.
-    /// Take care that this may cause side effects as it essentially assumes that all functions are pure.
+    /// Full optimizations performed, including evaluating functions and inlining small script-defined functions.
+    /// Only functions marked pure (e.g. via `Engine::set_fn_pure`) are evaluated, so take care to mark only
+    /// functions that have no side effects and do not change their arguments.
     Full,
 }
 
@@ -46,6 +63,70 @@ impl OptimizationLevel {
     }
 }
 
+/// A custom rewrite pass run by the optimizer on every statement and expression.
+///
+/// Custom passes run before the built-in optimizations on each node, and the whole `AST` is
+/// optimized repeatedly until nothing changes any more.  Therefore a pass must return `None`
+/// for any node it leaves alone - always returning a replacement makes the optimizer run
+/// until it gives up after a fixed number of rounds, slowing down every compile.
+///
+/// A replacement `Expr::FnCall` should use zero as its function hash (which skips looking up
+/// script-defined functions), or calculate it via `calc_fn_hash` with one `TypeId::of::<()>()`
+/// per argument.
+///
+/// Only available under the `internals` feature.
+#[cfg(feature = "internals")]
+#[cfg(not(feature = "sync"))]
+pub trait OptimizerPass {
+    /// Rewrite a statement, returning `None` to leave it unchanged.
+    fn rewrite_stmt(&self, _stmt: &Stmt) -> Option<Stmt> {
+        None
+    }
+    /// Rewrite an expression, returning `None` to leave it unchanged.
+    fn rewrite_expr(&self, _expr: &Expr) -> Option<Expr> {
+        None
+    }
+    /// Name of the pass, identifying the rewrites it does.
+    ///
+    /// Compiled modules cached by `FileModuleResolver` are only used with the same passes.
+    /// Defaults to the type name - override it if the rewrites also depend on the pass' data.
+    fn name(&self) -> &str {
+        type_name::<Self>()
+    }
+}
+
+/// A custom rewrite pass run by the optimizer on every statement and expression.
+///
+/// Custom passes run before the built-in optimizations on each node, and the whole `AST` is
+/// optimized repeatedly until nothing changes any more.  Therefore a pass must return `None`
+/// for any node it leaves alone - always returning a replacement makes the optimizer run
+/// until it gives up after a fixed number of rounds, slowing down every compile.
+///
+/// A replacement `Expr::FnCall` should use zero as its function hash (which skips looking up
+/// script-defined functions), or calculate it via `calc_fn_hash` with one `TypeId::of::<()>()`
+/// per argument.
+///
+/// Only available under the `internals` feature.
+#[cfg(feature = "internals")]
+#[cfg(feature = "sync")]
+pub trait OptimizerPass: Send + Sync {
+    /// Rewrite a statement, returning `None` to leave it unchanged.
+    fn rewrite_stmt(&self, _stmt: &Stmt) -> Option<Stmt> {
+        None
+    }
+    /// Rewrite an expression, returning `None` to leave it unchanged.
+    fn rewrite_expr(&self, _expr: &Expr) -> Option<Expr> {
+        None
+    }
+    /// Name of the pass, identifying the rewrites it does.
+    ///
+    /// Compiled modules cached by `FileModuleResolver` are only used with the same passes.
+    /// Defaults to the type name - override it if the rewrites also depend on the pass' data.
+    fn name(&self) -> &str {
+        type_name::<Self>()
+    }
+}
+
 /// Mutable state throughout an optimization pass.
 struct State<'a> {
     /// Has the AST been changed during this pass?
@@ -56,6 +137,8 @@ struct State<'a> {
     engine: &'a Engine,
     /// Library of script-defined functions.
     fn_lib: &'a [(&'a str, usize)],
+    /// Script-defined functions that can be inlined.
+    inline_fns: &'a [FnDef],
     /// Optimization level.
     optimization_level: OptimizationLevel,
 }
@@ -65,6 +148,7 @@ impl<'a> State<'a> {
     pub fn new(
         engine: &'a Engine,
         fn_lib: &'a [(&'a str, usize)],
+        inline_fns: &'a [FnDef],
         level: OptimizationLevel,
     ) -> Self {
         Self {
@@ -72,6 +156,7 @@ impl<'a> State<'a> {
             constants: vec![],
             engine,
             fn_lib,
+            inline_fns,
             optimization_level: level,
         }
     }
@@ -109,29 +194,325 @@ impl<'a> State<'a> {
 
         None
     }
+    /// Run the custom optimizer passes on a statement.
+    #[cfg(feature = "internals")]
+    pub fn rewrite_stmt(&mut self, stmt: Stmt) -> Stmt {
+        let engine = self.engine;
+
+        engine
+            .optimizer_passes
+            .iter()
+            .fold(stmt, |stmt, pass| match pass.rewrite_stmt(&stmt) {
+                Some(stmt) => {
+                    self.set_dirty();
+                    stmt
+                }
+                None => stmt,
+            })
+    }
+    /// Run the custom optimizer passes on an expression.
+    #[cfg(feature = "internals")]
+    pub fn rewrite_expr(&mut self, expr: Expr) -> Expr {
+        let engine = self.engine;
+
+        engine
+            .optimizer_passes
+            .iter()
+            .fold(expr, |expr, pass| match pass.rewrite_expr(&expr) {
+                Some(expr) => {
+                    self.set_dirty();
+                    expr
+                }
+                None => expr,
+            })
+    }
+    /// Look up a script-defined function that can be inlined.
+    pub fn find_inline_fn(&self, name: &str, params: usize) -> Option<&'a FnDef> {
+        self.inline_fns
+            .iter()
+            .find(|fn_def| fn_def.name == name && fn_def.params.len() == params)
+    }
 }
 
-/// Call a registered function
-fn call_fn(
-    packages: &PackagesCollection,
-    global_module: &Module,
-    fn_name: &str,
-    args: &mut FnCallArgs,
-    pos: Position,
-) -> Result<Option<Dynamic>, Box<EvalAltResult>> {
+/// Maximum number of expression nodes in the body of a script-defined function for it to be inlined.
+#[cfg(not(feature = "no_function"))]
+const MAX_INLINE_FN_SIZE: usize = 24;
+
+/// Call a registered pure function.
+///
+/// Returns `None` if the function is not pure or if the call fails,
+/// and `Some(None)` if the function is not found.
+fn call_fn(engine: &Engine, fn_name: &str, args: &mut FnCallArgs) -> Option<Option<Dynamic>> {
+    let packages = &engine.packages;
+    let global_module = &engine.global_module;
+
     // Search built-in's and external functions
     let hash = calc_fn_hash(empty(), fn_name, args.iter().map(|a| a.type_id()));
 
-    global_module
-        .get_fn(hash)
-        .or_else(|| packages.get_fn(hash))
-        .map(|func| func.call(args))
-        .transpose()
-        .map_err(|err| err.new_position(pos))
+    let func = if global_module.contains_fn(hash) {
+        global_module
+            .get_fn(hash)
+            .filter(|_| global_module.is_fn_pure(hash))
+    } else if packages.contains_fn(hash) {
+        packages.get_fn(hash).filter(|_| packages.is_fn_pure(hash))
+    } else {
+        return Some(None);
+    };
+
+    // Functions that are not pure must be called at runtime, and so do failed calls,
+    // so that errors are raised at runtime instead of during compilation
+    func.and_then(|func| {
+        func.call_with_context(&mut engine.call_context(), args)
+            .ok()
+    })
+    .map(Some)
+}
+
+/// Walk an expression tree, calling `f` on each expression.
+///
+/// Returns `false` if `f` returns `false` for any expression, or if a statement block is encountered.
+fn walk_expr(expr: &Expr, f: &mut impl FnMut(&Expr) -> bool) -> bool {
+    if !f(expr) {
+        return false;
+    }
+
+    match expr {
+        Expr::Stmt(_) => false,
+        Expr::FnCall(x) => x.3.iter().all(|expr| walk_expr(expr, f)),
+        Expr::Dot(x) | Expr::Index(x) => walk_expr(&x.0, f) && walk_expr(&x.1, f),
+        Expr::Assignment(x) | Expr::In(x) | Expr::And(x) | Expr::Or(x) | Expr::Coalesce(x) => {
+            walk_expr(&x.0, f) && walk_expr(&x.1, f)
+        }
+        Expr::Array(x) => x.0.iter().all(|expr| walk_expr(expr, f)),
+        Expr::Map(x) => x.0.iter().all(|(_, expr)| walk_expr(expr, f)),
+        #[cfg(not(feature = "no_index"))]
+        Expr::Spread(x) => walk_expr(&x.0, f),
+        Expr::Cast(x) => walk_expr(&x.0, f),
+        _ => true,
+    }
+}
+
+/// Get the name of the variable at the root of a chain of property accesses and/or indexing.
+fn root_variable(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Variable(x) if x.1.is_none() => Some(&(x.0).0),
+        Expr::Dot(x) | Expr::Index(x) => root_variable(&x.0),
+        _ => None,
+    }
+}
+
+/// Can a script-defined function be inlined?
+///
+/// Only functions whose bodies are small expressions, which refer to nothing but their own parameters
+/// and which do not call any script-defined functions (thus non-recursive), are inlined.
+///
+/// Functions with type annotations are never inlined, so that the types are still checked.
+/// Neither are generators, because calling them does not run their bodies.
+#[cfg(not(feature = "no_function"))]
+fn is_inline_candidate(fn_def: &FnDef, fn_lib: &[(&str, usize)]) -> bool {
+    if fn_def.is_generator
+        || fn_def.return_type.is_some()
+        || fn_def.param_types.iter().any(Option::is_some)
+    {
+        return false;
+    }
+
+    let body = match &fn_def.body {
+        Stmt::Expr(expr) => expr,
+        _ => return false,
+    };
+
+    let mut size = 0;
+
+    walk_expr(body, &mut |expr| {
+        size += 1;
+
+        match expr {
+            Expr::Variable(x) => x.1.is_none() && fn_def.params.iter().any(|p| *p == (x.0).0),
+            Expr::FnCall(x) => {
+                let name = (x.0).0.as_ref();
+                x.1.is_none() && name != KEYWORD_EVAL && fn_lib.iter().all(|(n, _)| *n != name)
+            }
+            _ => size <= MAX_INLINE_FN_SIZE,
+        }
+    }) && size <= MAX_INLINE_FN_SIZE
+}
+
+/// Is a parameter possibly modified in the body of a function?
+fn is_param_mutated(body: &Expr, param: &str) -> bool {
+    !walk_expr(body, &mut |expr| match expr {
+        // param = ...
+        Expr::Assignment(x) => root_variable(&x.0) != Some(param),
+        // param.method(...)
+        Expr::Dot(x) | Expr::Index(x) if root_variable(&x.0) == Some(param) => {
+            walk_expr(&x.1, &mut |expr| !matches!(expr, Expr::FnCall(_)))
+        }
+        _ => true,
+    })
+}
+
+/// Make a copy of an expression (without statement blocks), renaming variables.
+///
+/// All variables are resolved by name in the copy, as their positions on the stack may differ.
+fn rename_variables(expr: &Expr, rename: &impl Fn(&str) -> Option<String>) -> Expr {
+    match expr {
+        Expr::Variable(x) => {
+            let ((name, pos), modules, hash, _) = x.as_ref();
+            #[allow(unused_mut)]
+            let mut modules = modules.clone();
+
+            #[cfg(not(feature = "no_module"))]
+            {
+                if let Some(modules) = modules.as_mut() {
+                    modules.set_index(None);
+                }
+            }
+
+            let name = if modules.is_none() {
+                rename(name).unwrap_or_else(|| name.clone())
+            } else {
+                name.clone()
+            };
+
+            Expr::Variable(Box::new(((name, *pos), modules, *hash, None)))
+        }
+        Expr::FnCall(x) => {
+            let (name, modules, hash, args, def_value, named_args) = x.as_ref();
+            #[allow(unused_mut)]
+            let mut modules = modules.clone();
+
+            #[cfg(not(feature = "no_module"))]
+            {
+                if let Some(modules) = modules.as_mut() {
+                    modules.set_index(None);
+                }
+            }
+
+            let args = args
+                .iter()
+                .map(|expr| rename_variables(expr, rename))
+                .collect();
+            Expr::FnCall(Box::new((
+                name.clone(),
+                modules,
+                *hash,
+                args,
+                def_value.clone(),
+                named_args.clone(),
+            )))
+        }
+        Expr::Assignment(x) => Expr::Assignment(Box::new((
+            rename_variables(&x.0, rename),
+            rename_variables(&x.1, rename),
+            x.2,
+        ))),
+        Expr::Dot(x) => Expr::Dot(Box::new((
+            rename_variables(&x.0, rename),
+            rename_variables(&x.1, rename),
+            x.2,
+            x.3,
+        ))),
+        Expr::Index(x) => Expr::Index(Box::new((
+            rename_variables(&x.0, rename),
+            rename_variables(&x.1, rename),
+            x.2,
+            x.3,
+        ))),
+        Expr::In(x) => Expr::In(Box::new((
+            rename_variables(&x.0, rename),
+            rename_variables(&x.1, rename),
+            x.2,
+        ))),
+        Expr::And(x) => Expr::And(Box::new((
+            rename_variables(&x.0, rename),
+            rename_variables(&x.1, rename),
+            x.2,
+        ))),
+        Expr::Or(x) => Expr::Or(Box::new((
+            rename_variables(&x.0, rename),
+            rename_variables(&x.1, rename),
+            x.2,
+        ))),
+        Expr::Coalesce(x) => Expr::Coalesce(Box::new((
+            rename_variables(&x.0, rename),
+            rename_variables(&x.1, rename),
+            x.2,
+        ))),
+        #[cfg(not(feature = "no_index"))]
+        Expr::Spread(x) => Expr::Spread(Box::new((rename_variables(&x.0, rename), x.1))),
+        Expr::Cast(x) => Expr::Cast(Box::new((rename_variables(&x.0, rename), x.1.clone(), x.2))),
+        Expr::Array(x) => Expr::Array(Box::new((
+            x.0.iter()
+                .map(|expr| rename_variables(expr, rename))
+                .collect(),
+            x.1,
+        ))),
+        Expr::Map(x) => Expr::Map(Box::new((
+            x.0.iter()
+                .map(|(key, expr)| (key.clone(), rename_variables(expr, rename)))
+                .collect(),
+            x.1,
+        ))),
+        expr => expr.clone(),
+    }
+}
+
+/// Inline a call to a script-defined function.
+///
+/// `f(a, b)` becomes `{ let x$f = a; let y$f = b; body }` with the parameters `x` and `y` renamed
+/// in the body.  Since `$` is not valid in identifiers, the new names never conflict with others.
+/// Parameters that are bound to constants and never modified become constants, which can then be
+/// propagated into the body.
+///
+/// Returns `None` if any argument contains a statement block.
+fn inline_fn_call(fn_def: &FnDef, args: &[Expr], pos: Position) -> Option<Expr> {
+    let body = match &fn_def.body {
+        Stmt::Expr(expr) => expr,
+        _ => return None,
+    };
+
+    if !args.iter().all(|expr| walk_expr(expr, &mut |_| true)) {
+        return None;
+    }
+
+    let rename_param = |param: &str| format!("{}${}", param, fn_def.name);
+
+    let mut statements: StaticVec<_> = fn_def
+        .params
+        .iter()
+        .zip(args.iter())
+        .map(|(param, arg)| {
+            let name = (rename_param(param), arg.position());
+            let arg = rename_variables(arg, &|_| None);
+
+            if arg.is_constant() && !is_param_mutated(body, param) {
+                Stmt::Const(Box::new((name, arg)))
+            } else {
+                Stmt::Let(Box::new((name, Some(arg))))
+            }
+        })
+        .collect();
+
+    let body = rename_variables(body, &|name| {
+        if fn_def.params.iter().any(|param| param == name) {
+            Some(rename_param(name))
+        } else {
+            None
+        }
+    });
+    statements.push(Stmt::Expr(Box::new(body)));
+
+    Some(Expr::Stmt(Box::new((
+        Stmt::Block(Box::new((statements, pos))),
+        pos,
+    ))))
 }
 
 /// Optimize a statement.
 fn optimize_stmt<'a>(stmt: Stmt, state: &mut State<'a>, preserve_result: bool) -> Stmt {
+    #[cfg(feature = "internals")]
+    let stmt = state.rewrite_stmt(stmt);
+
     match stmt {
         // if expr { Noop }
         Stmt::IfThenElse(x) if matches!(x.1, Stmt::Noop(_)) => {
@@ -210,6 +591,31 @@ fn optimize_stmt<'a>(stmt: Stmt, state: &mut State<'a>, preserve_result: bool) -
                 stmt => Stmt::While(Box::new((optimize_expr(expr, state), stmt))),
             },
         },
+        // do { block } while|until expr
+        Stmt::Do(x) => {
+            let (block, expr, is_until) = *x;
+
+            match (optimize_stmt(block, state, false), expr) {
+                // do { break; } while|until expr -> Noop
+                (Stmt::Break(pos), _) => {
+                    // Only a single break statement - the guard expression is never run
+                    state.set_dirty();
+                    Stmt::Noop(pos)
+                }
+                // do { block } while true -> loop { block }
+                (stmt, Expr::True(_)) if !is_until => {
+                    state.set_dirty();
+                    Stmt::Loop(Box::new(stmt))
+                }
+                // do { block } until false -> loop { block }
+                (stmt, Expr::False(_)) if is_until => {
+                    state.set_dirty();
+                    Stmt::Loop(Box::new(stmt))
+                }
+                // do { block } while|until expr
+                (stmt, expr) => Stmt::Do(Box::new((stmt, optimize_expr(expr, state), is_until))),
+            }
+        }
         // loop { block }
         Stmt::Loop(block) => match optimize_stmt(*block, state, false) {
             // loop { break; } -> Noop
@@ -221,7 +627,7 @@ fn optimize_stmt<'a>(stmt: Stmt, state: &mut State<'a>, preserve_result: bool) -
             // loop { block }
             stmt => Stmt::Loop(Box::new(stmt)),
         },
-        // for id in expr { block }
+        // for pattern in expr { block }
         Stmt::For(x) => Stmt::For(Box::new((
             x.0,
             optimize_expr(x.1, state),
@@ -233,6 +639,8 @@ fn optimize_stmt<'a>(stmt: Stmt, state: &mut State<'a>, preserve_result: bool) -
         }
         // let id;
         stmt @ Stmt::Let(_) => stmt,
+        // let pattern = expr;
+        Stmt::LetPattern(x) => Stmt::LetPattern(Box::new((x.0, optimize_expr(x.1, state)))),
         // import expr as id;
         Stmt::Import(x) => Stmt::Import(Box::new((optimize_expr(x.0, state), x.1))),
         // { block }
@@ -331,7 +739,9 @@ fn optimize_stmt<'a>(stmt: Stmt, state: &mut State<'a>, preserve_result: bool) -
                     Stmt::Noop(pos)
                 }
                 // Only one let/import statement - leave it alone
-                [Stmt::Let(_)] | [Stmt::Import(_)] => Stmt::Block(Box::new((result.into(), pos))),
+                [Stmt::Let(_)] | [Stmt::LetPattern(_)] | [Stmt::Import(_)] => {
+                    Stmt::Block(Box::new((result.into(), pos)))
+                }
                 // Only one statement - promote
                 [_] => {
                     state.set_dirty();
@@ -346,6 +756,8 @@ fn optimize_stmt<'a>(stmt: Stmt, state: &mut State<'a>, preserve_result: bool) -
         Stmt::ReturnWithVal(x) if x.1.is_some() => {
             Stmt::ReturnWithVal(Box::new((x.0, Some(optimize_expr(x.1.unwrap(), state)))))
         }
+        // yield expr;
+        Stmt::Yield(x) => Stmt::Yield(Box::new((optimize_expr(x.0, state), x.1))),
         // All other statements - skip
         stmt => stmt,
     }
@@ -356,6 +768,9 @@ fn optimize_expr<'a>(expr: Expr, state: &mut State<'a>) -> Expr {
     // These keywords are handled specially
     const DONT_EVAL_KEYWORDS: [&str; 3] = [KEYWORD_PRINT, KEYWORD_DEBUG, KEYWORD_EVAL];
 
+    #[cfg(feature = "internals")]
+    let expr = state.rewrite_expr(expr);
+
     match expr {
         // ( stmt )
         Expr::Stmt(x) => match optimize_stmt(x.0, state, true) {
@@ -409,8 +824,13 @@ fn optimize_expr<'a>(expr: Expr, state: &mut State<'a>) -> Expr {
                     .map(|(_, expr)| expr.set_position(pos))
                     .unwrap_or_else(|| Expr::Unit(pos))
             }
+            // lhs.method(args ..) -> optimize method call arguments
+            (lhs, Expr::FnCall(mut f)) => {
+                f.3 = f.3.into_iter().map(|a| optimize_expr(a, state)).collect();
+                Expr::Dot(Box::new((optimize_expr(lhs, state), Expr::FnCall(f), x.2, x.3)))
+            }
             // lhs.rhs
-            (lhs, rhs) => Expr::Dot(Box::new((optimize_expr(lhs, state), optimize_expr(rhs, state), x.2)))
+            (lhs, rhs) => Expr::Dot(Box::new((optimize_expr(lhs, state), optimize_expr(rhs, state), x.2, x.3)))
         }
 
         // lhs[rhs]
@@ -442,7 +862,7 @@ fn optimize_expr<'a>(expr: Expr, state: &mut State<'a>) -> Expr {
                 Expr::CharConstant(Box::new((s.0.chars().nth(i.0 as usize).expect("should get char"), s.1)))
             }
             // lhs[rhs]
-            (lhs, rhs) => Expr::Index(Box::new((optimize_expr(lhs, state), optimize_expr(rhs, state), x.2))),
+            (lhs, rhs) => Expr::Index(Box::new((optimize_expr(lhs, state), optimize_expr(rhs, state), x.2, x.3))),
         },
         // [ items .. ]
         #[cfg(not(feature = "no_index"))]
@@ -529,6 +949,21 @@ fn optimize_expr<'a>(expr: Expr, state: &mut State<'a>) -> Expr {
             // lhs || rhs
             (lhs, rhs) => Expr::Or(Box::new((optimize_expr(lhs, state), optimize_expr(rhs, state), x.2))),
         },
+        // lhs ?? rhs
+        Expr::Coalesce(x) => match (x.0, x.1) {
+            // () ?? rhs -> rhs
+            (Expr::Unit(_), rhs) => {
+                state.set_dirty();
+                optimize_expr(rhs, state)
+            }
+            // constant ?? rhs -> constant
+            (lhs, _) if lhs.is_constant() => {
+                state.set_dirty();
+                optimize_expr(lhs, state)
+            }
+            // lhs ?? rhs
+            (lhs, rhs) => Expr::Coalesce(Box::new((optimize_expr(lhs, state), optimize_expr(rhs, state), x.2))),
+        },
 
         // Do not call some special keywords
         Expr::FnCall(mut x) if DONT_EVAL_KEYWORDS.contains(&(x.0).0.as_ref())=> {
@@ -536,13 +971,36 @@ fn optimize_expr<'a>(expr: Expr, state: &mut State<'a>) -> Expr {
             Expr::FnCall(x)
         }
 
+        // Inline small script-defined functions
+        Expr::FnCall(mut x)
+                if x.1.is_none() // Non-qualified
+                && x.5.is_empty() // No named arguments
+                && !x.3.iter().any(Expr::is_spread) // No spread arguments
+                && state.optimization_level == OptimizationLevel::Full // full optimizations
+                && state.find_inline_fn(&(x.0).0, x.3.len()).is_some() // function can be inlined
+        => {
+            let fn_def = state.find_inline_fn(&(x.0).0, x.3.len()).expect("should find function");
+
+            match inline_fn_call(fn_def, x.3.as_ref(), (x.0).1) {
+                Some(expr) => {
+                    state.set_dirty();
+                    expr
+                }
+                None => {
+                    x.3 = x.3.into_iter().map(|a| optimize_expr(a, state)).collect();
+                    Expr::FnCall(x)
+                }
+            }
+        }
+
         // Eagerly call functions
         Expr::FnCall(mut x)
                 if x.1.is_none() // Non-qualified
+                && x.5.is_empty() // No named arguments
                 && state.optimization_level == OptimizationLevel::Full // full optimizations
                 && x.3.iter().all(|expr| expr.is_constant()) // all arguments are constants
         => {
-            let ((name, pos), _, _, args, def_value) = x.as_mut();
+            let ((name, pos), _, _, args, def_value, _) = x.as_mut();
 
             // First search in script-defined functions (can override built-in)
             if state.fn_lib.iter().find(|(id, len)| *id == name && *len == args.len()).is_some() {
@@ -562,7 +1020,7 @@ fn optimize_expr<'a>(expr: Expr, state: &mut State<'a>) -> Expr {
                 ""
             };
 
-            call_fn(&state.engine.packages, &state.engine.global_module, name, call_args.as_mut(), *pos).ok()
+            call_fn(state.engine, name, call_args.as_mut())
                 .and_then(|result|
                     result.or_else(|| {
                         if !arg_for_type_of.is_empty() {
@@ -590,6 +1048,31 @@ fn optimize_expr<'a>(expr: Expr, state: &mut State<'a>) -> Expr {
             Expr::FnCall(x)
         }
 
+        // ..expr
+        #[cfg(not(feature = "no_index"))]
+        Expr::Spread(x) => Expr::Spread(Box::new((optimize_expr(x.0, state), x.1))),
+
+        // expr: type
+        Expr::Cast(x) => {
+            let (expr, typ, pos) = *x;
+
+            match optimize_expr(expr, state) {
+                // constant: type -> constant, when the constant matches the type
+                expr if expr.is_constant() => {
+                    let value = typ.cast(state.engine, expr.get_constant_value()).ok();
+
+                    match value.and_then(|value| map_dynamic_to_expr(value, expr.position())) {
+                        Some(constant) => {
+                            state.set_dirty();
+                            constant
+                        }
+                        None => Expr::Cast(Box::new((expr, typ, pos))),
+                    }
+                }
+                expr => Expr::Cast(Box::new((expr, typ, pos))),
+            }
+        }
+
         // constant-name
         Expr::Variable(x) if x.1.is_none() && state.contains_constant(&(x.0).0) => {
             let (name, pos) = x.0;
@@ -609,6 +1092,7 @@ fn optimize<'a>(
     engine: &Engine,
     scope: &Scope,
     fn_lib: &'a [(&'a str, usize)],
+    inline_fns: &'a [FnDef],
     level: OptimizationLevel,
 ) -> Vec<Stmt> {
     // If optimization level is None then skip optimizing
@@ -617,7 +1101,7 @@ fn optimize<'a>(
     }
 
     // Set up the state
-    let mut state = State::new(engine, fn_lib, level);
+    let mut state = State::new(engine, fn_lib, inline_fns, level);
 
     // Add constants from the scope into the state
     scope
@@ -638,8 +1122,8 @@ fn optimize<'a>(
 
     let mut result = statements;
 
-    // Optimization loop
-    loop {
+    // Optimization loop - stop when nothing changes, or after too many rounds
+    for _ in 0..MAX_OPTIMIZATION_ROUNDS {
         state.reset();
         state.restore_constants(orig_constants_len);
 
@@ -660,7 +1144,7 @@ fn optimize<'a>(
                         // Keep all variable declarations at this level
                         // and always keep the last return value
                         let keep = match stmt {
-                            Stmt::Let(_) | Stmt::Import(_) => true,
+                            Stmt::Let(_) | Stmt::LetPattern(_) | Stmt::Import(_) => true,
                             _ => i == num_statements - 1,
                         };
                         optimize_stmt(stmt, &mut state, keep)
@@ -690,6 +1174,45 @@ fn optimize<'a>(
     result
 }
 
+/// Optimize the body of a script-defined function.
+#[cfg(not(feature = "no_function"))]
+fn optimize_fn_def(
+    mut fn_def: FnDef,
+    engine: &Engine,
+    fn_lib: &[(&str, usize)],
+    inline_fns: &[FnDef],
+    level: OptimizationLevel,
+) -> FnDef {
+    if !level.is_none() {
+        let pos = fn_def.body.position();
+
+        // Optimize the function body
+        let mut body = optimize(
+            vec![fn_def.body],
+            engine,
+            &Scope::new(),
+            fn_lib,
+            inline_fns,
+            level,
+        );
+
+        // {} -> Noop
+        fn_def.body = match body.pop().unwrap_or_else(|| Stmt::Noop(pos)) {
+            // { return val; } -> val
+            Stmt::ReturnWithVal(x) if x.1.is_some() && (x.0).0 == ReturnType::Return => {
+                Stmt::Expr(Box::new(x.1.unwrap()))
+            }
+            // { return; } -> ()
+            Stmt::ReturnWithVal(x) if x.1.is_none() && (x.0).0 == ReturnType::Return => {
+                Stmt::Expr(Box::new(Expr::Unit((x.0).1)))
+            }
+            // All others
+            stmt => stmt,
+        };
+    }
+    fn_def
+}
+
 /// Optimize an AST.
 pub fn optimize_into_ast(
     engine: &Engine,
@@ -714,40 +1237,213 @@ pub fn optimize_into_ast(
     const fn_lib: &[(&str, usize)] = &[];
 
     #[cfg(not(feature = "no_function"))]
-    let lib = FunctionsLib::from_iter(functions.iter().cloned().map(|mut fn_def| {
-        if !level.is_none() {
-            let pos = fn_def.body.position();
-
-            // Optimize the function body
-            let mut body = optimize(vec![fn_def.body], engine, &Scope::new(), fn_lib, level);
-
-            // {} -> Noop
-            fn_def.body = match body.pop().unwrap_or_else(|| Stmt::Noop(pos)) {
-                // { return val; } -> val
-                Stmt::ReturnWithVal(x) if x.1.is_some() && (x.0).0 == ReturnType::Return => {
-                    Stmt::Expr(Box::new(x.1.unwrap()))
-                }
-                // { return; } -> ()
-                Stmt::ReturnWithVal(x) if x.1.is_none() && (x.0).0 == ReturnType::Return => {
-                    Stmt::Expr(Box::new(Expr::Unit((x.0).1)))
-                }
-                // All others
-                stmt => stmt,
-            };
+    let mut optimized: Vec<_> = functions
+        .iter()
+        .cloned()
+        .map(|fn_def| optimize_fn_def(fn_def, engine, fn_lib, &[], level))
+        .collect();
+
+    // Find all small functions that can be inlined
+    #[cfg(not(feature = "no_function"))]
+    let inline_fns: Vec<_> = if level.is_full() {
+        optimized
+            .iter()
+            .filter(|fn_def| is_inline_candidate(fn_def, fn_lib))
+            .cloned()
+            .collect()
+    } else {
+        vec![]
+    };
+
+    #[cfg(feature = "no_function")]
+    let inline_fns: Vec<FnDef> = vec![];
+
+    #[cfg(not(feature = "no_function"))]
+    let lib = {
+        // Inline functions into other functions
+        if !inline_fns.is_empty() {
+            optimized = optimized
+                .into_iter()
+                .map(|fn_def| optimize_fn_def(fn_def, engine, fn_lib, &inline_fns, level))
+                .collect();
         }
-        fn_def
-    }));
+
+        FunctionsLib::from_iter(optimized)
+    };
 
     #[cfg(feature = "no_function")]
     let lib: FunctionsLib = Default::default();
 
-    AST::new(
-        match level {
-            OptimizationLevel::None => statements,
-            OptimizationLevel::Simple | OptimizationLevel::Full => {
-                optimize(statements, engine, &scope, fn_lib, level)
-            }
+    let statements = match level {
+        OptimizationLevel::None => statements,
+        OptimizationLevel::Simple | OptimizationLevel::Full => {
+            optimize(statements, engine, &scope, fn_lib, &inline_fns, level)
+        }
+    };
+
+    // Remove private functions that are no longer called, e.g. because all calls are inlined.
+    // Public functions are kept as they can be called from outside the script.
+    #[cfg(not(feature = "no_function"))]
+    let lib = if level.is_full() {
+        let entry_points: Vec<_> = lib
+            .iter()
+            .filter(|(_, fn_def)| fn_def.access == FnAccess::Public)
+            .map(|(_, fn_def)| fn_def.name.as_str())
+            .collect();
+
+        shake_functions(&statements, &lib, &entry_points)
+    } else {
+        lib
+    };
+
+    AST::new(statements, lib)
+}
+
+/// Collect the hashes of all script-defined functions possibly called by a statement.
+///
+/// Returns `false` if the statement may call functions dynamically (i.e. via `eval` or spread arguments).
+#[cfg(not(feature = "no_function"))]
+fn collect_fn_calls_in_stmt(stmt: &Stmt, calls: &mut Vec<u64>) -> bool {
+    match stmt {
+        Stmt::IfThenElse(x) => {
+            collect_fn_calls_in_expr(&x.0, calls)
+                && collect_fn_calls_in_stmt(&x.1, calls)
+                && match &x.2 {
+                    Some(stmt) => collect_fn_calls_in_stmt(stmt, calls),
+                    None => true,
+                }
+        }
+        Stmt::While(x) => {
+            collect_fn_calls_in_expr(&x.0, calls) && collect_fn_calls_in_stmt(&x.1, calls)
+        }
+        Stmt::Do(x) => {
+            collect_fn_calls_in_stmt(&x.0, calls) && collect_fn_calls_in_expr(&x.1, calls)
+        }
+        Stmt::Loop(x) => collect_fn_calls_in_stmt(x, calls),
+        Stmt::For(x) => {
+            collect_fn_calls_in_expr(&x.1, calls) && collect_fn_calls_in_stmt(&x.2, calls)
+        }
+        Stmt::Let(x) => match &x.1 {
+            Some(expr) => collect_fn_calls_in_expr(expr, calls),
+            None => true,
+        },
+        Stmt::LetPattern(x) => collect_fn_calls_in_expr(&x.1, calls),
+        Stmt::Const(x) => collect_fn_calls_in_expr(&x.1, calls),
+        Stmt::Block(x) => x.0.iter().all(|stmt| collect_fn_calls_in_stmt(stmt, calls)),
+        Stmt::Expr(x) => collect_fn_calls_in_expr(x, calls),
+        Stmt::ReturnWithVal(x) => match &x.1 {
+            Some(expr) => collect_fn_calls_in_expr(expr, calls),
+            None => true,
         },
-        lib,
-    )
+        Stmt::Yield(x) => collect_fn_calls_in_expr(&x.0, calls),
+        Stmt::Import(x) => collect_fn_calls_in_expr(&x.0, calls),
+        Stmt::Noop(_) | Stmt::Continue(_) | Stmt::Break(_) | Stmt::Export(_) => true,
+    }
+}
+
+/// Collect the hashes of all script-defined functions possibly called by an expression.
+///
+/// Returns `false` if the expression may call functions dynamically (i.e. via `eval` or spread arguments).
+#[cfg(not(feature = "no_function"))]
+fn collect_fn_calls_in_expr(expr: &Expr, calls: &mut Vec<u64>) -> bool {
+    match expr {
+        Expr::Stmt(x) => collect_fn_calls_in_stmt(&x.0, calls),
+        Expr::FnCall(x) => {
+            // The number of arguments (hence the function called) is unknown with spread arguments
+            if (x.0).0 == KEYWORD_EVAL || x.3.iter().any(Expr::is_spread) {
+                return false;
+            }
+            // Module-qualified calls never call functions in the `AST`
+            if x.1.is_none() {
+                calls.push(x.2);
+            }
+            x.3.iter().all(|expr| collect_fn_calls_in_expr(expr, calls))
+        }
+        Expr::Dot(x) | Expr::Index(x) => {
+            collect_fn_calls_in_expr(&x.0, calls) && collect_fn_calls_in_expr(&x.1, calls)
+        }
+        Expr::Assignment(x) | Expr::In(x) | Expr::And(x) | Expr::Or(x) | Expr::Coalesce(x) => {
+            collect_fn_calls_in_expr(&x.0, calls) && collect_fn_calls_in_expr(&x.1, calls)
+        }
+        Expr::Array(x) => x.0.iter().all(|expr| collect_fn_calls_in_expr(expr, calls)),
+        Expr::Map(x) => {
+            x.0.iter()
+                .all(|(_, expr)| collect_fn_calls_in_expr(expr, calls))
+        }
+        Expr::Cast(x) => collect_fn_calls_in_expr(&x.0, calls),
+        _ => true,
+    }
+}
+
+/// Remove all script-defined functions that are unreachable from the statements
+/// and from the functions with any of the specified names.
+///
+/// If any reachable code calls `eval` or passes spread arguments, all functions are kept.
+#[cfg(not(feature = "no_function"))]
+pub fn shake_functions(
+    statements: &[Stmt],
+    functions: &FunctionsLib,
+    entry_points: &[&str],
+) -> FunctionsLib {
+    let mut calls = Vec::new();
+
+    // Start from the statements and the entry points
+    if !statements
+        .iter()
+        .all(|stmt| collect_fn_calls_in_stmt(stmt, &mut calls))
+    {
+        return functions.clone();
+    }
+
+    // Variadic functions are always kept because calls to them cannot be matched by hash
+    calls.extend(
+        functions
+            .iter()
+            .filter(|(_, fn_def)| {
+                entry_points.contains(&fn_def.name.as_str()) || fn_def.rest_param.is_some()
+            })
+            .map(|(&hash, _)| hash),
+    );
+
+    // Calls that leave out parameters with default values have a hash with fewer arguments
+    let aliases: Vec<_> = functions
+        .iter()
+        .flat_map(|(&hash, fn_def)| {
+            (fn_def.required_params()..fn_def.params.len()).map(move |num_args| {
+                let args_iter = repeat(EMPTY_TYPE_ID()).take(num_args);
+                (calc_fn_hash(empty(), &fn_def.name, args_iter), hash)
+            })
+        })
+        .collect();
+
+    // Follow all calls in reachable functions
+    let mut reachable = HashSet::new();
+
+    while let Some(hash) = calls.pop() {
+        if !reachable.insert(hash) {
+            continue;
+        }
+
+        calls.extend(
+            aliases
+                .iter()
+                .filter(|(call_hash, _)| *call_hash == hash)
+                .map(|(_, fn_hash)| *fn_hash),
+        );
+
+        if let Some(fn_def) = functions.get_function(hash) {
+            if !fn_def
+                .defaults
+                .iter()
+                .all(|expr| collect_fn_calls_in_expr(expr, &mut calls))
+                || !collect_fn_calls_in_stmt(&fn_def.body, &mut calls)
+            {
+                return functions.clone();
+            }
+        }
+    }
+
+    let mut functions = functions.clone();
+    functions.retain(|hash, _| reachable.contains(hash));
+    functions
 }
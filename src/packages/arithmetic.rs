@@ -268,7 +268,7 @@ macro_rules! reg_op {
     };
 }
 
-def_package!(crate:ArithmeticPackage:"Basic arithmetic", lib, {
+def_package!(crate:ArithmeticPackage:"Basic arithmetic", lib, pure, {
     // Checked basic arithmetic
     #[cfg(not(feature = "unchecked"))]
     {
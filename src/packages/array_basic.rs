@@ -2,44 +2,94 @@
 
 use crate::any::{Dynamic, Variant};
 use crate::def_package;
-use crate::engine::Array;
+use crate::engine::{calc_data_sizes, Array};
+use crate::fn_native::{CallContext, FnCallArgs};
 use crate::module::FuncReturn;
 use crate::parser::INT;
 
-use crate::stdlib::{any::TypeId, boxed::Box, string::String};
+use crate::stdlib::{any::TypeId, boxed::Box, mem, string::String};
 
 // Register array utility functions
-fn push<T: Variant + Clone>(list: &mut Array, item: T) -> FuncReturn<()> {
-    list.push(Dynamic::from(item));
+fn push<T: Variant + Clone>(
+    context: &mut CallContext,
+    list: &mut Array,
+    item: T,
+) -> FuncReturn<()> {
+    let item = Dynamic::from(item);
+
+    // Check the sizes before growing the array
+    context.check_array_size(list.len() + 1)?;
+    context.check_added_data_size(|| calc_data_sizes(&item).3)?;
+
+    list.push(item);
     Ok(())
 }
-fn ins<T: Variant + Clone>(list: &mut Array, position: INT, item: T) -> FuncReturn<()> {
+fn ins<T: Variant + Clone>(
+    context: &mut CallContext,
+    list: &mut Array,
+    position: INT,
+    item: T,
+) -> FuncReturn<()> {
+    let item = Dynamic::from(item);
+
+    // Check the sizes before growing the array
+    context.check_array_size(list.len() + 1)?;
+    context.check_added_data_size(|| calc_data_sizes(&item).3)?;
+
     if position <= 0 {
-        list.insert(0, Dynamic::from(item));
-    } else if (position as usize) >= list.len() - 1 {
-        push(list, item)?;
+        list.insert(0, item);
+    } else if (position as usize) + 1 >= list.len() {
+        list.push(item);
     } else {
-        list.insert(position as usize, Dynamic::from(item));
+        list.insert(position as usize, item);
     }
     Ok(())
 }
-fn pad<T: Variant + Clone>(list: &mut Array, len: INT, item: T) -> FuncReturn<()> {
-    if len >= 0 {
-        while list.len() < len as usize {
-            push(list, item.clone())?;
-        }
+fn pad<T: Variant + Clone>(
+    context: &mut CallContext,
+    list: &mut Array,
+    len: INT,
+    item: T,
+) -> FuncReturn<()> {
+    if len >= 0 && (len as usize) > list.len() {
+        let item = Dynamic::from(item);
+
+        // Check the sizes before allocating the new items
+        context.check_array_size(len as usize)?;
+        context.check_added_data_size(|| {
+            calc_data_sizes(&item)
+                .3
+                .saturating_mul(len as usize - list.len())
+        })?;
+
+        list.resize(len as usize, item);
     }
     Ok(())
 }
 
 macro_rules! reg_op {
     ($lib:expr, $op:expr, $func:ident, $($par:ty),*) => {
-        $( $lib.set_fn_2_mut($op, $func::<$par>); )*
+        $( $lib.set_fn_with_context(
+            $op,
+            &[TypeId::of::<Array>(), TypeId::of::<$par>()],
+            |context: &mut CallContext, args: &mut FnCallArgs| {
+                let item = mem::take(args[1]).cast::<$par>();
+                $func::<$par>(context, args[0].downcast_mut::<Array>().unwrap(), item)
+            },
+        ); )*
     };
 }
 macro_rules! reg_tri {
     ($lib:expr, $op:expr, $func:ident, $($par:ty),*) => {
-        $( $lib.set_fn_3_mut($op, $func::<$par>); )*
+        $( $lib.set_fn_with_context(
+            $op,
+            &[TypeId::of::<Array>(), TypeId::of::<INT>(), TypeId::of::<$par>()],
+            |context: &mut CallContext, args: &mut FnCallArgs| {
+                let len = mem::take(args[1]).cast::<INT>();
+                let item = mem::take(args[2]).cast::<$par>();
+                $func::<$par>(context, args[0].downcast_mut::<Array>().unwrap(), len, item)
+            },
+        ); )*
     };
 }
 
@@ -49,13 +99,29 @@ def_package!(crate:BasicArrayPackage:"Basic array utilities.", lib, {
     reg_tri!(lib, "pad", pad, INT, bool, char, String, Array, ());
     reg_tri!(lib, "insert", ins, INT, bool, char, String, Array, ());
 
-    lib.set_fn_2_mut("append", |x: &mut Array, y: Array| {
-        x.extend(y);
-        Ok(())
-    });
-    lib.set_fn_2(
+    lib.set_fn_with_context(
+        "append",
+        &[TypeId::of::<Array>(), TypeId::of::<Array>()],
+        |context: &mut CallContext, args: &mut FnCallArgs| {
+            let y = mem::take(args[1]).cast::<Array>();
+            let x = args[0].downcast_mut::<Array>().unwrap();
+
+            context.check_array_size(x.len() + y.len())?;
+            context.check_added_data_size(|| y.iter().map(|item| calc_data_sizes(item).3).sum())?;
+
+            x.extend(y);
+            Ok(())
+        },
+    );
+    lib.set_fn_with_context(
         "+",
-        |mut x: Array, y: Array| {
+        &[TypeId::of::<Array>(), TypeId::of::<Array>()],
+        |context: &mut CallContext, args: &mut FnCallArgs| {
+            let y = mem::take(args[1]).cast::<Array>();
+            let mut x = mem::take(args[0]).cast::<Array>();
+
+            context.check_array_size(x.len() + y.len())?;
+
             x.extend(y);
             Ok(x)
         },
@@ -76,13 +142,21 @@ def_package!(crate:BasicArrayPackage:"Basic array utilities.", lib, {
         reg_tri!(lib, "insert", ins, f32, f64);
     }
 
-    lib.set_fn_1_mut(
+    // Functions that remove items take a context only to tell the `Engine` that they add no data,
+    // so that it need not measure the array again
+    lib.set_fn_with_context(
         "pop",
-        |list: &mut Array| Ok(list.pop().unwrap_or_else(|| ().into())),
+        &[TypeId::of::<Array>()],
+        |_: &mut CallContext, args: &mut FnCallArgs| {
+            let list = args[0].downcast_mut::<Array>().unwrap();
+            Ok(list.pop().unwrap_or_else(|| ().into()))
+        },
     );
-    lib.set_fn_1_mut(
+    lib.set_fn_with_context(
         "shift",
-        |list: &mut Array| {
+        &[TypeId::of::<Array>()],
+        |_: &mut CallContext, args: &mut FnCallArgs| {
+            let list = args[0].downcast_mut::<Array>().unwrap();
             Ok(if list.is_empty() {
                 ().into()
             } else {
@@ -90,9 +164,12 @@ def_package!(crate:BasicArrayPackage:"Basic array utilities.", lib, {
             })
         },
     );
-    lib.set_fn_2_mut(
+    lib.set_fn_with_context(
         "remove",
-        |list: &mut Array, len: INT| {
+        &[TypeId::of::<Array>(), TypeId::of::<INT>()],
+        |_: &mut CallContext, args: &mut FnCallArgs| {
+            let len = mem::take(args[1]).cast::<INT>();
+            let list = args[0].downcast_mut::<Array>().unwrap();
             Ok(if len < 0 || (len as usize) >= list.len() {
                 ().into()
             } else {
@@ -101,13 +178,20 @@ def_package!(crate:BasicArrayPackage:"Basic array utilities.", lib, {
         },
     );
     lib.set_fn_1_mut("len", |list: &mut Array| Ok(list.len() as INT));
-    lib.set_fn_1_mut("clear", |list: &mut Array| {
-        list.clear();
-        Ok(())
-    });
-    lib.set_fn_2_mut(
+    lib.set_fn_with_context(
+        "clear",
+        &[TypeId::of::<Array>()],
+        |_: &mut CallContext, args: &mut FnCallArgs| {
+            args[0].downcast_mut::<Array>().unwrap().clear();
+            Ok(())
+        },
+    );
+    lib.set_fn_with_context(
         "truncate",
-        |list: &mut Array, len: INT| {
+        &[TypeId::of::<Array>(), TypeId::of::<INT>()],
+        |_: &mut CallContext, args: &mut FnCallArgs| {
+            let len = mem::take(args[1]).cast::<INT>();
+            let list = args[0].downcast_mut::<Array>().unwrap();
             if len >= 0 {
                 list.truncate(len as usize);
             } else {
@@ -117,6 +201,11 @@ def_package!(crate:BasicArrayPackage:"Basic array utilities.", lib, {
         },
     );
 
+    // Only functions that do not change the array are pure
+    ["+", "len"]
+        .iter()
+        .for_each(|name| lib.set_fns_pure_by_name(name, true));
+
     // Register array iterator
     lib.set_iter(
         TypeId::of::<Array>(),
@@ -6,7 +6,7 @@ use crate::parser::INT;
 use crate::stdlib::{
     any::TypeId,
     boxed::Box,
-    ops::{Add, Range},
+    ops::{Add, Range, RangeInclusive},
 };
 
 // Register range function
@@ -27,6 +27,24 @@ fn get_range<T: Variant + Clone>(from: T, to: T) -> FuncReturn<Range<T>> {
     Ok(from..to)
 }
 
+// Register inclusive range function
+fn reg_range_inclusive<T: Variant + Clone>(lib: &mut Module)
+where
+    RangeInclusive<T>: Iterator<Item = T>,
+{
+    lib.set_iter(
+        TypeId::of::<RangeInclusive<T>>(),
+        Box::new(|source| {
+            Box::new(source.cast::<RangeInclusive<T>>().map(|x| x.into_dynamic()))
+                as Box<dyn Iterator<Item = Dynamic>>
+        }),
+    );
+}
+
+fn get_range_inclusive<T: Variant + Clone>(from: T, to: T) -> FuncReturn<RangeInclusive<T>> {
+    Ok(from..=to)
+}
+
 // Register range function with step
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
 struct StepRange<T>(T, T, T)
@@ -75,9 +93,13 @@ where
     Ok(StepRange::<T>(from, to, step))
 }
 
-def_package!(crate:BasicIteratorPackage:"Basic range iterators.", lib, {
+def_package!(crate:BasicIteratorPackage:"Basic range iterators.", lib, pure, {
     reg_range::<INT>(lib);
     lib.set_fn_2("range", get_range::<INT>);
+    lib.set_fn_2("..", get_range::<INT>);
+
+    reg_range_inclusive::<INT>(lib);
+    lib.set_fn_2("..=", get_range_inclusive::<INT>);
 
     #[cfg(not(feature = "only_i32"))]
     #[cfg(not(feature = "only_i64"))]
@@ -87,6 +109,10 @@ def_package!(crate:BasicIteratorPackage:"Basic range iterators.", lib, {
                 $(
                     reg_range::<$y>($lib);
                     $lib.set_fn_2($x, get_range::<$y>);
+                    $lib.set_fn_2("..", get_range::<$y>);
+
+                    reg_range_inclusive::<$y>($lib);
+                    $lib.set_fn_2("..=", get_range_inclusive::<$y>);
                 )*
             )
         }
@@ -41,7 +41,7 @@ macro_rules! reg_op {
     };
 }
 
-def_package!(crate:LogicPackage:"Logical operators.", lib, {
+def_package!(crate:LogicPackage:"Logical operators.", lib, pure, {
     reg_op!(lib, "<", lt, INT, char);
     reg_op!(lib, "<=", lte, INT, char);
     reg_op!(lib, ">", gt, INT, char);
@@ -2,11 +2,15 @@
 
 use crate::any::Dynamic;
 use crate::def_package;
-use crate::engine::Map;
+use crate::engine::{calc_data_sizes, Map};
+use crate::fn_native::{CallContext, FnCallArgs};
 use crate::module::FuncReturn;
 use crate::parser::INT;
 
 use crate::stdlib::{
+    any::TypeId,
+    boxed::Box,
+    mem,
     string::{String, ToString},
     vec::Vec,
 };
@@ -17,6 +21,21 @@ fn map_get_keys(map: &mut Map) -> FuncReturn<Vec<Dynamic>> {
 fn map_get_values(map: &mut Map) -> FuncReturn<Vec<Dynamic>> {
     Ok(map.iter().map(|(_, v)| v.clone()).collect())
 }
+fn mixin(context: &mut CallContext, map1: &mut Map, map2: Map) -> FuncReturn<()> {
+    // Check the size before adding the new properties
+    let added = map2.keys().filter(|key| !map1.contains_key(*key)).count();
+    context.check_map_size(map1.len() + added)?;
+    context.check_added_data_size(|| {
+        map2.iter()
+            .map(|(key, value)| key.len() + calc_data_sizes(value).3)
+            .sum()
+    })?;
+
+    map2.into_iter().for_each(|(key, value)| {
+        map1.insert(key, value);
+    });
+    Ok(())
+}
 
 #[cfg(not(feature = "no_object"))]
 def_package!(crate:BasicMapPackage:"Basic object map utilities.", lib, {
@@ -25,29 +44,40 @@ def_package!(crate:BasicMapPackage:"Basic object map utilities.", lib, {
         |map: &mut Map, prop: String| Ok(map.contains_key(&prop)),
     );
     lib.set_fn_1_mut("len", |map: &mut Map| Ok(map.len() as INT));
-    lib.set_fn_1_mut("clear", |map: &mut Map| {
-        map.clear();
-        Ok(())
-    });
-    lib.set_fn_2_mut(
+    // Functions that remove properties take a context only to tell the `Engine` that they add no data,
+    // so that it need not measure the object map again
+    lib.set_fn_with_context(
+        "clear",
+        &[TypeId::of::<Map>()],
+        |_: &mut CallContext, args: &mut FnCallArgs| {
+            args[0].downcast_mut::<Map>().unwrap().clear();
+            Ok(())
+        },
+    );
+    lib.set_fn_with_context(
         "remove",
-        |x: &mut Map, name: String| Ok(x.remove(&name).unwrap_or_else(|| ().into())),
+        &[TypeId::of::<Map>(), TypeId::of::<String>()],
+        |_: &mut CallContext, args: &mut FnCallArgs| {
+            let name = mem::take(args[1]).cast::<String>();
+            let map = args[0].downcast_mut::<Map>().unwrap();
+            Ok(map.remove(&name).unwrap_or_else(|| ().into()))
+        },
     );
-    lib.set_fn_2_mut(
+    lib.set_fn_with_context(
         "mixin",
-        |map1: &mut Map, map2: Map| {
-            map2.into_iter().for_each(|(key, value)| {
-                map1.insert(key, value);
-            });
-            Ok(())
+        &[TypeId::of::<Map>(), TypeId::of::<Map>()],
+        |context: &mut CallContext, args: &mut FnCallArgs| {
+            let map2 = mem::take(args[1]).cast::<Map>();
+            mixin(context, args[0].downcast_mut::<Map>().unwrap(), map2)
         },
     );
-    lib.set_fn_2(
+    lib.set_fn_with_context(
         "+",
-        |mut map1: Map, map2: Map| {
-            map2.into_iter().for_each(|(key, value)| {
-                map1.insert(key, value);
-            });
+        &[TypeId::of::<Map>(), TypeId::of::<Map>()],
+        |context: &mut CallContext, args: &mut FnCallArgs| {
+            let map2 = mem::take(args[1]).cast::<Map>();
+            let mut map1 = mem::take(args[0]).cast::<Map>();
+            mixin(context, &mut map1, map2)?;
             Ok(map1)
         },
     );
@@ -58,4 +88,17 @@ def_package!(crate:BasicMapPackage:"Basic object map utilities.", lib, {
 
     #[cfg(not(feature = "no_index"))]
     lib.set_fn_1_mut("values", map_get_values);
+
+    // Only functions that do not change the object map are pure
+    ["has", "len", "+", "keys", "values"]
+        .iter()
+        .for_each(|name| lib.set_fns_pure_by_name(name, true));
+
+    // Register map iterator - iterates the property names
+    lib.set_iter(
+        TypeId::of::<Map>(),
+        Box::new(|map| Box::new(
+            map.cast::<Map>().into_iter().map(|(key, _)| key.into())) as Box<dyn Iterator<Item = Dynamic>>
+        ),
+    );
 });
@@ -1,6 +1,6 @@
 //! Module containing all built-in _packages_ available to Rhai, plus facilities to define custom packages.
 
-use crate::fn_native::{NativeCallable, SharedIteratorFunction};
+use crate::fn_native::{NativeCallable, SharedIteratorFunction, SharedNativeFunction};
 use crate::module::Module;
 use crate::utils::StaticVec;
 
@@ -76,6 +76,23 @@ impl PackagesCollection {
             .find(|f| f.is_some())
             .flatten()
     }
+    /// Get a shared handle to the specified function via its hash key.
+    pub(crate) fn get_shared_fn(&self, hash: u64) -> Option<SharedNativeFunction> {
+        self.packages.iter().find_map(|p| p.get_shared_fn(hash))
+    }
+    /// Is the specified function pure?
+    pub fn is_fn_pure(&self, hash: u64) -> bool {
+        match self.packages.iter().find(|p| p.contains_fn(hash)) {
+            Some(p) => p.is_fn_pure(hash),
+            None => false,
+        }
+    }
+    /// Get an iterator over the signatures of all Rust functions in the `PackagesCollection`.
+    pub(crate) fn iter_fn_signatures(
+        &self,
+    ) -> impl Iterator<Item = (&str, &[TypeId], Option<TypeId>)> {
+        self.packages.iter().flat_map(|p| p.iter_fn_signatures())
+    }
     /// Does the specified TypeId iterator exist in the `PackagesCollection`?
     pub fn contains_iter(&self, id: TypeId) -> bool {
         self.packages.iter().any(|p| p.contains_iter(id))
@@ -112,8 +129,33 @@ impl PackagesCollection {
 /// ```
 ///
 /// The above defines a package named 'MyPackage' with a single function named 'my_add'.
+///
+/// Add `pure` after the library name to mark all the functions in the package as pure,
+/// so calls to them with constant arguments can be evaluated by the optimizer under
+/// `OptimizationLevel::Full`.
+///
+/// ```
+/// use rhai::{Dynamic, EvalAltResult};
+/// use rhai::def_package;
+///
+/// fn add(x: i64, y: i64) -> Result<i64, Box<EvalAltResult>> { Ok(x + y) }
+///
+/// def_package!(rhai:MyPurePackage:"My super-duper pure package", lib, pure,
+/// {
+///     // Load a binary function with all value parameters.
+///     lib.set_fn_2("my_add", add);
+/// });
+/// ```
 #[macro_export]
 macro_rules! def_package {
+    ($root:ident : $package:ident : $comment:expr , $lib:ident , pure , $block:stmt) => {
+        $root::def_package!($root:$package:$comment, $lib, {
+            let pure = $lib.is_pure_by_default();
+            $lib.set_pure_by_default(true);
+            { $block }
+            $lib.set_pure_by_default(pure);
+        });
+    };
     ($root:ident : $package:ident : $comment:expr , $lib:ident , $block:stmt) => {
         #[doc=$comment]
         pub struct $package($root::packages::PackageLibrary);
@@ -1,5 +1,6 @@
 use crate::def_package;
 use crate::engine::{FUNC_TO_STRING, KEYWORD_DEBUG, KEYWORD_PRINT};
+use crate::fn_native::{CallContext, FnCallArgs};
 use crate::module::FuncReturn;
 use crate::parser::INT;
 
@@ -10,8 +11,10 @@ use crate::engine::Array;
 use crate::engine::Map;
 
 use crate::stdlib::{
+    any::TypeId,
     fmt::{Debug, Display},
-    format,
+    format, mem,
+    ops::{Range, RangeInclusive},
     string::{String, ToString},
 };
 
@@ -27,13 +30,25 @@ fn format_map(x: &mut Map) -> FuncReturn<String> {
     Ok(format!("#{:?}", x))
 }
 
+// Register string building - the sizes are checked before growing the strings
+pub(crate) fn append_char(context: &mut CallContext, s: &mut String, ch: char) -> FuncReturn<()> {
+    context.check_string_size(s.len() + ch.len_utf8())?;
+    s.push(ch);
+    Ok(())
+}
+pub(crate) fn append_str(context: &mut CallContext, s: &mut String, s2: &str) -> FuncReturn<()> {
+    context.check_string_size(s.len() + s2.len())?;
+    s.push_str(s2);
+    Ok(())
+}
+
 macro_rules! reg_op {
     ($lib:expr, $op:expr, $func:ident, $($par:ty),*) => {
         $( $lib.set_fn_1_mut($op, $func::<$par>); )*
     };
 }
 
-def_package!(crate:BasicStringPackage:"Basic string utilities, including printing.", lib, {
+def_package!(crate:BasicStringPackage:"Basic string utilities, including printing.", lib, pure, {
     reg_op!(lib, KEYWORD_PRINT, to_string, INT, bool, char);
     reg_op!(lib, FUNC_TO_STRING, to_string, INT, bool, char);
 
@@ -71,6 +86,10 @@ def_package!(crate:BasicStringPackage:"Basic string utilities, including printin
         reg_op!(lib, KEYWORD_DEBUG, to_debug, Array);
     }
 
+    reg_op!(lib, KEYWORD_PRINT, to_debug, Range<INT>, RangeInclusive<INT>);
+    reg_op!(lib, FUNC_TO_STRING, to_debug, Range<INT>, RangeInclusive<INT>);
+    reg_op!(lib, KEYWORD_DEBUG, to_debug, Range<INT>, RangeInclusive<INT>);
+
     #[cfg(not(feature = "no_object"))]
     {
         lib.set_fn_1_mut(KEYWORD_PRINT, format_map);
@@ -78,29 +97,43 @@ def_package!(crate:BasicStringPackage:"Basic string utilities, including printin
         lib.set_fn_1_mut(KEYWORD_DEBUG, format_map);
     }
 
-    lib.set_fn_2(
+    lib.set_fn_with_context(
         "+",
-        |mut s: String, ch: char| {
-            s.push(ch);
+        &[TypeId::of::<String>(), TypeId::of::<char>()],
+        |context: &mut CallContext, args: &mut FnCallArgs| {
+            let ch = mem::take(args[1]).cast::<char>();
+            let mut s = mem::take(args[0]).cast::<String>();
+            append_char(context, &mut s, ch)?;
             Ok(s)
         },
     );
-    lib.set_fn_2(
+    lib.set_fn_with_context(
         "+",
-        |mut s: String, s2: String| {
-            s.push_str(&s2);
+        &[TypeId::of::<String>(), TypeId::of::<String>()],
+        |context: &mut CallContext, args: &mut FnCallArgs| {
+            let s2 = mem::take(args[1]).cast::<String>();
+            let mut s = mem::take(args[0]).cast::<String>();
+            append_str(context, &mut s, &s2)?;
             Ok(s)
         },
     );
-    lib.set_fn_2_mut("append", |s: &mut String, ch: char| {
-        s.push(ch);
-        Ok(())
-    });
-    lib.set_fn_2_mut(
+    lib.set_fn_with_context(
         "append",
-        |s: &mut String, s2: String| {
-            s.push_str(&s2);
-            Ok(())
-        }
+        &[TypeId::of::<String>(), TypeId::of::<char>()],
+        |context: &mut CallContext, args: &mut FnCallArgs| {
+            let ch = mem::take(args[1]).cast::<char>();
+            append_char(context, args[0].downcast_mut::<String>().unwrap(), ch)
+        },
     );
+    lib.set_fn_with_context(
+        "append",
+        &[TypeId::of::<String>(), TypeId::of::<String>()],
+        |context: &mut CallContext, args: &mut FnCallArgs| {
+            let s2 = mem::take(args[1]).cast::<String>();
+            append_str(context, args[0].downcast_mut::<String>().unwrap(), &s2)
+        },
+    );
+
+    // `append` changes the string, so it is not pure
+    lib.set_fns_pure_by_name("append", false);
 });
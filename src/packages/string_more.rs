@@ -1,14 +1,20 @@
+use crate::any::Dynamic;
 use crate::def_package;
+use crate::fn_native::{CallContext, FnCallArgs};
 use crate::module::FuncReturn;
 use crate::parser::INT;
 use crate::utils::StaticVec;
 
+use super::string_basic::{append_char, append_str};
+
 #[cfg(not(feature = "no_index"))]
 use crate::engine::Array;
 
 use crate::stdlib::{
+    any::TypeId,
+    boxed::Box,
     fmt::Display,
-    format,
+    format, iter, mem,
     string::{String, ToString},
     vec::Vec,
 };
@@ -72,11 +78,35 @@ fn crop_string(s: &mut String, start: INT, len: INT) -> FuncReturn<()> {
     Ok(())
 }
 
+fn replace(context: &mut CallContext, s: &mut String, find: &str, sub: &str) -> FuncReturn<()> {
+    // Check the size before building the new string
+    if sub.len() > find.len() {
+        let count = s.matches(find).count();
+        context.check_string_size(s.len() + count * (sub.len() - find.len()))?;
+    }
+
+    *s = s.replace(find, sub);
+    Ok(())
+}
+
 macro_rules! reg_op {
     ($lib:expr, $op:expr, $func:ident, $($par:ty),*) => {
         $( $lib.set_fn_2($op, $func::<$par>); )*
     };
 }
+macro_rules! reg_replace {
+    ($lib:expr, $($find:ty, $sub:ty),*) => {
+        $( $lib.set_fn_with_context(
+            "replace",
+            &[TypeId::of::<String>(), TypeId::of::<$find>(), TypeId::of::<$sub>()],
+            |context: &mut CallContext, args: &mut FnCallArgs| {
+                let sub = mem::take(args[2]).cast::<$sub>().to_string();
+                let find = mem::take(args[1]).cast::<$find>().to_string();
+                replace(context, args[0].downcast_mut::<String>().unwrap(), &find, &sub)
+            },
+        ); )*
+    };
+}
 
 def_package!(crate:MoreStringPackage:"Additional string utilities, including string building.", lib, {
     reg_op!(lib, "+", append, INT, bool, char);
@@ -167,16 +197,21 @@ def_package!(crate:MoreStringPackage:"Additional string utilities, including str
         s.clear();
         Ok(())
     });
-    lib.set_fn_2_mut( "append", |s: &mut String, ch: char| {
-        s.push(ch);
-        Ok(())
-    });
-    lib.set_fn_2_mut(
+    lib.set_fn_with_context(
         "append",
-        |s: &mut String, add: String| {
-            s.push_str(&add);
-            Ok(())
-        }
+        &[TypeId::of::<String>(), TypeId::of::<char>()],
+        |context: &mut CallContext, args: &mut FnCallArgs| {
+            let ch = mem::take(args[1]).cast::<char>();
+            append_char(context, args[0].downcast_mut::<String>().unwrap(), ch)
+        },
+    );
+    lib.set_fn_with_context(
+        "append",
+        &[TypeId::of::<String>(), TypeId::of::<String>()],
+        |context: &mut CallContext, args: &mut FnCallArgs| {
+            let add = mem::take(args[1]).cast::<String>();
+            append_str(context, args[0].downcast_mut::<String>().unwrap(), &add)
+        },
     );
     lib.set_fn_3_mut( "sub_string", sub_string);
     lib.set_fn_2_mut(
@@ -201,51 +236,27 @@ def_package!(crate:MoreStringPackage:"Additional string utilities, including str
             Ok(())
         },
     );
-    lib.set_fn_3_mut(
+    lib.set_fn_with_context(
         "pad",
-        |s: &mut String, len: INT, ch: char| {
-            for _ in 0..s.chars().count() - len as usize {
-                s.push(ch);
+        &[TypeId::of::<String>(), TypeId::of::<INT>(), TypeId::of::<char>()],
+        |context: &mut CallContext, args: &mut FnCallArgs| {
+            let len = mem::take(args[1]).cast::<INT>();
+            let ch = mem::take(args[2]).cast::<char>();
+            let s = args[0].downcast_mut::<String>().unwrap();
+            let count = s.chars().count();
+
+            if len > 0 && (len as usize) > count {
+                let padding = len as usize - count;
+
+                // Check the size before allocating the padding
+                context.check_string_size(s.len().saturating_add(padding.saturating_mul(ch.len_utf8())))?;
+
+                s.extend(iter::repeat(ch).take(padding));
             }
             Ok(())
         },
     );
-    lib.set_fn_3_mut(
-        "replace",
-        |s: &mut String, find: String, sub: String| {
-            let new_str = s.replace(&find, &sub);
-            s.clear();
-            s.push_str(&new_str);
-            Ok(())
-        },
-    );
-    lib.set_fn_3_mut(
-        "replace",
-        |s: &mut String, find: String, sub: char| {
-            let new_str = s.replace(&find, &sub.to_string());
-            s.clear();
-            s.push_str(&new_str);
-            Ok(())
-        },
-    );
-    lib.set_fn_3_mut(
-        "replace",
-        |s: &mut String, find: char, sub: String| {
-            let new_str = s.replace(&find.to_string(), &sub);
-            s.clear();
-            s.push_str(&new_str);
-            Ok(())
-        },
-    );
-    lib.set_fn_3_mut(
-        "replace",
-        |s: &mut String, find: char, sub: char| {
-            let new_str = s.replace(&find.to_string(), &sub.to_string());
-            s.clear();
-            s.push_str(&new_str);
-            Ok(())
-        },
-    );
+    reg_replace!(lib, String, String, String, char, char, String, char, char);
     lib.set_fn_1_mut(
         "trim",
         |s: &mut String| {
@@ -257,4 +268,24 @@ def_package!(crate:MoreStringPackage:"Additional string utilities, including str
             Ok(())
         },
     );
+
+    // Only functions that do not change the string are pure
+    ["+", "len", "contains", "index_of", "sub_string"]
+        .iter()
+        .for_each(|name| lib.set_fns_pure_by_name(name, true));
+
+    // Register string iterator - iterates the characters
+    lib.set_iter(
+        TypeId::of::<String>(),
+        Box::new(|s| {
+            let s = s.cast::<String>();
+            let mut offset = 0;
+
+            Box::new(iter::from_fn(move || {
+                let ch = s[offset..].chars().next()?;
+                offset += ch.len_utf8();
+                Some(ch.into())
+            })) as Box<dyn Iterator<Item = Dynamic>>
+        }),
+    );
 });
@@ -4,11 +4,23 @@ use crate::any::{Dynamic, Union};
 use crate::calc_fn_hash;
 use crate::engine::{make_getter, make_setter, Engine, FunctionsLib};
 use crate::error::{LexError, ParseError, ParseErrorType};
+use crate::fn_native::Shared;
 use crate::optimize::{optimize_into_ast, OptimizationLevel};
 use crate::scope::{EntryType as ScopeEntryType, Scope};
+use crate::serialize::{deserialize_ast, serialize_ast};
 use crate::token::{Position, Token, TokenIterator};
+use crate::typecheck::check_types;
 use crate::utils::{StaticVec, EMPTY_TYPE_ID};
 
+#[cfg(not(feature = "no_function"))]
+use crate::optimize::shake_functions;
+
+#[cfg(not(feature = "no_index"))]
+use crate::engine::Array;
+
+#[cfg(not(feature = "no_object"))]
+use crate::engine::Map;
+
 #[cfg(not(feature = "no_module"))]
 use crate::module::ModuleRef;
 
@@ -17,12 +29,14 @@ use crate::module::ModuleRef;
 pub struct ModuleRef;
 
 use crate::stdlib::{
+    any::TypeId,
     borrow::Cow,
     boxed::Box,
     char,
     collections::HashMap,
-    format,
+    fmt, format,
     iter::{empty, repeat, Peekable},
+    mem,
     num::NonZeroUsize,
     ops::{Add, Deref, DerefMut},
     rc::Rc,
@@ -168,6 +182,101 @@ impl AST {
     pub fn retain_functions(&mut self) {
         self.0 = vec![];
     }
+
+    /// Remove all script-defined functions that can never be called, keeping only functions
+    /// reachable from the statements in the `AST` or from the functions named in `entry_points`.
+    ///
+    /// Functions that are to be called directly via `Engine::call_fn` must be listed in `entry_points`.
+    /// If any reachable code calls `eval` or passes spread arguments (e.g. `foo(..args)`), no function is removed.
+    /// Variadic functions are never removed.
+    ///
+    /// Call this _after_ merging `AST`'s, because functions removed may be needed by other `AST`'s.
+    ///
+    /// Compiling with `OptimizationLevel::Full` already removes private functions that are unreachable,
+    /// treating all public functions as entry points.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<rhai::EvalAltResult>> {
+    /// # #[cfg(not(feature = "no_function"))]
+    /// # {
+    /// use rhai::Engine;
+    ///
+    /// let engine = Engine::new();
+    ///
+    /// let lib = engine.compile(r"
+    ///                 fn foo(x) { bar(x) + 1 }
+    ///                 fn bar(x) { x * 2 }
+    ///                 fn baz() { 0 }
+    ///                 fn hello() { 42 }
+    ///           ")?;
+    ///
+    /// let script = engine.compile("foo(20)")?;
+    ///
+    /// let mut ast = lib.merge(&script);
+    ///
+    /// // Keep only 'foo' and 'bar' (called by 'foo'), plus 'hello'
+    /// ast.retain_reachable_functions(&["hello"]);
+    ///
+    /// assert_eq!(engine.eval_ast::<i64>(&ast)?, 41);
+    /// assert_eq!(engine.eval_ast::<i64>(&ast.merge(&engine.compile("hello()")?))?, 42);
+    /// assert!(engine.eval_ast::<i64>(&ast.merge(&engine.compile("baz()")?)).is_err());
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(feature = "no_function"))]
+    pub fn retain_reachable_functions(&mut self, entry_points: &[&str]) {
+        let functions = shake_functions(&self.0, &self.1, entry_points);
+
+        #[cfg(feature = "sync")]
+        {
+            self.1 = Arc::new(functions);
+        }
+        #[cfg(not(feature = "sync"))]
+        {
+            self.1 = Rc::new(functions);
+        }
+    }
+
+    /// Encode the `AST` into a compact, self-describing binary format.
+    ///
+    /// The encoding covers all statements, script-defined functions and positions, and can be
+    /// turned back into an `AST` via `AST::from_bytes`.
+    ///
+    /// Encoded `AST`'s are only portable between programs built from the same version of Rhai
+    /// with the same compiler.  Any other encoded `AST` is rejected by `AST::from_bytes`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<rhai::EvalAltResult>> {
+    /// use rhai::{Engine, AST};
+    ///
+    /// let engine = Engine::new();
+    ///
+    /// let bytes = engine.compile("40 + 2")?.to_bytes();
+    /// let ast = AST::from_bytes(&bytes)?;
+    ///
+    /// assert_eq!(engine.eval_ast::<i64>(&ast)?, 42);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serialize_ast(self)
+    }
+
+    /// Decode an `AST` previously encoded by `AST::to_bytes`.
+    ///
+    /// Returns `ParseErrorType::BadInput` if the data is malformed, is of an unsupported
+    /// format version, or was encoded by an incompatible build.
+    ///
+    /// The nesting depth recorded in the data is trusted, so an `AST` compiled under raised
+    /// nesting depth limits can be decoded.  Use `Engine::ast_from_bytes` for data from untrusted sources.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<ParseError>> {
+        deserialize_ast(bytes, None)
+    }
 }
 
 impl Add<Self> for &AST {
@@ -196,12 +305,42 @@ pub struct FnDef {
     pub access: FnAccess,
     /// Names of function parameters.
     pub params: StaticVec<String>,
+    /// Default value expressions of the trailing optional parameters.
+    pub defaults: StaticVec<Expr>,
+    /// Name of the rest parameter, which collects all extra arguments into an array.
+    pub rest_param: Option<String>,
+    /// Type annotations of function parameters, if any.
+    pub param_types: StaticVec<Option<Type>>,
+    /// Type annotation of the return value, if any.
+    pub return_type: Option<Type>,
+    /// Does the function body contain `yield` statements?
+    /// If so, calling the function returns a generator instead of running the body.
+    pub is_generator: bool,
     /// Function body.
     pub body: Stmt,
     /// Position of the function definition.
     pub pos: Position,
 }
 
+impl FnDef {
+    /// Number of parameters that must be passed, i.e. those without default values.
+    pub fn required_params(&self) -> usize {
+        self.params.len() - self.defaults.len()
+    }
+    /// Can this function be called with a certain number of arguments?
+    pub fn accepts_args(&self, num_args: usize) -> bool {
+        num_args >= self.required_params()
+            && (self.rest_param.is_some() || num_args <= self.params.len())
+    }
+    /// Position of the statement whose value is returned when the body ends without a `return`.
+    pub(crate) fn result_position(&self) -> Position {
+        match &self.body {
+            Stmt::Block(x) => x.0.iter().last().map_or(x.1, Stmt::position),
+            body => body.position(),
+        }
+    }
+}
+
 /// A sharable script-defined function.
 #[cfg(feature = "sync")]
 pub type SharedFnDef = Arc<FnDef>;
@@ -219,24 +358,80 @@ pub enum ReturnType {
 }
 
 /// A type that encapsulates a local stack with variable names to simulate an actual runtime scope.
+///
+/// It also keeps track of the nesting depths of expressions and statement blocks during parsing.
 #[derive(Debug, Clone, Default)]
-struct Stack(Vec<(String, ScopeEntryType)>);
+struct Stack {
+    /// Variables and modules in the simulated scope, with the type annotations of variables.
+    vars: Vec<(String, ScopeEntryType, Option<Type>)>,
+    /// Current nesting depth of expressions.
+    expr_depth: usize,
+    /// Maximum nesting depth of expressions.
+    max_expr_depth: usize,
+    /// Current nesting depth of statement blocks.
+    stmt_depth: usize,
+    /// Maximum nesting depth of statement blocks.
+    max_stmt_depth: usize,
+    /// Can `yield` statements be used here, i.e. in the body of a function but not in a statement expression?
+    allow_yield: bool,
+    /// Names of the custom types registered with the `Engine`, keyed by the Rust type names.
+    type_names: Shared<HashMap<String, String>>,
+}
 
 impl Stack {
-    /// Create a new `Stack`.
-    pub fn new() -> Self {
-        Default::default()
+    /// Create a new `Stack` with the nesting depth limits of an `Engine`.
+    pub fn new(engine: &Engine) -> Self {
+        Self {
+            max_expr_depth: engine.max_expr_depth,
+            max_stmt_depth: engine.max_stmt_depth,
+            type_names: engine.type_names.clone(),
+            ..Default::default()
+        }
+    }
+    /// Is a type name either built-in or registered with the `Engine`?
+    pub fn is_known_type(&self, name: &str) -> bool {
+        is_builtin_type_name(name) || self.type_names.values().any(|n| n == name)
+    }
+    /// Go one level deeper into a nested expression.
+    /// Returns an error if the expression is nested too deeply.
+    pub fn enter_expr(&mut self, pos: Position) -> Result<(), Box<ParseError>> {
+        self.expr_depth += 1;
+
+        if self.expr_depth > self.max_expr_depth {
+            Err(PERR::ExprTooDeep.into_err(pos))
+        } else {
+            Ok(())
+        }
+    }
+    /// Come back out of a nested expression.
+    pub fn exit_expr(&mut self) {
+        self.expr_depth -= 1;
+    }
+    /// Go one level deeper into a nested statement block.
+    /// Returns an error if the statement block is nested too deeply.
+    pub fn enter_stmt(&mut self, pos: Position) -> Result<(), Box<ParseError>> {
+        self.stmt_depth += 1;
+
+        if self.stmt_depth > self.max_stmt_depth {
+            Err(PERR::StmtTooDeep.into_err(pos))
+        } else {
+            Ok(())
+        }
+    }
+    /// Come back out of a nested statement block.
+    pub fn exit_stmt(&mut self) {
+        self.stmt_depth -= 1;
     }
     /// Find a variable by name in the `Stack`, searching in reverse.
     /// The return value is the offset to be deducted from `Stack::len`,
     /// i.e. the top element of the `Stack` is offset 1.
     /// Return zero when the variable name is not found in the `Stack`.
     pub fn find(&self, name: &str) -> Option<NonZeroUsize> {
-        self.0
+        self.vars
             .iter()
             .rev()
             .enumerate()
-            .find(|(_, (n, typ))| match typ {
+            .find(|(_, (n, typ, _))| match typ {
                 ScopeEntryType::Normal | ScopeEntryType::Constant => *n == name,
                 ScopeEntryType::Module => false,
             })
@@ -247,11 +442,11 @@ impl Stack {
     /// i.e. the top element of the `Stack` is offset 1.
     /// Return zero when the variable name is not found in the `Stack`.
     pub fn find_module(&self, name: &str) -> Option<NonZeroUsize> {
-        self.0
+        self.vars
             .iter()
             .rev()
             .enumerate()
-            .find(|(_, (n, typ))| match typ {
+            .find(|(_, (n, typ, _))| match typ {
                 ScopeEntryType::Module => *n == name,
                 ScopeEntryType::Normal | ScopeEntryType::Constant => false,
             })
@@ -260,16 +455,16 @@ impl Stack {
 }
 
 impl Deref for Stack {
-    type Target = Vec<(String, ScopeEntryType)>;
+    type Target = Vec<(String, ScopeEntryType, Option<Type>)>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.vars
     }
 }
 
 impl DerefMut for Stack {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        &mut self.vars
     }
 }
 
@@ -285,12 +480,16 @@ pub enum Stmt {
     IfThenElse(Box<(Expr, Stmt, Option<Stmt>)>),
     /// while expr { stmt }
     While(Box<(Expr, Stmt)>),
+    /// do { stmt } while|until expr
+    Do(Box<(Stmt, Expr, bool)>),
     /// loop { stmt }
     Loop(Box<Stmt>),
-    /// for id in expr { stmt }
-    For(Box<(String, Expr, Stmt)>),
+    /// for pattern in expr { stmt }
+    For(Box<(Pattern, Expr, Stmt)>),
     /// let id = expr
     Let(Box<((String, Position), Option<Expr>)>),
+    /// let pattern = expr
+    LetPattern(Box<(Pattern, Expr)>),
     /// const id = expr
     Const(Box<((String, Position), Expr)>),
     /// { stmt; ... }
@@ -303,6 +502,8 @@ pub enum Stmt {
     Break(Position),
     /// return/throw
     ReturnWithVal(Box<((ReturnType, Position), Option<Expr>)>),
+    /// yield expr
+    Yield(Box<(Expr, Position)>),
     /// import expr as module
     Import(Box<(Expr, (String, Position))>),
     /// expr id as name, ...
@@ -321,12 +522,15 @@ impl Stmt {
         match self {
             Stmt::Noop(pos) | Stmt::Continue(pos) | Stmt::Break(pos) => *pos,
             Stmt::Let(x) => (x.0).1,
+            Stmt::LetPattern(x) => x.0.position(),
             Stmt::Const(x) => (x.0).1,
             Stmt::ReturnWithVal(x) => (x.0).1,
+            Stmt::Yield(x) => x.1,
             Stmt::Block(x) => x.1,
             Stmt::IfThenElse(x) => x.0.position(),
             Stmt::Expr(x) => x.position(),
             Stmt::While(x) => x.1.position(),
+            Stmt::Do(x) => x.0.position(),
             Stmt::Loop(x) => x.position(),
             Stmt::For(x) => x.2.position(),
             Stmt::Import(x) => (x.1).1,
@@ -346,14 +550,19 @@ impl Stmt {
             // A No-op requires a semicolon in order to know it is an empty statement!
             Stmt::Noop(_) => false,
 
+            // A do loop ends with its guard expression.
+            Stmt::Do(_) => false,
+
             Stmt::Let(_)
+            | Stmt::LetPattern(_)
             | Stmt::Const(_)
             | Stmt::Import(_)
             | Stmt::Export(_)
             | Stmt::Expr(_)
             | Stmt::Continue(_)
             | Stmt::Break(_)
-            | Stmt::ReturnWithVal(_) => false,
+            | Stmt::ReturnWithVal(_)
+            | Stmt::Yield(_) => false,
         }
     }
 
@@ -367,15 +576,237 @@ impl Stmt {
             }
             Stmt::IfThenElse(x) => x.1.is_pure(),
             Stmt::While(x) => x.0.is_pure() && x.1.is_pure(),
+            Stmt::Do(x) => x.0.is_pure() && x.1.is_pure(),
             Stmt::Loop(x) => x.is_pure(),
             Stmt::For(x) => x.1.is_pure() && x.2.is_pure(),
-            Stmt::Let(_) | Stmt::Const(_) => false,
+            Stmt::Let(_) | Stmt::LetPattern(_) | Stmt::Const(_) => false,
             Stmt::Block(x) => x.0.iter().all(Stmt::is_pure),
             Stmt::Continue(_) | Stmt::Break(_) | Stmt::ReturnWithVal(_) => false,
+            Stmt::Yield(_) => false,
             Stmt::Import(_) => false,
             Stmt::Export(_) => false,
         }
     }
+
+    /// Does this statement contain a `yield` statement?
+    ///
+    /// `yield` cannot appear inside expressions, so only nested statements are searched.
+    pub fn contains_yield(&self) -> bool {
+        match self {
+            Stmt::Yield(_) => true,
+            Stmt::IfThenElse(x) => x.1.contains_yield() || x.2.iter().any(Stmt::contains_yield),
+            Stmt::While(x) => x.1.contains_yield(),
+            Stmt::Do(x) => x.0.contains_yield(),
+            Stmt::Loop(x) => x.contains_yield(),
+            Stmt::For(x) => x.2.contains_yield(),
+            Stmt::Block(x) => x.0.iter().any(Stmt::contains_yield),
+            _ => false,
+        }
+    }
+}
+
+/// A destructuring pattern, binding one or more variables to the parts of a value.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// id
+    Variable(Box<(String, Position)>),
+    /// [ pattern, ... ..id ]
+    #[cfg(not(feature = "no_index"))]
+    Array(Box<(StaticVec<Pattern>, Option<(String, Position)>, Position)>),
+    /// #{ id: pattern, ... }
+    #[cfg(not(feature = "no_object"))]
+    Map(Box<(StaticVec<((String, Position), Pattern)>, Position)>),
+    /// ( id, id ) - the name and value of each property when iterating an object map
+    #[cfg(not(feature = "no_object"))]
+    Pair(Box<((String, Position), (String, Position), Position)>),
+}
+
+impl Pattern {
+    /// Get the `Position` of this pattern.
+    pub fn position(&self) -> Position {
+        match self {
+            Self::Variable(x) => x.1,
+            #[cfg(not(feature = "no_index"))]
+            Self::Array(x) => x.2,
+            #[cfg(not(feature = "no_object"))]
+            Self::Map(x) => x.1,
+            #[cfg(not(feature = "no_object"))]
+            Self::Pair(x) => x.2,
+        }
+    }
+
+    /// Call a function with the name of each variable bound by this pattern,
+    /// in the order that the variables are pushed onto the `Scope`.
+    pub(crate) fn for_each_name(&self, f: &mut impl FnMut(&str)) {
+        match self {
+            Self::Variable(x) => f(&x.0),
+            #[cfg(not(feature = "no_index"))]
+            Self::Array(x) => {
+                x.0.iter().for_each(|pattern| pattern.for_each_name(f));
+                if let Some((name, _)) = &x.1 {
+                    f(name);
+                }
+            }
+            #[cfg(not(feature = "no_object"))]
+            Self::Map(x) => x.0.iter().for_each(|(_, pattern)| pattern.for_each_name(f)),
+            #[cfg(not(feature = "no_object"))]
+            Self::Pair(x) => {
+                f(&(x.0).0);
+                f(&(x.1).0);
+            }
+        }
+    }
+}
+
+/// A type annotation on a variable, a function parameter or the return value of a function.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Type {
+    /// ()
+    Unit,
+    /// bool
+    Bool,
+    /// int
+    Int,
+    /// float
+    #[cfg(not(feature = "no_float"))]
+    Float,
+    /// char
+    Char,
+    /// string
+    String,
+    /// array
+    #[cfg(not(feature = "no_index"))]
+    Array,
+    /// map
+    #[cfg(not(feature = "no_object"))]
+    Map,
+    /// Dynamic - any type.
+    Dynamic,
+    /// Any other type, by the name returned from `type_of`.
+    Custom(String),
+}
+
+impl Type {
+    /// Get the type named by an identifier.
+    pub(crate) fn from_name(name: &str) -> Self {
+        match name {
+            "bool" => Self::Bool,
+            "int" => Self::Int,
+            #[cfg(not(feature = "no_float"))]
+            "float" => Self::Float,
+            "char" => Self::Char,
+            "string" => Self::String,
+            #[cfg(not(feature = "no_index"))]
+            "array" => Self::Array,
+            #[cfg(not(feature = "no_object"))]
+            "map" => Self::Map,
+            "Dynamic" => Self::Dynamic,
+            name => Self::Custom(name.into()),
+        }
+    }
+
+    /// Get the `TypeId` of the values of this type, if it is a built-in type.
+    pub(crate) fn type_id(&self) -> Option<TypeId> {
+        Some(match self {
+            Self::Unit => TypeId::of::<()>(),
+            Self::Bool => TypeId::of::<bool>(),
+            Self::Int => TypeId::of::<INT>(),
+            #[cfg(not(feature = "no_float"))]
+            Self::Float => TypeId::of::<FLOAT>(),
+            Self::Char => TypeId::of::<char>(),
+            Self::String => TypeId::of::<String>(),
+            #[cfg(not(feature = "no_index"))]
+            Self::Array => TypeId::of::<Array>(),
+            #[cfg(not(feature = "no_object"))]
+            Self::Map => TypeId::of::<Map>(),
+            Self::Dynamic | Self::Custom(_) => return None,
+        })
+    }
+
+    /// Get the built-in type with a particular `TypeId`, if any.
+    pub(crate) fn from_type_id(id: TypeId) -> Option<Self> {
+        [
+            Self::Unit,
+            Self::Bool,
+            Self::Int,
+            #[cfg(not(feature = "no_float"))]
+            Self::Float,
+            Self::Char,
+            Self::String,
+            #[cfg(not(feature = "no_index"))]
+            Self::Array,
+            #[cfg(not(feature = "no_object"))]
+            Self::Map,
+        ]
+        .iter()
+        .find(|typ| typ.type_id() == Some(id))
+        .cloned()
+    }
+
+    /// Can a value of another type be cast to this type?
+    ///
+    /// Returns `None` if it is not known until the value is checked at runtime.
+    pub(crate) fn accepts(&self, other: &Self) -> Option<bool> {
+        match (self, other) {
+            (Self::Dynamic, _) => Some(true),
+            (_, Self::Dynamic) | (Self::Custom(_), _) | (_, Self::Custom(_)) => None,
+            #[cfg(not(feature = "no_float"))]
+            (Self::Float, Self::Int) => Some(true),
+            (typ, other) => Some(typ == other),
+        }
+    }
+
+    /// Cast a value to this type.
+    ///
+    /// An integer is converted when cast to `float`.  Returns the value back if it is of another type.
+    pub(crate) fn cast(&self, engine: &Engine, value: Dynamic) -> Result<Dynamic, Dynamic> {
+        match (self, value.0) {
+            (Self::Unit, v @ Union::Unit(_))
+            | (Self::Bool, v @ Union::Bool(_))
+            | (Self::Int, v @ Union::Int(_))
+            | (Self::Char, v @ Union::Char(_))
+            | (Self::String, v @ Union::Str(_)) => Ok(Dynamic(v)),
+            #[cfg(not(feature = "no_float"))]
+            (Self::Float, v @ Union::Float(_)) => Ok(Dynamic(v)),
+            #[cfg(not(feature = "no_float"))]
+            (Self::Float, Union::Int(n)) => Ok((n as FLOAT).into()),
+            #[cfg(not(feature = "no_index"))]
+            (Self::Array, v @ Union::Array(_)) => Ok(Dynamic(v)),
+            #[cfg(not(feature = "no_object"))]
+            (Self::Map, v @ Union::Map(_)) => Ok(Dynamic(v)),
+            (Self::Dynamic, v) => Ok(Dynamic(v)),
+            (Self::Custom(name), v) => {
+                let value = Dynamic(v);
+
+                if engine.map_type_name(value.type_name()) == name {
+                    Ok(value)
+                } else {
+                    Err(value)
+                }
+            }
+            (_, v) => Err(Dynamic(v)),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unit => write!(f, "()"),
+            Self::Bool => write!(f, "bool"),
+            Self::Int => write!(f, "int"),
+            #[cfg(not(feature = "no_float"))]
+            Self::Float => write!(f, "float"),
+            Self::Char => write!(f, "char"),
+            Self::String => write!(f, "string"),
+            #[cfg(not(feature = "no_index"))]
+            Self::Array => write!(f, "array"),
+            #[cfg(not(feature = "no_object"))]
+            Self::Map => write!(f, "map"),
+            Self::Dynamic => write!(f, "Dynamic"),
+            Self::Custom(name) => write!(f, "{}", name),
+        }
+    }
 }
 
 #[cfg(not(feature = "no_module"))]
@@ -404,7 +835,8 @@ pub enum Expr {
     Property(Box<((String, String, String), Position)>),
     /// { stmt }
     Stmt(Box<(Stmt, Position)>),
-    /// func(expr, ... ) - ((function name, position), optional modules, hash, arguments, optional default value)
+    /// func(expr, ... ) - ((function name, position), optional modules, hash, arguments, optional default value,
+    /// names of the trailing named arguments)
     /// Use `Cow<'static, str>` because a lot of operators (e.g. `==`, `>=`) are implemented as function calls
     /// and the function names are predictable, so no need to allocate a new `String`.
     FnCall(
@@ -414,14 +846,15 @@ pub enum Expr {
             u64,
             StaticVec<Expr>,
             Option<Dynamic>,
+            Vec<(String, Position)>,
         )>,
     ),
     /// expr = expr
     Assignment(Box<(Expr, Expr, Position)>),
-    /// lhs.rhs
-    Dot(Box<(Expr, Expr, Position)>),
-    /// expr[expr]
-    Index(Box<(Expr, Expr, Position)>),
+    /// lhs.rhs, or lhs?.rhs if optional
+    Dot(Box<(Expr, Expr, Position, bool)>),
+    /// expr[expr], or expr?[expr] if optional
+    Index(Box<(Expr, Expr, Position, bool)>),
     /// [ expr, ... ]
     Array(Box<(StaticVec<Expr>, Position)>),
     /// #{ name:expr, ... }
@@ -432,6 +865,13 @@ pub enum Expr {
     And(Box<(Expr, Expr, Position)>),
     /// lhs || rhs
     Or(Box<(Expr, Expr, Position)>),
+    /// lhs ?? rhs
+    Coalesce(Box<(Expr, Expr, Position)>),
+    /// ..expr - spread an array into the arguments of a function call
+    #[cfg(not(feature = "no_index"))]
+    Spread(Box<(Expr, Position)>),
+    /// expr: type - a value checked against a type annotation
+    Cast(Box<(Expr, Type, Position)>),
     /// true
     True(Position),
     /// false
@@ -519,12 +959,16 @@ impl Expr {
             Self::Stmt(x) => x.1,
             Self::Variable(x) => (x.0).1,
             Self::FnCall(x) => (x.0).1,
+            #[cfg(not(feature = "no_index"))]
+            Self::Spread(x) => x.1,
+            Self::Cast(x) => x.2,
 
-            Self::And(x) | Self::Or(x) | Self::In(x) => x.2,
+            Self::And(x) | Self::Or(x) | Self::Coalesce(x) | Self::In(x) => x.2,
 
             Self::True(pos) | Self::False(pos) | Self::Unit(pos) => *pos,
 
-            Self::Assignment(x) | Self::Dot(x) | Self::Index(x) => x.0.position(),
+            Self::Assignment(x) => x.0.position(),
+            Self::Dot(x) | Self::Index(x) => x.0.position(),
         }
     }
 
@@ -543,8 +987,12 @@ impl Expr {
             Self::Property(x) => x.1 = new_pos,
             Self::Stmt(x) => x.1 = new_pos,
             Self::FnCall(x) => (x.0).1 = new_pos,
+            #[cfg(not(feature = "no_index"))]
+            Self::Spread(x) => x.1 = new_pos,
+            Self::Cast(x) => x.2 = new_pos,
             Self::And(x) => x.2 = new_pos,
             Self::Or(x) => x.2 = new_pos,
+            Self::Coalesce(x) => x.2 = new_pos,
             Self::In(x) => x.2 = new_pos,
             Self::True(pos) => *pos = new_pos,
             Self::False(pos) => *pos = new_pos,
@@ -564,13 +1012,20 @@ impl Expr {
         match self {
             Self::Array(x) => x.0.iter().all(Self::is_pure),
 
-            Self::Index(x) | Self::And(x) | Self::Or(x) | Self::In(x) => {
+            Self::Index(x) => x.0.is_pure() && x.1.is_pure(),
+
+            Self::And(x) | Self::Or(x) | Self::Coalesce(x) | Self::In(x) => {
                 let (lhs, rhs, _) = x.as_ref();
                 lhs.is_pure() && rhs.is_pure()
             }
 
             Self::Stmt(x) => x.0.is_pure(),
 
+            #[cfg(not(feature = "no_index"))]
+            Self::Spread(x) => x.0.is_pure(),
+
+            Self::Cast(x) => x.0.is_pure(),
+
             Self::Variable(_) => true,
 
             expr => expr.is_constant(),
@@ -618,10 +1073,16 @@ impl Expr {
             | Self::In(_)
             | Self::And(_)
             | Self::Or(_)
+            | Self::Coalesce(_)
             | Self::True(_)
             | Self::False(_)
             | Self::Unit(_) => false,
 
+            #[cfg(not(feature = "no_index"))]
+            Self::Spread(_) => false,
+
+            Self::Cast(_) => false,
+
             Self::StringConstant(_)
             | Self::Stmt(_)
             | Self::FnCall(_)
@@ -630,24 +1091,41 @@ impl Expr {
             | Self::Index(_)
             | Self::Array(_)
             | Self::Map(_) => match token {
-                Token::LeftBracket => true,
+                Token::LeftBracket | Token::QuestionBracket => true,
                 _ => false,
             },
 
             Self::Variable(_) => match token {
-                Token::LeftBracket | Token::LeftParen => true,
+                Token::LeftBracket | Token::QuestionBracket | Token::LeftParen => true,
                 #[cfg(not(feature = "no_module"))]
                 Token::DoubleColon => true,
                 _ => false,
             },
 
             Self::Property(_) => match token {
-                Token::LeftBracket | Token::LeftParen => true,
+                Token::LeftBracket | Token::QuestionBracket | Token::LeftParen => true,
                 _ => false,
             },
         }
     }
 
+    /// Is this a spread argument (i.e. `..expr`)?
+    pub(crate) fn is_spread(&self) -> bool {
+        match self {
+            #[cfg(not(feature = "no_index"))]
+            Self::Spread(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Does this dot/index chain contain optional access (i.e. `?.` or `?[`)?
+    pub(crate) fn is_optional_chain(&self) -> bool {
+        match self {
+            Self::Dot(x) | Self::Index(x) => x.3 || x.1.is_optional_chain(),
+            _ => false,
+        }
+    }
+
     /// Convert a `Variable` into a `Property`.  All other variants are untouched.
     pub(crate) fn into_property(self) -> Self {
         match self {
@@ -705,7 +1183,7 @@ fn parse_paren_expr<'a>(
         // ( xxx )
         (Token::RightParen, _) => Ok(expr),
         // ( <error>
-        (Token::LexError(err), pos) => return Err(PERR::BadInput(err.to_string()).into_err(pos)),
+        (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(pos)),
         // ( xxx ???
         (_, pos) => Err(PERR::MissingToken(
             Token::RightParen.into(),
@@ -737,7 +1215,7 @@ fn parse_call_expr<'a>(
             .into_err(*pos))
         }
         // id <error>
-        (Token::LexError(err), pos) => return Err(PERR::BadInput(err.to_string()).into_err(*pos)),
+        (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(*pos)),
         // id()
         (Token::RightParen, _) => {
             eat_token(input, Token::RightParen);
@@ -768,14 +1246,54 @@ fn parse_call_expr<'a>(
                 hash_fn_def,
                 args,
                 None,
+                vec![],
             ))));
         }
         // id...
         _ => (),
     }
 
+    let mut named_args = Vec::new();
+
     loop {
-        args.push(parse_expr(input, stack, allow_stmt_expr)?);
+        let arg = match input.peek().unwrap() {
+            // id(...args, ..expr
+            #[cfg(not(feature = "no_index"))]
+            (Token::ExclusiveRange, _) => {
+                let pos = eat_token(input, Token::ExclusiveRange);
+                let expr = parse_expr(input, stack, allow_stmt_expr)?;
+                Expr::Spread(Box::new((expr, pos)))
+            }
+            _ => parse_expr(input, stack, allow_stmt_expr)?,
+        };
+
+        match (arg, input.peek().unwrap()) {
+            // id(...name: expr
+            (Expr::Variable(x), (Token::Colon, _)) if x.1.is_none() => {
+                eat_token(input, Token::Colon);
+                let (name, pos) = x.0;
+
+                if named_args.iter().any(|(n, _)| *n == name) {
+                    return Err(PERR::MalformedCallExpr(format!(
+                        "Duplicated named argument '{}' in this function call '{}'",
+                        name, id
+                    ))
+                    .into_err(pos));
+                }
+
+                args.push(parse_expr(input, stack, allow_stmt_expr)?);
+                named_args.push((name, pos));
+            }
+            // id(...name: expr, expr
+            (arg, _) if !named_args.is_empty() => {
+                return Err(PERR::MalformedCallExpr(format!(
+                    "Positional arguments cannot follow named arguments in this function call '{}'",
+                    id
+                ))
+                .into_err(arg.position()))
+            }
+            (arg, _) => args.push(arg),
+        }
 
         match input.peek().unwrap() {
             // id(...args)
@@ -809,6 +1327,7 @@ fn parse_call_expr<'a>(
                     hash_fn_def,
                     args,
                     None,
+                    named_args,
                 ))));
             }
             // id(...args,
@@ -824,9 +1343,7 @@ fn parse_call_expr<'a>(
                 .into_err(*pos))
             }
             // id(...args <error>
-            (Token::LexError(err), pos) => {
-                return Err(PERR::BadInput(err.to_string()).into_err(*pos))
-            }
+            (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(*pos)),
             // id(...args ???
             (_, pos) => {
                 return Err(PERR::MissingToken(
@@ -846,6 +1363,7 @@ fn parse_index_chain<'a>(
     stack: &mut Stack,
     lhs: Expr,
     pos: Position,
+    is_optional: bool,
     allow_stmt_expr: bool,
 ) -> Result<Expr, Box<ParseError>> {
     let idx_expr = parse_expr(input, stack, allow_stmt_expr)?;
@@ -853,13 +1371,6 @@ fn parse_index_chain<'a>(
     // Check type of indexing - must be integer or string
     match &idx_expr {
         // lhs[int]
-        Expr::IntegerConstant(x) if x.0 < 0 => {
-            return Err(PERR::MalformedIndexExpr(format!(
-                "Array access expects non-negative index: {} < 0",
-                x.0
-            ))
-            .into_err(x.1))
-        }
         Expr::IntegerConstant(x) => match lhs {
             Expr::Array(_) | Expr::StringConstant(_) => (),
 
@@ -998,18 +1509,28 @@ fn parse_index_chain<'a>(
             // Any more indexing following?
             match input.peek().unwrap() {
                 // If another indexing level, right-bind it
-                (Token::LeftBracket, _) => {
-                    let idx_pos = eat_token(input, Token::LeftBracket);
+                (Token::LeftBracket, _) | (Token::QuestionBracket, _) => {
+                    let (token, idx_pos) = input.next().unwrap();
+                    let is_idx_optional = token == Token::QuestionBracket;
                     // Recursively parse the indexing chain, right-binding each
-                    let idx = parse_index_chain(input, stack, idx_expr, idx_pos, allow_stmt_expr)?;
+                    stack.enter_expr(idx_pos)?;
+                    let idx = parse_index_chain(
+                        input,
+                        stack,
+                        idx_expr,
+                        idx_pos,
+                        is_idx_optional,
+                        allow_stmt_expr,
+                    )?;
+                    stack.exit_expr();
                     // Indexing binds to right
-                    Ok(Expr::Index(Box::new((lhs, idx, pos))))
+                    Ok(Expr::Index(Box::new((lhs, idx, pos, is_optional))))
                 }
                 // Otherwise terminate the indexing chain
-                _ => Ok(Expr::Index(Box::new((lhs, idx_expr, pos)))),
+                _ => Ok(Expr::Index(Box::new((lhs, idx_expr, pos, is_optional)))),
             }
         }
-        (Token::LexError(err), pos) => return Err(PERR::BadInput(err.to_string()).into_err(*pos)),
+        (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(*pos)),
         (_, pos) => Err(PERR::MissingToken(
             Token::RightBracket.into(),
             "for a matching [ in this index expression".into(),
@@ -1045,7 +1566,7 @@ fn parse_array_literal<'a>(
                     .into_err(*pos))
                 }
                 (Token::LexError(err), pos) => {
-                    return Err(PERR::BadInput(err.to_string()).into_err(*pos))
+                    return Err(err.to_parse_error_type().into_err(*pos))
                 }
                 (_, pos) => {
                     return Err(PERR::MissingToken(
@@ -1075,11 +1596,9 @@ fn parse_map_literal<'a>(
             const MISSING_RBRACE: &str = "to end this object map literal";
 
             let (name, pos) = match input.next().unwrap() {
-                (Token::Identifier(s), pos) => (s, pos),
-                (Token::StringConst(s), pos) => (s, pos),
-                (Token::LexError(err), pos) => {
-                    return Err(PERR::BadInput(err.to_string()).into_err(pos))
-                }
+                (Token::Identifier(s), pos) => (s.into_owned(), pos),
+                (Token::StringConst(s), pos) => (s.into_owned(), pos),
+                (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(pos)),
                 (_, pos) if map.is_empty() => {
                     return Err(
                         PERR::MissingToken(Token::RightBrace.into(), MISSING_RBRACE.into())
@@ -1097,9 +1616,7 @@ fn parse_map_literal<'a>(
 
             match input.next().unwrap() {
                 (Token::Colon, _) => (),
-                (Token::LexError(err), pos) => {
-                    return Err(PERR::BadInput(err.to_string()).into_err(pos))
-                }
+                (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(pos)),
                 (_, pos) => {
                     return Err(PERR::MissingToken(
                         Token::Colon.into(),
@@ -1132,7 +1649,7 @@ fn parse_map_literal<'a>(
                     .into_err(*pos))
                 }
                 (Token::LexError(err), pos) => {
-                    return Err(PERR::BadInput(err.to_string()).into_err(*pos))
+                    return Err(err.to_parse_error_type().into_err(*pos))
                 }
                 (_, pos) => {
                     return Err(
@@ -1168,8 +1685,10 @@ fn parse_primary<'a>(
         // { - block statement as expression
         (Token::LeftBrace, pos) if allow_stmt_expr => {
             let pos = *pos;
-            return parse_block(input, stack, false, allow_stmt_expr)
-                .map(|block| Expr::Stmt(Box::new((block, pos))));
+            let allow_yield = mem::replace(&mut stack.allow_yield, false);
+            let block = parse_block(input, stack, false, allow_stmt_expr);
+            stack.allow_yield = allow_yield;
+            return block.map(|block| Expr::Stmt(Box::new((block, pos))));
         }
         (Token::EOF, pos) => return Err(PERR::UnexpectedEOF.into_err(*pos)),
         _ => input.next().unwrap(),
@@ -1180,10 +1699,10 @@ fn parse_primary<'a>(
         #[cfg(not(feature = "no_float"))]
         Token::FloatConstant(x) => Expr::FloatConstant(Box::new((x, pos))),
         Token::CharConstant(c) => Expr::CharConstant(Box::new((c, pos))),
-        Token::StringConst(s) => Expr::StringConstant(Box::new((s, pos))),
+        Token::StringConst(s) => Expr::StringConstant(Box::new((s.into_owned(), pos))),
         Token::Identifier(s) => {
             let index = stack.find(&s);
-            Expr::Variable(Box::new(((s, pos), None, 0, index)))
+            Expr::Variable(Box::new(((s.into_owned(), pos), None, 0, index)))
         }
         Token::LeftParen => parse_paren_expr(input, stack, pos, allow_stmt_expr)?,
         #[cfg(not(feature = "no_index"))]
@@ -1192,7 +1711,7 @@ fn parse_primary<'a>(
         Token::MapStart => parse_map_literal(input, stack, pos, allow_stmt_expr)?,
         Token::True => Expr::True(pos),
         Token::False => Expr::False(pos),
-        Token::LexError(err) => return Err(PERR::BadInput(err.to_string()).into_err(pos)),
+        Token::LexError(err) => return Err(err.to_parse_error_type().into_err(pos)),
         token => {
             return Err(PERR::BadInput(format!("Unexpected '{}'", token.syntax())).into_err(pos))
         }
@@ -1228,17 +1747,26 @@ fn parse_primary<'a>(
                         modules = Some(Box::new(m));
                     }
 
-                    Expr::Variable(Box::new(((id2, pos2), modules, 0, index)))
+                    Expr::Variable(Box::new(((id2.into_owned(), pos2), modules, 0, index)))
                 }
                 (_, pos2) => return Err(PERR::VariableExpected.into_err(pos2)),
             },
             // Indexing
             #[cfg(not(feature = "no_index"))]
             (expr, Token::LeftBracket) => {
-                parse_index_chain(input, stack, expr, token_pos, allow_stmt_expr)?
+                parse_index_chain(input, stack, expr, token_pos, false, allow_stmt_expr)?
+            }
+            // Optional indexing
+            #[cfg(not(feature = "no_index"))]
+            (expr, Token::QuestionBracket) => {
+                parse_index_chain(input, stack, expr, token_pos, true, allow_stmt_expr)?
+            }
+            // Postfix operator not supported (e.g. indexing under `no_index`)
+            (_, token) => {
+                return Err(
+                    PERR::BadInput(format!("Unexpected '{}'", token.syntax())).into_err(token_pos)
+                )
             }
-            // Unknown postfix operator
-            (expr, token) => panic!("unknown postfix operator {:?} for {:?}", token, expr),
         }
     }
 
@@ -1274,11 +1802,19 @@ fn parse_unary<'a>(
                 pos,
             ))))
         }
+        // ..expr - range from the start
+        (Token::ExclusiveRange, pos) | (Token::InclusiveRange, pos) => {
+            Ok(Expr::IntegerConstant(Box::new((0, *pos))))
+        }
         // -expr
         (Token::UnaryMinus, _) => {
             let pos = eat_token(input, Token::UnaryMinus);
 
-            match parse_unary(input, stack, allow_stmt_expr)? {
+            stack.enter_expr(pos)?;
+            let expr = parse_unary(input, stack, allow_stmt_expr)?;
+            stack.exit_expr();
+
+            match expr {
                 // Negative integer
                 Expr::IntegerConstant(x) => {
                     let (num, pos) = *x;
@@ -1320,14 +1856,20 @@ fn parse_unary<'a>(
                         hash,
                         args,
                         None,
+                        vec![],
                     ))))
                 }
             }
         }
         // +expr
         (Token::UnaryPlus, _) => {
-            eat_token(input, Token::UnaryPlus);
-            parse_unary(input, stack, allow_stmt_expr)
+            let pos = eat_token(input, Token::UnaryPlus);
+
+            stack.enter_expr(pos)?;
+            let expr = parse_unary(input, stack, allow_stmt_expr)?;
+            stack.exit_expr();
+
+            Ok(expr)
         }
         // !expr
         (Token::Bang, _) => {
@@ -1344,6 +1886,7 @@ fn parse_unary<'a>(
                 hash,
                 args,
                 Some(false.into()), // NOT operator, when operating on invalid operand, defaults to false
+                vec![],
             ))))
         }
         // <EOF>
@@ -1353,6 +1896,12 @@ fn parse_unary<'a>(
     }
 }
 
+/// Make an expression checking the value of another expression against a type annotation.
+fn make_cast_expr(expr: Expr, typ: Type) -> Expr {
+    let pos = expr.position();
+    Expr::Cast(Box::new((expr, typ, pos)))
+}
+
 fn make_assignment_stmt<'a>(
     stack: &mut Stack,
     lhs: Expr,
@@ -1363,15 +1912,27 @@ fn make_assignment_stmt<'a>(
         Expr::Variable(x) if x.3.is_none() => Ok(Expr::Assignment(Box::new((lhs, rhs, pos)))),
         Expr::Variable(x) => {
             let ((name, name_pos), _, _, index) = x.as_ref();
-            match stack[(stack.len() - index.unwrap().get())].1 {
-                ScopeEntryType::Normal => Ok(Expr::Assignment(Box::new((lhs, rhs, pos)))),
+            match &stack[(stack.len() - index.unwrap().get())] {
+                // Values assigned to a variable with a type annotation are checked against the type
+                (_, ScopeEntryType::Normal, Some(typ)) => {
+                    let rhs = make_cast_expr(rhs, typ.clone());
+                    Ok(Expr::Assignment(Box::new((lhs, rhs, pos))))
+                }
+                (_, ScopeEntryType::Normal, None) => {
+                    Ok(Expr::Assignment(Box::new((lhs, rhs, pos))))
+                }
                 // Constant values cannot be assigned to
-                ScopeEntryType::Constant => {
+                (_, ScopeEntryType::Constant, _) => {
                     Err(PERR::AssignmentToConstant(name.clone()).into_err(*name_pos))
                 }
-                ScopeEntryType::Module => unreachable!(),
+                (_, ScopeEntryType::Module, _) => unreachable!(),
             }
         }
+        // An optional chain may have nothing to assign to
+        Expr::Index(_) | Expr::Dot(_) if lhs.is_optional_chain() => Err(PERR::BadInput(
+            "Cannot assign to an optional chain ('?.' or '?[')".into(),
+        )
+        .into_err(pos)),
         Expr::Index(x) | Expr::Dot(x) => match &x.0 {
             Expr::Variable(x) if x.3.is_none() => Ok(Expr::Assignment(Box::new((lhs, rhs, pos)))),
             Expr::Variable(x) => {
@@ -1418,6 +1979,7 @@ fn parse_op_assignment_stmt<'a>(
         (Token::AndAssign, pos) => ("&", pos),
         (Token::OrAssign, pos) => ("|", pos),
         (Token::XOrAssign, pos) => ("^", pos),
+        (Token::DoubleQuestionAssign, pos) => ("??", pos),
         (_, _) => return Ok(lhs),
     };
 
@@ -1431,8 +1993,15 @@ fn parse_op_assignment_stmt<'a>(
     args.push(lhs_copy);
     args.push(rhs);
 
-    let hash = calc_fn_hash(empty(), op, repeat(EMPTY_TYPE_ID()).take(args.len()));
-    let rhs_expr = Expr::FnCall(Box::new(((op.into(), pos), None, hash, args, None)));
+    let rhs_expr = if op == "??" {
+        // lhs ??= rhs -> lhs = lhs ?? rhs
+        let rhs = args.pop();
+        let lhs_copy = args.pop();
+        Expr::Coalesce(Box::new((lhs_copy, rhs, pos)))
+    } else {
+        let hash = calc_fn_hash(empty(), op, repeat(EMPTY_TYPE_ID()).take(args.len()));
+        Expr::FnCall(Box::new(((op.into(), pos), None, hash, args, None, vec![])))
+    };
 
     make_assignment_stmt(stack, lhs, rhs_expr, pos)
 }
@@ -1443,12 +2012,15 @@ fn make_dot_expr(
     rhs: Expr,
     op_pos: Position,
     is_index: bool,
+    is_optional: bool,
 ) -> Result<Expr, Box<ParseError>> {
     Ok(match (lhs, rhs) {
         // idx_lhs[idx_rhs].rhs
         // Attach dot chain to the bottom level of indexing chain
         (Expr::Index(x), rhs) => {
-            Expr::Index(Box::new((x.0, make_dot_expr(x.1, rhs, op_pos, true)?, x.2)))
+            let (idx_lhs, idx_rhs, pos, is_idx_optional) = *x;
+            let idx_rhs = make_dot_expr(idx_rhs, rhs, op_pos, true, is_optional)?;
+            Expr::Index(Box::new((idx_lhs, idx_rhs, pos, is_idx_optional)))
         }
         // lhs.id
         (lhs, Expr::Variable(x)) if x.1.is_none() => {
@@ -1459,12 +2031,12 @@ fn make_dot_expr(
             let setter = make_setter(&name);
             let rhs = Expr::Property(Box::new(((name, getter, setter), pos)));
 
-            Expr::Dot(Box::new((lhs, rhs, op_pos)))
+            Expr::Dot(Box::new((lhs, rhs, op_pos, is_optional)))
         }
         (lhs, Expr::Property(x)) => {
             let lhs = if is_index { lhs.into_property() } else { lhs };
             let rhs = Expr::Property(x);
-            Expr::Dot(Box::new((lhs, rhs, op_pos)))
+            Expr::Dot(Box::new((lhs, rhs, op_pos, is_optional)))
         }
         // lhs.module::id - syntax error
         (_, Expr::Variable(x)) if x.1.is_some() => {
@@ -1475,32 +2047,36 @@ fn make_dot_expr(
         }
         // lhs.dot_lhs.dot_rhs
         (lhs, Expr::Dot(x)) => {
-            let (dot_lhs, dot_rhs, pos) = *x;
+            let (dot_lhs, dot_rhs, pos, is_dot_optional) = *x;
             Expr::Dot(Box::new((
                 lhs,
                 Expr::Dot(Box::new((
                     dot_lhs.into_property(),
                     dot_rhs.into_property(),
                     pos,
+                    is_dot_optional,
                 ))),
                 op_pos,
+                is_optional,
             )))
         }
         // lhs.idx_lhs[idx_rhs]
         (lhs, Expr::Index(x)) => {
-            let (idx_lhs, idx_rhs, pos) = *x;
+            let (idx_lhs, idx_rhs, pos, is_idx_optional) = *x;
             Expr::Dot(Box::new((
                 lhs,
                 Expr::Index(Box::new((
                     idx_lhs.into_property(),
                     idx_rhs.into_property(),
                     pos,
+                    is_idx_optional,
                 ))),
                 op_pos,
+                is_optional,
             )))
         }
         // lhs.rhs
-        (lhs, rhs) => Expr::Dot(Box::new((lhs, rhs.into_property(), op_pos))),
+        (lhs, rhs) => Expr::Dot(Box::new((lhs, rhs.into_property(), op_pos, is_optional))),
     })
 }
 
@@ -1696,16 +2272,32 @@ fn parse_binary_op<'a>(
 
         let (op_token, pos) = input.next().unwrap();
 
-        let rhs = parse_unary(input, stack, allow_stmt_expr)?;
+        let rhs = match (&op_token, input.peek().unwrap()) {
+            // expr.. - range to the end
+            (Token::ExclusiveRange, (Token::RightBracket, pos))
+            | (Token::ExclusiveRange, (Token::RightParen, pos))
+            | (Token::ExclusiveRange, (Token::Comma, pos))
+            | (Token::ExclusiveRange, (Token::SemiColon, pos))
+            | (Token::ExclusiveRange, (Token::EOF, pos)) => {
+                Expr::IntegerConstant(Box::new((INT::MAX, *pos)))
+            }
+            _ => parse_unary(input, stack, allow_stmt_expr)?,
+        };
 
         let next_precedence = input.peek().unwrap().0.precedence();
 
         // Bind to right if the next operator has higher precedence
         // If same precedence, then check if the operator binds right
-        let rhs = if (current_precedence == next_precedence && bind_right)
-            || current_precedence < next_precedence
-        {
+        let rhs = if current_precedence == next_precedence && bind_right {
+            // A chain of right-binding operators (e.g. `a.b.c`) is flat and does not nest
             parse_binary_op(input, stack, current_precedence, rhs, allow_stmt_expr)?
+        } else if current_precedence < next_precedence {
+            // Only binding to an operator of higher precedence nests the expression one level deeper -
+            // a chain of left-binding operators is parsed in this loop
+            stack.enter_expr(pos)?;
+            let rhs = parse_binary_op(input, stack, current_precedence, rhs, allow_stmt_expr)?;
+            stack.exit_expr();
+            rhs
         } else {
             // Otherwise bind to left (even if next operator has the same precedence)
             rhs
@@ -1720,26 +2312,40 @@ fn parse_binary_op<'a>(
         args.push(rhs);
 
         current_lhs = match op_token {
-            Token::Plus => Expr::FnCall(Box::new(((op, pos), None, hash, args, None))),
-            Token::Minus => Expr::FnCall(Box::new(((op, pos), None, hash, args, None))),
-            Token::Multiply => Expr::FnCall(Box::new(((op, pos), None, hash, args, None))),
-            Token::Divide => Expr::FnCall(Box::new(((op, pos), None, hash, args, None))),
+            Token::Plus => Expr::FnCall(Box::new(((op, pos), None, hash, args, None, vec![]))),
+            Token::Minus => Expr::FnCall(Box::new(((op, pos), None, hash, args, None, vec![]))),
+            Token::Multiply => Expr::FnCall(Box::new(((op, pos), None, hash, args, None, vec![]))),
+            Token::Divide => Expr::FnCall(Box::new(((op, pos), None, hash, args, None, vec![]))),
+
+            Token::LeftShift => Expr::FnCall(Box::new(((op, pos), None, hash, args, None, vec![]))),
+            Token::RightShift => {
+                Expr::FnCall(Box::new(((op, pos), None, hash, args, None, vec![])))
+            }
+            Token::Modulo => Expr::FnCall(Box::new(((op, pos), None, hash, args, None, vec![]))),
+            Token::PowerOf => Expr::FnCall(Box::new(((op, pos), None, hash, args, None, vec![]))),
 
-            Token::LeftShift => Expr::FnCall(Box::new(((op, pos), None, hash, args, None))),
-            Token::RightShift => Expr::FnCall(Box::new(((op, pos), None, hash, args, None))),
-            Token::Modulo => Expr::FnCall(Box::new(((op, pos), None, hash, args, None))),
-            Token::PowerOf => Expr::FnCall(Box::new(((op, pos), None, hash, args, None))),
+            Token::ExclusiveRange | Token::InclusiveRange => {
+                Expr::FnCall(Box::new(((op, pos), None, hash, args, None, vec![])))
+            }
 
             // Comparison operators default to false when passed invalid operands
-            Token::EqualsTo => Expr::FnCall(Box::new(((op, pos), None, hash, args, cmp_def))),
-            Token::NotEqualsTo => Expr::FnCall(Box::new(((op, pos), None, hash, args, cmp_def))),
-            Token::LessThan => Expr::FnCall(Box::new(((op, pos), None, hash, args, cmp_def))),
+            Token::EqualsTo => {
+                Expr::FnCall(Box::new(((op, pos), None, hash, args, cmp_def, vec![])))
+            }
+            Token::NotEqualsTo => {
+                Expr::FnCall(Box::new(((op, pos), None, hash, args, cmp_def, vec![])))
+            }
+            Token::LessThan => {
+                Expr::FnCall(Box::new(((op, pos), None, hash, args, cmp_def, vec![])))
+            }
             Token::LessThanEqualsTo => {
-                Expr::FnCall(Box::new(((op, pos), None, hash, args, cmp_def)))
+                Expr::FnCall(Box::new(((op, pos), None, hash, args, cmp_def, vec![])))
+            }
+            Token::GreaterThan => {
+                Expr::FnCall(Box::new(((op, pos), None, hash, args, cmp_def, vec![])))
             }
-            Token::GreaterThan => Expr::FnCall(Box::new(((op, pos), None, hash, args, cmp_def))),
             Token::GreaterThanEqualsTo => {
-                Expr::FnCall(Box::new(((op, pos), None, hash, args, cmp_def)))
+                Expr::FnCall(Box::new(((op, pos), None, hash, args, cmp_def, vec![])))
             }
 
             Token::Or => {
@@ -1752,9 +2358,14 @@ fn parse_binary_op<'a>(
                 let current_lhs = args.pop();
                 Expr::And(Box::new((current_lhs, rhs, pos)))
             }
-            Token::Ampersand => Expr::FnCall(Box::new(((op, pos), None, hash, args, None))),
-            Token::Pipe => Expr::FnCall(Box::new(((op, pos), None, hash, args, None))),
-            Token::XOr => Expr::FnCall(Box::new(((op, pos), None, hash, args, None))),
+            Token::DoubleQuestion => {
+                let rhs = args.pop();
+                let current_lhs = args.pop();
+                Expr::Coalesce(Box::new((current_lhs, rhs, pos)))
+            }
+            Token::Ampersand => Expr::FnCall(Box::new(((op, pos), None, hash, args, None, vec![]))),
+            Token::Pipe => Expr::FnCall(Box::new(((op, pos), None, hash, args, None, vec![]))),
+            Token::XOr => Expr::FnCall(Box::new(((op, pos), None, hash, args, None, vec![]))),
 
             Token::In => {
                 let rhs = args.pop();
@@ -1763,14 +2374,22 @@ fn parse_binary_op<'a>(
             }
 
             #[cfg(not(feature = "no_object"))]
-            Token::Period => {
+            Token::Period | Token::QuestionDot => {
                 let mut rhs = args.pop();
                 let current_lhs = args.pop();
 
                 match &mut rhs {
                     // current_lhs.rhs(...) - method call
                     Expr::FnCall(x) => {
-                        let ((id, _), _, hash, args, _) = x.as_mut();
+                        let ((id, _), _, hash, args, _, named_args) = x.as_mut();
+
+                        if let Some((_, pos)) = named_args.first() {
+                            return Err(PERR::MalformedCallExpr(
+                                "Named arguments cannot be used in method calls".into(),
+                            )
+                            .into_err(*pos));
+                        }
+
                         // Recalculate function call hash because there is an additional argument
                         let args_iter = repeat(EMPTY_TYPE_ID()).take(args.len() + 1);
                         *hash = calc_fn_hash(empty(), id, args_iter);
@@ -1778,7 +2397,8 @@ fn parse_binary_op<'a>(
                     _ => (),
                 }
 
-                make_dot_expr(current_lhs, rhs, pos, false)?
+                let is_optional = op_token == Token::QuestionDot;
+                make_dot_expr(current_lhs, rhs, pos, false, is_optional)?
             }
 
             token => return Err(PERR::UnknownOperator(token.into()).into_err(pos)),
@@ -1792,8 +2412,13 @@ fn parse_expr<'a>(
     stack: &mut Stack,
     allow_stmt_expr: bool,
 ) -> Result<Expr, Box<ParseError>> {
+    stack.enter_expr(input.peek().unwrap().1)?;
+
     let lhs = parse_unary(input, stack, allow_stmt_expr)?;
-    parse_binary_op(input, stack, 1, lhs, allow_stmt_expr)
+    let expr = parse_binary_op(input, stack, 1, lhs, allow_stmt_expr)?;
+
+    stack.exit_expr();
+    Ok(expr)
 }
 
 /// Make sure that the expression is not a statement expression (i.e. wrapped in `{}`).
@@ -1829,7 +2454,8 @@ fn ensure_not_assignment<'a>(
         | (Token::PowerOfAssign, pos)
         | (Token::AndAssign, pos)
         | (Token::OrAssign, pos)
-        | (Token::XOrAssign, pos) => {
+        | (Token::XOrAssign, pos)
+        | (Token::DoubleQuestionAssign, pos) => {
             return Err(PERR::BadInput(
                 "Expecting a boolean expression, not an assignment".to_string(),
             )
@@ -1847,29 +2473,41 @@ fn parse_if<'a>(
     breakable: bool,
     allow_stmt_expr: bool,
 ) -> Result<Stmt, Box<ParseError>> {
-    // if ...
-    eat_token(input, Token::If);
+    let mut branches = Vec::new();
 
-    // if guard { if_body }
-    ensure_not_statement_expr(input, "a boolean")?;
-    let guard = parse_expr(input, stack, allow_stmt_expr)?;
-    ensure_not_assignment(input)?;
-    let if_body = parse_block(input, stack, breakable, allow_stmt_expr)?;
+    let else_body = loop {
+        // if ...
+        eat_token(input, Token::If);
 
-    // if guard { if_body } else ...
-    let else_body = if match_token(input, Token::Else).unwrap_or(false) {
-        Some(if let (Token::If, _) = input.peek().unwrap() {
-            // if guard { if_body } else if ...
-            parse_if(input, stack, breakable, allow_stmt_expr)?
-        } else {
+        // if guard { if_body }
+        ensure_not_statement_expr(input, "a boolean")?;
+        let guard = parse_expr(input, stack, allow_stmt_expr)?;
+        ensure_not_assignment(input)?;
+        let if_body = parse_block(input, stack, breakable, allow_stmt_expr)?;
+        branches.push((guard, if_body));
+
+        // if guard { if_body } else ...
+        if !match_token(input, Token::Else).unwrap_or(false) {
+            break None;
+        }
+
+        match input.peek().unwrap() {
+            // if guard { if_body } else if ... - an `else if` ladder is parsed in this loop
+            // and does not nest the statement any deeper
+            (Token::If, _) => (),
             // if guard { if_body } else { else-body }
-            parse_block(input, stack, breakable, allow_stmt_expr)?
-        })
-    } else {
-        None
+            _ => break Some(parse_block(input, stack, breakable, allow_stmt_expr)?),
+        }
     };
 
-    Ok(Stmt::IfThenElse(Box::new((guard, if_body, else_body))))
+    // Build the nested if-else statements from the last branch backwards
+    Ok(branches
+        .into_iter()
+        .rev()
+        .fold(else_body, |else_body, (guard, if_body)| {
+            Some(Stmt::IfThenElse(Box::new((guard, if_body, else_body))))
+        })
+        .unwrap())
 }
 
 /// Parse a while loop.
@@ -1890,6 +2528,40 @@ fn parse_while<'a>(
     Ok(Stmt::While(Box::new((guard, body))))
 }
 
+/// Parse a do loop.
+fn parse_do<'a>(
+    input: &mut Peekable<TokenIterator<'a>>,
+    stack: &mut Stack,
+    allow_stmt_expr: bool,
+) -> Result<Stmt, Box<ParseError>> {
+    // do ...
+    eat_token(input, Token::Do);
+
+    // do { body } ...
+    let body = parse_block(input, stack, true, allow_stmt_expr)?;
+
+    // do { body } while|until ...
+    let is_until = match input.next().unwrap() {
+        (Token::While, _) => false,
+        (Token::Until, _) => true,
+        (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(pos)),
+        (_, pos) => {
+            return Err(PERR::MissingToken(
+                Token::While.into(),
+                "or 'until' after the body of the do loop".into(),
+            )
+            .into_err(pos))
+        }
+    };
+
+    // do { body } while|until guard
+    ensure_not_statement_expr(input, "a boolean")?;
+    let guard = parse_expr(input, stack, allow_stmt_expr)?;
+    ensure_not_assignment(input)?;
+
+    Ok(Stmt::Do(Box::new((body, guard, is_until))))
+}
+
 /// Parse a loop statement.
 fn parse_loop<'a>(
     input: &mut Peekable<TokenIterator<'a>>,
@@ -1914,22 +2586,18 @@ fn parse_for<'a>(
     // for ...
     eat_token(input, Token::For);
 
-    // for name ...
-    let name = match input.next().unwrap() {
-        // Variable name
-        (Token::Identifier(s), _) => s,
-        // Bad identifier
-        (Token::LexError(err), pos) => return Err(PERR::BadInput(err.to_string()).into_err(pos)),
-        // EOF
-        (Token::EOF, pos) => return Err(PERR::VariableExpected.into_err(pos)),
-        // Not a variable name
-        (_, pos) => return Err(PERR::VariableExpected.into_err(pos)),
+    // for pattern ...
+    let pattern = match input.peek().unwrap() {
+        // for (key, value) ...
+        #[cfg(not(feature = "no_object"))]
+        (Token::LeftParen, _) => parse_pair_pattern(input)?,
+        _ => parse_pattern(input, stack)?,
     };
 
-    // for name in ...
+    // for pattern in ...
     match input.next().unwrap() {
         (Token::In, _) => (),
-        (Token::LexError(err), pos) => return Err(PERR::BadInput(err.to_string()).into_err(pos)),
+        (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(pos)),
         (_, pos) => {
             return Err(
                 PERR::MissingToken(Token::In.into(), "after the iteration variable".into())
@@ -1938,18 +2606,274 @@ fn parse_for<'a>(
         }
     }
 
-    // for name in expr { body }
+    // for pattern in expr { body }
     ensure_not_statement_expr(input, "a boolean")?;
     let expr = parse_expr(input, stack, allow_stmt_expr)?;
 
     let prev_len = stack.len();
-    stack.push((name.clone(), ScopeEntryType::Normal));
+    pattern.for_each_name(&mut |name| stack.push((name.into(), ScopeEntryType::Normal, None)));
 
     let body = parse_block(input, stack, true, allow_stmt_expr)?;
 
     stack.truncate(prev_len);
 
-    Ok(Stmt::For(Box::new((name, expr, body))))
+    Ok(Stmt::For(Box::new((pattern, expr, body))))
+}
+
+/// Parse a destructuring pattern.
+fn parse_pattern<'a>(
+    input: &mut Peekable<TokenIterator<'a>>,
+    stack: &mut Stack,
+) -> Result<Pattern, Box<ParseError>> {
+    match input.next().unwrap() {
+        // id
+        (Token::Identifier(s), pos) => Ok(Pattern::Variable(Box::new((s.into_owned(), pos)))),
+        // [ pattern, ... ]
+        #[cfg(not(feature = "no_index"))]
+        (Token::LeftBracket, pos) => {
+            stack.enter_expr(pos)?;
+            let pattern = parse_array_pattern(input, stack, pos)?;
+            stack.exit_expr();
+            Ok(pattern)
+        }
+        // #{ id: pattern, ... }
+        #[cfg(not(feature = "no_object"))]
+        (Token::MapStart, pos) => {
+            stack.enter_expr(pos)?;
+            let pattern = parse_map_pattern(input, stack, pos)?;
+            stack.exit_expr();
+            Ok(pattern)
+        }
+        (Token::LexError(err), pos) => Err(err.to_parse_error_type().into_err(pos)),
+        (_, pos) => Err(PERR::VariableExpected.into_err(pos)),
+    }
+}
+
+/// Parse a key/value pair pattern.
+#[cfg(not(feature = "no_object"))]
+fn parse_pair_pattern<'a>(
+    input: &mut Peekable<TokenIterator<'a>>,
+) -> Result<Pattern, Box<ParseError>> {
+    // ( ...
+    let pos = eat_token(input, Token::LeftParen);
+
+    // ( key ...
+    let key = match input.next().unwrap() {
+        (Token::Identifier(s), pos) => (s.into_owned(), pos),
+        (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(pos)),
+        (_, pos) => return Err(PERR::VariableExpected.into_err(pos)),
+    };
+
+    // ( key, ...
+    match input.next().unwrap() {
+        (Token::Comma, _) => (),
+        (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(pos)),
+        (_, pos) => {
+            return Err(PERR::MissingToken(
+                Token::Comma.into(),
+                "to separate the property name and value".into(),
+            )
+            .into_err(pos))
+        }
+    }
+
+    // ( key, value ...
+    let value = match input.next().unwrap() {
+        (Token::Identifier(s), pos) => (s.into_owned(), pos),
+        (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(pos)),
+        (_, pos) => return Err(PERR::VariableExpected.into_err(pos)),
+    };
+
+    // ( key, value )
+    match input.next().unwrap() {
+        (Token::RightParen, _) => (),
+        (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(pos)),
+        (_, pos) => {
+            return Err(PERR::MissingToken(
+                Token::RightParen.into(),
+                "to end the property name and value".into(),
+            )
+            .into_err(pos))
+        }
+    }
+
+    Ok(Pattern::Pair(Box::new((key, value, pos))))
+}
+
+/// Parse an array pattern.
+#[cfg(not(feature = "no_index"))]
+fn parse_array_pattern<'a>(
+    input: &mut Peekable<TokenIterator<'a>>,
+    stack: &mut Stack,
+    pos: Position,
+) -> Result<Pattern, Box<ParseError>> {
+    const MISSING_RBRACKET: &str = "to end this array pattern";
+
+    let mut items = StaticVec::new();
+    let mut rest = None;
+
+    if !match_token(input, Token::RightBracket)? {
+        loop {
+            // ..id - must be the last item
+            if match_token(input, Token::ExclusiveRange)? {
+                rest = match input.next().unwrap() {
+                    (Token::Identifier(s), pos) => Some((s.into_owned(), pos)),
+                    (Token::LexError(err), pos) => {
+                        return Err(err.to_parse_error_type().into_err(pos))
+                    }
+                    (_, pos) => return Err(PERR::VariableExpected.into_err(pos)),
+                };
+
+                match input.next().unwrap() {
+                    (Token::RightBracket, _) => break,
+                    (Token::LexError(err), pos) => {
+                        return Err(err.to_parse_error_type().into_err(pos))
+                    }
+                    (_, pos) => {
+                        return Err(PERR::MissingToken(
+                            Token::RightBracket.into(),
+                            "after the rest of this array pattern".into(),
+                        )
+                        .into_err(pos))
+                    }
+                }
+            }
+
+            items.push(parse_pattern(input, stack)?);
+
+            match input.next().unwrap() {
+                (Token::Comma, _) => (),
+                (Token::RightBracket, _) => break,
+                (Token::EOF, pos) => {
+                    return Err(PERR::MissingToken(
+                        Token::RightBracket.into(),
+                        MISSING_RBRACKET.into(),
+                    )
+                    .into_err(pos))
+                }
+                (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(pos)),
+                (_, pos) => {
+                    return Err(PERR::MissingToken(
+                        Token::Comma.into(),
+                        "to separate the items of this array pattern".into(),
+                    )
+                    .into_err(pos))
+                }
+            }
+        }
+    }
+
+    Ok(Pattern::Array(Box::new((items, rest, pos))))
+}
+
+/// Parse an object map pattern.
+#[cfg(not(feature = "no_object"))]
+fn parse_map_pattern<'a>(
+    input: &mut Peekable<TokenIterator<'a>>,
+    stack: &mut Stack,
+    pos: Position,
+) -> Result<Pattern, Box<ParseError>> {
+    const MISSING_RBRACE: &str = "to end this object map pattern";
+
+    let mut props = StaticVec::new();
+
+    if !match_token(input, Token::RightBrace)? {
+        loop {
+            let (name, pos, is_identifier) = match input.next().unwrap() {
+                (Token::Identifier(s), pos) => (s.into_owned(), pos, true),
+                (Token::StringConst(s), pos) => (s.into_owned(), pos, false),
+                (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(pos)),
+                (Token::EOF, pos) => {
+                    return Err(
+                        PERR::MissingToken(Token::RightBrace.into(), MISSING_RBRACE.into())
+                            .into_err(pos),
+                    )
+                }
+                (_, pos) => return Err(PERR::PropertyExpected.into_err(pos)),
+            };
+
+            let pattern = if match_token(input, Token::Colon)? {
+                // id: pattern
+                parse_pattern(input, stack)?
+            } else if is_identifier {
+                // id - short-hand for id: id
+                Pattern::Variable(Box::new((name.clone(), pos)))
+            } else {
+                // "string" must always be followed by a pattern
+                return Err(PERR::MissingToken(
+                    Token::Colon.into(),
+                    format!(
+                        "to follow the property '{}' in this object map pattern",
+                        name
+                    ),
+                )
+                .into_err(input.peek().unwrap().1));
+            };
+
+            props.push(((name, pos), pattern));
+
+            match input.next().unwrap() {
+                (Token::Comma, _) => (),
+                (Token::RightBrace, _) => break,
+                (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(pos)),
+                (Token::Identifier(_), pos) => {
+                    return Err(PERR::MissingToken(
+                        Token::Comma.into(),
+                        "to separate the items of this object map pattern".into(),
+                    )
+                    .into_err(pos))
+                }
+                (_, pos) => {
+                    return Err(
+                        PERR::MissingToken(Token::RightBrace.into(), MISSING_RBRACE.into())
+                            .into_err(pos),
+                    )
+                }
+            }
+        }
+    }
+
+    Ok(Pattern::Map(Box::new((props, pos))))
+}
+
+/// Is this the name returned by `type_of` for a value of a built-in type without a keyword?
+fn is_builtin_type_name(name: &str) -> bool {
+    match name {
+        "range" | "range_inclusive" => true,
+        #[cfg(not(feature = "no_std"))]
+        "timestamp" => true,
+        #[cfg(not(feature = "no_function"))]
+        "generator" => true,
+        _ => false,
+    }
+}
+
+/// Parse a type annotation.
+fn parse_type<'a>(
+    input: &mut Peekable<TokenIterator<'a>>,
+    stack: &Stack,
+) -> Result<Type, Box<ParseError>> {
+    match input.next().unwrap() {
+        // ()
+        (Token::LeftParen, _) => match input.next().unwrap() {
+            (Token::RightParen, _) => Ok(Type::Unit),
+            (Token::LexError(err), pos) => Err(err.to_parse_error_type().into_err(pos)),
+            (_, pos) => Err(PERR::MissingToken(
+                Token::RightParen.into(),
+                "for the '()' type".into(),
+            )
+            .into_err(pos)),
+        },
+        // type
+        (Token::Identifier(s), pos) => match Type::from_name(&s) {
+            Type::Custom(name) if !stack.is_known_type(&name) => {
+                Err(PERR::UnknownType(name).into_err(pos))
+            }
+            typ => Ok(typ),
+        },
+        (Token::LexError(err), pos) => Err(err.to_parse_error_type().into_err(pos)),
+        (_, pos) => Err(PERR::TypeExpected.into_err(pos)),
+    }
 }
 
 /// Parse a variable definition statement.
@@ -1962,27 +2886,55 @@ fn parse_let<'a>(
     // let/const... (specified in `var_type`)
     input.next();
 
+    // let pattern = ...
+    match (input.peek().unwrap(), var_type) {
+        #[cfg(not(feature = "no_index"))]
+        ((Token::LeftBracket, _), ScopeEntryType::Normal) => {
+            return parse_let_pattern(input, stack, allow_stmt_expr)
+        }
+        #[cfg(not(feature = "no_object"))]
+        ((Token::MapStart, _), ScopeEntryType::Normal) => {
+            return parse_let_pattern(input, stack, allow_stmt_expr)
+        }
+        _ => (),
+    }
+
     // let name ...
     let (name, pos) = match input.next().unwrap() {
-        (Token::Identifier(s), pos) => (s, pos),
-        (Token::LexError(err), pos) => return Err(PERR::BadInput(err.to_string()).into_err(pos)),
+        (Token::Identifier(s), pos) => (s.into_owned(), pos),
+        (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(pos)),
         (_, pos) => return Err(PERR::VariableExpected.into_err(pos)),
     };
 
+    // let name: type ...
+    let typ = match var_type {
+        ScopeEntryType::Normal if match_token(input, Token::Colon)? => {
+            Some(parse_type(input, stack)?)
+        }
+        _ => None,
+    };
+
     // let name = ...
     if match_token(input, Token::Equals)? {
         // let name = expr
         let init_value = parse_expr(input, stack, allow_stmt_expr)?;
 
         match var_type {
+            // let name: type = expr
+            ScopeEntryType::Normal if typ.is_some() => {
+                let typ = typ.unwrap();
+                let init_value = make_cast_expr(init_value, typ.clone());
+                stack.push((name.clone(), ScopeEntryType::Normal, Some(typ)));
+                Ok(Stmt::Let(Box::new(((name, pos), Some(init_value)))))
+            }
             // let name = expr
             ScopeEntryType::Normal => {
-                stack.push((name.clone(), ScopeEntryType::Normal));
+                stack.push((name.clone(), ScopeEntryType::Normal, None));
                 Ok(Stmt::Let(Box::new(((name, pos), Some(init_value)))))
             }
             // const name = { expr:constant }
             ScopeEntryType::Constant if init_value.is_constant() => {
-                stack.push((name.clone(), ScopeEntryType::Constant));
+                stack.push((name.clone(), ScopeEntryType::Constant, None));
                 Ok(Stmt::Const(Box::new(((name, pos), init_value))))
             }
             // const name = expr - error
@@ -1995,12 +2947,22 @@ fn parse_let<'a>(
     } else {
         // let name
         match var_type {
+            // let name: type - error
+            ScopeEntryType::Normal if typ.is_some() => Err(PERR::MissingToken(
+                Token::Equals.into(),
+                format!(
+                    "to initialize the variable '{}' of type '{}'",
+                    name,
+                    typ.unwrap()
+                ),
+            )
+            .into_err(input.peek().unwrap().1)),
             ScopeEntryType::Normal => {
-                stack.push((name.clone(), ScopeEntryType::Normal));
+                stack.push((name.clone(), ScopeEntryType::Normal, None));
                 Ok(Stmt::Let(Box::new(((name, pos), None))))
             }
             ScopeEntryType::Constant => {
-                stack.push((name.clone(), ScopeEntryType::Constant));
+                stack.push((name.clone(), ScopeEntryType::Constant, None));
                 Ok(Stmt::Const(Box::new(((name, pos), Expr::Unit(pos)))))
             }
             // Variable cannot be a module
@@ -2009,6 +2971,37 @@ fn parse_let<'a>(
     }
 }
 
+/// Parse a destructuring variable definition statement.
+#[cfg(any(not(feature = "no_index"), not(feature = "no_object")))]
+fn parse_let_pattern<'a>(
+    input: &mut Peekable<TokenIterator<'a>>,
+    stack: &mut Stack,
+    allow_stmt_expr: bool,
+) -> Result<Stmt, Box<ParseError>> {
+    // let pattern ...
+    let pattern = parse_pattern(input, stack)?;
+
+    // let pattern = ...
+    match input.next().unwrap() {
+        (Token::Equals, _) => (),
+        (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(pos)),
+        (_, pos) => {
+            return Err(PERR::MissingToken(
+                Token::Equals.into(),
+                "to initialize the variables in this pattern".into(),
+            )
+            .into_err(pos))
+        }
+    }
+
+    // let pattern = expr
+    let init_value = parse_expr(input, stack, allow_stmt_expr)?;
+
+    pattern.for_each_name(&mut |name| stack.push((name.into(), ScopeEntryType::Normal, None)));
+
+    Ok(Stmt::LetPattern(Box::new((pattern, init_value))))
+}
+
 /// Parse an import statement.
 fn parse_import<'a>(
     input: &mut Peekable<TokenIterator<'a>>,
@@ -2034,12 +3027,12 @@ fn parse_import<'a>(
 
     // import expr as name ...
     let (name, _) = match input.next().unwrap() {
-        (Token::Identifier(s), pos) => (s, pos),
-        (Token::LexError(err), pos) => return Err(PERR::BadInput(err.to_string()).into_err(pos)),
+        (Token::Identifier(s), pos) => (s.into_owned(), pos),
+        (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(pos)),
         (_, pos) => return Err(PERR::VariableExpected.into_err(pos)),
     };
 
-    stack.push((name.clone(), ScopeEntryType::Module));
+    stack.push((name.clone(), ScopeEntryType::Module, None));
     Ok(Stmt::Import(Box::new((expr, (name, pos)))))
 }
 
@@ -2051,16 +3044,14 @@ fn parse_export<'a>(input: &mut Peekable<TokenIterator<'a>>) -> Result<Stmt, Box
 
     loop {
         let (id, id_pos) = match input.next().unwrap() {
-            (Token::Identifier(s), pos) => (s.clone(), pos),
-            (Token::LexError(err), pos) => {
-                return Err(PERR::BadInput(err.to_string()).into_err(pos))
-            }
+            (Token::Identifier(s), pos) => (s.to_string(), pos),
+            (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(pos)),
             (_, pos) => return Err(PERR::VariableExpected.into_err(pos)),
         };
 
         let rename = if match_token(input, Token::As)? {
             match input.next().unwrap() {
-                (Token::Identifier(s), pos) => Some((s.clone(), pos)),
+                (Token::Identifier(s), pos) => Some((s.to_string(), pos)),
                 (_, pos) => return Err(PERR::VariableExpected.into_err(pos)),
             }
         } else {
@@ -2110,7 +3101,7 @@ fn parse_block<'a>(
     // Must start with {
     let pos = match input.next().unwrap() {
         (Token::LeftBrace, pos) => pos,
-        (Token::LexError(err), pos) => return Err(PERR::BadInput(err.to_string()).into_err(pos)),
+        (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(pos)),
         (_, pos) => {
             return Err(PERR::MissingToken(
                 Token::LeftBrace.into(),
@@ -2120,6 +3111,8 @@ fn parse_block<'a>(
         }
     };
 
+    stack.enter_stmt(pos)?;
+
     let mut statements = StaticVec::new();
     let prev_len = stack.len();
 
@@ -2147,9 +3140,7 @@ fn parse_block<'a>(
             // { ... { stmt } ???
             (_, _) if !need_semicolon => (),
             // { ... stmt <error>
-            (Token::LexError(err), pos) => {
-                return Err(PERR::BadInput(err.to_string()).into_err(*pos))
-            }
+            (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(*pos)),
             // { ... stmt ???
             (_, pos) => {
                 // Semicolons are not optional between statements
@@ -2163,6 +3154,7 @@ fn parse_block<'a>(
     }
 
     stack.truncate(prev_len);
+    stack.exit_stmt();
 
     Ok(Stmt::Block(Box::new((statements, pos))))
 }
@@ -2203,6 +3195,7 @@ fn parse_stmt<'a>(
 
         Token::If => parse_if(input, stack, breakable, allow_stmt_expr),
         Token::While => parse_while(input, stack, allow_stmt_expr),
+        Token::Do => parse_do(input, stack, allow_stmt_expr),
         Token::Loop => parse_loop(input, stack, allow_stmt_expr),
         Token::For => parse_for(input, stack, allow_stmt_expr),
 
@@ -2245,6 +3238,13 @@ fn parse_stmt<'a>(
             }
         }
 
+        Token::Yield if stack.allow_yield => {
+            let pos = eat_token(input, Token::Yield);
+            let expr = parse_expr(input, stack, allow_stmt_expr)?;
+            Ok(Stmt::Yield(Box::new((expr, pos))))
+        }
+        Token::Yield => Err(PERR::WrongYield.into_err(*pos)),
+
         Token::Let => parse_let(input, stack, ScopeEntryType::Normal, allow_stmt_expr),
         Token::Const => parse_let(input, stack, ScopeEntryType::Constant, allow_stmt_expr),
 
@@ -2271,7 +3271,7 @@ fn parse_fn<'a>(
     let pos = eat_token(input, Token::Fn);
 
     let name = match input.next().unwrap() {
-        (Token::Identifier(s), _) => s,
+        (Token::Identifier(s), _) => s.into_owned(),
         (_, pos) => return Err(PERR::FnMissingName.into_err(pos)),
     };
 
@@ -2281,20 +3281,67 @@ fn parse_fn<'a>(
     };
 
     let mut params = Vec::new();
+    let mut param_types = StaticVec::new();
+    let mut defaults = StaticVec::new();
+    let mut has_rest_param = false;
 
     if !match_token(input, Token::RightParen)? {
         let end_err = format!("to close the parameters list of function '{}'", name);
         let sep_err = format!("to separate the parameters of function '{}'", name);
 
         loop {
+            // ..id - must be the last parameter
+            #[cfg(not(feature = "no_index"))]
+            {
+                if match_token(input, Token::ExclusiveRange)? {
+                    match input.next().unwrap() {
+                        (Token::Identifier(s), pos) => {
+                            stack.push((s.to_string(), ScopeEntryType::Normal, None));
+                            params.push((s.into_owned(), pos));
+                            has_rest_param = true;
+                        }
+                        (Token::LexError(err), pos) => {
+                            return Err(err.to_parse_error_type().into_err(pos))
+                        }
+                        (_, pos) => return Err(PERR::VariableExpected.into_err(pos)),
+                    }
+
+                    match input.next().unwrap() {
+                        (Token::RightParen, _) => break,
+                        (Token::LexError(err), pos) => {
+                            return Err(err.to_parse_error_type().into_err(pos))
+                        }
+                        (_, pos) => {
+                            return Err(PERR::MissingToken(
+                                Token::RightParen.into(),
+                                format!("after the rest parameter of function '{}'", name),
+                            )
+                            .into_err(pos))
+                        }
+                    }
+                }
+            }
+
             match input.next().unwrap() {
                 (Token::Identifier(s), pos) => {
-                    stack.push((s.clone(), ScopeEntryType::Normal));
-                    params.push((s, pos))
-                }
-                (Token::LexError(err), pos) => {
-                    return Err(PERR::BadInput(err.to_string()).into_err(pos))
+                    let typ = if match_token(input, Token::Colon)? {
+                        Some(parse_type(input, stack)?)
+                    } else {
+                        None
+                    };
+
+                    // The default value can refer to previous parameters, but not to this one
+                    if match_token(input, Token::Equals)? {
+                        defaults.push(parse_expr(input, stack, allow_stmt_expr)?);
+                    } else if !defaults.is_empty() {
+                        return Err(PERR::FnMissingParamDefault(name, s.into_owned()).into_err(pos));
+                    }
+
+                    stack.push((s.to_string(), ScopeEntryType::Normal, typ.clone()));
+                    params.push((s.into_owned(), pos));
+                    param_types.push(typ);
                 }
+                (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(pos)),
                 (_, pos) => {
                     return Err(PERR::MissingToken(Token::RightParen.into(), end_err).into_err(pos))
                 }
@@ -2306,9 +3353,7 @@ fn parse_fn<'a>(
                 (Token::Identifier(_), pos) => {
                     return Err(PERR::MissingToken(Token::Comma.into(), sep_err).into_err(pos))
                 }
-                (Token::LexError(err), pos) => {
-                    return Err(PERR::BadInput(err.to_string()).into_err(pos))
-                }
+                (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(pos)),
                 (_, pos) => {
                     return Err(PERR::MissingToken(Token::Comma.into(), sep_err).into_err(pos))
                 }
@@ -2316,6 +3361,13 @@ fn parse_fn<'a>(
         }
     }
 
+    // -> type
+    let return_type = if match_token(input, Token::RightArrow)? {
+        Some(parse_type(input, stack)?)
+    } else {
+        None
+    };
+
     // Check for duplicating parameters
     params
         .iter()
@@ -2333,16 +3385,32 @@ fn parse_fn<'a>(
 
     // Parse function body
     let body = match input.peek().unwrap() {
-        (Token::LeftBrace, _) => parse_block(input, stack, false, allow_stmt_expr)?,
+        (Token::LeftBrace, _) => {
+            stack.allow_yield = true;
+            let body = parse_block(input, stack, false, allow_stmt_expr);
+            stack.allow_yield = false;
+            body?
+        }
         (_, pos) => return Err(PERR::FnMissingBody(name).into_err(*pos)),
     };
 
+    let rest_param = if has_rest_param {
+        params.pop().map(|(p, _)| p)
+    } else {
+        None
+    };
+
     let params = params.into_iter().map(|(p, _)| p).collect();
 
     Ok(FnDef {
         name,
         access,
         params,
+        defaults,
+        rest_param,
+        param_types,
+        return_type,
+        is_generator: body.contains_yield(),
         body,
         pos,
     })
@@ -2354,7 +3422,7 @@ pub fn parse_global_expr<'a>(
     scope: &Scope,
     optimization_level: OptimizationLevel,
 ) -> Result<AST, Box<ParseError>> {
-    let mut stack = Stack::new();
+    let mut stack = Stack::new(engine);
     let expr = parse_expr(input, &mut stack, false)?;
 
     match input.peek().unwrap() {
@@ -2365,25 +3433,26 @@ pub fn parse_global_expr<'a>(
         }
     }
 
+    let statements = vec![Stmt::Expr(Box::new(expr))];
+
+    if engine.type_checking {
+        check_types(engine, &statements, &[])?;
+    }
+
     Ok(
         // Optimize AST
-        optimize_into_ast(
-            engine,
-            scope,
-            vec![Stmt::Expr(Box::new(expr))],
-            vec![],
-            optimization_level,
-        ),
+        optimize_into_ast(engine, scope, statements, vec![], optimization_level),
     )
 }
 
 /// Parse the global level statements.
 fn parse_global_level<'a>(
     input: &mut Peekable<TokenIterator<'a>>,
+    engine: &Engine,
 ) -> Result<(Vec<Stmt>, HashMap<u64, FnDef>), Box<ParseError>> {
     let mut statements = Vec::<Stmt>::new();
     let mut functions = HashMap::<u64, FnDef>::new();
-    let mut stack = Stack::new();
+    let mut stack = Stack::new(engine);
 
     while !input.peek().unwrap().0.is_eof() {
         // Collect all the function definitions
@@ -2399,7 +3468,7 @@ fn parse_global_level<'a>(
 
             match input.peek().unwrap() {
                 (Token::Fn, _) => {
-                    let mut stack = Stack::new();
+                    let mut stack = Stack::new(engine);
                     let func = parse_fn(input, &mut stack, access, true)?;
 
                     // Qualifiers (none) + function name + argument `TypeId`'s
@@ -2441,9 +3510,7 @@ fn parse_global_level<'a>(
             // { stmt } ???
             (_, _) if !need_semicolon => (),
             // stmt <error>
-            (Token::LexError(err), pos) => {
-                return Err(PERR::BadInput(err.to_string()).into_err(*pos))
-            }
+            (Token::LexError(err), pos) => return Err(err.to_parse_error_type().into_err(*pos)),
             // stmt ???
             (_, pos) => {
                 // Semicolons are not optional between statements
@@ -2466,9 +3533,14 @@ pub fn parse<'a>(
     scope: &Scope,
     optimization_level: OptimizationLevel,
 ) -> Result<AST, Box<ParseError>> {
-    let (statements, functions) = parse_global_level(input)?;
+    let (statements, functions) = parse_global_level(input, engine)?;
+
+    let fn_lib: Vec<_> = functions.into_iter().map(|(_, v)| v).collect();
+
+    if engine.type_checking {
+        check_types(engine, &statements, &fn_lib)?;
+    }
 
-    let fn_lib = functions.into_iter().map(|(_, v)| v).collect();
     Ok(
         // Optimize AST
         optimize_into_ast(engine, scope, statements, fn_lib, optimization_level),
@@ -40,10 +40,21 @@ pub enum EvalAltResult {
     /// Wrapped values are the name of the function, the number of parameters required
     /// and the actual number of arguments passed.
     ErrorFunctionArgsMismatch(String, usize, usize, Position),
+    /// Named arguments in a function call do not match the parameters of the function.
+    /// Wrapped value is the error message.
+    ErrorNamedArgument(String, Position),
     /// Non-boolean operand encountered for boolean operator. Wrapped value is the operator.
     ErrorBooleanArgMismatch(String, Position),
     /// Non-character value encountered where a character is required.
     ErrorCharMismatch(Position),
+    /// Non-array value encountered where an array is required.
+    ErrorArrayMismatch(Position),
+    /// A value does not match the shape of a destructuring pattern.
+    /// Wrapped values are the kind of pattern and the type of the actual value.
+    ErrorPatternMismatch(String, String, Position),
+    /// A value does not match a type annotation.
+    /// Wrapped values are the expected type and the type of the actual value.
+    ErrorMismatchDataType(String, String, Position),
     /// Array access out-of-bounds.
     /// Wrapped values are the current number of elements in the array and the index number.
     ErrorArrayBounds(usize, INT, Position),
@@ -83,12 +94,20 @@ pub enum EvalAltResult {
     ErrorTooManyOperations(Position),
     /// Modules over maximum limit.
     ErrorTooManyModules(Position),
+    /// Data value over maximum size limit.
+    /// Wrapped values are the type of size, the maximum size and the actual size.
+    ErrorDataTooLarge(String, usize, usize, Position),
     /// Call stack over maximum limit.
     ErrorStackOverflow(Position),
+    /// The script ran past its time limit.
+    ErrorTimeout(Position),
     /// The script is prematurely terminated.
     ErrorTerminated(Position),
     /// Run-time error encountered. Wrapped value is the error message.
     ErrorRuntime(String, Position),
+    /// Internal error encountered, usually due to a malformed `AST` (e.g. one decoded via `AST::from_bytes`).
+    /// Wrapped value is the error message.
+    ErrorInternal(String, Position),
 
     /// Breaking out of loops - not an error if within a loop.
     /// The wrapped value, if true, means breaking clean out of the loop (i.e. a `break` statement).
@@ -111,10 +130,14 @@ impl EvalAltResult {
             Self::ErrorFunctionArgsMismatch(_, _, _, _) => {
                 "Function call with wrong number of arguments"
             }
+            Self::ErrorNamedArgument(_, _) => "Named arguments do not match the function parameters",
             Self::ErrorBooleanArgMismatch(_, _) => "Boolean operator expects boolean operands",
             Self::ErrorCharMismatch(_) => "Character expected",
+            Self::ErrorArrayMismatch(_) => "Array expected",
+            Self::ErrorPatternMismatch(_, _, _) => "Value does not match the pattern",
+            Self::ErrorMismatchDataType(_, _, _) => "Data type is incorrect",
             Self::ErrorNumericIndexExpr(_) => {
-                "Indexing into an array or string expects an integer index"
+                "Indexing into an array or string expects an integer or range index"
             }
             Self::ErrorStringIndexExpr(_) => "Indexing into an object map expects a string index",
             Self::ErrorIndexingType(_, _) => {
@@ -145,9 +168,12 @@ impl EvalAltResult {
             Self::ErrorArithmetic(_, _) => "Arithmetic error",
             Self::ErrorTooManyOperations(_) => "Too many operations",
             Self::ErrorTooManyModules(_) => "Too many modules imported",
+            Self::ErrorDataTooLarge(_, _, _, _) => "Data size exceeds maximum limit",
             Self::ErrorStackOverflow(_) => "Stack overflow",
+            Self::ErrorTimeout(_) => "Script timed out",
             Self::ErrorTerminated(_) => "Script terminated.",
             Self::ErrorRuntime(_, _) => "Runtime error",
+            Self::ErrorInternal(_, _) => "Internal error",
             Self::ErrorLoopBreak(true, _) => "Break statement not inside a loop",
             Self::ErrorLoopBreak(false, _) => "Continue statement not inside a loop",
             Self::Return(_, _) => "[Not Error] Function returns value",
@@ -195,15 +221,24 @@ impl fmt::Display for EvalAltResult {
             | Self::ErrorTooManyOperations(pos)
             | Self::ErrorTooManyModules(pos)
             | Self::ErrorStackOverflow(pos)
+            | Self::ErrorTimeout(pos)
             | Self::ErrorTerminated(pos) => write!(f, "{} ({})", desc, pos),
 
             Self::ErrorRuntime(s, pos) => {
                 write!(f, "{} ({})", if s.is_empty() { desc } else { s }, pos)
             }
 
+            Self::ErrorInternal(s, pos) => write!(f, "{}: {} ({})", desc, s, pos),
+
             Self::ErrorAssignmentToConstant(s, pos) => write!(f, "{}: '{}' ({})", desc, s, pos),
             Self::ErrorMismatchOutputType(s, pos) => write!(f, "{}: {} ({})", desc, s, pos),
             Self::ErrorArithmetic(s, pos) => write!(f, "{} ({})", s, pos),
+            Self::ErrorNamedArgument(s, pos) => write!(f, "{} ({})", s, pos),
+            Self::ErrorDataTooLarge(typ, max, size, pos) => write!(
+                f,
+                "{} ({}) exceeds the maximum limit ({}) ({})",
+                typ, size, max, pos
+            ),
 
             Self::ErrorLoopBreak(_, pos) => write!(f, "{} ({})", desc, pos),
             Self::Return(_, pos) => write!(f, "{} ({})", desc, pos),
@@ -229,6 +264,25 @@ impl fmt::Display for EvalAltResult {
             Self::ErrorCharMismatch(pos) => {
                 write!(f, "string indexing expects a character value ({})", pos)
             }
+            Self::ErrorArrayMismatch(pos) => {
+                write!(
+                    f,
+                    "array slicing or spreading expects an array value ({})",
+                    pos
+                )
+            }
+            Self::ErrorMismatchDataType(expected, actual, pos) => write!(
+                f,
+                "{}: expecting '{}' but found '{}' ({})",
+                desc, expected, actual, pos
+            ),
+            Self::ErrorPatternMismatch(kind, typ, pos) => {
+                write!(
+                    f,
+                    "{} pattern cannot match a value of type {} ({})",
+                    kind, typ, pos
+                )
+            }
             Self::ErrorArrayBounds(_, index, pos) if *index < 0 => {
                 write!(f, "{}: {} < 0 ({})", desc, index, pos)
             }
@@ -293,8 +347,12 @@ impl EvalAltResult {
             Self::ErrorFunctionNotFound(_, pos)
             | Self::ErrorInFunctionCall(_, _, pos)
             | Self::ErrorFunctionArgsMismatch(_, _, _, pos)
+            | Self::ErrorNamedArgument(_, pos)
             | Self::ErrorBooleanArgMismatch(_, pos)
             | Self::ErrorCharMismatch(pos)
+            | Self::ErrorArrayMismatch(pos)
+            | Self::ErrorPatternMismatch(_, _, pos)
+            | Self::ErrorMismatchDataType(_, _, pos)
             | Self::ErrorArrayBounds(_, _, pos)
             | Self::ErrorStringBounds(_, _, pos)
             | Self::ErrorIndexingType(_, pos)
@@ -313,9 +371,12 @@ impl EvalAltResult {
             | Self::ErrorArithmetic(_, pos)
             | Self::ErrorTooManyOperations(pos)
             | Self::ErrorTooManyModules(pos)
+            | Self::ErrorDataTooLarge(_, _, _, pos)
             | Self::ErrorStackOverflow(pos)
+            | Self::ErrorTimeout(pos)
             | Self::ErrorTerminated(pos)
             | Self::ErrorRuntime(_, pos)
+            | Self::ErrorInternal(_, pos)
             | Self::ErrorLoopBreak(_, pos)
             | Self::Return(_, pos) => *pos,
         }
@@ -332,8 +393,12 @@ impl EvalAltResult {
             Self::ErrorFunctionNotFound(_, pos)
             | Self::ErrorInFunctionCall(_, _, pos)
             | Self::ErrorFunctionArgsMismatch(_, _, _, pos)
+            | Self::ErrorNamedArgument(_, pos)
             | Self::ErrorBooleanArgMismatch(_, pos)
             | Self::ErrorCharMismatch(pos)
+            | Self::ErrorArrayMismatch(pos)
+            | Self::ErrorPatternMismatch(_, _, pos)
+            | Self::ErrorMismatchDataType(_, _, pos)
             | Self::ErrorArrayBounds(_, _, pos)
             | Self::ErrorStringBounds(_, _, pos)
             | Self::ErrorIndexingType(_, pos)
@@ -352,9 +417,12 @@ impl EvalAltResult {
             | Self::ErrorArithmetic(_, pos)
             | Self::ErrorTooManyOperations(pos)
             | Self::ErrorTooManyModules(pos)
+            | Self::ErrorDataTooLarge(_, _, _, pos)
             | Self::ErrorStackOverflow(pos)
+            | Self::ErrorTimeout(pos)
             | Self::ErrorTerminated(pos)
             | Self::ErrorRuntime(_, pos)
+            | Self::ErrorInternal(_, pos)
             | Self::ErrorLoopBreak(_, pos)
             | Self::Return(_, pos) => *pos = new_position,
         }
@@ -2,12 +2,19 @@
 
 use crate::any::{Dynamic, Union, Variant};
 use crate::parser::{map_dynamic_to_expr, Expr};
+use crate::result::EvalAltResult;
 use crate::token::Position;
 
 #[cfg(not(feature = "no_module"))]
 use crate::module::Module;
 
-use crate::stdlib::{borrow::Cow, boxed::Box, iter, string::String, vec::Vec};
+use crate::stdlib::{
+    borrow::Cow,
+    boxed::Box,
+    iter,
+    string::{String, ToString},
+    vec::Vec,
+};
 
 /// Type of an entry in the Scope.
 #[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
@@ -351,7 +358,7 @@ impl<'a> Scope<'a> {
     #[cfg(not(feature = "no_module"))]
     pub fn find_module(&mut self, name: &str) -> Option<&mut Module> {
         let index = self.get_module_index(name)?;
-        self.get_mut(index).0.downcast_mut::<Module>()
+        self.get_mut(index)?.0.downcast_mut::<Module>()
     }
 
     /// Get the value of an entry in the Scope, starting from the last.
@@ -383,13 +390,14 @@ impl<'a> Scope<'a> {
     /// Search starts backwards from the last, and only the first entry matching the specified name is updated.
     /// If no entry matching the specified name is found, a new one is added.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// Panics when trying to update the value of a constant.
+    /// Returns `EvalAltResult::ErrorAssignmentToConstant` when the named entry is a constant or a module.
     ///
     /// # Examples
     ///
     /// ```
+    /// # fn main() -> Result<(), Box<rhai::EvalAltResult>> {
     /// use rhai::Scope;
     ///
     /// let mut my_scope = Scope::new();
@@ -397,31 +405,72 @@ impl<'a> Scope<'a> {
     /// my_scope.push("x", 42_i64);
     /// assert_eq!(my_scope.get_value::<i64>("x").unwrap(), 42);
     ///
-    /// my_scope.set_value("x", 0_i64);
+    /// my_scope.set_value("x", 0_i64)?;
     /// assert_eq!(my_scope.get_value::<i64>("x").unwrap(), 0);
+    ///
+    /// my_scope.push_constant("y", 1_i64);
+    /// assert!(my_scope.set_value("y", 0_i64).is_err());
+    /// # Ok(())
+    /// # }
     /// ```
-    pub fn set_value<T: Variant + Clone>(&mut self, name: &'a str, value: T) {
+    pub fn set_value<T: Variant + Clone>(
+        &mut self,
+        name: &'a str,
+        value: T,
+    ) -> Result<(), Box<EvalAltResult>> {
         match self.get_index(name) {
             None => self.push(name, value),
-            Some((_, EntryType::Constant)) => panic!("variable {} is constant", name),
             Some((index, EntryType::Normal)) => {
-                self.0.get_mut(index).unwrap().value = Dynamic::from(value)
+                let (var, _) = self.get_mut(index).ok_or_else(|| {
+                    Box::new(EvalAltResult::ErrorInternal(
+                        "invalid index in Scope".into(),
+                        Position::none(),
+                    ))
+                })?;
+                *var = Dynamic::from(value);
+            }
+            // constants and modules cannot be modified
+            Some((_, EntryType::Constant)) | Some((_, EntryType::Module)) => {
+                return Err(Box::new(EvalAltResult::ErrorAssignmentToConstant(
+                    name.to_string(),
+                    Position::none(),
+                )))
             }
-            // modules cannot be modified
-            Some((_, EntryType::Module)) => unreachable!(),
         }
+
+        Ok(())
     }
 
-    /// Get a mutable reference to an entry in the Scope.
-    pub(crate) fn get_mut(&mut self, index: usize) -> (&mut Dynamic, EntryType) {
-        let entry = self.0.get_mut(index).expect("invalid index in Scope");
-        (&mut entry.value, entry.typ)
+    /// Get a mutable reference to an entry in the Scope, or `None` if the index is invalid.
+    pub(crate) fn get_mut(&mut self, index: usize) -> Option<(&mut Dynamic, EntryType)> {
+        let entry = self.0.get_mut(index)?;
+        Some((&mut entry.value, entry.typ))
     }
 
-    /// Update the access type of an entry in the Scope.
-    pub(crate) fn set_entry_alias(&mut self, index: usize, alias: String) {
-        let entry = self.0.get_mut(index).expect("invalid index in Scope");
+    /// Update the alias of an entry in the Scope, or return `None` if the index is invalid.
+    pub(crate) fn set_entry_alias(&mut self, index: usize, alias: String) -> Option<()> {
+        let entry = self.0.get_mut(index)?;
         entry.alias = Some(Box::new(alias));
+        Some(())
+    }
+
+    /// Remove all entries after a certain number of entries, returning them in a new `Scope`
+    /// which owns all their names.
+    #[cfg(not(feature = "no_function"))]
+    pub(crate) fn split_off(&mut self, size: usize) -> Scope<'static> {
+        Scope(
+            self.0
+                .split_off(size)
+                .into_iter()
+                .map(|entry| Entry {
+                    name: entry.name.into_owned().into(),
+                    typ: entry.typ,
+                    value: entry.value,
+                    alias: entry.alias,
+                    expr: entry.expr,
+                })
+                .collect(),
+        )
     }
 
     /// Get an iterator to entries in the Scope.
@@ -0,0 +1,1057 @@
+//! Module implementing a binary format for compiled `AST`'s.
+//!
+//! # Format
+//!
+//! The encoded `AST` starts with a header:
+//!
+//! * the magic bytes `RHAI`,
+//! * a `u16` format version,
+//! * a `u64` fingerprint of the function hashing scheme of the build,
+//! * the maximum nesting depth of statements and expressions in the encoded `AST`.
+//!
+//! The header is followed by the list of global statements, then the list of script-defined functions.
+//!
+//! Every statement, expression and value is prefixed by a one-byte tag identifying its kind.
+//! Lengths and indices are encoded as LEB128 variable-length integers, and integer constants as
+//! zig-zag encoded variable-length integers.  Hashes, floating-point numbers and positions are
+//! encoded as little-endian fixed-width values.
+//!
+//! Pre-calculated function and variable hashes are stored in the encoded `AST`.  Since they depend on
+//! the build (via `TypeId`), the fingerprint guards against loading an `AST` encoded by a different build.
+//!
+//! The parser limits the nesting depth of scripts (see `Engine::set_max_expr_depth` and
+//! `Engine::set_max_stmt_depth`), so an `AST` compiled under raised limits is nested deeper than
+//! one compiled under the defaults.  The nesting depth is therefore recorded in the header, and
+//! anything nested deeper than that fails to decode.
+
+use crate::any::{Dynamic, Union};
+use crate::calc_fn_hash;
+use crate::engine::FunctionsLib;
+use crate::error::{ParseError, ParseErrorType};
+use crate::parser::{Expr, FnAccess, FnDef, Pattern, ReturnType, Stmt, Type, AST, INT};
+use crate::token::Position;
+use crate::utils::{StaticVec, EMPTY_TYPE_ID};
+
+#[cfg(not(feature = "no_float"))]
+use crate::parser::FLOAT;
+
+#[cfg(not(feature = "no_module"))]
+use crate::module::ModuleRef;
+
+use crate::stdlib::{
+    borrow::Cow,
+    boxed::Box,
+    char,
+    convert::TryFrom,
+    format,
+    iter::{empty, once},
+    num::NonZeroUsize,
+    str,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// Magic bytes at the start of an encoded `AST`.
+const MAGIC: &[u8; 4] = b"RHAI";
+
+/// Version of the binary format.  Bump whenever the encoding changes.
+const FORMAT_VERSION: u16 = 9;
+
+/// Tags for statements.
+mod stmt_tag {
+    pub const NOOP: u8 = 0;
+    pub const IF_THEN_ELSE: u8 = 1;
+    pub const WHILE: u8 = 2;
+    pub const LOOP: u8 = 3;
+    pub const FOR: u8 = 4;
+    pub const LET: u8 = 5;
+    pub const CONST: u8 = 6;
+    pub const BLOCK: u8 = 7;
+    pub const EXPR: u8 = 8;
+    pub const CONTINUE: u8 = 9;
+    pub const BREAK: u8 = 10;
+    pub const RETURN: u8 = 11;
+    pub const IMPORT: u8 = 12;
+    pub const EXPORT: u8 = 13;
+    pub const DO: u8 = 14;
+    pub const LET_PATTERN: u8 = 15;
+    pub const YIELD: u8 = 16;
+}
+
+/// Tags for destructuring patterns.
+mod pattern_tag {
+    pub const VARIABLE: u8 = 0;
+    pub const ARRAY: u8 = 1;
+    pub const MAP: u8 = 2;
+    pub const PAIR: u8 = 3;
+}
+
+/// Tags for expressions.
+mod expr_tag {
+    pub const INTEGER: u8 = 0;
+    pub const FLOAT: u8 = 1;
+    pub const CHAR: u8 = 2;
+    pub const STRING: u8 = 3;
+    pub const VARIABLE: u8 = 4;
+    pub const PROPERTY: u8 = 5;
+    pub const STMT: u8 = 6;
+    pub const FN_CALL: u8 = 7;
+    pub const ASSIGNMENT: u8 = 8;
+    pub const DOT: u8 = 9;
+    pub const INDEX: u8 = 10;
+    pub const ARRAY: u8 = 11;
+    pub const MAP: u8 = 12;
+    pub const IN: u8 = 13;
+    pub const AND: u8 = 14;
+    pub const OR: u8 = 15;
+    pub const TRUE: u8 = 16;
+    pub const FALSE: u8 = 17;
+    pub const UNIT: u8 = 18;
+    pub const COALESCE: u8 = 19;
+    pub const SPREAD: u8 = 20;
+    pub const CAST: u8 = 21;
+}
+
+/// Tags for type annotations.
+mod type_tag {
+    pub const UNIT: u8 = 0;
+    pub const BOOL: u8 = 1;
+    pub const INT: u8 = 2;
+    pub const FLOAT: u8 = 3;
+    pub const CHAR: u8 = 4;
+    pub const STRING: u8 = 5;
+    pub const ARRAY: u8 = 6;
+    pub const MAP: u8 = 7;
+    pub const CUSTOM: u8 = 8;
+    pub const DYNAMIC: u8 = 9;
+}
+
+/// Tags for constant values.
+mod value_tag {
+    pub const UNIT: u8 = 0;
+    pub const BOOL: u8 = 1;
+    pub const INT: u8 = 2;
+    pub const FLOAT: u8 = 3;
+    pub const CHAR: u8 = 4;
+    pub const STRING: u8 = 5;
+}
+
+/// Fingerprint of the function hashing scheme of this build.
+fn fingerprint() -> u64 {
+    calc_fn_hash(empty(), "", once(EMPTY_TYPE_ID()))
+}
+
+/// Make a `ParseError` for a malformed encoded `AST`.
+fn malformed(msg: &str) -> Box<ParseError> {
+    ParseErrorType::BadInput(format!("Malformed compiled AST: {}", msg)).into_err(Position::none())
+}
+
+/// Encode an `AST` into bytes.
+pub fn serialize_ast(ast: &AST) -> Vec<u8> {
+    let mut w = Writer::default();
+
+    w.usize(ast.statements().len());
+    ast.statements().iter().for_each(|stmt| w.stmt(stmt));
+
+    // Sort the functions to keep the encoding deterministic
+    let mut functions: Vec<_> = ast.fn_lib().values().collect();
+    functions.sort_by(|a, b| (&a.name, a.params.len()).cmp(&(&b.name, b.params.len())));
+
+    w.usize(functions.len());
+    functions.into_iter().for_each(|fn_def| w.fn_def(fn_def));
+
+    // The nesting depth is only known after encoding everything, so the header is put in front last
+    let mut header = Writer::default();
+
+    header.bytes.extend_from_slice(MAGIC);
+    header
+        .bytes
+        .extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    header.u64(fingerprint());
+    header.usize(w.max_depth);
+
+    header.bytes.extend(w.bytes);
+    header.bytes
+}
+
+/// Decode an `AST` from bytes, optionally rejecting an `AST` nested deeper than `max_depth`.
+pub fn deserialize_ast(bytes: &[u8], max_depth: Option<usize>) -> Result<AST, Box<ParseError>> {
+    let mut r = Reader {
+        bytes,
+        offset: 0,
+        depth: 0,
+        max_depth: 0,
+    };
+
+    if r.take(MAGIC.len())? != MAGIC {
+        return Err(malformed("not a compiled Rhai script"));
+    }
+    let version = r.take(2)?;
+    if u16::from_le_bytes([version[0], version[1]]) != FORMAT_VERSION {
+        return Err(malformed("unsupported format version"));
+    }
+    if r.u64()? != fingerprint() {
+        return Err(malformed("compiled by an incompatible build"));
+    }
+    r.max_depth = r.usize()?;
+    if matches!(max_depth, Some(max) if r.max_depth > max) {
+        return Err(malformed("nesting too deep"));
+    }
+
+    let statements = (0..r.usize()?)
+        .map(|_| r.stmt())
+        .collect::<Result<Vec<_>, _>>()?;
+    let functions = (0..r.usize()?)
+        .map(|_| r.fn_def())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if r.offset != bytes.len() {
+        return Err(malformed("trailing bytes"));
+    }
+
+    Ok(AST::new(statements, FunctionsLib::from_iter(functions)))
+}
+
+/// Encoder of an `AST`.
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+    depth: usize,
+    max_depth: usize,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    fn usize(&mut self, value: usize) {
+        self.varint(value as u64);
+    }
+    fn varint(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+
+            if value == 0 {
+                self.u8(byte);
+                break;
+            }
+            self.u8(byte | 0x80);
+        }
+    }
+    fn int(&mut self, value: INT) {
+        #[cfg(feature = "only_i32")]
+        let value = value as i64;
+        // Zig-zag encoding
+        self.varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+    fn str(&mut self, s: &str) {
+        self.usize(s.len());
+        self.bytes.extend_from_slice(s.as_bytes());
+    }
+    fn name(&mut self, (name, pos): &(String, Position)) {
+        self.str(name);
+        self.pos(*pos);
+    }
+    fn pos(&mut self, pos: Position) {
+        let line = pos.line().unwrap_or(0) as u16;
+        let position = pos.position().unwrap_or(0) as u16;
+        self.bytes.extend_from_slice(&line.to_le_bytes());
+        self.bytes.extend_from_slice(&position.to_le_bytes());
+    }
+    fn index(&mut self, index: Option<NonZeroUsize>) {
+        self.usize(index.map_or(0, NonZeroUsize::get));
+    }
+    fn value(&mut self, value: &Dynamic) {
+        match &value.0 {
+            Union::Bool(b) => {
+                self.u8(value_tag::BOOL);
+                self.u8(*b as u8);
+            }
+            Union::Int(n) => {
+                self.u8(value_tag::INT);
+                self.int(*n);
+            }
+            #[cfg(not(feature = "no_float"))]
+            Union::Float(f) => {
+                self.u8(value_tag::FLOAT);
+                self.u64(f.to_bits());
+            }
+            Union::Char(c) => {
+                self.u8(value_tag::CHAR);
+                self.usize(*c as usize);
+            }
+            Union::Str(s) => {
+                self.u8(value_tag::STRING);
+                self.str(s);
+            }
+            // Default values generated by the parser are always primitive values
+            _ => self.u8(value_tag::UNIT),
+        }
+    }
+    #[cfg(not(feature = "no_module"))]
+    fn modules(&mut self, modules: &Option<Box<ModuleRef>>) {
+        match modules {
+            None => self.usize(0),
+            Some(modules) => {
+                self.usize(modules.len());
+                modules.iter().for_each(|m| self.name(m));
+                self.index(modules.index());
+            }
+        }
+    }
+    #[cfg(feature = "no_module")]
+    fn modules<T>(&mut self, _: &Option<T>) {
+        self.usize(0);
+    }
+    fn fn_def(&mut self, fn_def: &FnDef) {
+        self.str(&fn_def.name);
+        self.u8(match fn_def.access {
+            FnAccess::Private => 0,
+            FnAccess::Public => 1,
+        });
+        self.usize(fn_def.params.len());
+        fn_def.params.iter().for_each(|p| self.str(p));
+        self.usize(fn_def.defaults.len());
+        fn_def.defaults.iter().for_each(|e| self.expr(e));
+        match &fn_def.rest_param {
+            None => self.u8(0),
+            Some(p) => {
+                self.u8(1);
+                self.str(p);
+            }
+        }
+        fn_def
+            .param_types
+            .iter()
+            .for_each(|t| self.opt_typ(t.as_ref()));
+        self.opt_typ(fn_def.return_type.as_ref());
+        self.u8(fn_def.is_generator as u8);
+        self.stmt(&fn_def.body);
+        self.pos(fn_def.pos);
+    }
+    fn enter(&mut self) {
+        self.depth += 1;
+        self.max_depth = self.max_depth.max(self.depth);
+    }
+    fn stmt(&mut self, stmt: &Stmt) {
+        self.enter();
+        self.stmt_node(stmt);
+        self.depth -= 1;
+    }
+    fn stmt_node(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Noop(pos) => {
+                self.u8(stmt_tag::NOOP);
+                self.pos(*pos);
+            }
+            Stmt::IfThenElse(x) => {
+                self.u8(stmt_tag::IF_THEN_ELSE);
+                self.expr(&x.0);
+                self.stmt(&x.1);
+                match &x.2 {
+                    None => self.u8(0),
+                    Some(stmt) => {
+                        self.u8(1);
+                        self.stmt(stmt);
+                    }
+                }
+            }
+            Stmt::While(x) => {
+                self.u8(stmt_tag::WHILE);
+                self.expr(&x.0);
+                self.stmt(&x.1);
+            }
+            Stmt::Do(x) => {
+                self.u8(stmt_tag::DO);
+                self.stmt(&x.0);
+                self.expr(&x.1);
+                self.u8(x.2 as u8);
+            }
+            Stmt::Loop(x) => {
+                self.u8(stmt_tag::LOOP);
+                self.stmt(x);
+            }
+            Stmt::For(x) => {
+                self.u8(stmt_tag::FOR);
+                self.pattern(&x.0);
+                self.expr(&x.1);
+                self.stmt(&x.2);
+            }
+            Stmt::Let(x) => {
+                self.u8(stmt_tag::LET);
+                self.name(&x.0);
+                match &x.1 {
+                    None => self.u8(0),
+                    Some(expr) => {
+                        self.u8(1);
+                        self.expr(expr);
+                    }
+                }
+            }
+            Stmt::LetPattern(x) => {
+                self.u8(stmt_tag::LET_PATTERN);
+                self.pattern(&x.0);
+                self.expr(&x.1);
+            }
+            Stmt::Const(x) => {
+                self.u8(stmt_tag::CONST);
+                self.name(&x.0);
+                self.expr(&x.1);
+            }
+            Stmt::Block(x) => {
+                self.u8(stmt_tag::BLOCK);
+                self.usize(x.0.len());
+                x.0.iter().for_each(|stmt| self.stmt(stmt));
+                self.pos(x.1);
+            }
+            Stmt::Expr(x) => {
+                self.u8(stmt_tag::EXPR);
+                self.expr(x);
+            }
+            Stmt::Continue(pos) => {
+                self.u8(stmt_tag::CONTINUE);
+                self.pos(*pos);
+            }
+            Stmt::Break(pos) => {
+                self.u8(stmt_tag::BREAK);
+                self.pos(*pos);
+            }
+            Stmt::ReturnWithVal(x) => {
+                self.u8(stmt_tag::RETURN);
+                self.u8(match (x.0).0 {
+                    ReturnType::Return => 0,
+                    ReturnType::Exception => 1,
+                });
+                self.pos((x.0).1);
+                match &x.1 {
+                    None => self.u8(0),
+                    Some(expr) => {
+                        self.u8(1);
+                        self.expr(expr);
+                    }
+                }
+            }
+            Stmt::Yield(x) => {
+                self.u8(stmt_tag::YIELD);
+                self.expr(&x.0);
+                self.pos(x.1);
+            }
+            Stmt::Import(x) => {
+                self.u8(stmt_tag::IMPORT);
+                self.expr(&x.0);
+                self.name(&x.1);
+            }
+            Stmt::Export(x) => {
+                self.u8(stmt_tag::EXPORT);
+                self.usize(x.len());
+                x.iter().for_each(|(name, alias)| {
+                    self.name(name);
+                    match alias {
+                        None => self.u8(0),
+                        Some(alias) => {
+                            self.u8(1);
+                            self.name(alias);
+                        }
+                    }
+                });
+            }
+        }
+    }
+    fn typ(&mut self, typ: &Type) {
+        match typ {
+            Type::Unit => self.u8(type_tag::UNIT),
+            Type::Bool => self.u8(type_tag::BOOL),
+            Type::Int => self.u8(type_tag::INT),
+            #[cfg(not(feature = "no_float"))]
+            Type::Float => self.u8(type_tag::FLOAT),
+            Type::Char => self.u8(type_tag::CHAR),
+            Type::String => self.u8(type_tag::STRING),
+            #[cfg(not(feature = "no_index"))]
+            Type::Array => self.u8(type_tag::ARRAY),
+            #[cfg(not(feature = "no_object"))]
+            Type::Map => self.u8(type_tag::MAP),
+            Type::Dynamic => self.u8(type_tag::DYNAMIC),
+            Type::Custom(name) => {
+                self.u8(type_tag::CUSTOM);
+                self.str(name);
+            }
+        }
+    }
+    fn opt_typ(&mut self, typ: Option<&Type>) {
+        match typ {
+            None => self.u8(0),
+            Some(typ) => {
+                self.u8(1);
+                self.typ(typ);
+            }
+        }
+    }
+    fn binary(&mut self, tag: u8, x: &(Expr, Expr, Position)) {
+        self.u8(tag);
+        self.expr(&x.0);
+        self.expr(&x.1);
+        self.pos(x.2);
+    }
+    fn pattern(&mut self, pattern: &Pattern) {
+        self.enter();
+        self.pattern_node(pattern);
+        self.depth -= 1;
+    }
+    fn pattern_node(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Variable(x) => {
+                self.u8(pattern_tag::VARIABLE);
+                self.name(x);
+            }
+            #[cfg(not(feature = "no_index"))]
+            Pattern::Array(x) => {
+                self.u8(pattern_tag::ARRAY);
+                self.usize(x.0.len());
+                x.0.iter().for_each(|item| self.pattern(item));
+                match &x.1 {
+                    None => self.u8(0),
+                    Some(rest) => {
+                        self.u8(1);
+                        self.name(rest);
+                    }
+                }
+                self.pos(x.2);
+            }
+            #[cfg(not(feature = "no_object"))]
+            Pattern::Map(x) => {
+                self.u8(pattern_tag::MAP);
+                self.usize(x.0.len());
+                x.0.iter().for_each(|(prop, item)| {
+                    self.name(prop);
+                    self.pattern(item);
+                });
+                self.pos(x.1);
+            }
+            #[cfg(not(feature = "no_object"))]
+            Pattern::Pair(x) => {
+                self.u8(pattern_tag::PAIR);
+                self.name(&x.0);
+                self.name(&x.1);
+                self.pos(x.2);
+            }
+        }
+    }
+    fn chain(&mut self, tag: u8, x: &(Expr, Expr, Position, bool)) {
+        self.u8(tag);
+        self.expr(&x.0);
+        self.expr(&x.1);
+        self.pos(x.2);
+        self.u8(x.3 as u8);
+    }
+    fn expr(&mut self, expr: &Expr) {
+        self.enter();
+        self.expr_node(expr);
+        self.depth -= 1;
+    }
+    fn expr_node(&mut self, expr: &Expr) {
+        match expr {
+            Expr::IntegerConstant(x) => {
+                self.u8(expr_tag::INTEGER);
+                self.int(x.0);
+                self.pos(x.1);
+            }
+            #[cfg(not(feature = "no_float"))]
+            Expr::FloatConstant(x) => {
+                self.u8(expr_tag::FLOAT);
+                self.u64(x.0.to_bits());
+                self.pos(x.1);
+            }
+            Expr::CharConstant(x) => {
+                self.u8(expr_tag::CHAR);
+                self.usize(x.0 as usize);
+                self.pos(x.1);
+            }
+            Expr::StringConstant(x) => {
+                self.u8(expr_tag::STRING);
+                self.name(x);
+            }
+            Expr::Variable(x) => {
+                self.u8(expr_tag::VARIABLE);
+                self.name(&x.0);
+                self.modules(&x.1);
+                self.u64(x.2);
+                self.index(x.3);
+            }
+            Expr::Property(x) => {
+                self.u8(expr_tag::PROPERTY);
+                self.str(&(x.0).0);
+                self.str(&(x.0).1);
+                self.str(&(x.0).2);
+                self.pos(x.1);
+            }
+            Expr::Stmt(x) => {
+                self.u8(expr_tag::STMT);
+                self.stmt(&x.0);
+                self.pos(x.1);
+            }
+            Expr::FnCall(x) => {
+                self.u8(expr_tag::FN_CALL);
+                self.str(&(x.0).0);
+                self.pos((x.0).1);
+                self.modules(&x.1);
+                self.u64(x.2);
+                self.usize(x.3.len());
+                x.3.iter().for_each(|expr| self.expr(expr));
+                match &x.4 {
+                    None => self.u8(0),
+                    Some(value) => {
+                        self.u8(1);
+                        self.value(value);
+                    }
+                }
+                self.usize(x.5.len());
+                x.5.iter().for_each(|name| self.name(name));
+            }
+            Expr::Assignment(x) => self.binary(expr_tag::ASSIGNMENT, x),
+            Expr::Dot(x) => self.chain(expr_tag::DOT, x),
+            Expr::Index(x) => self.chain(expr_tag::INDEX, x),
+            Expr::Array(x) => {
+                self.u8(expr_tag::ARRAY);
+                self.usize(x.0.len());
+                x.0.iter().for_each(|expr| self.expr(expr));
+                self.pos(x.1);
+            }
+            Expr::Map(x) => {
+                self.u8(expr_tag::MAP);
+                self.usize(x.0.len());
+                x.0.iter().for_each(|(name, expr)| {
+                    self.name(name);
+                    self.expr(expr);
+                });
+                self.pos(x.1);
+            }
+            Expr::In(x) => self.binary(expr_tag::IN, x),
+            Expr::And(x) => self.binary(expr_tag::AND, x),
+            Expr::Or(x) => self.binary(expr_tag::OR, x),
+            Expr::Coalesce(x) => self.binary(expr_tag::COALESCE, x),
+            #[cfg(not(feature = "no_index"))]
+            Expr::Spread(x) => {
+                self.u8(expr_tag::SPREAD);
+                self.expr(&x.0);
+                self.pos(x.1);
+            }
+            Expr::Cast(x) => {
+                self.u8(expr_tag::CAST);
+                self.expr(&x.0);
+                self.typ(&x.1);
+                self.pos(x.2);
+            }
+            Expr::True(pos) => {
+                self.u8(expr_tag::TRUE);
+                self.pos(*pos);
+            }
+            Expr::False(pos) => {
+                self.u8(expr_tag::FALSE);
+                self.pos(*pos);
+            }
+            Expr::Unit(pos) => {
+                self.u8(expr_tag::UNIT);
+                self.pos(*pos);
+            }
+        }
+    }
+}
+
+/// Decoder of an `AST`.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    depth: usize,
+    max_depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Box<ParseError>> {
+        if len > self.bytes.len() - self.offset {
+            return Err(malformed("unexpected end of data"));
+        }
+        let bytes = &self.bytes[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(bytes)
+    }
+    fn u8(&mut self) -> Result<u8, Box<ParseError>> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16, Box<ParseError>> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+    fn u64(&mut self) -> Result<u64, Box<ParseError>> {
+        let mut buf = [0_u8; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+    fn usize(&mut self) -> Result<usize, Box<ParseError>> {
+        usize::try_from(self.varint()?).map_err(|_| malformed("number too large"))
+    }
+    fn varint(&mut self) -> Result<u64, Box<ParseError>> {
+        let mut value: u64 = 0;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(malformed("number too large"))
+    }
+    fn flag(&mut self) -> Result<bool, Box<ParseError>> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(malformed("invalid flag")),
+        }
+    }
+    fn int(&mut self) -> Result<INT, Box<ParseError>> {
+        let value = self.varint()?;
+        // Zig-zag decoding
+        let value = ((value >> 1) as i64) ^ -((value & 1) as i64);
+        INT::try_from(value).map_err(|_| malformed("integer out of range"))
+    }
+    fn char(&mut self) -> Result<char, Box<ParseError>> {
+        u32::try_from(self.usize()?)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| malformed("invalid character"))
+    }
+    #[cfg(not(feature = "no_float"))]
+    fn float(&mut self) -> Result<FLOAT, Box<ParseError>> {
+        Ok(FLOAT::from_bits(self.u64()?))
+    }
+    #[cfg(feature = "no_float")]
+    fn float(&mut self) -> Result<(), Box<ParseError>> {
+        Err(malformed("floating-point numbers are not supported"))
+    }
+    fn str(&mut self) -> Result<String, Box<ParseError>> {
+        let len = self.usize()?;
+        str::from_utf8(self.take(len)?)
+            .map(ToString::to_string)
+            .map_err(|_| malformed("invalid string"))
+    }
+    fn name(&mut self) -> Result<(String, Position), Box<ParseError>> {
+        Ok((self.str()?, self.pos()?))
+    }
+    fn pos(&mut self) -> Result<Position, Box<ParseError>> {
+        let line = self.u16()?;
+        let position = self.u16()?;
+
+        Ok(if line == 0 {
+            Position::none()
+        } else {
+            Position::new(line, position)
+        })
+    }
+    fn index(&mut self) -> Result<Option<NonZeroUsize>, Box<ParseError>> {
+        Ok(NonZeroUsize::new(self.usize()?))
+    }
+    fn value(&mut self) -> Result<Dynamic, Box<ParseError>> {
+        Ok(match self.u8()? {
+            value_tag::UNIT => ().into(),
+            value_tag::BOOL => self.flag()?.into(),
+            value_tag::INT => self.int()?.into(),
+            value_tag::FLOAT => self.float()?.into(),
+            value_tag::CHAR => self.char()?.into(),
+            value_tag::STRING => self.str()?.into(),
+            _ => return Err(malformed("unknown value type")),
+        })
+    }
+    #[cfg(not(feature = "no_module"))]
+    fn modules(&mut self) -> Result<Option<Box<ModuleRef>>, Box<ParseError>> {
+        match self.usize()? {
+            0 => Ok(None),
+            len => {
+                let modules = (0..len)
+                    .map(|_| self.name())
+                    .collect::<Result<StaticVec<_>, _>>()?;
+                let mut modules = ModuleRef::from(modules);
+                modules.set_index(self.index()?);
+                Ok(Some(Box::new(modules)))
+            }
+        }
+    }
+    #[cfg(feature = "no_module")]
+    fn modules<T>(&mut self) -> Result<Option<T>, Box<ParseError>> {
+        match self.usize()? {
+            0 => Ok(None),
+            _ => Err(malformed("modules are not supported")),
+        }
+    }
+    fn optional_stmt(&mut self) -> Result<Option<Stmt>, Box<ParseError>> {
+        Ok(if self.flag()? {
+            Some(self.stmt()?)
+        } else {
+            None
+        })
+    }
+    fn optional_expr(&mut self) -> Result<Option<Expr>, Box<ParseError>> {
+        Ok(if self.flag()? {
+            Some(self.expr()?)
+        } else {
+            None
+        })
+    }
+    fn fn_def(&mut self) -> Result<FnDef, Box<ParseError>> {
+        let name = self.str()?;
+        let access = match self.u8()? {
+            0 => FnAccess::Private,
+            1 => FnAccess::Public,
+            _ => return Err(malformed("unknown function access mode")),
+        };
+        let params: StaticVec<_> = (0..self.usize()?)
+            .map(|_| self.str())
+            .collect::<Result<_, _>>()?;
+        let defaults: StaticVec<_> = (0..self.usize()?)
+            .map(|_| self.expr())
+            .collect::<Result<_, _>>()?;
+
+        if defaults.len() > params.len() {
+            return Err(malformed("more default values than parameters"));
+        }
+
+        let rest_param = if self.flag()? {
+            Some(self.str()?)
+        } else {
+            None
+        };
+
+        let param_types = (0..params.len())
+            .map(|_| self.opt_typ())
+            .collect::<Result<_, _>>()?;
+
+        Ok(FnDef {
+            name,
+            access,
+            params,
+            defaults,
+            rest_param,
+            param_types,
+            return_type: self.opt_typ()?,
+            is_generator: self.flag()?,
+            body: self.stmt()?,
+            pos: self.pos()?,
+        })
+    }
+    fn enter(&mut self) -> Result<(), Box<ParseError>> {
+        self.depth += 1;
+
+        if self.depth > self.max_depth {
+            return Err(malformed("nesting too deep"));
+        }
+        Ok(())
+    }
+    fn pattern(&mut self) -> Result<Pattern, Box<ParseError>> {
+        self.enter()?;
+        let pattern = self.pattern_node();
+        self.depth -= 1;
+        pattern
+    }
+    fn pattern_node(&mut self) -> Result<Pattern, Box<ParseError>> {
+        Ok(match self.u8()? {
+            pattern_tag::VARIABLE => Pattern::Variable(Box::new(self.name()?)),
+            #[cfg(not(feature = "no_index"))]
+            pattern_tag::ARRAY => {
+                let items = (0..self.usize()?)
+                    .map(|_| self.pattern())
+                    .collect::<Result<StaticVec<_>, _>>()?;
+                let rest = if self.flag()? {
+                    Some(self.name()?)
+                } else {
+                    None
+                };
+                Pattern::Array(Box::new((items, rest, self.pos()?)))
+            }
+            #[cfg(not(feature = "no_object"))]
+            pattern_tag::MAP => {
+                let props = (0..self.usize()?)
+                    .map(|_| Ok((self.name()?, self.pattern()?)))
+                    .collect::<Result<StaticVec<_>, Box<ParseError>>>()?;
+                Pattern::Map(Box::new((props, self.pos()?)))
+            }
+            #[cfg(not(feature = "no_object"))]
+            pattern_tag::PAIR => Pattern::Pair(Box::new((self.name()?, self.name()?, self.pos()?))),
+            _ => return Err(malformed("unknown pattern type")),
+        })
+    }
+    fn stmt(&mut self) -> Result<Stmt, Box<ParseError>> {
+        self.enter()?;
+        let stmt = self.stmt_node();
+        self.depth -= 1;
+        stmt
+    }
+    fn stmt_node(&mut self) -> Result<Stmt, Box<ParseError>> {
+        Ok(match self.u8()? {
+            stmt_tag::NOOP => Stmt::Noop(self.pos()?),
+            stmt_tag::IF_THEN_ELSE => Stmt::IfThenElse(Box::new((
+                self.expr()?,
+                self.stmt()?,
+                self.optional_stmt()?,
+            ))),
+            stmt_tag::WHILE => Stmt::While(Box::new((self.expr()?, self.stmt()?))),
+            stmt_tag::DO => Stmt::Do(Box::new((self.stmt()?, self.expr()?, self.flag()?))),
+            stmt_tag::LOOP => Stmt::Loop(Box::new(self.stmt()?)),
+            stmt_tag::FOR => Stmt::For(Box::new((self.pattern()?, self.expr()?, self.stmt()?))),
+            stmt_tag::LET => Stmt::Let(Box::new((self.name()?, self.optional_expr()?))),
+            stmt_tag::LET_PATTERN => Stmt::LetPattern(Box::new((self.pattern()?, self.expr()?))),
+            stmt_tag::CONST => Stmt::Const(Box::new((self.name()?, self.expr()?))),
+            stmt_tag::BLOCK => {
+                let statements = (0..self.usize()?)
+                    .map(|_| self.stmt())
+                    .collect::<Result<StaticVec<_>, _>>()?;
+                Stmt::Block(Box::new((statements, self.pos()?)))
+            }
+            stmt_tag::EXPR => Stmt::Expr(Box::new(self.expr()?)),
+            stmt_tag::CONTINUE => Stmt::Continue(self.pos()?),
+            stmt_tag::BREAK => Stmt::Break(self.pos()?),
+            stmt_tag::RETURN => {
+                let return_type = match self.u8()? {
+                    0 => ReturnType::Return,
+                    1 => ReturnType::Exception,
+                    _ => return Err(malformed("unknown return type")),
+                };
+                Stmt::ReturnWithVal(Box::new((
+                    (return_type, self.pos()?),
+                    self.optional_expr()?,
+                )))
+            }
+            stmt_tag::YIELD => Stmt::Yield(Box::new((self.expr()?, self.pos()?))),
+            stmt_tag::IMPORT => Stmt::Import(Box::new((self.expr()?, self.name()?))),
+            stmt_tag::EXPORT => {
+                let list = (0..self.usize()?)
+                    .map(|_| {
+                        let name = self.name()?;
+                        let alias = if self.flag()? {
+                            Some(self.name()?)
+                        } else {
+                            None
+                        };
+                        Ok((name, alias))
+                    })
+                    .collect::<Result<StaticVec<_>, Box<ParseError>>>()?;
+                Stmt::Export(Box::new(list))
+            }
+            _ => return Err(malformed("unknown statement type")),
+        })
+    }
+    fn typ(&mut self) -> Result<Type, Box<ParseError>> {
+        Ok(match self.u8()? {
+            type_tag::UNIT => Type::Unit,
+            type_tag::BOOL => Type::Bool,
+            type_tag::INT => Type::Int,
+            #[cfg(not(feature = "no_float"))]
+            type_tag::FLOAT => Type::Float,
+            type_tag::CHAR => Type::Char,
+            type_tag::STRING => Type::String,
+            #[cfg(not(feature = "no_index"))]
+            type_tag::ARRAY => Type::Array,
+            #[cfg(not(feature = "no_object"))]
+            type_tag::MAP => Type::Map,
+            type_tag::CUSTOM => Type::Custom(self.str()?),
+            type_tag::DYNAMIC => Type::Dynamic,
+            _ => return Err(malformed("unknown type annotation")),
+        })
+    }
+    fn opt_typ(&mut self) -> Result<Option<Type>, Box<ParseError>> {
+        Ok(if self.flag()? {
+            Some(self.typ()?)
+        } else {
+            None
+        })
+    }
+    fn binary(&mut self) -> Result<Box<(Expr, Expr, Position)>, Box<ParseError>> {
+        Ok(Box::new((self.expr()?, self.expr()?, self.pos()?)))
+    }
+    fn chain(&mut self) -> Result<Box<(Expr, Expr, Position, bool)>, Box<ParseError>> {
+        Ok(Box::new((
+            self.expr()?,
+            self.expr()?,
+            self.pos()?,
+            self.flag()?,
+        )))
+    }
+    fn expr(&mut self) -> Result<Expr, Box<ParseError>> {
+        self.enter()?;
+        let expr = self.expr_node();
+        self.depth -= 1;
+        expr
+    }
+    fn expr_node(&mut self) -> Result<Expr, Box<ParseError>> {
+        Ok(match self.u8()? {
+            expr_tag::INTEGER => Expr::IntegerConstant(Box::new((self.int()?, self.pos()?))),
+            #[cfg(not(feature = "no_float"))]
+            expr_tag::FLOAT => Expr::FloatConstant(Box::new((self.float()?, self.pos()?))),
+            expr_tag::CHAR => Expr::CharConstant(Box::new((self.char()?, self.pos()?))),
+            expr_tag::STRING => Expr::StringConstant(Box::new(self.name()?)),
+            expr_tag::VARIABLE => Expr::Variable(Box::new((
+                self.name()?,
+                self.modules()?,
+                self.u64()?,
+                self.index()?,
+            ))),
+            expr_tag::PROPERTY => Expr::Property(Box::new((
+                (self.str()?, self.str()?, self.str()?),
+                self.pos()?,
+            ))),
+            expr_tag::STMT => Expr::Stmt(Box::new((self.stmt()?, self.pos()?))),
+            expr_tag::FN_CALL => {
+                let name = (Cow::Owned(self.str()?), self.pos()?);
+                let modules = self.modules()?;
+                let hash = self.u64()?;
+                let args = (0..self.usize()?)
+                    .map(|_| self.expr())
+                    .collect::<Result<StaticVec<_>, _>>()?;
+                let def_val = if self.flag()? {
+                    Some(self.value()?)
+                } else {
+                    None
+                };
+                let named_args = (0..self.usize()?)
+                    .map(|_| self.name())
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                if named_args.len() > args.len() {
+                    return Err(malformed("more named arguments than arguments"));
+                }
+
+                Expr::FnCall(Box::new((name, modules, hash, args, def_val, named_args)))
+            }
+            expr_tag::ASSIGNMENT => Expr::Assignment(self.binary()?),
+            expr_tag::DOT => Expr::Dot(self.chain()?),
+            expr_tag::INDEX => Expr::Index(self.chain()?),
+            expr_tag::ARRAY => {
+                let items = (0..self.usize()?)
+                    .map(|_| self.expr())
+                    .collect::<Result<StaticVec<_>, _>>()?;
+                Expr::Array(Box::new((items, self.pos()?)))
+            }
+            expr_tag::MAP => {
+                let items = (0..self.usize()?)
+                    .map(|_| Ok((self.name()?, self.expr()?)))
+                    .collect::<Result<StaticVec<_>, Box<ParseError>>>()?;
+                Expr::Map(Box::new((items, self.pos()?)))
+            }
+            expr_tag::IN => Expr::In(self.binary()?),
+            expr_tag::AND => Expr::And(self.binary()?),
+            expr_tag::OR => Expr::Or(self.binary()?),
+            expr_tag::COALESCE => Expr::Coalesce(self.binary()?),
+            #[cfg(not(feature = "no_index"))]
+            expr_tag::SPREAD => Expr::Spread(Box::new((self.expr()?, self.pos()?))),
+            expr_tag::CAST => Expr::Cast(Box::new((self.expr()?, self.typ()?, self.pos()?))),
+            expr_tag::TRUE => Expr::True(self.pos()?),
+            expr_tag::FALSE => Expr::False(self.pos()?),
+            expr_tag::UNIT => Expr::Unit(self.pos()?),
+            _ => return Err(malformed("unknown expression type")),
+        })
+    }
+}
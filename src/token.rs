@@ -10,11 +10,11 @@ use crate::parser::FLOAT;
 use crate::stdlib::{
     borrow::Cow,
     boxed::Box,
-    char, fmt,
-    iter::Peekable,
-    str::{Chars, FromStr},
+    char,
+    collections::HashSet,
+    fmt,
+    str::FromStr,
     string::{String, ToString},
-    vec::Vec,
 };
 
 type LERR = LexError;
@@ -128,14 +128,16 @@ impl fmt::Debug for Position {
 }
 
 /// Tokens.
+///
+/// Identifiers and string literals borrow from the input script text where possible.
 #[derive(Debug, PartialEq, Clone)]
-pub enum Token {
+pub enum Token<'a> {
     IntegerConstant(INT),
     #[cfg(not(feature = "no_float"))]
     FloatConstant(FLOAT),
-    Identifier(String),
+    Identifier(Cow<'a, str>),
     CharConstant(char),
-    StringConst(String),
+    StringConst(Cow<'a, str>),
     LeftBrace,
     RightBrace,
     LeftParen,
@@ -155,8 +157,15 @@ pub enum Token {
     SemiColon,
     Colon,
     DoubleColon,
+    RightArrow,
     Comma,
     Period,
+    QuestionDot,
+    QuestionBracket,
+    DoubleQuestion,
+    DoubleQuestionAssign,
+    ExclusiveRange,
+    InclusiveRange,
     MapStart,
     Equals,
     True,
@@ -166,6 +175,8 @@ pub enum Token {
     If,
     Else,
     While,
+    Do,
+    Until,
     Loop,
     For,
     In,
@@ -186,6 +197,7 @@ pub enum Token {
     Break,
     Return,
     Throw,
+    Yield,
     PlusAssign,
     MinusAssign,
     MultiplyAssign,
@@ -205,7 +217,7 @@ pub enum Token {
     EOF,
 }
 
-impl Token {
+impl Token<'_> {
     /// Get the syntax of the token.
     pub fn syntax(&self) -> Cow<'static, str> {
         use Token::*;
@@ -214,7 +226,7 @@ impl Token {
             IntegerConstant(i) => i.to_string().into(),
             #[cfg(not(feature = "no_float"))]
             FloatConstant(f) => f.to_string().into(),
-            Identifier(s) => s.clone().into(),
+            Identifier(s) => s.to_string().into(),
             CharConstant(c) => c.to_string().into(),
             LexError(err) => err.to_string().into(),
 
@@ -235,8 +247,15 @@ impl Token {
                 SemiColon => ";",
                 Colon => ":",
                 DoubleColon => "::",
+                RightArrow => "->",
                 Comma => ",",
                 Period => ".",
+                QuestionDot => "?.",
+                QuestionBracket => "?[",
+                DoubleQuestion => "??",
+                DoubleQuestionAssign => "??=",
+                ExclusiveRange => "..",
+                InclusiveRange => "..=",
                 MapStart => "#{",
                 Equals => "=",
                 True => "true",
@@ -246,6 +265,8 @@ impl Token {
                 If => "if",
                 Else => "else",
                 While => "while",
+                Do => "do",
+                Until => "until",
                 Loop => "loop",
                 For => "for",
                 In => "in",
@@ -265,6 +286,7 @@ impl Token {
                 Break => "break",
                 Return => "return",
                 Throw => "throw",
+                Yield => "yield",
                 PlusAssign => "+=",
                 MinusAssign => "-=",
                 MultiplyAssign => "*=",
@@ -324,6 +346,12 @@ impl Token {
             Colon            |
             Comma            |
             Period           |
+            QuestionDot      |
+            QuestionBracket  |
+            DoubleQuestion   |
+            DoubleQuestionAssign |
+            ExclusiveRange   |
+            InclusiveRange   |
             Equals           |
             LessThan         |
             GreaterThan      |
@@ -338,6 +366,7 @@ impl Token {
             And              |
             If               |
             While            |
+            Until            |
             PlusAssign       |
             MinusAssign      |
             MultiplyAssign   |
@@ -354,6 +383,7 @@ impl Token {
             ModuloAssign     |
             Return           |
             Throw            |
+            Yield            |
             PowerOf          |
             In               |
             PowerOfAssign => true,
@@ -370,7 +400,9 @@ impl Token {
             // Assignments are not considered expressions - set to zero
             Equals | PlusAssign | MinusAssign | MultiplyAssign | DivideAssign | LeftShiftAssign
             | RightShiftAssign | AndAssign | OrAssign | XOrAssign | ModuloAssign
-            | PowerOfAssign => 0,
+            | PowerOfAssign | DoubleQuestionAssign => 0,
+
+            DoubleQuestion => 30,
 
             Or | XOr | Pipe => 40,
 
@@ -381,6 +413,8 @@ impl Token {
 
             In => 70,
 
+            ExclusiveRange | InclusiveRange => 75,
+
             Plus | Minus => 80,
 
             Divide | Multiply | PowerOf => 90,
@@ -389,7 +423,7 @@ impl Token {
 
             Modulo => 110,
 
-            Period => 120,
+            Period | QuestionDot => 120,
 
             _ => 0,
         }
@@ -403,33 +437,127 @@ impl Token {
             // Assignments bind to the right
             Equals | PlusAssign | MinusAssign | MultiplyAssign | DivideAssign | LeftShiftAssign
             | RightShiftAssign | AndAssign | OrAssign | XOrAssign | ModuloAssign
-            | PowerOfAssign => true,
+            | PowerOfAssign | DoubleQuestionAssign => true,
 
             // Property access binds to the right
-            Period => true,
+            Period | QuestionDot => true,
 
             _ => false,
         }
     }
 }
 
-impl From<Token> for String {
+impl From<Token<'_>> for String {
     fn from(token: Token) -> Self {
         token.syntax().into()
     }
 }
 
+/// A span of bytes in the input script, with `start` inclusive and `end` exclusive.
+///
+/// Byte offsets are counted across all the input script streams, as if they were concatenated.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, Default)]
+pub struct Span {
+    /// Byte offset of the first byte of the token.
+    pub start: usize,
+    /// Byte offset one past the last byte of the token.
+    pub end: usize,
+}
+
+impl Span {
+    /// Create a new `Span`.
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Length of the `Span` in bytes.
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Is the `Span` empty?
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// A location in the input streams, in bytes.
+#[derive(Debug, Clone, Copy)]
+struct Cursor {
+    /// Index of the input stream.
+    stream: usize,
+    /// Byte offset within the input stream.
+    offset: usize,
+    /// Byte offset across all input streams.
+    global: usize,
+}
+
 /// An iterator on a `Token` stream.
+///
+/// The input script text is scanned in place without copying.  Identifiers and string literals
+/// without escape sequences are borrowed directly from the input.
 pub struct TokenIterator<'a> {
     /// Can the next token be a unary operator?
     can_be_unary: bool,
     /// Current position.
     pos: Position,
-    /// The input character streams.
-    streams: StaticVec<Peekable<Chars<'a>>>,
+    /// The input script streams.
+    streams: StaticVec<&'a str>,
+    /// Index of the current input stream.
+    stream: usize,
+    /// Byte offset within the current input stream.
+    offset: usize,
+    /// Byte offset of the start of the current input stream across all input streams.
+    base: usize,
+    /// Symbols (keywords, operators and identifiers) that are disabled.
+    disabled_symbols: Option<&'a HashSet<String>>,
 }
 
 impl<'a> TokenIterator<'a> {
+    /// Get the current location in the input streams.
+    fn cursor(&self) -> Cursor {
+        Cursor {
+            stream: self.stream,
+            offset: self.offset,
+            global: self.base + self.offset,
+        }
+    }
+    /// Get the location in the input streams of a character just consumed.
+    fn cursor_before(&self, ch: char) -> Cursor {
+        let len = ch.len_utf8();
+
+        Cursor {
+            stream: self.stream,
+            offset: self.offset - len,
+            global: self.base + self.offset - len,
+        }
+    }
+    /// Get the text between a location and the current location in the input streams.
+    ///
+    /// The text is borrowed from the input unless it spans multiple input streams.
+    fn text_from(&self, start: Cursor) -> Cow<'a, str> {
+        self.text_between(start, self.cursor())
+    }
+    /// Get the text between two locations in the input streams.
+    ///
+    /// The text is borrowed from the input unless it spans multiple input streams.
+    fn text_between(&self, start: Cursor, mut end: Cursor) -> Cow<'a, str> {
+        // The end of one stream is the same location as the start of the next
+        while end.offset == 0 && end.stream > start.stream {
+            end.stream -= 1;
+            end.offset = self.streams[end.stream].len();
+        }
+
+        if start.stream == end.stream {
+            return self.streams[start.stream][start.offset..end.offset].into();
+        }
+
+        let mut text = String::with_capacity(end.global - start.global);
+        text.push_str(&self.streams[start.stream][start.offset..]);
+        (start.stream + 1..end.stream).for_each(|i| text.push_str(self.streams[i]));
+        text.push_str(&self.streams[end.stream][..end.offset]);
+        text.into()
+    }
     /// Consume the next character.
     fn eat_next(&mut self) {
         self.get_next();
@@ -437,34 +565,46 @@ impl<'a> TokenIterator<'a> {
     }
     /// Get the next character
     fn get_next(&mut self) -> Option<char> {
-        loop {
-            if self.streams.is_empty() {
-                // No more streams
-                return None;
-            } else if let Some(ch) = self.streams[0].next() {
-                // Next character in current stream
-                return Some(ch);
-            } else {
-                // Jump to the next stream
-                let _ = self.streams.remove(0);
-            }
-        }
+        let ch = self.peek_next()?;
+        self.offset += ch.len_utf8();
+        Some(ch)
     }
     /// Peek the next character
     fn peek_next(&mut self) -> Option<char> {
         loop {
-            if self.streams.is_empty() {
+            if self.stream >= self.streams.len() {
                 // No more streams
                 return None;
-            } else if let Some(ch) = self.streams[0].peek() {
-                // Next character in current stream
-                return Some(*ch);
+            }
+
+            let stream = self.streams[self.stream];
+
+            if let Some(&b) = stream.as_bytes().get(self.offset) {
+                // Next character in current stream - fast path for ASCII
+                return Some(if b.is_ascii() {
+                    b as char
+                } else {
+                    stream[self.offset..].chars().next().unwrap()
+                });
             } else {
                 // Jump to the next stream
-                let _ = self.streams.remove(0);
+                self.base += stream.len();
+                self.stream += 1;
+                self.offset = 0;
             }
         }
     }
+    /// Peek the character after the next character
+    #[cfg(not(feature = "no_float"))]
+    fn peek_second(&mut self) -> Option<char> {
+        let (stream, offset, base) = (self.stream, self.offset, self.base);
+        self.get_next()?;
+        let ch = self.peek_next();
+        self.stream = stream;
+        self.offset = offset;
+        self.base = base;
+        ch
+    }
     /// Move the current position one character ahead.
     fn advance(&mut self) {
         self.pos.advance();
@@ -483,14 +623,22 @@ impl<'a> TokenIterator<'a> {
     }
 
     /// Parse a string literal wrapped by `enclosing_char`.
+    ///
+    /// The result is borrowed from the input unless the literal contains escape sequences.
     pub fn parse_string_literal(
         &mut self,
         enclosing_char: char,
-    ) -> Result<String, (LexError, Position)> {
-        let mut result = Vec::new();
-        let mut escape = String::with_capacity(12);
+    ) -> Result<Cow<'a, str>, (LexError, Position)> {
+        let start = self.cursor();
+        // Only allocated when the first escape sequence is encountered
+        let mut result: Option<String> = None;
+        let mut escape = String::new();
+
+        let end = loop {
+            // Make sure the cursor does not sit at the end of a stream
+            self.peek_next();
+            let before = self.cursor();
 
-        loop {
             let next_char = self
                 .get_next()
                 .ok_or((LERR::UnterminatedString, self.pos))?;
@@ -500,27 +648,30 @@ impl<'a> TokenIterator<'a> {
             match next_char {
                 // \...
                 '\\' if escape.is_empty() => {
+                    if result.is_none() {
+                        result = Some(self.text_between(start, before).into_owned());
+                    }
                     escape.push('\\');
                 }
                 // \\
                 '\\' if !escape.is_empty() => {
                     escape.clear();
-                    result.push('\\');
+                    result.as_mut().unwrap().push('\\');
                 }
                 // \t
                 't' if !escape.is_empty() => {
                     escape.clear();
-                    result.push('\t');
+                    result.as_mut().unwrap().push('\t');
                 }
                 // \n
                 'n' if !escape.is_empty() => {
                     escape.clear();
-                    result.push('\n');
+                    result.as_mut().unwrap().push('\n');
                 }
                 // \r
                 'r' if !escape.is_empty() => {
                     escape.clear();
-                    result.push('\r');
+                    result.as_mut().unwrap().push('\r');
                 }
                 // \x??, \u????, \U????????
                 ch @ 'x' | ch @ 'u' | ch @ 'U' if !escape.is_empty() => {
@@ -550,7 +701,7 @@ impl<'a> TokenIterator<'a> {
                         })?;
                     }
 
-                    result.push(
+                    result.as_mut().unwrap().push(
                         char::from_u32(out_val)
                             .ok_or_else(|| (LERR::MalformedEscapeSequence(seq), self.pos))?,
                     );
@@ -559,11 +710,11 @@ impl<'a> TokenIterator<'a> {
                 // \{enclosing_char} - escaped
                 ch if enclosing_char == ch && !escape.is_empty() => {
                     escape.clear();
-                    result.push(ch)
+                    result.as_mut().unwrap().push(ch)
                 }
 
                 // Close wrapper
-                ch if enclosing_char == ch && escape.is_empty() => break,
+                ch if enclosing_char == ch && escape.is_empty() => break before,
 
                 // Unknown escape sequence
                 _ if !escape.is_empty() => {
@@ -578,474 +729,594 @@ impl<'a> TokenIterator<'a> {
 
                 // All other characters
                 ch => {
-                    escape.clear();
-                    result.push(ch);
+                    if let Some(result) = result.as_mut() {
+                        result.push(ch);
+                    }
                 }
             }
-        }
+        };
 
-        Ok(result.iter().collect())
+        Ok(match result {
+            Some(result) => result.into(),
+            None => self.text_between(start, end),
+        })
     }
 
-    /// Get the next token.
-    fn inner_next(&mut self) -> Option<(Token, Position)> {
-        let mut negated = false;
+    /// Get the next token, together with its position and span.
+    fn inner_next(&mut self) -> Option<(Token<'a>, Position, Span)> {
+        let mut negated: Option<Cursor> = None;
 
         while let Some(c) = self.get_next() {
             self.advance();
 
             let pos = self.pos;
+            let start = negated.unwrap_or_else(|| self.cursor_before(c));
 
-            match (c, self.peek_next().unwrap_or('\0')) {
-                // \n
-                ('\n', _) => self.new_line(),
-
-                // digit ...
-                ('0'..='9', _) => {
-                    let mut result = Vec::new();
-                    let mut radix_base: Option<u32> = None;
-                    result.push(c);
-
-                    while let Some(next_char) = self.peek_next() {
-                        match next_char {
-                            '0'..='9' | '_' => {
-                                result.push(next_char);
-                                self.eat_next();
-                            }
-                            #[cfg(not(feature = "no_float"))]
-                            '.' => {
-                                result.push(next_char);
-                                self.eat_next();
-                                while let Some(next_char_in_float) = self.peek_next() {
-                                    match next_char_in_float {
-                                        '0'..='9' | '_' => {
-                                            result.push(next_char_in_float);
-                                            self.eat_next();
-                                        }
-                                        _ => break,
-                                    }
+            match self.scan_token(c, pos, start, negated.is_some()) {
+                Scanned::Token(token, pos) => {
+                    let span = Span::new(start.global, self.cursor().global);
+                    return Some((token, pos, span));
+                }
+                Scanned::Negated => negated = Some(start),
+                Scanned::Skipped => (),
+            }
+        }
+
+        self.advance();
+        let end = self.cursor().global;
+        Some((Token::EOF, self.pos, Span::new(end, end)))
+    }
+
+    /// Scan a token starting with a character just consumed.
+    fn scan_token(&mut self, c: char, pos: Position, start: Cursor, negated: bool) -> Scanned<'a> {
+        match (c, self.peek_next().unwrap_or('\0')) {
+            // \n
+            ('\n', _) => self.new_line(),
+
+            // digit ...
+            ('0'..='9', _) => {
+                let mut radix_base: Option<u32> = None;
+
+                while let Some(next_char) = self.peek_next() {
+                    match next_char {
+                        '0'..='9' | '_' => self.eat_next(),
+                        // Not a decimal point if followed by another (i.e. a range)
+                        #[cfg(not(feature = "no_float"))]
+                        '.' if self.peek_second() != Some('.') => {
+                            self.eat_next();
+                            while let Some(next_char_in_float) = self.peek_next() {
+                                match next_char_in_float {
+                                    '0'..='9' | '_' => self.eat_next(),
+                                    _ => break,
                                 }
                             }
-                            // 0x????, 0o????, 0b????
-                            ch @ 'x' | ch @ 'X' | ch @ 'o' | ch @ 'O' | ch @ 'b' | ch @ 'B'
-                                if c == '0' =>
-                            {
-                                result.push(next_char);
-                                self.eat_next();
+                        }
+                        // 0x????, 0o????, 0b????
+                        ch @ 'x' | ch @ 'X' | ch @ 'o' | ch @ 'O' | ch @ 'b' | ch @ 'B'
+                            if c == '0' =>
+                        {
+                            self.eat_next();
 
-                                let valid = match ch {
-                                    'x' | 'X' => [
-                                        'a', 'b', 'c', 'd', 'e', 'f', 'A', 'B', 'C', 'D', 'E', 'F',
-                                        '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', '_',
-                                    ],
-                                    'o' | 'O' => [
-                                        '0', '1', '2', '3', '4', '5', '6', '7', '_', '_', '_', '_',
-                                        '_', '_', '_', '_', '_', '_', '_', '_', '_', '_', '_',
-                                    ],
-                                    'b' | 'B' => [
-                                        '0', '1', '_', '_', '_', '_', '_', '_', '_', '_', '_', '_',
-                                        '_', '_', '_', '_', '_', '_', '_', '_', '_', '_', '_',
-                                    ],
-                                    _ => panic!("unexpected character {}", ch),
-                                };
-
-                                radix_base = Some(match ch {
-                                    'x' | 'X' => 16,
-                                    'o' | 'O' => 8,
-                                    'b' | 'B' => 2,
-                                    _ => panic!("unexpected character {}", ch),
-                                });
-
-                                while let Some(next_char_in_hex) = self.peek_next() {
-                                    if !valid.contains(&next_char_in_hex) {
-                                        break;
-                                    }
-
-                                    result.push(next_char_in_hex);
-                                    self.eat_next();
+                            let valid: fn(char) -> bool = match ch {
+                                'x' | 'X' => |c| c.is_ascii_hexdigit() || c == '_',
+                                'o' | 'O' => |c| ('0'..='7').contains(&c) || c == '_',
+                                'b' | 'B' => |c| c == '0' || c == '1' || c == '_',
+                                _ => panic!("unexpected character {}", ch),
+                            };
+
+                            radix_base = Some(match ch {
+                                'x' | 'X' => 16,
+                                'o' | 'O' => 8,
+                                'b' | 'B' => 2,
+                                _ => panic!("unexpected character {}", ch),
+                            });
+
+                            while let Some(next_char_in_hex) = self.peek_next() {
+                                if !valid(next_char_in_hex) {
+                                    break;
                                 }
+                                self.eat_next();
                             }
-
-                            _ => break,
                         }
-                    }
 
-                    if negated {
-                        result.insert(0, '-');
+                        _ => break,
                     }
+                }
 
-                    // Parse number
-                    if let Some(radix) = radix_base {
-                        let out: String = result.iter().skip(2).filter(|&&c| c != '_').collect();
-
-                        return Some((
-                            INT::from_str_radix(&out, radix)
-                                .map(Token::IntegerConstant)
-                                .unwrap_or_else(|_| {
-                                    Token::LexError(Box::new(LERR::MalformedNumber(
-                                        result.into_iter().collect(),
-                                    )))
-                                }),
-                            pos,
-                        ));
+                let result = self.text_from(start);
+
+                // Parse number
+                let token = if let Some(radix) = radix_base {
+                    let out: String = result.chars().skip(2).filter(|&c| c != '_').collect();
+
+                    INT::from_str_radix(&out, radix)
+                        .map(Token::IntegerConstant)
+                        .unwrap_or_else(|_| {
+                            Token::LexError(Box::new(LERR::MalformedNumber(result.into_owned())))
+                        })
+                } else {
+                    let out: Cow<str> = if result.contains('_') {
+                        result
+                            .chars()
+                            .filter(|&c| c != '_')
+                            .collect::<String>()
+                            .into()
                     } else {
-                        let out: String = result.iter().filter(|&&c| c != '_').collect();
-                        let num = INT::from_str(&out).map(Token::IntegerConstant);
+                        result.as_ref().into()
+                    };
+                    let num = INT::from_str(&out).map(Token::IntegerConstant);
 
-                        // If integer parsing is unnecessary, try float instead
-                        #[cfg(not(feature = "no_float"))]
-                        let num = num.or_else(|_| FLOAT::from_str(&out).map(Token::FloatConstant));
-
-                        return Some((
-                            num.unwrap_or_else(|_| {
-                                Token::LexError(Box::new(LERR::MalformedNumber(
-                                    result.into_iter().collect(),
-                                )))
-                            }),
-                            pos,
-                        ));
-                    }
-                }
+                    // If integer parsing is unnecessary, try float instead
+                    #[cfg(not(feature = "no_float"))]
+                    let num = num.or_else(|_| FLOAT::from_str(&out).map(Token::FloatConstant));
 
-                // letter or underscore ...
-                ('A'..='Z', _) | ('a'..='z', _) | ('_', _) => {
-                    let mut result = Vec::new();
-                    result.push(c);
+                    num.unwrap_or_else(|_| {
+                        Token::LexError(Box::new(LERR::MalformedNumber(result.into_owned())))
+                    })
+                };
 
-                    while let Some(next_char) = self.peek_next() {
-                        match next_char {
-                            x if x.is_ascii_alphanumeric() || x == '_' => {
-                                result.push(x);
-                                self.eat_next();
-                            }
-                            _ => break,
-                        }
-                    }
+                return Scanned::Token(token, pos);
+            }
 
-                    let is_valid_identifier = result
-                        .iter()
-                        .find(|&ch| char::is_ascii_alphanumeric(ch)) // first alpha-numeric character
-                        .map(char::is_ascii_alphabetic) // is a letter
-                        .unwrap_or(false); // if no alpha-numeric at all - syntax error
+            // letter or underscore ...
+            ('A'..='Z', _) | ('a'..='z', _) | ('_', _) => {
+                while let Some(next_char) = self.peek_next() {
+                    match next_char {
+                        x if x.is_ascii_alphanumeric() || x == '_' => self.eat_next(),
+                        _ => break,
+                    }
+                }
 
-                    let identifier: String = result.iter().collect();
+                let identifier = self.text_from(start);
 
-                    if !is_valid_identifier {
-                        return Some((
-                            Token::LexError(Box::new(LERR::MalformedIdentifier(identifier))),
-                            pos,
-                        ));
-                    }
+                let is_valid_identifier = identifier
+                    .chars()
+                    .find(char::is_ascii_alphanumeric) // first alpha-numeric character
+                    .map(|ch| ch.is_ascii_alphabetic()) // is a letter
+                    .unwrap_or(false); // if no alpha-numeric at all - syntax error
 
-                    return Some((
-                        match identifier.as_str() {
-                            "true" => Token::True,
-                            "false" => Token::False,
-                            "let" => Token::Let,
-                            "const" => Token::Const,
-                            "if" => Token::If,
-                            "else" => Token::Else,
-                            "while" => Token::While,
-                            "loop" => Token::Loop,
-                            "continue" => Token::Continue,
-                            "break" => Token::Break,
-                            "return" => Token::Return,
-                            "throw" => Token::Throw,
-                            "for" => Token::For,
-                            "in" => Token::In,
-                            "private" => Token::Private,
-
-                            #[cfg(not(feature = "no_module"))]
-                            "import" => Token::Import,
-                            #[cfg(not(feature = "no_module"))]
-                            "export" => Token::Export,
-                            #[cfg(not(feature = "no_module"))]
-                            "as" => Token::As,
-
-                            #[cfg(not(feature = "no_function"))]
-                            "fn" => Token::Fn,
-
-                            _ => Token::Identifier(identifier),
-                        },
+                if !is_valid_identifier {
+                    return Scanned::Token(
+                        Token::LexError(Box::new(LERR::MalformedIdentifier(
+                            identifier.into_owned(),
+                        ))),
                         pos,
-                    ));
-                }
-
-                // " - string literal
-                ('"', _) => {
-                    return self.parse_string_literal('"').map_or_else(
-                        |err| Some((Token::LexError(Box::new(err.0)), err.1)),
-                        |out| Some((Token::StringConst(out), pos)),
                     );
                 }
 
-                // ' - character literal
-                ('\'', '\'') => {
-                    return Some((
-                        Token::LexError(Box::new(LERR::MalformedChar("".to_string()))),
-                        pos,
-                    ));
-                }
-                ('\'', _) => {
-                    return Some(self.parse_string_literal('\'').map_or_else(
-                        |err| (Token::LexError(Box::new(err.0)), err.1),
-                        |result| {
-                            let mut chars = result.chars();
-                            let first = chars.next();
-
-                            if chars.next().is_some() {
-                                (Token::LexError(Box::new(LERR::MalformedChar(result))), pos)
-                            } else {
-                                (Token::CharConstant(first.expect("should be Some")), pos)
-                            }
-                        },
-                    ));
-                }
+                return Scanned::Token(
+                    match identifier.as_ref() {
+                        "true" => Token::True,
+                        "false" => Token::False,
+                        "let" => Token::Let,
+                        "const" => Token::Const,
+                        "if" => Token::If,
+                        "else" => Token::Else,
+                        "while" => Token::While,
+                        "do" => Token::Do,
+                        "until" => Token::Until,
+                        "loop" => Token::Loop,
+                        "continue" => Token::Continue,
+                        "break" => Token::Break,
+                        "return" => Token::Return,
+                        "throw" => Token::Throw,
+                        "for" => Token::For,
+                        "in" => Token::In,
+                        "private" => Token::Private,
+
+                        #[cfg(not(feature = "no_module"))]
+                        "import" => Token::Import,
+                        #[cfg(not(feature = "no_module"))]
+                        "export" => Token::Export,
+                        #[cfg(not(feature = "no_module"))]
+                        "as" => Token::As,
+
+                        #[cfg(not(feature = "no_function"))]
+                        "fn" => Token::Fn,
+                        #[cfg(not(feature = "no_function"))]
+                        "yield" => Token::Yield,
+
+                        _ => Token::Identifier(identifier),
+                    },
+                    pos,
+                );
+            }
 
-                // Braces
-                ('{', _) => return Some((Token::LeftBrace, pos)),
-                ('}', _) => return Some((Token::RightBrace, pos)),
+            // " - string literal
+            ('"', _) => {
+                return self.parse_string_literal('"').map_or_else(
+                    |err| Scanned::Token(Token::LexError(Box::new(err.0)), err.1),
+                    |out| Scanned::Token(Token::StringConst(out), pos),
+                );
+            }
 
-                // Parentheses
-                ('(', _) => return Some((Token::LeftParen, pos)),
-                (')', _) => return Some((Token::RightParen, pos)),
+            // ' - character literal
+            ('\'', '\'') => {
+                return Scanned::Token(
+                    Token::LexError(Box::new(LERR::MalformedChar("".to_string()))),
+                    pos,
+                );
+            }
+            ('\'', _) => {
+                return self.parse_string_literal('\'').map_or_else(
+                    |err| Scanned::Token(Token::LexError(Box::new(err.0)), err.1),
+                    |result| {
+                        let mut chars = result.chars();
+
+                        match (chars.next(), chars.next()) {
+                            (Some(ch), None) => Scanned::Token(Token::CharConstant(ch), pos),
+                            _ => Scanned::Token(
+                                Token::LexError(Box::new(LERR::MalformedChar(result.into_owned()))),
+                                pos,
+                            ),
+                        }
+                    },
+                );
+            }
 
-                // Indexing
-                ('[', _) => return Some((Token::LeftBracket, pos)),
-                (']', _) => return Some((Token::RightBracket, pos)),
+            // Braces
+            ('{', _) => return Scanned::Token(Token::LeftBrace, pos),
+            ('}', _) => return Scanned::Token(Token::RightBrace, pos),
 
-                // Map literal
-                #[cfg(not(feature = "no_object"))]
-                ('#', '{') => {
-                    self.eat_next();
-                    return Some((Token::MapStart, pos));
-                }
+            // Parentheses
+            ('(', _) => return Scanned::Token(Token::LeftParen, pos),
+            (')', _) => return Scanned::Token(Token::RightParen, pos),
 
-                // Operators
-                ('+', '=') => {
-                    self.eat_next();
-                    return Some((Token::PlusAssign, pos));
-                }
-                ('+', _) if self.can_be_unary => return Some((Token::UnaryPlus, pos)),
-                ('+', _) => return Some((Token::Plus, pos)),
+            // Indexing
+            ('[', _) => return Scanned::Token(Token::LeftBracket, pos),
+            (']', _) => return Scanned::Token(Token::RightBracket, pos),
 
-                ('-', '0'..='9') if self.can_be_unary => negated = true,
-                ('-', '0'..='9') => return Some((Token::Minus, pos)),
-                ('-', '=') => {
-                    self.eat_next();
-                    return Some((Token::MinusAssign, pos));
-                }
-                ('-', _) if self.can_be_unary => return Some((Token::UnaryMinus, pos)),
-                ('-', _) => return Some((Token::Minus, pos)),
+            // Map literal
+            #[cfg(not(feature = "no_object"))]
+            ('#', '{') => {
+                self.eat_next();
+                return Scanned::Token(Token::MapStart, pos);
+            }
 
-                ('*', '=') => {
-                    self.eat_next();
-                    return Some((Token::MultiplyAssign, pos));
-                }
-                ('*', _) => return Some((Token::Multiply, pos)),
+            // Operators
+            ('+', '=') => {
+                self.eat_next();
+                return Scanned::Token(Token::PlusAssign, pos);
+            }
+            ('+', _) if self.can_be_unary => return Scanned::Token(Token::UnaryPlus, pos),
+            ('+', _) => return Scanned::Token(Token::Plus, pos),
+
+            ('-', '0'..='9') if self.can_be_unary && !negated => return Scanned::Negated,
+            ('-', '0'..='9') => return Scanned::Token(Token::Minus, pos),
+            ('-', '=') => {
+                self.eat_next();
+                return Scanned::Token(Token::MinusAssign, pos);
+            }
+            ('-', '>') => {
+                self.eat_next();
+                return Scanned::Token(Token::RightArrow, pos);
+            }
+            ('-', _) if self.can_be_unary => return Scanned::Token(Token::UnaryMinus, pos),
+            ('-', _) => return Scanned::Token(Token::Minus, pos),
 
-                // Comments
-                ('/', '/') => {
-                    self.eat_next();
+            ('*', '=') => {
+                self.eat_next();
+                return Scanned::Token(Token::MultiplyAssign, pos);
+            }
+            ('*', _) => return Scanned::Token(Token::Multiply, pos),
 
-                    while let Some(c) = self.get_next() {
-                        if c == '\n' {
-                            self.new_line();
-                            break;
-                        }
+            // Comments
+            ('/', '/') => {
+                self.eat_next();
 
-                        self.advance();
+                while let Some(c) = self.get_next() {
+                    if c == '\n' {
+                        self.new_line();
+                        break;
                     }
+
+                    self.advance();
                 }
-                ('/', '*') => {
-                    let mut level = 1;
+            }
+            ('/', '*') => {
+                let mut level = 1;
 
-                    self.eat_next();
+                self.eat_next();
 
-                    while let Some(c) = self.get_next() {
-                        self.advance();
+                while let Some(c) = self.get_next() {
+                    self.advance();
 
-                        match c {
-                            '/' => {
-                                if self.get_next() == Some('*') {
-                                    level += 1;
-                                }
-                                self.advance();
+                    match c {
+                        '/' => {
+                            if self.get_next() == Some('*') {
+                                level += 1;
                             }
-                            '*' => {
-                                if self.get_next() == Some('/') {
-                                    level -= 1;
-                                }
-                                self.advance();
+                            self.advance();
+                        }
+                        '*' => {
+                            if self.get_next() == Some('/') {
+                                level -= 1;
                             }
-                            '\n' => self.new_line(),
-                            _ => (),
+                            self.advance();
                         }
+                        '\n' => self.new_line(),
+                        _ => (),
+                    }
 
-                        if level == 0 {
-                            break;
-                        }
+                    if level == 0 {
+                        break;
                     }
                 }
+            }
 
-                ('/', '=') => {
-                    self.eat_next();
-                    return Some((Token::DivideAssign, pos));
-                }
-                ('/', _) => return Some((Token::Divide, pos)),
+            ('/', '=') => {
+                self.eat_next();
+                return Scanned::Token(Token::DivideAssign, pos);
+            }
+            ('/', _) => return Scanned::Token(Token::Divide, pos),
 
-                (';', _) => return Some((Token::SemiColon, pos)),
-                (',', _) => return Some((Token::Comma, pos)),
-                ('.', _) => return Some((Token::Period, pos)),
+            (';', _) => return Scanned::Token(Token::SemiColon, pos),
+            (',', _) => return Scanned::Token(Token::Comma, pos),
+            ('.', '.') => {
+                self.eat_next();
 
-                ('=', '=') => {
+                if self.peek_next() == Some('=') {
                     self.eat_next();
+                    return Scanned::Token(Token::InclusiveRange, pos);
+                }
 
-                    // Warn against `===`
-                    if self.peek_next() == Some('=') {
-                        return Some((
-                                Token::LexError(Box::new(LERR::ImproperKeyword(
-                                    "'===' is not a valid operator. This is not JavaScript! Should it be '=='?"
-                                        .to_string(),
-                                ))),
-                                pos,
-                            ));
-                    }
+                return Scanned::Token(Token::ExclusiveRange, pos);
+            }
+            ('.', _) => return Scanned::Token(Token::Period, pos),
 
-                    return Some((Token::EqualsTo, pos));
-                }
-                ('=', _) => return Some((Token::Equals, pos)),
+            ('?', '.') => {
+                self.eat_next();
+                return Scanned::Token(Token::QuestionDot, pos);
+            }
+            ('?', '[') => {
+                self.eat_next();
+                return Scanned::Token(Token::QuestionBracket, pos);
+            }
+            ('?', '?') => {
+                self.eat_next();
 
-                #[cfg(not(feature = "no_module"))]
-                (':', ':') => {
+                if self.peek_next() == Some('=') {
                     self.eat_next();
-                    return Some((Token::DoubleColon, pos));
+                    return Scanned::Token(Token::DoubleQuestionAssign, pos);
                 }
-                (':', _) => return Some((Token::Colon, pos)),
 
-                ('<', '=') => {
-                    self.eat_next();
-                    return Some((Token::LessThanEqualsTo, pos));
-                }
-                ('<', '<') => {
-                    self.eat_next();
+                return Scanned::Token(Token::DoubleQuestion, pos);
+            }
 
-                    return Some((
-                        if self.peek_next() == Some('=') {
-                            self.eat_next();
-                            Token::LeftShiftAssign
-                        } else {
-                            Token::LeftShift
-                        },
+            ('=', '=') => {
+                self.eat_next();
+
+                // Warn against `===`
+                if self.peek_next() == Some('=') {
+                    return Scanned::Token(
+                        Token::LexError(Box::new(LERR::ImproperKeyword(
+                            "'===' is not a valid operator. This is not JavaScript! Should it be '=='?"
+                                .to_string(),
+                        ))),
                         pos,
-                    ));
+                    );
                 }
-                ('<', _) => return Some((Token::LessThan, pos)),
 
-                ('>', '=') => {
-                    self.eat_next();
-                    return Some((Token::GreaterThanEqualsTo, pos));
-                }
-                ('>', '>') => {
-                    self.eat_next();
+                return Scanned::Token(Token::EqualsTo, pos);
+            }
+            ('=', _) => return Scanned::Token(Token::Equals, pos),
 
-                    return Some((
-                        if self.peek_next() == Some('=') {
-                            self.eat_next();
-                            Token::RightShiftAssign
-                        } else {
-                            Token::RightShift
-                        },
-                        pos,
-                    ));
-                }
-                ('>', _) => return Some((Token::GreaterThan, pos)),
+            #[cfg(not(feature = "no_module"))]
+            (':', ':') => {
+                self.eat_next();
+                return Scanned::Token(Token::DoubleColon, pos);
+            }
+            (':', _) => return Scanned::Token(Token::Colon, pos),
 
-                ('!', '=') => {
-                    self.eat_next();
+            ('<', '=') => {
+                self.eat_next();
+                return Scanned::Token(Token::LessThanEqualsTo, pos);
+            }
+            ('<', '<') => {
+                self.eat_next();
 
-                    // Warn against `!==`
+                return Scanned::Token(
                     if self.peek_next() == Some('=') {
-                        return Some((
-                                Token::LexError(Box::new(LERR::ImproperKeyword(
-                                    "'!==' is not a valid operator. This is not JavaScript! Should it be '!='?"
-                                        .to_string(),
-                                ))),
-                                pos,
-                            ));
-                    }
+                        self.eat_next();
+                        Token::LeftShiftAssign
+                    } else {
+                        Token::LeftShift
+                    },
+                    pos,
+                );
+            }
+            ('<', _) => return Scanned::Token(Token::LessThan, pos),
 
-                    return Some((Token::NotEqualsTo, pos));
-                }
-                ('!', _) => return Some((Token::Bang, pos)),
+            ('>', '=') => {
+                self.eat_next();
+                return Scanned::Token(Token::GreaterThanEqualsTo, pos);
+            }
+            ('>', '>') => {
+                self.eat_next();
 
-                ('|', '|') => {
-                    self.eat_next();
-                    return Some((Token::Or, pos));
-                }
-                ('|', '=') => {
-                    self.eat_next();
-                    return Some((Token::OrAssign, pos));
+                return Scanned::Token(
+                    if self.peek_next() == Some('=') {
+                        self.eat_next();
+                        Token::RightShiftAssign
+                    } else {
+                        Token::RightShift
+                    },
+                    pos,
+                );
+            }
+            ('>', _) => return Scanned::Token(Token::GreaterThan, pos),
+
+            ('!', '=') => {
+                self.eat_next();
+
+                // Warn against `!==`
+                if self.peek_next() == Some('=') {
+                    return Scanned::Token(
+                        Token::LexError(Box::new(LERR::ImproperKeyword(
+                            "'!==' is not a valid operator. This is not JavaScript! Should it be '!='?"
+                                .to_string(),
+                        ))),
+                        pos,
+                    );
                 }
-                ('|', _) => return Some((Token::Pipe, pos)),
 
-                ('&', '&') => {
-                    self.eat_next();
-                    return Some((Token::And, pos));
-                }
-                ('&', '=') => {
-                    self.eat_next();
-                    return Some((Token::AndAssign, pos));
-                }
-                ('&', _) => return Some((Token::Ampersand, pos)),
+                return Scanned::Token(Token::NotEqualsTo, pos);
+            }
+            ('!', _) => return Scanned::Token(Token::Bang, pos),
 
-                ('^', '=') => {
-                    self.eat_next();
-                    return Some((Token::XOrAssign, pos));
-                }
-                ('^', _) => return Some((Token::XOr, pos)),
+            ('|', '|') => {
+                self.eat_next();
+                return Scanned::Token(Token::Or, pos);
+            }
+            ('|', '=') => {
+                self.eat_next();
+                return Scanned::Token(Token::OrAssign, pos);
+            }
+            ('|', _) => return Scanned::Token(Token::Pipe, pos),
 
-                ('%', '=') => {
-                    self.eat_next();
-                    return Some((Token::ModuloAssign, pos));
-                }
-                ('%', _) => return Some((Token::Modulo, pos)),
+            ('&', '&') => {
+                self.eat_next();
+                return Scanned::Token(Token::And, pos);
+            }
+            ('&', '=') => {
+                self.eat_next();
+                return Scanned::Token(Token::AndAssign, pos);
+            }
+            ('&', _) => return Scanned::Token(Token::Ampersand, pos),
 
-                ('~', '=') => {
-                    self.eat_next();
-                    return Some((Token::PowerOfAssign, pos));
-                }
-                ('~', _) => return Some((Token::PowerOf, pos)),
+            ('^', '=') => {
+                self.eat_next();
+                return Scanned::Token(Token::XOrAssign, pos);
+            }
+            ('^', _) => return Scanned::Token(Token::XOr, pos),
+
+            ('%', '=') => {
+                self.eat_next();
+                return Scanned::Token(Token::ModuloAssign, pos);
+            }
+            ('%', _) => return Scanned::Token(Token::Modulo, pos),
 
-                ('\0', _) => panic!("should not be EOF"),
+            ('~', '=') => {
+                self.eat_next();
+                return Scanned::Token(Token::PowerOfAssign, pos);
+            }
+            ('~', _) => return Scanned::Token(Token::PowerOf, pos),
 
-                (ch, _) if ch.is_whitespace() => (),
-                (ch, _) => return Some((Token::LexError(Box::new(LERR::UnexpectedChar(ch))), pos)),
+            (ch, _) if ch.is_whitespace() => (),
+            (ch, _) => {
+                return Scanned::Token(Token::LexError(Box::new(LERR::UnexpectedChar(ch))), pos)
             }
         }
 
-        self.advance();
-        Some((Token::EOF, self.pos))
+        Scanned::Skipped
+    }
+
+    /// Get the next token, together with its position and its span in the input.
+    pub fn next_with_span(&mut self) -> Option<(Token<'a>, Position, Span)> {
+        let disabled_symbols = self.disabled_symbols;
+
+        self.inner_next().map(|(token, pos, span)| {
+            // Save the last token
+            self.can_be_unary = token.is_next_unary();
+
+            match disabled_symbols {
+                Some(symbols) => (disable_token(token, symbols), pos, span),
+                None => (token, pos, span),
+            }
+        })
+    }
+    /// Turn all the symbols in a set into errors when they are encountered.
+    pub(crate) fn disable_symbols(mut self, symbols: &'a HashSet<String>) -> Self {
+        self.disabled_symbols = if symbols.is_empty() {
+            None
+        } else {
+            Some(symbols)
+        };
+        self
     }
 }
 
+/// Replace a token with an error if it is a disabled symbol.
+fn disable_token<'a>(token: Token<'a>, symbols: &HashSet<String>) -> Token<'a> {
+    let disabled = match &token {
+        Token::IntegerConstant(_)
+        | Token::CharConstant(_)
+        | Token::StringConst(_)
+        | Token::LexError(_)
+        | Token::EOF => false,
+        #[cfg(not(feature = "no_float"))]
+        Token::FloatConstant(_) => false,
+        Token::Identifier(s) => symbols.contains(s.as_ref()),
+        // A compound assignment is also disabled by disabling its operator (e.g. `+=` by `+`)
+        Token::PlusAssign
+        | Token::MinusAssign
+        | Token::MultiplyAssign
+        | Token::DivideAssign
+        | Token::LeftShiftAssign
+        | Token::RightShiftAssign
+        | Token::ModuloAssign
+        | Token::PowerOfAssign
+        | Token::AndAssign
+        | Token::OrAssign
+        | Token::XOrAssign
+        | Token::DoubleQuestionAssign => {
+            let syntax = token.syntax();
+            symbols.contains(syntax.as_ref()) || symbols.contains(syntax.trim_end_matches('='))
+        }
+        token => symbols.contains(token.syntax().as_ref()),
+    };
+
+    if disabled {
+        Token::LexError(Box::new(LERR::DisabledSymbol(token.syntax().into_owned())))
+    } else {
+        token
+    }
+}
+
+/// Result of scanning from a character in the input.
+enum Scanned<'a> {
+    /// A token is found.
+    Token(Token<'a>, Position),
+    /// A negative sign is found in front of a number literal.
+    Negated,
+    /// No token is found (e.g. white-space or comments).
+    Skipped,
+}
+
 impl<'a> Iterator for TokenIterator<'a> {
-    type Item = (Token, Position);
+    type Item = (Token<'a>, Position);
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.inner_next().map(|x| {
-            // Save the last token
-            self.can_be_unary = x.0.is_next_unary();
-            x
-        })
+        self.next_with_span().map(|(token, pos, _)| (token, pos))
     }
 }
 
 /// Tokenize an input text stream.
+///
+/// Multiple input streams are treated as if they were concatenated.
+///
+/// # Examples
+///
+/// ```
+/// use rhai::{lex, Span, Token};
+///
+/// let scripts = ["let answer = 42;"];
+/// let mut stream = lex(&scripts);
+///
+/// assert_eq!(stream.next_with_span().unwrap().2, Span::new(0, 3));
+///
+/// let (token, _, span) = stream.next_with_span().unwrap();
+/// assert_eq!(token, Token::Identifier("answer".into()));
+/// assert_eq!(span, Span::new(4, 10));
+/// ```
 pub fn lex<'a>(input: &'a [&'a str]) -> TokenIterator<'a> {
     TokenIterator {
         can_be_unary: true,
         pos: Position::new(1, 0),
-        streams: input.iter().map(|s| s.chars().peekable()).collect(),
+        streams: input.iter().cloned().collect(),
+        stream: 0,
+        offset: 0,
+        base: 0,
+        disabled_symbols: None,
     }
 }
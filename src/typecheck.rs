@@ -0,0 +1,575 @@
+//! Module implementing the type checker run on scripts during compilation.
+
+use crate::any::Dynamic;
+use crate::engine::{Engine, KEYWORD_DEBUG, KEYWORD_EVAL, KEYWORD_PRINT, KEYWORD_TYPE_OF};
+use crate::error::{ParseError, ParseErrorType};
+use crate::parser::{Expr, FnDef, ReturnType, Stmt, Type};
+use crate::token::Position;
+use crate::utils::StaticVec;
+
+use crate::stdlib::{
+    any::TypeId,
+    borrow::Cow,
+    boxed::Box,
+    collections::HashMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// Signatures of Rust functions, keyed by function name and number of parameters.
+type Signatures<'a> = HashMap<(&'a str, usize), Vec<(&'a [TypeId], Option<TypeId>)>>;
+
+/// The statically-known type of a value, if any.
+type StaticType = Option<Type>;
+
+/// State of the type checker.
+struct Checker<'a> {
+    /// Signatures of all Rust functions registered with the `Engine`, in the order they are searched.
+    signatures: Signatures<'a>,
+    /// Script-defined functions in the `AST`.
+    functions: &'a [FnDef],
+    /// Variables in the current scope with their known types.
+    vars: Vec<(String, StaticType)>,
+    /// Type annotation of the return value of the function being checked.
+    return_type: Option<&'a Type>,
+    /// Type annotation of the values yielded by the generator being checked.
+    yield_type: Option<&'a Type>,
+}
+
+impl<'a> Checker<'a> {
+    /// Create a new `Checker`.
+    fn new(engine: &'a Engine, functions: &'a [FnDef]) -> Self {
+        let mut signatures = Signatures::new();
+
+        engine
+            .global_module
+            .iter_fn_signatures()
+            .chain(engine.packages.iter_fn_signatures())
+            .for_each(|(name, params, return_type)| {
+                signatures
+                    .entry((name, params.len()))
+                    .or_default()
+                    .push((params, return_type));
+            });
+
+        Self {
+            signatures,
+            functions,
+            vars: Vec::new(),
+            return_type: None,
+            yield_type: None,
+        }
+    }
+
+    /// Find the known type of a variable.
+    fn find_var(&self, name: &str) -> StaticType {
+        self.vars
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, typ)| typ.clone())
+    }
+
+    /// Set the known type of a variable.
+    fn set_var(&mut self, name: &str, typ: StaticType) {
+        if let Some((_, t)) = self.vars.iter_mut().rev().find(|(n, _)| n == name) {
+            *t = typ;
+        }
+    }
+
+    /// Check code that may or may not run (e.g. a branch or a loop body).
+    ///
+    /// Variables assigned a value of a different type afterwards have unknown types.
+    fn branch<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, Box<ParseError>>,
+    ) -> Result<T, Box<ParseError>> {
+        let vars_len = self.vars.len();
+        let before: StaticVec<_> = self.vars.iter().map(|(_, typ)| typ.clone()).collect();
+
+        let result = f(self)?;
+
+        self.vars.truncate(vars_len);
+        self.vars
+            .iter_mut()
+            .zip(before.into_iter())
+            .filter(|((_, typ), before)| *typ != *before)
+            .for_each(|((_, typ), _)| *typ = None);
+
+        Ok(result)
+    }
+
+    /// Check a statement, returning the known type of its value.
+    fn stmt(&mut self, stmt: &'a Stmt) -> Result<StaticType, Box<ParseError>> {
+        match stmt {
+            Stmt::Noop(_) | Stmt::Continue(_) | Stmt::Break(_) | Stmt::Export(_) => Ok(None),
+
+            Stmt::IfThenElse(x) => {
+                self.condition(&x.0)?;
+
+                match &x.2 {
+                    None => {
+                        self.branch(|c| c.stmt(&x.1))?;
+                        Ok(None)
+                    }
+                    Some(else_body) => {
+                        // Check each branch starting from the same known types
+                        let vars = self.vars.clone();
+                        let if_type = self.branch(|c| c.stmt(&x.1))?;
+                        let if_vars = crate::stdlib::mem::replace(&mut self.vars, vars);
+                        let else_type = self.branch(|c| c.stmt(else_body))?;
+
+                        self.vars
+                            .iter_mut()
+                            .zip(if_vars)
+                            .filter(|((_, typ), (_, if_typ))| *typ != *if_typ)
+                            .for_each(|((_, typ), _)| *typ = None);
+
+                        Ok(if if_type == else_type { if_type } else { None })
+                    }
+                }
+            }
+
+            Stmt::While(x) => {
+                self.condition(&x.0)?;
+                self.branch(|c| c.stmt(&x.1))?;
+                Ok(None)
+            }
+
+            Stmt::Do(x) => {
+                self.branch(|c| {
+                    c.stmt(&x.0)?;
+                    c.condition(&x.1)
+                })?;
+                Ok(None)
+            }
+
+            Stmt::Loop(body) => {
+                self.branch(|c| c.stmt(body))?;
+                Ok(None)
+            }
+
+            Stmt::For(x) => {
+                self.expr(&x.1)?;
+                self.branch(|c| {
+                    x.0.for_each_name(&mut |name| c.vars.push((name.into(), None)));
+                    c.stmt(&x.2)
+                })?;
+                Ok(None)
+            }
+
+            Stmt::Let(x) => {
+                let typ = match &x.1 {
+                    Some(expr) => self.expr(expr)?,
+                    None => Some(Type::Unit),
+                };
+                self.vars.push(((x.0).0.clone(), typ));
+                Ok(None)
+            }
+
+            Stmt::LetPattern(x) => {
+                self.expr(&x.1)?;
+                x.0.for_each_name(&mut |name| self.vars.push((name.into(), None)));
+                Ok(None)
+            }
+
+            Stmt::Const(x) => {
+                let typ = self.expr(&x.1)?;
+                self.vars.push(((x.0).0.clone(), typ));
+                Ok(None)
+            }
+
+            Stmt::Block(x) => self.branch(|c| {
+                let mut typ = None;
+
+                for stmt in x.0.iter() {
+                    typ = c.stmt(stmt)?;
+                }
+
+                Ok(typ)
+            }),
+
+            Stmt::Expr(expr) => self.expr(expr),
+
+            Stmt::ReturnWithVal(x) => {
+                let typ = match &x.1 {
+                    Some(expr) => self.expr(expr)?,
+                    None => Some(Type::Unit),
+                };
+
+                if (x.0).0 == ReturnType::Return {
+                    let pos = x.1.as_ref().map_or((x.0).1, Expr::position);
+                    check_type(self.return_type, &typ, pos)?;
+                }
+
+                Ok(None)
+            }
+
+            Stmt::Yield(x) => {
+                let typ = self.expr(&x.0)?;
+                check_type(self.yield_type, &typ, x.0.position())?;
+                Ok(None)
+            }
+
+            Stmt::Import(x) => {
+                self.expr(&x.0)?;
+                self.vars.push(((x.1).0.clone(), None));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Check the condition of an `if` statement or a loop.
+    fn condition(&mut self, expr: &'a Expr) -> Result<(), Box<ParseError>> {
+        let typ = self.expr(expr)?;
+        check_type(Some(&Type::Bool), &typ, expr.position())
+    }
+
+    /// Check an expression, returning its known type.
+    fn expr(&mut self, expr: &'a Expr) -> Result<StaticType, Box<ParseError>> {
+        Ok(match expr {
+            Expr::IntegerConstant(_) => Some(Type::Int),
+            #[cfg(not(feature = "no_float"))]
+            Expr::FloatConstant(_) => Some(Type::Float),
+            Expr::CharConstant(_) => Some(Type::Char),
+            Expr::StringConstant(_) => Some(Type::String),
+            Expr::True(_) | Expr::False(_) => Some(Type::Bool),
+            Expr::Unit(_) => Some(Type::Unit),
+
+            Expr::Variable(x) if x.1.is_none() => self.find_var(&(x.0).0),
+            Expr::Variable(_) | Expr::Property(_) => None,
+
+            Expr::Stmt(x) => self.stmt(&x.0)?,
+
+            Expr::FnCall(x) => {
+                let args = x.3.iter().map(|expr| (None, expr));
+                self.call(&x.0, x.1.is_some(), &x.4, &x.5, args)?
+            }
+
+            Expr::Assignment(x) => {
+                let typ = self.expr(&x.1)?;
+
+                match &x.0 {
+                    Expr::Variable(v) if v.1.is_none() => self.set_var(&(v.0).0, typ),
+                    lhs => {
+                        self.expr(lhs)?;
+                    }
+                }
+
+                Some(Type::Unit)
+            }
+
+            Expr::Dot(x) => {
+                let typ = self.expr(&x.0)?;
+                let typ = if x.3 { None } else { typ };
+                self.dot_rhs(typ, &x.0, &x.1)?
+            }
+
+            Expr::Index(x) => {
+                self.expr(&x.0)?;
+                self.expr(&x.1)?;
+                None
+            }
+
+            #[cfg(not(feature = "no_index"))]
+            Expr::Array(x) => {
+                for item in x.0.iter() {
+                    self.expr(item)?;
+                }
+                Some(Type::Array)
+            }
+            #[cfg(feature = "no_index")]
+            Expr::Array(_) => None,
+
+            #[cfg(not(feature = "no_object"))]
+            Expr::Map(x) => {
+                for (_, value) in x.0.iter() {
+                    self.expr(value)?;
+                }
+                Some(Type::Map)
+            }
+            #[cfg(feature = "no_object")]
+            Expr::Map(_) => None,
+
+            Expr::In(x) => {
+                self.expr(&x.0)?;
+                self.expr(&x.1)?;
+                Some(Type::Bool)
+            }
+
+            Expr::And(x) | Expr::Or(x) => {
+                self.condition(&x.0)?;
+                self.branch(|c| c.condition(&x.1))?;
+                Some(Type::Bool)
+            }
+
+            Expr::Coalesce(x) => {
+                let lhs_type = self.expr(&x.0)?;
+                let rhs_type = self.branch(|c| c.expr(&x.1))?;
+
+                match lhs_type {
+                    Some(Type::Unit) => rhs_type,
+                    Some(_) => lhs_type,
+                    None => None,
+                }
+            }
+
+            #[cfg(not(feature = "no_index"))]
+            Expr::Spread(x) => {
+                self.expr(&x.0)?;
+                None
+            }
+
+            Expr::Cast(x) => {
+                let typ = self.expr(&x.0)?;
+                check_type(Some(&x.1), &typ, x.0.position())?;
+                Some(x.1.clone())
+            }
+        })
+    }
+
+    /// Check the right-hand side of a dot expression, returning its known type.
+    fn dot_rhs(
+        &mut self,
+        obj_type: StaticType,
+        obj: &'a Expr,
+        rhs: &'a Expr,
+    ) -> Result<StaticType, Box<ParseError>> {
+        match rhs {
+            // obj.method(args)
+            Expr::FnCall(x) => {
+                let args = Some((Some(obj_type), obj))
+                    .into_iter()
+                    .chain(x.3.iter().map(|expr| (None, expr)));
+                self.call(&x.0, x.1.is_some(), &x.4, &x.5, args)
+            }
+            // obj.prop
+            Expr::Property(_) => Ok(None),
+            // obj.prop.rhs or obj.method(args).rhs
+            Expr::Dot(x) => {
+                let typ = self.dot_rhs(obj_type, obj, &x.0)?;
+                let typ = if x.3 { None } else { typ };
+                self.dot_rhs(typ, &x.0, &x.1)
+            }
+            // obj.prop[idx]
+            Expr::Index(x) => {
+                self.dot_rhs(obj_type, obj, &x.0)?;
+                self.expr(&x.1)?;
+                Ok(None)
+            }
+            expr => self.expr(expr),
+        }
+    }
+
+    /// Check a function call, returning the known type of its return value.
+    ///
+    /// Each argument comes with its type if it is already known (e.g. the object of a method call).
+    fn call(
+        &mut self,
+        (name, pos): &'a (Cow<'static, str>, Position),
+        is_qualified: bool,
+        def_val: &'a Option<Dynamic>,
+        named_args: &'a [(String, Position)],
+        args: impl Iterator<Item = (Option<StaticType>, &'a Expr)>,
+    ) -> Result<StaticType, Box<ParseError>> {
+        let mut arg_types: StaticVec<StaticType> = StaticVec::new();
+        let mut arg_exprs: StaticVec<&Expr> = StaticVec::new();
+
+        for (typ, expr) in args {
+            arg_types.push(match typ {
+                Some(typ) => typ,
+                None => self.expr(expr)?,
+            });
+            arg_exprs.push(expr);
+        }
+
+        // Comparison operators return a default value instead of failing
+        if let Some(value) = def_val {
+            return Ok(Type::from_type_id(value.type_id()));
+        }
+
+        // Module-qualified calls, named and spread arguments are only resolved at runtime
+        if is_qualified || !named_args.is_empty() || arg_exprs.iter().any(|e| e.is_spread()) {
+            return Ok(None);
+        }
+
+        let name = name.as_ref();
+        let num_args = arg_types.len();
+
+        match name {
+            KEYWORD_TYPE_OF if num_args == 1 => return Ok(Some(Type::String)),
+            KEYWORD_EVAL => return Ok(None),
+            _ => (),
+        }
+
+        // A script-defined function with the exact number of parameters takes precedence
+        if let Some(fn_def) = self
+            .functions
+            .iter()
+            .find(|f| f.name == name && f.params.len() == num_args)
+        {
+            return self.script_call(fn_def, &arg_types, &arg_exprs);
+        }
+
+        // Then Rust functions
+        let signatures = self.signatures.get(&(name, num_args));
+
+        if let Some(signatures) = signatures {
+            let mut matches = signatures.iter().filter(|(params, _)| {
+                params.iter().zip(arg_types.iter()).all(|(param, typ)| {
+                    match typ.as_ref().and_then(Type::type_id) {
+                        Some(id) => id == *param,
+                        None => true,
+                    }
+                })
+            });
+
+            if let Some((_, return_type)) = matches.next() {
+                let all_known = arg_types.iter().all(|typ| match typ {
+                    Some(typ) => typ.type_id().is_some(),
+                    None => false,
+                });
+
+                return Ok(match (print_or_debug(name), all_known) {
+                    (true, _) => Some(Type::Unit),
+                    (false, true) => return_type.and_then(Type::from_type_id),
+                    (false, false) => None,
+                });
+            }
+        }
+
+        // Finally script-defined functions with default values or rest parameters
+        let fn_def = self
+            .functions
+            .iter()
+            .filter(|f| f.name == name && f.accepts_args(num_args))
+            .min_by_key(|f| (f.rest_param.is_some(), f.params.len()));
+
+        match (fn_def, signatures) {
+            (Some(fn_def), _) => self.script_call(fn_def, &arg_types, &arg_exprs),
+            // No Rust function matches the types of the arguments
+            (None, Some(_)) => {
+                let types_list: Vec<_> = arg_types
+                    .iter()
+                    .map(|typ| match typ {
+                        Some(typ) => typ.to_string(),
+                        None => "?".to_string(),
+                    })
+                    .collect();
+
+                Err(ParseErrorType::FnArgTypesMismatch(format!(
+                    "{} ({})",
+                    name,
+                    types_list.join(", ")
+                ))
+                .into_err(*pos))
+            }
+            // The function may be registered later
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Check a call to a script-defined function, returning the known type of its return value.
+    fn script_call(
+        &self,
+        fn_def: &'a FnDef,
+        arg_types: &StaticVec<StaticType>,
+        arg_exprs: &StaticVec<&Expr>,
+    ) -> Result<StaticType, Box<ParseError>> {
+        fn_def
+            .param_types
+            .iter()
+            .zip(arg_types.iter().zip(arg_exprs.iter()))
+            .try_for_each(|(param_type, (typ, expr))| {
+                check_type(param_type.as_ref(), typ, expr.position())
+            })?;
+
+        // Calling a generator returns a generator, whatever type it yields
+        if fn_def.is_generator {
+            Ok(Some(Type::Custom("generator".into())))
+        } else {
+            Ok(fn_def.return_type.clone())
+        }
+    }
+
+    /// Check a script-defined function.
+    fn fn_def(&mut self, fn_def: &'a FnDef) -> Result<(), Box<ParseError>> {
+        self.vars.clear();
+
+        // The return type annotation of a generator is the type of the values it yields
+        if fn_def.is_generator {
+            self.yield_type = fn_def.return_type.as_ref();
+        } else {
+            self.return_type = fn_def.return_type.as_ref();
+        }
+
+        let required = fn_def.required_params();
+
+        for (index, name) in fn_def.params.iter().enumerate() {
+            let param_type = fn_def.param_types[index].as_ref();
+
+            if index >= required {
+                let expr = &fn_def.defaults[index - required];
+                let typ = self.expr(expr)?;
+                check_type(param_type, &typ, expr.position())?;
+            }
+
+            self.vars.push((name.clone(), param_type.cloned()));
+        }
+
+        #[cfg(not(feature = "no_index"))]
+        {
+            if let Some(name) = &fn_def.rest_param {
+                self.vars.push((name.clone(), Some(Type::Array)));
+            }
+        }
+
+        let typ = self.stmt(&fn_def.body)?;
+        check_type(self.return_type, &typ, fn_def.result_position())?;
+
+        self.return_type = None;
+        self.yield_type = None;
+        Ok(())
+    }
+}
+
+/// Is this the name of the `print` or `debug` functions, which always return `()`?
+fn print_or_debug(name: &str) -> bool {
+    name == KEYWORD_PRINT || name == KEYWORD_DEBUG
+}
+
+/// Check a known type against a type annotation, if any.
+fn check_type(
+    annotation: Option<&Type>,
+    typ: &StaticType,
+    pos: Position,
+) -> Result<(), Box<ParseError>> {
+    match (annotation, typ) {
+        (Some(annotation), Some(typ)) if annotation.accepts(typ) == Some(false) => Err(
+            ParseErrorType::MismatchedType(annotation.to_string(), typ.to_string()).into_err(pos),
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Check the types of values in the statements and script-defined functions of a script.
+pub(crate) fn check_types(
+    engine: &Engine,
+    statements: &[Stmt],
+    functions: &[FnDef],
+) -> Result<(), Box<ParseError>> {
+    let mut checker = Checker::new(engine, functions);
+
+    for fn_def in functions {
+        checker.fn_def(fn_def)?;
+    }
+
+    checker.vars.clear();
+
+    for stmt in statements {
+        checker.stmt(stmt)?;
+    }
+
+    Ok(())
+}
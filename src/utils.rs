@@ -297,10 +297,13 @@ impl<T> StaticVec<T> {
             panic!("nothing to pop!");
         }
 
-        let result = if self.is_fixed_storage() {
-            self.extract_from_list(self.len - 1)
+        if self.is_fixed_storage() {
+            let value = self.extract_from_list(self.len - 1);
+            self.len -= 1;
+            value
         } else {
             let value = self.more.pop().unwrap();
+            self.len -= 1;
 
             // Move back to the fixed list
             if self.more.len() == MAX_STATIC_VEC {
@@ -311,11 +314,7 @@ impl<T> StaticVec<T> {
             }
 
             value
-        };
-
-        self.len -= 1;
-
-        result
+        }
     }
     /// Remove a value from this `StaticVec` at a particular position.
     ///
@@ -327,7 +326,7 @@ impl<T> StaticVec<T> {
             panic!("index OOB in StaticVec");
         }
 
-        let result = if self.is_fixed_storage() {
+        if self.is_fixed_storage() {
             let value = self.extract_from_list(index);
 
             // Move all items one slot to the left
@@ -335,10 +334,12 @@ impl<T> StaticVec<T> {
                 let orig_value = self.extract_from_list(x + 1);
                 self.set_into_list(x, orig_value, false);
             }
+            self.len -= 1;
 
             value
         } else {
             let value = self.more.remove(index);
+            self.len -= 1;
 
             // Move back to the fixed list
             if self.more.len() == MAX_STATIC_VEC {
@@ -349,11 +350,7 @@ impl<T> StaticVec<T> {
             }
 
             value
-        };
-
-        self.len -= 1;
-
-        result
+        }
     }
     /// Get the number of items in this `StaticVec`.
     pub fn len(&self) -> usize {
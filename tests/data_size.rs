@@ -0,0 +1,244 @@
+#![cfg(not(feature = "unchecked"))]
+use rhai::{Engine, EvalAltResult, INT};
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_max_string_size() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+    engine.set_max_string_size(10);
+
+    assert_eq!(
+        engine.eval::<String>(r#"let s = "hello"; s + "!""#)?,
+        "hello!"
+    );
+
+    assert!(matches!(
+        *engine
+            .eval::<()>(r#"let s = "x"; loop { s += s; }"#)
+            .expect_err("should error"),
+        EvalAltResult::ErrorDataTooLarge(_, 10, 16, _)
+    ));
+
+    assert!(matches!(
+        *engine
+            .eval::<String>(r#""this string is too long""#)
+            .expect_err("should error"),
+        EvalAltResult::ErrorDataTooLarge(_, 10, 23, _)
+    ));
+
+    #[cfg(not(feature = "no_object"))]
+    assert!(matches!(
+        *engine
+            .eval::<()>(r#"let s = "hello"; s.append(", world!");"#)
+            .expect_err("should error"),
+        EvalAltResult::ErrorDataTooLarge(_, 10, 13, _)
+    ));
+
+    // Sizes are checked before the strings are built
+    #[cfg(not(feature = "no_object"))]
+    {
+        assert!(matches!(
+            *engine
+                .eval::<()>(r#"let s = "x"; s.pad(200000000, 'y');"#)
+                .expect_err("should error"),
+            EvalAltResult::ErrorDataTooLarge(_, 10, 200000000, _)
+        ));
+        assert!(matches!(
+            *engine
+                .eval::<()>(r#"let s = "xxxxx"; s.replace("x", "yyyyyy");"#)
+                .expect_err("should error"),
+            EvalAltResult::ErrorDataTooLarge(_, 10, 30, _)
+        ));
+        assert_eq!(
+            engine.eval::<String>(r#"let s = "x"; s.pad(5, 'y'); s"#)?,
+            "xyyyy"
+        );
+    }
+
+    engine.set_max_string_size(0);
+
+    assert_eq!(
+        engine.eval::<INT>(r#"let s = "x"; for x in range(0, 10) { s += s; } len(s)"#)?,
+        1024
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_index"))]
+fn test_max_array_size() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+    engine.set_max_array_size(10);
+
+    assert_eq!(engine.eval::<INT>("let a = [1, 2, 3]; len(a + [4, 5])")?, 5);
+
+    assert!(matches!(
+        *engine
+            .eval::<()>("[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]")
+            .expect_err("should error"),
+        EvalAltResult::ErrorDataTooLarge(_, 10, 11, _)
+    ));
+
+    #[cfg(not(feature = "no_object"))]
+    assert!(matches!(
+        *engine
+            .eval::<()>("let a = []; loop { a.push(42); }")
+            .expect_err("should error"),
+        EvalAltResult::ErrorDataTooLarge(_, 10, 11, _)
+    ));
+
+    assert!(matches!(
+        *engine
+            .eval::<()>("let a = [1, 2, 3, 4, 5, 6]; a + a")
+            .expect_err("should error"),
+        EvalAltResult::ErrorDataTooLarge(_, 10, 12, _)
+    ));
+
+    // Sizes are checked before the arrays are built
+    #[cfg(not(feature = "no_object"))]
+    assert!(matches!(
+        *engine
+            .eval::<()>("let a = []; a.pad(50000000, 0);")
+            .expect_err("should error"),
+        EvalAltResult::ErrorDataTooLarge(_, 10, 50000000, _)
+    ));
+    #[cfg(not(feature = "no_object"))]
+    assert!(matches!(
+        *engine
+            .eval::<()>("let a = [1, 2, 3, 4, 5, 6]; a.append(a);")
+            .expect_err("should error"),
+        EvalAltResult::ErrorDataTooLarge(_, 10, 12, _)
+    ));
+
+    // Nested arrays are checked as well
+    assert!(matches!(
+        *engine
+            .eval::<()>("let a = [1]; a[0] = [1, 2, 3, 4, 5, 6] + [7, 8, 9, 10, 11];")
+            .expect_err("should error"),
+        EvalAltResult::ErrorDataTooLarge(_, 10, 11, _)
+    ));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_object"))]
+fn test_max_map_size() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+    engine.set_max_map_size(3);
+
+    assert_eq!(
+        engine.eval::<INT>("let m = #{a: 1, b: 2}; m.c = 3; m.c")?,
+        3
+    );
+
+    assert!(matches!(
+        *engine
+            .eval::<()>("#{a: 1, b: 2, c: 3, d: 4}")
+            .expect_err("should error"),
+        EvalAltResult::ErrorDataTooLarge(_, 3, 4, _)
+    ));
+
+    assert!(matches!(
+        *engine
+            .eval::<()>("let m = #{a: 1, b: 2, c: 3}; m.d = 4;")
+            .expect_err("should error"),
+        EvalAltResult::ErrorDataTooLarge(_, 3, 4, _)
+    ));
+
+    assert!(matches!(
+        *engine
+            .eval::<()>("let m = #{a: 1, b: 2}; m.mixin(#{b: 3, c: 4, d: 5});")
+            .expect_err("should error"),
+        EvalAltResult::ErrorDataTooLarge(_, 3, 4, _)
+    ));
+    assert_eq!(
+        engine.eval::<INT>("let m = #{a: 1, b: 2} + #{b: 3, c: 4}; m.b + m.c")?,
+        7
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_index"))]
+fn test_max_data_size() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+    engine.set_max_data_size(1000);
+
+    engine.eval::<()>(r#"let a = ["hello", "world"];"#)?;
+
+    // Doubling a nested array via index assignment
+    assert!(matches!(
+        *engine
+            .eval::<()>("let a = [0, 0]; loop { a[0] = a; a[1] = a; }")
+            .expect_err("should error"),
+        EvalAltResult::ErrorDataTooLarge(_, 1000, _, _)
+    ));
+
+    // Padding is checked before the items are added
+    #[cfg(not(feature = "no_object"))]
+    assert!(matches!(
+        *engine
+            .eval::<()>(r#"let a = []; a.pad(100000000, "hello");"#)
+            .expect_err("should error"),
+        EvalAltResult::ErrorDataTooLarge(_, 1000, _, _)
+    ));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_index"))]
+#[cfg(not(feature = "no_object"))]
+fn test_max_array_size_growing() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+    engine.set_max_array_size(100000);
+    engine.set_max_string_size(100000);
+
+    // Building up values item by item only checks the sizes of the values changed
+    assert_eq!(
+        engine.eval::<INT>("let a = []; for x in range(0, 20000) { a.push(x); } len(a)")?,
+        20000
+    );
+    assert_eq!(
+        engine.eval::<INT>(r#"let s = ""; for x in range(0, 20000) { s.append('x'); } len(s)"#)?,
+        20000
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_index"))]
+#[cfg(not(feature = "no_object"))]
+fn test_max_data_size_growing() -> Result<(), Box<EvalAltResult>> {
+    let script = r#"
+        let a = [];
+        for x in range(0, 20000) { a.push("x"); a[x] = "y"; }
+        for x in range(0, 10000) { a.pop(); }
+        a.len()
+    "#;
+
+    let engine = Engine::new();
+    let now = Instant::now();
+    assert_eq!(engine.eval::<INT>(script)?, 10000);
+    let unlimited = now.elapsed();
+
+    let mut engine = Engine::new();
+    engine.set_max_data_size(100000000);
+    let now = Instant::now();
+    assert_eq!(engine.eval::<INT>(script)?, 10000);
+    let limited = now.elapsed();
+
+    // Changing a large array must not walk the whole array every time
+    assert!(
+        limited < unlimited * 4 + Duration::from_millis(500),
+        "{:?} with a limit on the size of data vs {:?} without",
+        limited,
+        unlimited
+    );
+
+    Ok(())
+}
@@ -0,0 +1,239 @@
+#![cfg(not(feature = "no_function"))]
+use rhai::{Engine, EvalAltResult, ParseErrorType, RegisterFn, Scope, INT};
+
+#[test]
+fn test_default_params() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    assert_eq!(
+        engine.eval::<String>(
+            r#"
+                fn greet(name, greeting = "Hello") { greeting + ", " + name }
+                greet("Bob") + " / " + greet("Alice", "Hi")
+            "#
+        )?,
+        "Hello, Bob / Hi, Alice"
+    );
+
+    // Defaults are evaluated at call time and can refer to previous parameters
+    assert_eq!(
+        engine.eval::<INT>("fn area(w, h = w) { w * h } area(3) + area(3, 4)")?,
+        21
+    );
+
+    // Each call gets a fresh default value
+    #[cfg(not(feature = "no_index"))]
+    #[cfg(not(feature = "no_object"))]
+    assert_eq!(
+        engine.eval::<INT>(
+            r"
+                fn append(x, list = []) { list.push(x); list }
+                append(1);
+                len(append(2))
+            "
+        )?,
+        1
+    );
+
+    // Defaults can call other functions
+    assert_eq!(
+        engine.eval::<INT>("fn base() { 40 } fn add(x = base(), y = 2) { x + y } add()")?,
+        42
+    );
+
+    // A function taking exactly the number of arguments takes precedence
+    assert_eq!(
+        engine.eval::<INT>("fn f(x) { 1 } fn f(x, y = 0) { 2 } f(0) * 10 + f(0, 0)")?,
+        12
+    );
+
+    #[cfg(not(feature = "no_object"))]
+    assert_eq!(
+        engine.eval::<INT>("fn add(x, n = 2) { x + n } let x = 40; x.add()")?,
+        42
+    );
+
+    assert!(matches!(
+        *engine
+            .eval::<INT>("fn add(x, y = 2) { x + y } add()")
+            .expect_err("should error"),
+        EvalAltResult::ErrorFunctionNotFound(_, _)
+    ));
+
+    assert!(matches!(
+        engine
+            .compile("fn add(x = 1, y) { x + y }")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::FnMissingParamDefault(f, p) if f == "add" && p == "y"
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_default_params_native_precedence() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+
+    engine.register_fn("calc", |x: INT| x * 10);
+
+    // Native functions matching exactly take precedence over default values
+    assert_eq!(
+        engine.eval::<INT>("fn calc(x, y = 1) { x + y } calc(4) + calc(4, 2)")?,
+        46
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_named_args() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    let script = r#"
+        fn greet(name, greeting = "Hello", punctuation = "!") {
+            greeting + ", " + name + punctuation
+        }
+    "#;
+
+    assert_eq!(
+        engine.eval::<String>(&format!("{} greet(name: \"Bob\")", script))?,
+        "Hello, Bob!"
+    );
+    assert_eq!(
+        engine.eval::<String>(&format!("{} greet(\"Bob\", punctuation: \"?\")", script))?,
+        "Hello, Bob?"
+    );
+    assert_eq!(
+        engine.eval::<String>(&format!(
+            "{} greet(punctuation: \".\", name: \"Bob\", greeting: \"Bye\")",
+            script
+        ))?,
+        "Bye, Bob."
+    );
+
+    // A variable is still passed positionally when not followed by a colon
+    assert_eq!(
+        engine.eval::<String>(&format!(
+            "{} let name = \"Alice\"; greet(name, greeting: \"Hi\")",
+            script
+        ))?,
+        "Hi, Alice!"
+    );
+
+    assert!(matches!(
+        *engine
+            .eval::<String>(&format!("{} greet(nme: \"Bob\")", script))
+            .expect_err("should error"),
+        EvalAltResult::ErrorNamedArgument(_, _)
+    ));
+    assert!(matches!(
+        *engine
+            .eval::<String>(&format!("{} greet(\"Bob\", name: \"Bob\")", script))
+            .expect_err("should error"),
+        EvalAltResult::ErrorNamedArgument(_, _)
+    ));
+    assert!(matches!(
+        *engine
+            .eval::<String>(&format!("{} greet(greeting: \"Hi\")", script))
+            .expect_err("should error"),
+        EvalAltResult::ErrorNamedArgument(_, _)
+    ));
+    assert!(matches!(
+        *engine.eval::<INT>("abs(x: 1)").expect_err("should error"),
+        EvalAltResult::ErrorFunctionNotFound(_, _)
+    ));
+
+    assert!(matches!(
+        engine
+            .compile("foo(x: 1, 2)")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::MalformedCallExpr(_)
+    ));
+    assert!(matches!(
+        engine
+            .compile("foo(x: 1, x: 2)")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::MalformedCallExpr(_)
+    ));
+
+    #[cfg(not(feature = "no_object"))]
+    assert!(matches!(
+        engine
+            .compile("let x = 1; x.foo(y: 2)")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::MalformedCallExpr(_)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_default_params_call_fn() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+    let mut scope = Scope::new();
+
+    let ast = engine.compile("fn scale(x, factor = 2) { x * factor }")?;
+
+    let r: INT = engine.call_fn(&mut scope, &ast, "scale", (21 as INT,))?;
+    assert_eq!(r, 42);
+
+    let r: INT = engine.call_fn(&mut scope, &ast, "scale", (21 as INT, 3 as INT))?;
+    assert_eq!(r, 63);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_module"))]
+fn test_default_params_module() -> Result<(), Box<EvalAltResult>> {
+    use rhai::Module;
+
+    let engine = Engine::new();
+
+    let ast = engine.compile(
+        r#"
+            fn greet(name, greeting = "Hello") { greeting + ", " + name }
+        "#,
+    )?;
+    let module = Module::eval_ast_as_new(Scope::new(), &ast, &engine)?;
+
+    let mut scope = Scope::new();
+    scope.push_module("m", module);
+
+    assert_eq!(
+        engine.eval_with_scope::<String>(&mut scope, r#"m::greet("Bob")"#)?,
+        "Hello, Bob"
+    );
+    assert_eq!(
+        engine.eval_with_scope::<String>(&mut scope, r#"m::greet(greeting: "Hi", name: "Bob")"#)?,
+        "Hi, Bob"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_default_params_serialize() -> Result<(), Box<EvalAltResult>> {
+    use rhai::AST;
+
+    let engine = Engine::new();
+
+    let ast = engine.compile(
+        r"
+            fn add(x, y = x * 2, z = 1) { x + y + z }
+            add(1) + add(1, z: 10)
+        ",
+    )?;
+
+    let bytes = ast.to_bytes();
+    let ast2 = AST::from_bytes(&bytes)?;
+
+    assert_eq!(engine.eval_ast::<INT>(&ast2)?, 17);
+    assert_eq!(ast2.to_bytes(), bytes);
+
+    Ok(())
+}
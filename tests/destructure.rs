@@ -0,0 +1,191 @@
+#![cfg(any(not(feature = "no_index"), not(feature = "no_object")))]
+use rhai::{Engine, EvalAltResult, ParseErrorType, INT};
+
+#[test]
+#[cfg(not(feature = "no_index"))]
+fn test_destructure_array() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    assert_eq!(
+        engine.eval::<INT>("let [a, b, c] = [1, 2, 3]; a * 100 + b * 10 + c")?,
+        123
+    );
+    assert_eq!(
+        engine.eval::<INT>("let [a, b, ..rest] = [1, 2, 3, 4, 5]; a + b + len(rest)")?,
+        6
+    );
+    assert_eq!(engine.eval::<INT>("let [..rest] = [1, 2, 3]; rest[2]")?, 3);
+    assert_eq!(
+        engine.eval::<INT>("let [a, [b, c]] = [1, [2, 3]]; a + b + c")?,
+        6
+    );
+
+    // Missing items bind to ()
+    assert!(engine.eval::<()>("let [a, b] = [1]; b").is_ok());
+    assert_eq!(
+        engine.eval::<INT>("let [a, b, ..rest] = [1]; len(rest)")?,
+        0
+    );
+
+    // Nested patterns must match
+    assert!(matches!(
+        *engine
+            .eval::<()>("let [a, [b, c]] = [1]; b")
+            .expect_err("should error"),
+        EvalAltResult::ErrorPatternMismatch(_, _, _)
+    ));
+    assert!(matches!(
+        *engine
+            .eval::<()>("let [a, b] = 42;")
+            .expect_err("should error"),
+        EvalAltResult::ErrorPatternMismatch(_, _, _)
+    ));
+
+    assert!(matches!(
+        engine
+            .compile("let [a, ..rest, b] = [1, 2, 3];")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::MissingToken(_, _)
+    ));
+    assert!(matches!(
+        engine
+            .compile("let [a, b];")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::MissingToken(_, _)
+    ));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_object"))]
+fn test_destructure_map() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    assert_eq!(
+        engine.eval::<String>(
+            r#"
+                let #{ name, age: years } = #{ name: "Bob", age: 42 };
+                name + years
+            "#
+        )?,
+        "Bob42"
+    );
+    assert_eq!(
+        engine
+            .eval::<INT>(r#"let #{ "a b": x, c: #{ d } } = #{ "a b": 1, c: #{ d: 2 } }; x + d"#)?,
+        3
+    );
+
+    // Missing properties bind to ()
+    assert!(engine.eval::<()>("let #{ a, b } = #{ a: 1 }; b").is_ok());
+
+    assert!(matches!(
+        *engine
+            .eval::<()>("let #{ a: #{ b } } = #{ x: 1 }; b")
+            .expect_err("should error"),
+        EvalAltResult::ErrorPatternMismatch(_, _, _)
+    ));
+
+    assert!(matches!(
+        engine
+            .compile(r#"let #{ "a b" } = #{};"#)
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::MissingToken(_, _)
+    ));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_index"))]
+fn test_destructure_for() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    assert_eq!(
+        engine.eval::<INT>(
+            r"
+                let sum = 0;
+                let pairs = [[1, 2], [3, 4], [5, 6]];
+
+                for [k, v] in pairs {
+                    if k == 3 { continue; }
+                    sum += k * v;
+                }
+
+                let after = 1000;
+                sum + after
+            "
+        )?,
+        1032
+    );
+
+    #[cfg(not(feature = "no_object"))]
+    assert_eq!(
+        engine.eval::<INT>(
+            r"
+                let total = 0;
+                for #{ x, y: [a, b] } in [#{ x: 1, y: [2, 3] }, #{ x: 4, y: [5, 6] }] {
+                    total += x * a + b;
+                }
+                total
+            "
+        )?,
+        31
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_index"))]
+#[cfg(not(feature = "no_function"))]
+fn test_destructure_in_function() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    assert_eq!(
+        engine.eval::<INT>(
+            r"
+                fn swap(pair) {
+                    let [a, b] = pair;
+                    let c = 10;
+                    [b * c, a]
+                }
+
+                let [x, y] = swap([1, 2]);
+                x + y
+            "
+        )?,
+        21
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_index"))]
+#[cfg(not(feature = "no_object"))]
+fn test_destructure_serialize() -> Result<(), Box<EvalAltResult>> {
+    use rhai::AST;
+
+    let engine = Engine::new();
+
+    let ast = engine.compile(
+        r"
+            let #{ a, b: [c, ..d] } = #{ a: 1, b: [2, 3, 4] };
+            for [x, y] in [[a, c]] { d.push(x + y); }
+            d.len()
+        ",
+    )?;
+
+    let bytes = ast.to_bytes();
+    let ast2 = AST::from_bytes(&bytes)?;
+
+    assert_eq!(engine.eval_ast::<INT>(&ast2)?, 3);
+    assert_eq!(ast2.to_bytes(), bytes);
+
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use rhai::{Engine, EvalAltResult, ParseErrorType, AST, INT};
+
+#[test]
+fn test_do() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    assert_eq!(
+        engine.eval::<INT>(
+            r"
+                let x = 0;
+
+                do {
+                    x = x + 1;
+                    if x > 5 { break; }
+                    if x > 3 { continue; }
+                    x = x + 3;
+                } while x < 10;
+
+                x
+        ",
+        )?,
+        6
+    );
+
+    assert_eq!(
+        engine.eval::<INT>(
+            r"
+                let x = 0;
+                do { x += 1; } until x >= 10;
+                x
+        ",
+        )?,
+        10
+    );
+
+    // The body always runs at least once
+    assert_eq!(
+        engine.eval::<INT>("let x = 0; do { x += 1; } while false; x")?,
+        1
+    );
+    assert_eq!(
+        engine.eval::<INT>("let x = 0; do { x += 1; } until true; x")?,
+        1
+    );
+
+    // 'continue' goes on to check the guard
+    assert_eq!(
+        engine.eval::<INT>("let x = 0; do { x += 1; continue; } while x < 3; x")?,
+        3
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_do_errors() {
+    let engine = Engine::new();
+
+    assert!(matches!(
+        *engine
+            .compile("do { 42; } x")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::MissingToken(_, _)
+    ));
+
+    assert!(matches!(
+        *engine
+            .eval::<()>("do { 42; } while 1;")
+            .expect_err("should error"),
+        EvalAltResult::ErrorLogicGuard(_)
+    ));
+}
+
+#[test]
+fn test_do_serialize() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    let ast = engine.compile("let x = 0; do { x += 2; } until x > 5; x")?;
+    let ast = AST::from_bytes(&ast.to_bytes())?;
+
+    assert_eq!(engine.eval_ast::<INT>(&ast)?, 6);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_optimize"))]
+fn test_do_optimizer() -> Result<(), Box<EvalAltResult>> {
+    use rhai::OptimizationLevel;
+
+    let mut engine = Engine::new();
+
+    for level in &[
+        OptimizationLevel::None,
+        OptimizationLevel::Simple,
+        OptimizationLevel::Full,
+    ] {
+        engine.set_optimization_level(*level);
+
+        assert_eq!(
+            engine.eval::<INT>("let x = 0; do { x += 1; if x > 4 { break; } } while true; x")?,
+            5
+        );
+        assert_eq!(
+            engine.eval::<INT>("let x = 0; do { x += 1; if x > 4 { break; } } until false; x")?,
+            5
+        );
+        assert_eq!(
+            engine.eval::<INT>("let x = 0; do { break; } while x / 0 == 1; x")?,
+            0
+        );
+    }
+
+    Ok(())
+}
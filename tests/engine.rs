@@ -0,0 +1,53 @@
+use rhai::{Engine, EvalAltResult, RegisterFn, INT};
+use std::sync::{Arc, RwLock};
+
+#[test]
+fn test_engine_clone() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+    engine.register_fn("add", |x: INT, y: INT| x + y);
+
+    let mut engine2 = engine.clone();
+
+    // Registered functions are shared with the clone
+    assert_eq!(engine2.eval::<INT>("add(40, 2)")?, 42);
+
+    // Registering into the clone does not affect the original
+    engine2.register_fn("sub", |x: INT, y: INT| x - y);
+    assert_eq!(engine2.eval::<INT>("sub(44, 2)")?, 42);
+    assert!(engine.eval::<INT>("sub(44, 2)").is_err());
+
+    // Each clone has its own callbacks
+    let logbook = Arc::new(RwLock::new(Vec::<String>::new()));
+
+    let log = logbook.clone();
+    engine.on_print(move |s| log.write().unwrap().push(format!("1: {}", s)));
+
+    let log = logbook.clone();
+    engine2.on_print(move |s| log.write().unwrap().push(format!("2: {}", s)));
+
+    engine.eval::<()>("print(add(1, 2))")?;
+    engine2.eval::<()>("print(sub(1, 2))")?;
+
+    assert_eq!(*logbook.read().unwrap(), vec!["1: 3", "2: -1"]);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "unchecked"))]
+fn test_engine_clone_limits() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    let mut engine2 = engine.clone();
+    engine2.set_max_operations(100);
+
+    let script = "let x = 0; while x < 1000 { x += 1; } x";
+
+    assert_eq!(engine.eval::<INT>(script)?, 1000);
+    assert!(matches!(
+        *engine2.eval::<INT>(script).expect_err("should error"),
+        EvalAltResult::ErrorTooManyOperations(_)
+    ));
+
+    Ok(())
+}
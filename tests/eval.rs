@@ -1,4 +1,4 @@
-use rhai::{Engine, EvalAltResult, Scope, INT};
+use rhai::{Engine, EvalAltResult, EvalMode, Position, Scope, INT};
 
 #[test]
 fn test_eval() -> Result<(), Box<EvalAltResult>> {
@@ -78,3 +78,101 @@ fn test_eval_override() -> Result<(), Box<EvalAltResult>> {
 
     Ok(())
 }
+
+#[test]
+fn test_eval_parse_error() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    let err = engine
+        .eval::<INT>("let x = 1;\neval(\"40 +\\n+\")")
+        .expect_err("should error");
+
+    // The error is reported at the call site, wrapping the position inside the evaluated string
+    match *err {
+        EvalAltResult::ErrorInFunctionCall(name, inner, pos) => {
+            assert_eq!(name, "eval");
+            assert_eq!(pos, Position::new(2, 6));
+
+            match *inner {
+                EvalAltResult::ErrorParsing(err) => {
+                    assert_eq!(err.position(), Position::new(2, 2));
+                }
+                err => panic!("wrong inner error: {}", err),
+            }
+        }
+        err => panic!("wrong error: {}", err),
+    }
+
+    #[cfg(not(feature = "no_function"))]
+    assert!(matches!(
+        *engine.eval::<()>(r#"eval("fn foo() { 42 }")"#).expect_err("should error"),
+        EvalAltResult::ErrorInFunctionCall(_, inner, _)
+            if matches!(&*inner, EvalAltResult::ErrorParsing(err) if *err.error_type() == rhai::ParseErrorType::WrongFnDefinition)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_eval_isolated() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+    engine.set_eval_mode(EvalMode::Isolated);
+
+    let mut scope = Scope::new();
+
+    assert_eq!(
+        engine.eval_with_scope::<INT>(
+            &mut scope,
+            r#"
+                let x = 40;
+                eval("let y = 2; y") + x
+    "#
+        )?,
+        42
+    );
+
+    // Variables defined by eval do not leak into the caller's scope
+    assert!(!scope.contains("y"));
+    assert_eq!(scope.len(), 1);
+
+    // The caller's variables are not visible
+    assert!(matches!(
+        *engine
+            .eval::<INT>(r#"let x = 40; eval("x = 0; x")"#)
+            .expect_err("should error"),
+        EvalAltResult::ErrorVariableNotFound(_, _)
+    ));
+
+    // Script-defined functions are not visible
+    #[cfg(not(feature = "no_function"))]
+    assert!(matches!(
+        *engine
+            .eval::<INT>(r#"fn foo() { 42 } eval("foo()")"#)
+            .expect_err("should error"),
+        EvalAltResult::ErrorFunctionNotFound(_, _)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_eval_disabled() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+    engine.set_eval_mode(EvalMode::Disabled);
+
+    assert!(matches!(
+        *engine
+            .eval::<INT>(r#"eval("40 + 2")"#)
+            .expect_err("should error"),
+        EvalAltResult::ErrorFunctionNotFound(_, _)
+    ));
+
+    // A script-defined eval can still be called
+    #[cfg(not(feature = "no_function"))]
+    assert_eq!(
+        engine.eval::<String>(r#"fn eval(x) { x } eval("40 + 2")"#)?,
+        "40 + 2"
+    );
+
+    Ok(())
+}
@@ -55,3 +55,65 @@ fn test_for_object() -> Result<(), Box<EvalAltResult>> {
 
     Ok(())
 }
+
+#[cfg(not(feature = "no_object"))]
+#[test]
+fn test_for_object_pairs() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    let script = r#"
+        let sum = 0;
+        let keys = "";
+        let map = #{a: 1, b: 2, c: 3};
+
+        for (key, value) in map {
+            if key == "b" { continue; }
+            keys += key;
+            sum += value;
+        }
+
+        let after = 100;
+        len(keys) + sum + after
+    "#;
+
+    assert_eq!(engine.eval::<INT>(script)?, 106);
+
+    let script = r#"
+        let keys = "";
+        for key in #{x: 1, y: 2} { keys += key; }
+        len(keys)
+    "#;
+
+    assert_eq!(engine.eval::<INT>(script)?, 2);
+
+    assert!(matches!(
+        *engine
+            .eval::<()>("for (key, value) in 42 {}")
+            .expect_err("should error"),
+        EvalAltResult::ErrorPatternMismatch(_, _, _)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_for_string() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    let script = r#"
+        let s = "héllo";
+        let count = 0;
+        let last = ' ';
+
+        for ch in s {
+            count += 1;
+            last = ch;
+        }
+
+        last == 'o' && count == 5
+    "#;
+
+    assert!(engine.eval::<bool>(script)?);
+
+    Ok(())
+}
@@ -1,5 +1,5 @@
 #![cfg(not(feature = "no_function"))]
-use rhai::{Engine, EvalAltResult, INT};
+use rhai::{Engine, EvalAltResult, RegisterFn, INT};
 
 #[test]
 fn test_functions() -> Result<(), Box<EvalAltResult>> {
@@ -23,3 +23,87 @@ fn test_functions() -> Result<(), Box<EvalAltResult>> {
 
     Ok(())
 }
+
+#[test]
+#[cfg(not(feature = "no_index"))]
+fn test_functions_overloaded_in_loop() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+
+    engine.register_fn("describe", |x: INT| format!("int {}", x));
+    engine.register_fn("describe", |x: bool| format!("bool {}", x));
+
+    assert_eq!(
+        engine.eval::<String>(
+            r#"
+                let result = "";
+                let values = [1, true, 2, false];
+
+                for v in values {
+                    result += describe(v) + ";";
+                }
+
+                result
+            "#
+        )?,
+        "int 1;bool true;int 2;bool false;"
+    );
+
+    assert_eq!(
+        engine.eval::<String>(
+            r#"
+                fn describe(x) { "script " + x }
+
+                let result = "";
+                for i in range(0, 2) {
+                    result += describe(i) + ";";
+                }
+                result
+            "#
+        )?,
+        "script 0;script 1;"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_functions_retain_reachable() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    let lib = engine.compile(
+        r"
+            fn foo(x) { bar(x) + 1 }
+            fn bar(x) { x * 2 }
+            fn bar() { 0 }
+            fn baz(x) { x }
+            fn method(x) { x + 1 }
+            fn entry() { 42 }
+        ",
+    )?;
+
+    let call = |ast: &rhai::AST, script: &str| {
+        engine.eval_ast::<INT>(&ast.merge(&engine.compile(script).unwrap()))
+    };
+
+    #[cfg(not(feature = "no_object"))]
+    let script = "let x = 1; foo(x) + x.method()";
+    #[cfg(feature = "no_object")]
+    let script = "let x = 1; foo(x) + method(x)";
+
+    let mut ast = lib.merge(&engine.compile(script)?);
+    ast.retain_reachable_functions(&["entry"]);
+
+    assert_eq!(engine.eval_ast::<INT>(&ast)?, 5);
+    assert_eq!(call(&ast, "entry()")?, 42);
+    assert!(call(&ast, "bar()").is_err());
+    assert!(call(&ast, "baz(1)").is_err());
+
+    // Functions called within 'eval' cannot be tracked
+    let mut ast = lib.merge(&engine.compile(r#"eval("baz(42)")"#)?);
+    ast.retain_reachable_functions(&[]);
+
+    assert_eq!(engine.eval_ast::<INT>(&ast)?, 42);
+    assert_eq!(call(&ast, "bar()")?, 0);
+
+    Ok(())
+}
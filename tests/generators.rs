@@ -0,0 +1,308 @@
+#![cfg(not(feature = "no_function"))]
+use rhai::{Engine, EvalAltResult, Generator, ParseErrorType, Scope, INT};
+use std::sync::{Arc, RwLock};
+
+#[test]
+fn test_generators() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    assert_eq!(
+        engine.eval::<INT>(
+            r"
+                fn numbers() { yield 1; yield 2; yield 3; }
+
+                let sum = 0;
+                for x in numbers() { sum += x; }
+                sum
+            "
+        )?,
+        6
+    );
+
+    assert_eq!(
+        engine.eval::<INT>(
+            r"
+                fn count_to(n) {
+                    let i = 1;
+                    while i <= n {
+                        if i % 2 == 0 { yield i * 10; } else { yield i; }
+                        i += 1;
+                    }
+                }
+
+                let sum = 0;
+                for x in count_to(4) { sum += x; }
+                sum
+            "
+        )?,
+        64
+    );
+
+    // Generators can be stored and iterated again, running from the beginning each time
+    assert_eq!(
+        engine.eval::<INT>(
+            r"
+                fn numbers(n) { let i = 0; do { yield i; i += 1; } while i < n; }
+
+                let gen = numbers(3);
+                let sum = 0;
+                for x in gen { sum += x; }
+                for x in gen { sum += x * 10; }
+                sum
+            "
+        )?,
+        33
+    );
+
+    assert_eq!(
+        engine.eval::<String>("fn numbers() { yield 1; } type_of(numbers())")?,
+        "generator"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_generators_lazy() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+
+    let logbook = Arc::new(RwLock::new(Vec::<String>::new()));
+    let log = logbook.clone();
+    engine.on_print(move |s| log.write().unwrap().push(s.to_string()));
+
+    // A generator runs only as far as it is iterated
+    assert_eq!(
+        engine.eval::<INT>(
+            r#"
+                fn naturals() {
+                    let i = 0;
+                    loop {
+                        print("gen " + i);
+                        yield i;
+                        i += 1;
+                    }
+                }
+
+                let gen = naturals();
+                print("start");
+
+                let sum = 0;
+                for x in gen {
+                    print("loop " + x);
+                    if x >= 2 { break; }
+                    sum += x;
+                }
+                sum
+            "#
+        )?,
+        1
+    );
+
+    assert_eq!(
+        *logbook.read().unwrap(),
+        ["start", "gen 0", "loop 0", "gen 1", "loop 1", "gen 2", "loop 2"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_generators_nested() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    assert_eq!(
+        engine.eval::<INT>(
+            r"
+                fn range_gen(a, b) { for i in range(a, b) { yield i; } }
+                fn squares(n) { for x in range_gen(0, n) { if x == 3 { continue; } yield x * x; } }
+
+                let sum = 0;
+                for x in squares(5) { sum += x; }
+                sum
+            "
+        )?,
+        21
+    );
+
+    // Variables declared in the body keep their values between yields
+    assert_eq!(
+        engine.eval::<INT>(
+            r"
+                fn fibonacci() {
+                    let a = 0;
+                    let b = 1;
+                    loop {
+                        yield a;
+                        let c = a + b;
+                        a = b;
+                        b = c;
+                    }
+                }
+
+                let result = 0;
+                for x in fibonacci() {
+                    if x > 50 { result = x; break; }
+                }
+                result
+            "
+        )?,
+        55
+    );
+
+    #[cfg(not(feature = "no_index"))]
+    assert_eq!(
+        engine.eval::<INT>(
+            r"
+                fn pairs(list) { for x in list { for y in list { yield [x, y]; } } }
+
+                let count = 0;
+                for [x, y] in pairs([1, 2, 3]) { count += x * y; }
+                count
+            "
+        )?,
+        36
+    );
+
+    #[cfg(not(feature = "no_object"))]
+    assert_eq!(
+        engine.eval::<INT>(
+            r"
+                fn values(map) { for (key, value) in map { yield value; } }
+
+                let sum = 0;
+                for x in values(#{ a: 1, b: 2, c: 3 }) { sum += x; }
+                sum
+            "
+        )?,
+        6
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_generators_return() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    // A return statement ends the generator
+    assert_eq!(
+        engine.eval::<INT>(
+            r"
+                fn numbers() { yield 1; yield 2; return 42; yield 3; }
+
+                let sum = 0;
+                for x in numbers() { sum += x; }
+                sum
+            "
+        )?,
+        3
+    );
+
+    // Errors are reported as coming from the generator function
+    assert!(matches!(
+        *engine
+            .eval::<INT>(r#"fn numbers() { yield 1; throw "oops"; } for x in numbers() { } 0"#)
+            .expect_err("should error"),
+        EvalAltResult::ErrorInFunctionCall(f, err, _)
+            if f == "numbers" && matches!(*err, EvalAltResult::ErrorRuntime(_, _))
+    ));
+
+    // The return type annotation is the type of the values yielded
+    assert!(matches!(
+        *engine
+            .eval::<INT>(r#"fn numbers() -> int { yield 1; yield "two"; } for x in numbers() { } 0"#)
+            .expect_err("should error"),
+        EvalAltResult::ErrorInFunctionCall(f, err, _)
+            if f == "numbers" && matches!(*err, EvalAltResult::ErrorMismatchDataType(_, _, _))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_generators_iter() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    let ast = engine.compile(
+        r#"
+            fn double(x) { x * 2 }
+            fn numbers(n) { for x in range(0, n) { yield double(x); } throw "done"; }
+            fn make() { numbers(3) }
+        "#,
+    )?;
+
+    // A generator returned to Rust can be iterated, calling the functions in the AST
+    let generator: Generator = engine.call_fn(&mut Scope::new(), &ast, "make", ())?;
+    let mut iter = engine.iter_generator(&ast, generator.clone());
+
+    assert_eq!(iter.next().unwrap()?.cast::<INT>(), 0);
+    assert_eq!(iter.next().unwrap()?.cast::<INT>(), 2);
+    assert_eq!(iter.next().unwrap()?.cast::<INT>(), 4);
+
+    // Errors end the iteration
+    assert!(matches!(
+        *iter.next().unwrap().expect_err("should error"),
+        EvalAltResult::ErrorInFunctionCall(f, err, _)
+            if f == "numbers" && matches!(*err, EvalAltResult::ErrorRuntime(_, _))
+    ));
+    assert!(iter.next().is_none());
+
+    // Iterating a generator again starts it again from the beginning
+    assert_eq!(
+        engine
+            .iter_generator(&ast, generator)
+            .take(2)
+            .map(|v| v.map(|v| v.cast::<INT>()))
+            .collect::<Result<Vec<_>, _>>()?,
+        [0, 2]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_generators_parse() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    assert!(matches!(
+        engine
+            .compile("yield 1;")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::WrongYield
+    ));
+    assert!(matches!(
+        engine
+            .compile("fn f() { let x = { yield 1; 2 }; }")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::WrongYield
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_generators_serialize() -> Result<(), Box<EvalAltResult>> {
+    use rhai::AST;
+
+    let engine = Engine::new();
+
+    let ast = engine.compile(
+        r"
+            fn numbers(n) { let i = 0; while i < n { yield i; i += 1; } }
+
+            let sum = 0;
+            for x in numbers(5) { sum += x; }
+            sum
+        ",
+    )?;
+
+    let bytes = ast.to_bytes();
+    let ast2 = AST::from_bytes(&bytes)?;
+
+    assert_eq!(engine.eval_ast::<INT>(&ast2)?, 10);
+    assert_eq!(ast2.to_bytes(), bytes);
+
+    Ok(())
+}
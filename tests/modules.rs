@@ -224,3 +224,56 @@ fn test_module_from_ast() -> Result<(), Box<EvalAltResult>> {
 
     Ok(())
 }
+
+#[test]
+#[cfg(not(feature = "unchecked"))]
+#[cfg(not(feature = "no_std"))]
+fn test_module_time_limit() -> Result<(), Box<EvalAltResult>> {
+    use rhai::{ModuleResolver, Position, RegisterFn, Usage, AST};
+    use std::{thread, time::Duration};
+
+    // Evaluates the script of the module every time it is imported
+    struct ScriptResolver(AST);
+
+    impl ModuleResolver for ScriptResolver {
+        fn resolve(
+            &self,
+            engine: &Engine,
+            scope: Scope,
+            path: &str,
+            pos: Position,
+        ) -> Result<Module, Box<EvalAltResult>> {
+            self.resolve_with_usage(engine, scope, path, pos, &mut Default::default())
+        }
+
+        fn resolve_with_usage(
+            &self,
+            engine: &Engine,
+            scope: Scope,
+            _: &str,
+            _: Position,
+            usage: &mut Usage,
+        ) -> Result<Module, Box<EvalAltResult>> {
+            Module::eval_ast_as_new_with_usage(scope, &self.0, engine, usage)
+        }
+    }
+
+    let mut engine = Engine::new();
+    engine.set_max_duration(Duration::from_millis(100));
+    engine.register_fn("slow", || thread::sleep(Duration::from_millis(25)));
+
+    let ast = engine.compile("slow(); slow(); slow(); let x = 42; export x;")?;
+    engine.set_module_resolver(Some(ScriptResolver(ast)));
+
+    engine.eval::<()>(r#"import "slow" as m;"#)?;
+
+    // The imported module runs within the time limit of the script importing it
+    assert!(matches!(
+        *engine
+            .eval::<()>(r#"slow(); slow(); slow(); import "slow" as m;"#)
+            .expect_err("should error"),
+        EvalAltResult::ErrorTimeout(_)
+    ));
+
+    Ok(())
+}
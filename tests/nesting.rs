@@ -0,0 +1,152 @@
+#![cfg(not(feature = "unchecked"))]
+use rhai::{Engine, EvalAltResult, ParseErrorType, INT};
+
+#[test]
+fn test_max_expr_depth() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+    engine.set_max_expr_depth(10);
+
+    assert_eq!(engine.eval::<INT>("((((1 + 2))))")?, 3);
+
+    let too_deep = format!("{}1{}", "(".repeat(20), ")".repeat(20));
+    assert_eq!(
+        *engine
+            .compile(&too_deep)
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::ExprTooDeep
+    );
+
+    let too_deep = format!("{}1", "-".repeat(20));
+    assert_eq!(
+        *engine
+            .compile(&too_deep)
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::ExprTooDeep
+    );
+
+    // Flat chains of operators and properties do not nest
+    assert_eq!(engine.eval::<INT>(&vec!["1"; 200].join(" + "))?, 200);
+
+    #[cfg(not(feature = "no_object"))]
+    engine.compile(&format!("x{}", ".b".repeat(200)))?;
+
+    // The limit applies inside function bodies too
+    #[cfg(not(feature = "no_function"))]
+    assert_eq!(
+        *engine
+            .compile(&format!("fn foo() {{ {} }}", "-".repeat(20) + "1"))
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::ExprTooDeep
+    );
+
+    // A huge nesting depth no longer crashes the parser
+    engine.set_max_expr_depth(100);
+    let too_deep = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+    assert_eq!(
+        *engine
+            .compile(&too_deep)
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::ExprTooDeep
+    );
+
+    engine.set_max_expr_depth(0);
+    assert_eq!(engine.eval::<INT>(&vec!["1"; 50].join(" + "))?, 50);
+
+    Ok(())
+}
+
+#[test]
+fn test_max_stmt_depth() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+    engine.set_max_stmt_depth(5);
+
+    assert_eq!(engine.eval::<INT>("{ { { 42 } } }")?, 42);
+
+    let too_deep = format!("{}42{}", "{ ".repeat(10), " }".repeat(10));
+    assert_eq!(
+        *engine
+            .compile(&too_deep)
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::StmtTooDeep
+    );
+
+    // An `else if` ladder does not nest
+    let script = format!(
+        "let x = 0; {} {{ x = 1; }} x",
+        "if x > 0 { x = 2; } else ".repeat(100)
+    );
+    assert_eq!(engine.eval::<INT>(&script)?, 1);
+
+    let too_deep = format!(
+        "let x = 0; {} x = 1; {}",
+        "if x == 0 { ".repeat(10),
+        "}".repeat(10)
+    );
+    assert_eq!(
+        *engine
+            .compile(&too_deep)
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::StmtTooDeep
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_default_nesting_depths() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    // Long flat chains are not limited by the nesting depth
+    let script = (1..=200)
+        .map(|n| n.to_string())
+        .collect::<Vec<_>>()
+        .join(" + ");
+    assert_eq!(engine.eval::<INT>(&script)?, 20100);
+
+    let script = vec!["\"x\""; 200].join(" + ");
+    assert_eq!(engine.eval::<String>(&script)?, "x".repeat(200));
+
+    #[cfg(not(feature = "no_object"))]
+    {
+        let script = format!(
+            "let x = 42; let i = 0; while i < 200 {{ x = #{{ b: x }}; i += 1; }} x{}",
+            ".b".repeat(200)
+        );
+        assert_eq!(engine.eval::<INT>(&script)?, 42);
+    }
+
+    let script = format!(
+        "let x = 40; {} {{ 0 }}",
+        (1..=40)
+            .map(|n| format!("if x == {} {{ {} }} else ", n, n))
+            .collect::<String>()
+    );
+    assert_eq!(engine.eval::<INT>(&script)?, 40);
+
+    // Real nesting up to the default limits neither fails nor overflows the stack
+    let script = format!("{}1{}", "(".repeat(60), ")".repeat(60));
+    assert_eq!(engine.eval::<INT>(&script)?, 1);
+
+    let script = format!("{}1", "-".repeat(60));
+    assert_eq!(engine.eval::<INT>(&script)?, 1);
+
+    let script = format!("{}42{}", "{ ".repeat(60), " }".repeat(60));
+    assert_eq!(engine.eval::<INT>(&script)?, 42);
+
+    let script = format!("{}1{}", "(".repeat(100), ")".repeat(100));
+    assert_eq!(
+        *engine
+            .compile(&script)
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::ExprTooDeep
+    );
+
+    Ok(())
+}
@@ -0,0 +1,260 @@
+#![cfg(not(feature = "unchecked"))]
+use rhai::{lex, Dynamic, Engine, EvalAltResult, Scope, AST, INT};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// Create an `Engine` with tight limits, as used for untrusted scripts.
+fn new_engine() -> Engine {
+    let mut engine = Engine::new();
+
+    engine.set_max_operations(1_000);
+    engine.set_max_call_levels(16);
+    engine.set_max_expr_depth(32);
+    engine.set_max_stmt_depth(32);
+    engine.set_max_string_size(100);
+    #[cfg(not(feature = "no_index"))]
+    engine.set_max_array_size(20);
+    #[cfg(not(feature = "no_object"))]
+    engine.set_max_map_size(20);
+
+    engine
+}
+
+/// Tokenize, compile and run a script, failing the test if anything panics.
+fn assert_no_panic(engine: &Engine, script: &str) {
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        lex(&[script])
+            .take_while(|(token, _)| !token.is_eof())
+            .take(script.len() + 1)
+            .for_each(drop);
+
+        if let Ok(ast) = engine.compile(script) {
+            let _ = engine.eval_ast_with_scope::<Dynamic>(&mut Scope::new(), &ast);
+        }
+    }));
+
+    assert!(result.is_ok(), "panicked on script: {:?}", script);
+}
+
+#[test]
+fn test_no_panic_lexer() {
+    let engine = new_engine();
+
+    for script in &[
+        "\0",
+        "let x = \0;",
+        "'\0'",
+        "''",
+        "'ab'",
+        "'\\u{110000}'",
+        "\"\\u{d800}\"",
+        "\"\\x",
+        "0x",
+        "0b2",
+        "1e999",
+        "/* unterminated",
+        "\u{feff}let x = 1;",
+    ] {
+        assert_no_panic(&engine, script);
+    }
+
+    assert!(engine.compile("let x = \0;").is_err());
+}
+
+#[test]
+#[cfg(not(feature = "no_module"))]
+fn test_no_panic_stale_module_offset() -> Result<(), Box<EvalAltResult>> {
+    use rhai::{module_resolvers::StaticModuleResolver, Module};
+
+    let mut module = Module::new();
+    module.set_var("answer", 42 as INT);
+
+    let mut resolver = StaticModuleResolver::new();
+    resolver.insert("hello".to_string(), module);
+
+    let mut engine = Engine::new();
+    engine.set_module_resolver(Some(resolver));
+
+    // Variables defined by 'eval' shift the offsets of the module in the scope
+    assert_eq!(
+        engine.eval::<INT>(
+            r#"
+                import "hello" as h;
+                eval("let x = 1; let y = 2;");
+                h::answer
+    "#
+        )?,
+        42
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_no_panic_malformed_ast() {
+    let engine = new_engine();
+
+    let ast = engine
+        .compile(
+            r#"
+                let x = 40;
+                const y = 2;
+                let s = "hello";
+                let c = 'H';
+                let n = len(s);
+                x += if x > y { y } else { 0 };
+                while x < 50 { x += 1; if x == 45 { break; } }
+                x
+            "#,
+        )
+        .expect("script should compile");
+
+    let bytes = ast.to_bytes();
+
+    // Decoding and running a corrupted AST must never panic
+    for index in 0..bytes.len() {
+        for delta in &[1_u8, 0x7f, 0x80, 0xff] {
+            let mut bytes = bytes.clone();
+            bytes[index] = bytes[index].wrapping_add(*delta);
+
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                if let Ok(ast) = engine.ast_from_bytes(&bytes) {
+                    let _ = engine.eval_ast::<Dynamic>(&ast);
+                }
+            }));
+
+            assert!(result.is_ok(), "panicked on byte {} + {}", index, delta);
+        }
+    }
+}
+
+#[test]
+fn test_no_panic_random_scripts() {
+    const TOKENS: &[&str] = &[
+        "let",
+        "const",
+        "x",
+        "y",
+        "=",
+        "+=",
+        "-=",
+        "*=",
+        "/=",
+        "%=",
+        "<<=",
+        ">>=",
+        "1",
+        "0",
+        "-1",
+        "9223372036854775807",
+        "2.5",
+        "\"s\"",
+        "'c'",
+        "(",
+        ")",
+        "[",
+        "]",
+        "{",
+        "}",
+        "#{",
+        ":",
+        ";",
+        ",",
+        ".",
+        "::",
+        "+",
+        "-",
+        "*",
+        "/",
+        "%",
+        "~",
+        "<<",
+        ">>",
+        "&",
+        "|",
+        "^",
+        "&&",
+        "||",
+        "!",
+        "==",
+        "!=",
+        "<",
+        ">",
+        "in",
+        "if",
+        "else",
+        "while",
+        "loop",
+        "for",
+        "break",
+        "continue",
+        "return",
+        "throw",
+        "fn",
+        "private",
+        "import",
+        "export",
+        "as",
+        "eval",
+        "type_of",
+        "len",
+        "x[0]",
+        "x.a",
+        "x.len()",
+        "[1, 2]",
+        "#{a: 1}",
+        "range(0, 3)",
+        "\"x\"",
+        "'",
+        "\"",
+        "\n",
+        "/*",
+        "*/",
+        "\0",
+        "m::x",
+    ];
+
+    let engine = new_engine();
+
+    // A simple xorshift generator keeps the test deterministic
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed as usize
+    };
+
+    for _ in 0..2_000 {
+        let len = 1 + next() % 20;
+        let script: Vec<_> = (0..len).map(|_| TOKENS[next() % TOKENS.len()]).collect();
+        assert_no_panic(&engine, &script.join(" "));
+    }
+}
+
+#[test]
+fn test_no_panic_deep_ast() {
+    // Parsing such a deeply-nested script needs a bigger stack
+    std::thread::Builder::new()
+        .stack_size(64 * 1024 * 1024)
+        .spawn(|| {
+            let mut engine = Engine::new();
+            engine.set_max_expr_depth(0);
+
+            let script = format!("let x = 1; {}", vec!["x"; 2_000].join(" + "));
+            let bytes = engine.compile(&script).unwrap().to_bytes();
+
+            // The nesting depth is recorded when encoding...
+            assert!(AST::from_bytes(&bytes).is_ok());
+
+            // ... but is deeper than an engine with the default limits allows
+            assert!(Engine::new().ast_from_bytes(&bytes).is_err());
+
+            // Nesting deeper than recorded is rejected when decoding
+            let mut bytes = bytes;
+            bytes[15] = 1;
+            assert!(AST::from_bytes(&bytes).is_err());
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
@@ -111,3 +111,101 @@ fn test_max_operations_eval() -> Result<(), Box<EvalAltResult>> {
 
     Ok(())
 }
+
+#[test]
+fn test_max_eval_operations() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+    engine.set_max_eval_operations(100);
+
+    // The caller itself is not limited
+    engine.eval::<()>("for x in range(0, 500) {}")?;
+
+    assert!(matches!(
+        *engine
+            .eval::<()>(
+                r#"
+                    for x in range(0, 500) {}
+                    eval("for x in range(0, 500) {}");
+            "#
+            )
+            .expect_err("should error"),
+        EvalAltResult::ErrorTooManyOperations(_)
+    ));
+
+    // Operations inside eval still count towards the overall limit
+    engine.set_max_eval_operations(0);
+    engine.set_max_operations(500);
+
+    assert!(matches!(
+        *engine
+            .eval::<()>(
+                r#"
+                    for x in range(0, 300) {}
+                    eval("for x in range(0, 300) {}");
+            "#
+            )
+            .expect_err("should error"),
+        EvalAltResult::ErrorTooManyOperations(_)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_cancellation_token() -> Result<(), Box<EvalAltResult>> {
+    use rhai::CancellationToken;
+    use std::{thread, time::Duration};
+
+    let token = CancellationToken::new();
+
+    let mut engine = Engine::new();
+    engine.set_cancellation_token(Some(token.clone()));
+
+    let handle = {
+        let token = token.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            token.cancel();
+        })
+    };
+
+    assert!(matches!(
+        *engine.eval::<()>("loop {}").expect_err("should error"),
+        EvalAltResult::ErrorTerminated(_)
+    ));
+
+    handle.join().unwrap();
+
+    token.reset();
+    engine.eval::<()>("let x = 0; while x < 20 { x += 1; }")?;
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_std"))]
+fn test_max_duration() -> Result<(), Box<EvalAltResult>> {
+    use rhai::RegisterFn;
+    use std::{thread, time::Duration};
+
+    let mut engine = Engine::new();
+    engine.set_max_duration(Duration::from_millis(50));
+    engine.register_fn("slow", || thread::sleep(Duration::from_millis(20)));
+
+    engine.eval::<()>("slow();")?;
+
+    assert!(matches!(
+        *engine
+            .eval::<()>("for x in range(0, 10) { slow(); }")
+            .expect_err("should error"),
+        EvalAltResult::ErrorTimeout(_)
+    ));
+
+    // Each run gets its own time limit
+    engine.eval::<()>("slow();")?;
+
+    engine.set_max_duration(Duration::from_millis(0));
+    engine.eval::<()>("for x in range(0, 5) { slow(); }")?;
+
+    Ok(())
+}
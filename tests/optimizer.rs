@@ -1,6 +1,6 @@
 #![cfg(not(feature = "no_optimize"))]
 
-use rhai::{Engine, EvalAltResult, OptimizationLevel, INT};
+use rhai::{Engine, EvalAltResult, OptimizationLevel, RegisterResultFn, INT};
 
 #[test]
 fn test_optimizer() -> Result<(), Box<EvalAltResult>> {
@@ -30,3 +30,270 @@ fn test_optimizer() -> Result<(), Box<EvalAltResult>> {
 
     Ok(())
 }
+
+#[test]
+#[cfg(not(feature = "no_function"))]
+fn test_optimizer_inline_functions() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+    engine.set_optimization_level(OptimizationLevel::Full);
+
+    // Calls with constant arguments are inlined and folded
+    let mut ast = engine.compile("fn add1(x) { x + 1 } add1(41)")?;
+    assert_eq!(engine.eval_ast::<INT>(&ast)?, 42);
+    ast.clear_functions();
+    assert!(!format!("{:?}", ast).contains("add1"));
+    assert_eq!(engine.eval_ast::<INT>(&ast)?, 42);
+
+    // Parameters do not conflict with variables of the caller
+    let mut ast = engine.compile(
+        r"
+            fn diff(x, y) { x - y }
+            fn double(x) { x + x }
+            let x = 10;
+            let y = 3;
+            double(diff(y, x)) + diff(x, 1)
+        ",
+    )?;
+    assert_eq!(engine.eval_ast::<INT>(&ast)?, -5);
+    ast.clear_functions();
+    assert_eq!(engine.eval_ast::<INT>(&ast)?, -5);
+
+    // Modified parameters do not affect the arguments
+    assert_eq!(
+        engine.eval::<INT>(
+            r"
+                fn inc(x) { x += 1 }
+                let x = 1;
+                inc(x);
+                inc(2);
+                x
+            "
+        )?,
+        1
+    );
+
+    // Recursive functions are not inlined
+    let mut ast = engine.compile(
+        r"
+            fn fib(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }
+            fn sum(n) { sum2(n) }
+            fn sum2(n) { n }
+            fib(10) + sum(0)
+        ",
+    )?;
+    assert_eq!(engine.eval_ast::<INT>(&ast)?, 55);
+    ast.clear_functions();
+    assert!(engine.eval_ast::<INT>(&ast).is_err());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_function"))]
+fn test_optimizer_remove_unreachable_functions() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+    engine.set_optimization_level(OptimizationLevel::Full);
+
+    let script = r"
+        private fn unused() { 0 }
+        private fn add1(x) { x + 1 }
+        private fn double(x) { x * 2 }
+        fn api(x) { double(x) }
+        add1(41)
+    ";
+
+    // Private functions no longer called after inlining are removed,
+    // while public functions and the functions they call are kept
+    let ast = engine.compile(script)?;
+    assert_eq!(engine.eval_ast::<INT>(&ast)?, 42);
+
+    let dump = format!("{:?}", ast);
+    assert!(!dump.contains("unused"));
+    assert!(!dump.contains("add1"));
+    assert!(dump.contains("double"));
+    assert!(dump.contains("api"));
+
+    // Lower optimization levels keep all functions
+    engine.set_optimization_level(OptimizationLevel::Simple);
+    let ast = engine.compile(script)?;
+    let dump = format!("{:?}", ast);
+    assert!(dump.contains("unused"));
+    assert!(dump.contains("add1"));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_function"))]
+#[cfg(not(feature = "no_index"))]
+#[cfg(not(feature = "no_object"))]
+fn test_optimizer_inline_methods() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+    engine.set_optimization_level(OptimizationLevel::Full);
+
+    let mut ast = engine.compile(
+        r"
+            fn is_vip(x) { x.tier > 3 }
+            let customers = [#{ tier: 1 }, #{ tier: 5 }, #{ tier: 4 }];
+            let count = 0;
+            for c in customers { if is_vip(c) { count += 1; } }
+            if is_vip(#{ tier: 9 }) { count } else { 0 }
+        ",
+    )?;
+    assert_eq!(engine.eval_ast::<INT>(&ast)?, 2);
+    ast.clear_functions();
+    assert_eq!(engine.eval_ast::<INT>(&ast)?, 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_optimizer_pure_functions() -> Result<(), Box<EvalAltResult>> {
+    fn parse_rate(s: &str) -> Result<INT, Box<EvalAltResult>> {
+        s.trim_end_matches('%')
+            .parse::<INT>()
+            .map_err(|_| format!("invalid rate: {}", s).into())
+    }
+
+    let mut engine = Engine::new();
+    engine.set_optimization_level(OptimizationLevel::Full);
+    engine.register_result_fn("parse_rate", |s: String| parse_rate(&s));
+
+    // Functions are not evaluated during optimization unless pure
+    let ast = engine.compile(r#"parse_rate("5%")"#)?;
+    assert!(format!("{:?}", ast).contains("parse_rate"));
+    assert_eq!(engine.eval_ast::<INT>(&ast)?, 5);
+
+    engine.set_fn_pure("parse_rate", true);
+
+    let ast = engine.compile(r#"parse_rate("5%") * 2"#)?;
+    assert!(!format!("{:?}", ast).contains("parse_rate"));
+    assert_eq!(engine.eval_ast::<INT>(&ast)?, 10);
+
+    // Errors are raised at runtime
+    let ast = engine.compile(r#"let x = 1; parse_rate("five")"#)?;
+    assert!(matches!(
+        *engine.eval_ast::<INT>(&ast).expect_err("should error"),
+        EvalAltResult::ErrorRuntime(_, _)
+    ));
+
+    // Not evaluated under simple optimizations
+    engine.set_optimization_level(OptimizationLevel::Simple);
+    let ast = engine.compile(r#"parse_rate("5%")"#)?;
+    assert!(format!("{:?}", ast).contains("parse_rate"));
+
+    // Built-in functions that change their arguments are not pure
+    engine.set_optimization_level(OptimizationLevel::Full);
+    let ast = engine.compile(r#"len("hello") + 1"#)?;
+    assert!(!format!("{:?}", ast).contains("len"));
+    let ast = engine.compile(r#"let s = "hello"; truncate("hello", 2); s"#)?;
+    assert!(format!("{:?}", ast).contains("truncate"));
+
+    #[cfg(not(feature = "no_index"))]
+    {
+        let ast = engine.compile("len([1, 2, 3]) + 1")?;
+        assert!(!format!("{:?}", ast).contains("len"));
+        let ast = engine.compile("let x = 1; push([1, 2, 3], 4); x")?;
+        assert!(format!("{:?}", ast).contains("push"));
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "internals")]
+fn test_optimizer_custom_passes() -> Result<(), Box<EvalAltResult>> {
+    use rhai::{Expr, OptimizerPass, RegisterFn, Stmt};
+
+    // Rename calls to the deprecated `twice` to `double`, and turn `DEBUG` into `false`
+    struct Rewrite;
+
+    impl OptimizerPass for Rewrite {
+        fn rewrite_expr(&self, expr: &Expr) -> Option<Expr> {
+            match expr {
+                Expr::FnCall(x) if (x.0).0 == "twice" => {
+                    let mut x = x.clone();
+                    (x.0).0 = "double".into();
+                    x.2 = 0;
+                    Some(Expr::FnCall(x))
+                }
+                Expr::Variable(x) if (x.0).0 == "DEBUG" => Some(Expr::False((x.0).1)),
+                _ => None,
+            }
+        }
+    }
+
+    // Remove all `trace(...)` statements
+    struct StripTrace;
+
+    impl OptimizerPass for StripTrace {
+        fn rewrite_stmt(&self, stmt: &Stmt) -> Option<Stmt> {
+            match stmt {
+                Stmt::Expr(expr) => match expr.as_ref() {
+                    Expr::FnCall(x) if (x.0).0 == "trace" => Some(Stmt::Noop((x.0).1)),
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+    }
+
+    let mut engine = Engine::new();
+    engine.register_fn("double", |x: INT| x * 2);
+    engine.add_optimizer_pass(Rewrite);
+    engine.add_optimizer_pass(StripTrace);
+
+    assert_eq!(
+        engine.eval::<INT>("trace(1); if DEBUG { 0 } else { twice(21) }")?,
+        42
+    );
+
+    engine.set_optimization_level(OptimizationLevel::None);
+
+    assert!(engine.eval::<INT>("twice(21)").is_err());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "internals")]
+fn test_optimizer_custom_passes_never_settle() -> Result<(), Box<EvalAltResult>> {
+    use rhai::{Expr, OptimizerPass};
+
+    // Swap `x` and `y` back and forth, so the `AST` is changed by every round
+    struct Swap;
+
+    impl OptimizerPass for Swap {
+        fn rewrite_expr(&self, expr: &Expr) -> Option<Expr> {
+            match expr {
+                Expr::Variable(x) if (x.0).0 == "x" || (x.0).0 == "y" => {
+                    let mut x = x.clone();
+                    (x.0).0 = if (x.0).0 == "x" { "y" } else { "x" }.into();
+                    Some(Expr::Variable(x))
+                }
+                _ => None,
+            }
+        }
+    }
+
+    // A pass that always returns a replacement
+    struct Replace;
+
+    impl OptimizerPass for Replace {
+        fn rewrite_expr(&self, expr: &Expr) -> Option<Expr> {
+            Some(expr.clone())
+        }
+    }
+
+    let mut engine = Engine::new();
+    engine.add_optimizer_pass(Swap);
+
+    // The optimizer gives up after a fixed number of rounds
+    assert_eq!(engine.eval::<INT>("let x = 1; let y = 1; x + y")?, 2);
+
+    engine.add_optimizer_pass(Replace);
+
+    assert_eq!(engine.eval::<INT>("40 + 2")?, 42);
+
+    Ok(())
+}
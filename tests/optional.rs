@@ -0,0 +1,80 @@
+use rhai::{Engine, EvalAltResult, ParseErrorType, INT};
+
+#[test]
+fn test_coalesce() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    assert_eq!(engine.eval::<INT>("() ?? 42")?, 42);
+    assert_eq!(engine.eval::<INT>("1 ?? 42")?, 1);
+    assert_eq!(engine.eval::<bool>("false ?? true")?, false);
+    assert_eq!(engine.eval::<INT>("let x; let y; x ?? y ?? 3")?, 3);
+    assert_eq!(engine.eval::<INT>("let x; 1 + 2 * 3 ?? x")?, 7);
+    assert_eq!(engine.eval::<bool>("let x; x ?? 1 == 1")?, true);
+
+    // The right-hand side is not evaluated unless needed
+    assert_eq!(
+        engine.eval::<INT>("let x = 0; let y = 1 ?? { x = 99; 2 }; x + y")?,
+        1
+    );
+
+    assert_eq!(engine.eval::<INT>("let x; x ??= 42; x")?, 42);
+    assert_eq!(engine.eval::<INT>("let x = 1; x ??= 42; x")?, 1);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_object"))]
+fn test_optional_dot() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    assert_eq!(
+        engine.eval::<INT>("let x = #{ a: #{ b: 42 } }; x.a?.b")?,
+        42
+    );
+    assert!(engine.eval::<()>("let x = #{ a: 1 }; x.c?.b").is_ok());
+    assert!(engine.eval::<()>("let x; x?.a.b.c").is_ok());
+    assert!(engine.eval::<()>("let x; x?.foo(1, 2)").is_ok());
+    assert_eq!(
+        engine.eval::<INT>("let x = #{ a: 1 }; x.c?.b.d ?? 123")?,
+        123
+    );
+
+    // Without '?.' it is still an error to access a property on ()
+    assert!(engine.eval::<()>("let x = #{ a: 1 }; x.c.b").is_err());
+
+    assert!(matches!(
+        engine
+            .compile("let x = #{ a: 1 }; x?.a = 42")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::BadInput(_)
+    ));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_index"))]
+fn test_optional_index() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    assert_eq!(engine.eval::<INT>("let x = [1, 2, 3]; x?[1]")?, 2);
+    assert!(engine.eval::<()>("let x; x?[1]").is_ok());
+    assert!(engine.eval::<()>("let x = [(), 2]; x[0]?[1]").is_ok());
+    assert_eq!(engine.eval::<INT>("let x = [[1, 2], 3]; x[0]?[1]")?, 2);
+    assert_eq!(engine.eval::<INT>("let x; x?[0] ?? -1")?, -1);
+
+    #[cfg(not(feature = "no_object"))]
+    assert!(engine.eval::<()>("let x = #{ a: () }; x.a?[0].b").is_ok());
+
+    assert!(matches!(
+        engine
+            .compile("let x = [1, 2]; x?[0] = 42")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::BadInput(_)
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use rhai::{Engine, EvalAltResult, INT};
+
+#[test]
+fn test_range() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    assert_eq!(
+        engine.eval::<INT>("let sum = 0; for x in 1..5 { sum += x; } sum")?,
+        10
+    );
+    assert_eq!(
+        engine.eval::<INT>("let sum = 0; for x in 1..=5 { sum += x; } sum")?,
+        15
+    );
+    assert_eq!(
+        engine.eval::<INT>("let n = 3; let sum = 0; for x in n-1..n*2 { sum += x; } sum")?,
+        14
+    );
+    assert_eq!(
+        engine.eval::<INT>("let sum = 0; for x in -2..0 { sum += x; } sum")?,
+        -3
+    );
+
+    // Ranges are values
+    assert_eq!(
+        engine.eval::<INT>("let r = 0..3; let sum = 0; for x in r { sum += x; } sum")?,
+        3
+    );
+    assert_eq!(engine.eval::<String>("type_of(1..2)")?, "range");
+    assert_eq!(engine.eval::<String>("type_of(1..=2)")?, "range_inclusive");
+    assert_eq!(engine.eval::<String>("to_string(1..2)")?, "1..2");
+    assert_eq!(engine.eval::<String>("to_string(1..=2)")?, "1..=2");
+
+    // Decimal points are not confused with ranges
+    #[cfg(not(feature = "no_float"))]
+    assert_eq!(engine.eval::<rhai::FLOAT>("1.5 + 1.")?, 2.5);
+
+    Ok(())
+}
+
+#[test]
+fn test_range_in() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    assert!(engine.eval::<bool>("5 in 1..10")?);
+    assert!(!engine.eval::<bool>("10 in 1..10")?);
+    assert!(engine.eval::<bool>("10 in 1..=10")?);
+    assert!(!engine.eval::<bool>("0 in 1..=10")?);
+    assert!(engine.eval::<bool>("let x = 4; x + 1 in x..x+2")?);
+
+    assert!(matches!(
+        *engine
+            .eval::<bool>(r#""x" in 1..10"#)
+            .expect_err("should error"),
+        EvalAltResult::ErrorInExpr(_)
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,211 @@
+use rhai::{Engine, EvalAltResult, ParseErrorType, AST, INT};
+
+#[test]
+fn test_serialize_round_trip() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    let ast = engine.compile(
+        r#"
+            let x = 0;
+            let s = "hello";
+            while x < 10 { x += 1; if x == 5 { break; } }
+            len(s) + x
+        "#,
+    )?;
+
+    let bytes = ast.to_bytes();
+    let ast2 = AST::from_bytes(&bytes)?;
+
+    assert_eq!(engine.eval_ast::<INT>(&ast)?, 10);
+    assert_eq!(engine.eval_ast::<INT>(&ast2)?, 10);
+    assert_eq!(ast2.to_bytes(), bytes);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_function"))]
+fn test_serialize_functions() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    let ast = engine.compile(
+        r#"
+            fn add(x, y) { x + y }
+            fn add(x) { x + 1 }
+            private fn fail() { throw "boom"; }
+            add(40, 2) + add(0) - 1
+        "#,
+    )?;
+
+    let ast = AST::from_bytes(&ast.to_bytes())?;
+
+    assert_eq!(engine.eval_ast::<INT>(&ast)?, 42);
+
+    let ast = AST::from_bytes(&engine.compile("let x = 1;\nfoo(x)")?.to_bytes())?;
+
+    match *engine.eval_ast::<()>(&ast).expect_err("should error") {
+        EvalAltResult::ErrorFunctionNotFound(_, pos) => {
+            assert_eq!(pos.line(), Some(2));
+            assert_eq!(pos.position(), Some(1));
+        }
+        err => panic!("unexpected error: {}", err),
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_function"))]
+fn test_serialize_type_annotations() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    let ast = engine.compile(
+        r#"
+            fn add(x: int, y: int) -> int { x + y }
+            let x: int = add(40, 2);
+            x
+        "#,
+    )?;
+
+    let bytes = ast.to_bytes();
+    let ast2 = AST::from_bytes(&bytes)?;
+
+    assert_eq!(engine.eval_ast::<INT>(&ast2)?, 42);
+    assert_eq!(ast2.to_bytes(), bytes);
+
+    let ast = AST::from_bytes(&engine.compile(r#"let s: int = "hello"; 0"#)?.to_bytes())?;
+
+    assert!(matches!(
+        *engine.eval_ast::<INT>(&ast).expect_err("should error"),
+        EvalAltResult::ErrorMismatchDataType(_, _, _)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_serialize_bad_input() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+    let bytes = engine.compile("let x = 42; x")?.to_bytes();
+
+    assert!(matches!(
+        AST::from_bytes(b"not an AST")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::BadInput(_)
+    ));
+    assert!(matches!(
+        AST::from_bytes(&bytes[..bytes.len() - 1])
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::BadInput(_)
+    ));
+
+    let mut bad_version = bytes.clone();
+    bad_version[4] = 0xff;
+    assert!(AST::from_bytes(&bad_version).is_err());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "unchecked"))]
+fn test_serialize_raised_limits() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+    engine.set_max_expr_depth(400);
+
+    // Nested deeper than the default limits allow
+    let script = format!(
+        "let x = 1; let y = {}x{}; y",
+        "-(".repeat(150),
+        ")".repeat(150)
+    );
+    let ast = engine.compile(&script)?;
+    let ast = AST::from_bytes(&ast.to_bytes())?;
+
+    assert_eq!(engine.eval_ast::<INT>(&ast)?, 1);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_module"))]
+#[cfg(not(feature = "no_std"))]
+fn test_serialize_module_cache() -> Result<(), Box<EvalAltResult>> {
+    use rhai::module_resolvers::FileModuleResolver;
+    use std::fs;
+
+    let dir = std::env::temp_dir().join(format!("rhai-test-module-cache-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("answer.rhai"), "let answer = 42; export answer;").unwrap();
+
+    let mut resolver = FileModuleResolver::new_with_path(&dir);
+    resolver.enable_cache(true);
+
+    let mut engine = Engine::new();
+    engine.set_module_resolver(Some(resolver));
+
+    let script = r#"import "answer" as m; m::answer"#;
+
+    assert_eq!(engine.eval::<INT>(script)?, 42);
+    assert!(dir.join("answer.rhaic").exists());
+
+    // Loaded from the compiled module
+    assert_eq!(engine.eval::<INT>(script)?, 42);
+
+    // A compiled module is not used by an engine with different compilation settings
+    fs::write(dir.join("sum.rhai"), "let sum = (40 + 2); export sum;").unwrap();
+    let script = r#"import "sum" as m; m::sum"#;
+    assert_eq!(engine.eval::<INT>(script)?, 42);
+
+    let mut engine2 = engine.clone();
+    engine2.disable_symbol("+");
+    assert!(engine2.eval::<INT>(script).is_err());
+    assert_eq!(engine.eval::<INT>(script)?, 42);
+
+    #[cfg(not(feature = "unchecked"))]
+    {
+        let mut engine2 = engine.clone();
+        engine2.set_max_expr_depth(1);
+        assert!(engine2.eval::<INT>(script).is_err());
+        assert_eq!(engine.eval::<INT>(script)?, 42);
+    }
+
+    // A corrupted compiled module is ignored and replaced
+    let script = r#"import "answer" as m; m::answer"#;
+    fs::write(dir.join("answer.rhaic"), b"garbage").unwrap();
+    assert_eq!(engine.eval::<INT>(script)?, 42);
+    assert_ne!(fs::read(dir.join("answer.rhaic")).unwrap(), b"garbage");
+
+    // A compiled module is not used once the script changes, whatever the modification times
+    let compiled = fs::read(dir.join("answer.rhaic")).unwrap();
+    fs::write(dir.join("answer.rhai"), "let answer = 0; export answer;").unwrap();
+    fs::write(dir.join("answer.rhaic"), compiled).unwrap();
+    assert_eq!(engine.eval::<INT>(script)?, 0);
+
+    // Calls to native functions folded by full optimization are not kept in the compiled module
+    #[cfg(not(feature = "no_optimize"))]
+    {
+        use rhai::{OptimizationLevel, RegisterFn};
+
+        fs::write(
+            dir.join("value.rhai"),
+            "let value = get_value(); export value;",
+        )
+        .unwrap();
+        let script = r#"import "value" as m; m::value"#;
+
+        engine.set_optimization_level(OptimizationLevel::Full);
+        engine.register_fn("get_value", || 1 as INT);
+        engine.set_fn_pure("get_value", true);
+        assert_eq!(engine.eval::<INT>(script)?, 1);
+
+        engine.register_fn("get_value", || 2 as INT);
+        engine.set_fn_pure("get_value", true);
+        assert_eq!(engine.eval::<INT>(script)?, 2);
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    Ok(())
+}
@@ -0,0 +1,112 @@
+#![cfg(not(feature = "no_index"))]
+use rhai::{Array, Engine, EvalAltResult, INT};
+
+#[test]
+fn test_negative_index() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    assert_eq!(engine.eval::<INT>("let a = [1, 2, 3]; a[-1]")?, 3);
+    assert_eq!(engine.eval::<INT>("let a = [1, 2, 3]; a[-3]")?, 1);
+    assert_eq!(
+        engine.eval::<INT>("let a = [1, 2, 3]; a[-1] = 42; a[2]")?,
+        42
+    );
+    assert_eq!(engine.eval::<char>(r#"let s = "hello"; s[-1]"#)?, 'o');
+    assert_eq!(
+        engine.eval::<String>(r#"let s = "hello"; s[-1] = 'O'; s"#)?,
+        "hellO"
+    );
+
+    assert!(matches!(
+        *engine
+            .eval::<INT>("let a = [1, 2, 3]; a[-4]")
+            .expect_err("should error"),
+        EvalAltResult::ErrorArrayBounds(3, -4, _)
+    ));
+    assert!(matches!(
+        *engine
+            .eval::<char>(r#"let s = "hello"; s[-6]"#)
+            .expect_err("should error"),
+        EvalAltResult::ErrorStringBounds(5, -6, _)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_array_slice() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    let slice = |script: &str| -> Result<Vec<INT>, Box<EvalAltResult>> {
+        Ok(engine
+            .eval::<Array>(script)?
+            .into_iter()
+            .map(|v| v.cast::<INT>())
+            .collect())
+    };
+
+    assert_eq!(slice("let a = [1, 2, 3, 4, 5]; a[1..3]")?, [2, 3]);
+    assert_eq!(slice("let a = [1, 2, 3, 4, 5]; a[1..=3]")?, [2, 3, 4]);
+    assert_eq!(slice("let a = [1, 2, 3, 4, 5]; a[-2..]")?, [4, 5]);
+    assert_eq!(slice("let a = [1, 2, 3, 4, 5]; a[..2]")?, [1, 2]);
+    assert_eq!(slice("let a = [1, 2, 3, 4, 5]; a[..-1]")?, [1, 2, 3, 4]);
+    assert_eq!(slice("let a = [1, 2, 3, 4, 5]; a[..]")?, [1, 2, 3, 4, 5]);
+
+    // Out-of-bounds ranges are clamped
+    assert_eq!(slice("let a = [1, 2, 3, 4, 5]; a[3..100]")?, [4, 5]);
+    assert_eq!(
+        slice("let a = [1, 2, 3, 4, 5]; a[4..2]")?,
+        Vec::<INT>::new()
+    );
+
+    // The slice is a copy
+    assert_eq!(
+        slice("let a = [1, 2, 3]; let b = a[0..2]; b[0] = 42; a")?,
+        [1, 2, 3]
+    );
+
+    // Assigning to a slice replaces its items
+    assert_eq!(
+        slice("let a = [1, 2, 3, 4, 5]; a[1..4] = [42]; a")?,
+        [1, 42, 5]
+    );
+    assert_eq!(
+        slice("let a = [1, 2, 3]; a[-1..] = [4, 5, 6]; a")?,
+        [1, 2, 4, 5, 6]
+    );
+    assert_eq!(slice("let a = [1, 2, 3]; a[..0] = [0]; a")?, [0, 1, 2, 3]);
+
+    assert!(matches!(
+        *engine
+            .eval::<()>("let a = [1, 2, 3]; a[0..2] = 42;")
+            .expect_err("should error"),
+        EvalAltResult::ErrorArrayMismatch(_)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_string_slice() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    assert_eq!(
+        engine.eval::<String>(r#"let s = "hello, world"; s[0..5]"#)?,
+        "hello"
+    );
+    assert_eq!(
+        engine.eval::<String>(r#"let s = "hello, world"; s[-5..]"#)?,
+        "world"
+    );
+    assert_eq!(engine.eval::<String>(r#""日本語です"[1..=2]"#)?, "本語");
+    assert_eq!(engine.eval::<String>(r#""hello"[10..]"#)?, "");
+
+    assert!(matches!(
+        *engine
+            .eval::<()>(r#"let s = "hello"; s[0..2] = "HE";"#)
+            .expect_err("should error"),
+        EvalAltResult::ErrorAssignmentToUnknownLHS(_)
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,168 @@
+use rhai::{lex, Engine, EvalAltResult, Span, Token, INT};
+use std::borrow::Cow;
+
+#[test]
+fn test_tokens_spans() {
+    let scripts = [r#"let x = "hello";  x.len() + -42"#];
+
+    let tokens: Vec<_> = {
+        let mut stream = lex(&scripts);
+        let mut tokens = Vec::new();
+
+        loop {
+            let (token, _, span) = stream.next_with_span().unwrap();
+            if token.is_eof() {
+                assert_eq!(span, Span::new(scripts[0].len(), scripts[0].len()));
+                break;
+            }
+            tokens.push((token, span));
+        }
+
+        tokens
+    };
+
+    let texts: Vec<_> = tokens
+        .iter()
+        .map(|(_, span)| &scripts[0][span.start..span.end])
+        .collect();
+
+    assert_eq!(
+        texts,
+        vec![
+            "let",
+            "x",
+            "=",
+            r#""hello""#,
+            ";",
+            "x",
+            ".",
+            "len",
+            "(",
+            ")",
+            "+",
+            "-42"
+        ]
+    );
+
+    assert!(matches!(
+        &tokens[1].0,
+        Token::Identifier(Cow::Borrowed("x"))
+    ));
+    assert!(matches!(
+        &tokens[3].0,
+        Token::StringConst(Cow::Borrowed("hello"))
+    ));
+    assert_eq!(tokens[11].0, Token::IntegerConstant(-42));
+}
+
+#[test]
+fn test_tokens_escapes() {
+    let scripts = [r#""a\tb" 'x'"#];
+    let mut stream = lex(&scripts);
+
+    let (token, _, span) = stream.next_with_span().unwrap();
+    assert_eq!(token, Token::StringConst(Cow::Owned("a\tb".into())));
+    assert_eq!(span, Span::new(0, 6));
+
+    let (token, _, span) = stream.next_with_span().unwrap();
+    assert_eq!(token, Token::CharConstant('x'));
+    assert_eq!(span, Span::new(7, 10));
+}
+
+#[test]
+fn test_tokens_multiple_streams() -> Result<(), Box<EvalAltResult>> {
+    let scripts = ["let ab", "cd = 4", "2; abcd"];
+
+    let mut stream = lex(&scripts);
+    stream.next();
+
+    let (token, _, span) = stream.next_with_span().unwrap();
+    assert_eq!(token, Token::Identifier("abcd".into()));
+    assert_eq!(span, Span::new(4, 8));
+
+    let engine = Engine::new();
+    let ast = engine.compile_scripts_with_scope(&Default::default(), &scripts)?;
+    assert_eq!(engine.eval_ast::<INT>(&ast)?, 42);
+
+    Ok(())
+}
+
+#[test]
+fn test_tokens_disabled() -> Result<(), Box<EvalAltResult>> {
+    use rhai::ParseErrorType;
+
+    let mut engine = Engine::new();
+
+    engine.disable_symbol("loop");
+    engine.disable_symbol("eval");
+    engine.disable_symbol("*");
+
+    assert_eq!(
+        *engine
+            .compile("let x = 0; loop { x += 1; }")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::DisabledSymbol("loop".into())
+    );
+    assert_eq!(
+        *engine
+            .compile(r#"eval("40 + 2")"#)
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::DisabledSymbol("eval".into())
+    );
+    assert_eq!(
+        *engine
+            .compile("let x = 6; x * 7")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::DisabledSymbol("*".into())
+    );
+
+    // Disabling an operator also disables its compound assignment
+    assert_eq!(
+        *engine
+            .compile("let x = 6; x *= 7; x")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::DisabledSymbol("*=".into())
+    );
+
+    // Disabled symbols inside strings are fine
+    assert_eq!(engine.eval::<String>(r#""loop * eval""#)?, "loop * eval");
+    assert_eq!(
+        engine.eval::<INT>("let x = 0; while x < 42 { x += 1; } x")?,
+        42
+    );
+
+    // Other engines are not affected
+    let mut engine = Engine::new();
+    assert_eq!(engine.eval::<INT>("let x = 6; x * 7")?, 42);
+
+    // A compound assignment can be disabled on its own
+    engine.disable_symbol("*=");
+    assert_eq!(engine.eval::<INT>("let x = 6; x = x * 7; x")?, 42);
+    assert!(engine.compile("let x = 6; x *= 7; x").is_err());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_object"))]
+fn test_tokens_disabled_json() -> Result<(), Box<EvalAltResult>> {
+    use rhai::ParseErrorType;
+
+    let mut engine = Engine::new();
+    let json = r#"{"a": 1, "b": true}"#;
+
+    assert_eq!(engine.parse_json(json, false)?.len(), 2);
+
+    engine.disable_symbol("true");
+
+    assert!(matches!(
+        *engine.parse_json(json, false).expect_err("should error"),
+        EvalAltResult::ErrorParsing(ref err) if *err.error_type() == ParseErrorType::DisabledSymbol("true".into())
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,375 @@
+use rhai::{Engine, EvalAltResult, ParseErrorType, Position, RegisterFn, INT};
+
+#[test]
+fn test_type_annotations_let() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    assert_eq!(engine.eval::<INT>("let x: int = 42; x")?, 42);
+    assert_eq!(
+        engine.eval::<String>(r#"let s: string = "hello"; s"#)?,
+        "hello"
+    );
+    assert_eq!(engine.eval::<bool>("let b: bool = true; b")?, true);
+    assert_eq!(engine.eval::<()>("let u: () = (); u")?, ());
+
+    #[cfg(not(feature = "no_float"))]
+    {
+        use rhai::FLOAT;
+
+        // Integers are converted when cast to `float`
+        assert_eq!(engine.eval::<FLOAT>("let x: float = 42; x")?, 42.0);
+        assert_eq!(engine.eval::<FLOAT>("let x: float = 1.5; x = 2; x")?, 2.0);
+    }
+
+    #[cfg(not(feature = "no_index"))]
+    assert_eq!(engine.eval::<INT>("let a: array = [1, 2, 3]; len(a)")?, 3);
+
+    assert!(matches!(
+        *engine.eval::<INT>(r#"let x: int = "42"; x"#).expect_err("should error"),
+        EvalAltResult::ErrorMismatchDataType(t, _, _) if t == "int"
+    ));
+
+    // Values assigned to a variable with a type annotation are also checked
+    assert!(matches!(
+        *engine.eval::<INT>(r#"let x: int = 1; x = "hello"; x"#).expect_err("should error"),
+        EvalAltResult::ErrorMismatchDataType(t, _, _) if t == "int"
+    ));
+
+    // Variables without type annotations can still hold anything
+    assert_eq!(
+        engine.eval::<String>(r#"let x = 1; x = "hello"; x"#)?,
+        "hello"
+    );
+
+    assert!(matches!(
+        engine
+            .compile("let x: int;")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::MissingToken(_, _)
+    ));
+    assert!(matches!(
+        engine
+            .compile("let x: 42 = 42;")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::TypeExpected
+    ));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_object"))]
+fn test_type_annotations_custom() -> Result<(), Box<EvalAltResult>> {
+    #[derive(Clone)]
+    struct TestStruct {
+        x: INT,
+    }
+
+    let mut engine = Engine::new();
+
+    engine.register_type_with_name::<TestStruct>("TestStruct");
+    engine.register_fn("new_ts", || TestStruct { x: 1 });
+    engine.register_fn("get_x", |ts: &mut TestStruct| ts.x);
+
+    assert_eq!(
+        engine.eval::<INT>("let ts: TestStruct = new_ts(); get_x(ts)")?,
+        1
+    );
+    assert!(matches!(
+        *engine.eval::<INT>("let ts: TestStruct = 1; 0").expect_err("should error"),
+        EvalAltResult::ErrorMismatchDataType(t, _, _) if t == "TestStruct"
+    ));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_function"))]
+fn test_type_annotations_functions() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    assert_eq!(
+        engine.eval::<INT>("fn add(x: int, y: int) -> int { x + y } add(40, 2)")?,
+        42
+    );
+    assert_eq!(
+        engine
+            .eval::<String>(r#"fn greet(name: string) -> string { "hi " + name } greet("bob")"#)?,
+        "hi bob"
+    );
+
+    #[cfg(not(feature = "no_float"))]
+    {
+        use rhai::FLOAT;
+
+        // Parameters and return values are cast like variables
+        assert_eq!(
+            engine.eval::<FLOAT>("fn half(x: float) -> float { x / 2.0 } half(3)")?,
+            1.5
+        );
+        assert_eq!(engine.eval::<FLOAT>("fn one() -> float { 1 } one()")?, 1.0);
+    }
+
+    assert!(matches!(
+        *engine
+            .eval::<INT>(r#"fn f(x: int) { x } f("hello")"#)
+            .expect_err("should error"),
+        EvalAltResult::ErrorInFunctionCall(f, err, _)
+            if f == "f" && matches!(*err, EvalAltResult::ErrorMismatchDataType(_, _, _))
+    ));
+    assert!(matches!(
+        *engine
+            .eval::<INT>(r#"fn f(x) -> int { x } f("hello")"#)
+            .expect_err("should error"),
+        EvalAltResult::ErrorInFunctionCall(f, err, _)
+            if f == "f" && matches!(*err, EvalAltResult::ErrorMismatchDataType(_, _, pos) if pos == Position::new(1, 18))
+    ));
+
+    // Return statements are checked too
+    assert!(matches!(
+        *engine
+            .eval::<INT>(r#"fn f(x) -> int { if x { return "hello"; } 0 } f(true)"#)
+            .expect_err("should error"),
+        EvalAltResult::ErrorInFunctionCall(f, err, _)
+            if f == "f" && matches!(*err, EvalAltResult::ErrorMismatchDataType(_, _, pos) if pos == Position::new(1, 32))
+    ));
+
+    // Default parameter values can be annotated
+    assert_eq!(
+        engine.eval::<INT>("fn f(x: int, y: int = 2) -> int { x * y } f(21)")?,
+        42
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_type_checker() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+
+    // Type checking is off by default
+    assert!(engine.compile(r#"let x: int = "hello";"#).is_ok());
+
+    engine.set_type_checking(true);
+
+    assert_eq!(engine.eval::<INT>("let x: int = 40; x + 2")?, 42);
+    assert_eq!(engine.eval::<INT>(r#"let x = "hello"; x = 42; x + 1"#)?, 43);
+
+    assert!(matches!(
+        engine
+            .compile(r#"let x: int = "hello";"#)
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::MismatchedType(expected, actual) if expected == "int" && actual == "string"
+    ));
+
+    // Assignments change the known type of a variable
+    assert!(matches!(
+        engine
+            .compile(r#"let x = 1; x = "hello"; let y: int = x;"#)
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::MismatchedType(_, _)
+    ));
+
+    // Unless it depends on which branch runs
+    assert!(engine
+        .compile(r#"let x = 1; if foo() { x = "hello"; } let y: int = x;"#)
+        .is_ok());
+
+    assert!(matches!(
+        engine
+            .compile(r#"if "hello" { 1 }"#)
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::MismatchedType(expected, _) if expected == "bool"
+    ));
+
+    // Rust functions are checked against the types of their parameters
+    assert!(matches!(
+        engine
+            .compile(r#"let x = "hello"; abs(x)"#)
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::FnArgTypesMismatch(s) if s == "abs (string)"
+    ));
+    assert!(matches!(
+        engine
+            .compile(r#"let x = true; x - 1"#)
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::FnArgTypesMismatch(s) if s == "- (bool, int)"
+    ));
+
+    // Types returned from Rust functions are known
+    assert!(matches!(
+        engine
+            .compile(r#"let x: string = 1 + 2;"#)
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::MismatchedType(_, actual) if actual == "int"
+    ));
+
+    // Calls to unknown functions are only checked at runtime
+    assert!(engine.compile("fn_not_yet_registered(1, 2)").is_ok());
+
+    // Comparisons always return `bool`
+    assert!(engine
+        .compile(r#"let x: int = type_of(42) == "hello";"#)
+        .is_err());
+
+    engine.register_fn("double", |x: INT| x * 2);
+
+    assert_eq!(engine.eval::<INT>("double(21)")?, 42);
+    assert!(matches!(
+        engine
+            .compile(r#"double("hello")"#)
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::FnArgTypesMismatch(s) if s == "double (string)"
+    ));
+
+    assert!(matches!(
+        engine
+            .compile_expression(r#"abs("hello")"#)
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::FnArgTypesMismatch(_)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_type_checker_type_names() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+
+    // `Dynamic` accepts a value of any type
+    assert_eq!(engine.eval::<INT>("let x: Dynamic = 1; x")?, 1);
+    assert_eq!(
+        engine.eval::<String>(r#"let x: Dynamic = 1; x = "hello"; x"#)?,
+        "hello"
+    );
+
+    // Unknown type names are rejected even when type checking is off
+    assert!(matches!(
+        engine
+            .compile("let x: Intt = 1;")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::UnknownType(name) if name == "Intt"
+    ));
+    assert!(engine.eval::<bool>("let r: range_inclusive = 1..=3; 3 in r")?);
+
+    engine.set_type_checking(true);
+
+    assert!(matches!(
+        engine
+            .compile("let x: Intt = 1;")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::UnknownType(name) if name == "Intt"
+    ));
+
+    assert_eq!(engine.eval::<INT>("let x: Dynamic = 1; x")?, 1);
+    assert!(engine
+        .compile(r#"let x: Dynamic = "hello"; let y: int = x;"#)
+        .is_ok());
+    assert!(engine.compile("let r: range = range(0, 10);").is_ok());
+
+    #[cfg(not(feature = "no_function"))]
+    {
+        assert!(matches!(
+            engine
+                .compile("fn f(x: Intt) { x }")
+                .expect_err("should error")
+                .error_type(),
+            ParseErrorType::UnknownType(name) if name == "Intt"
+        ));
+        assert!(matches!(
+            engine
+                .compile("fn f(x) -> Intt { x }")
+                .expect_err("should error")
+                .error_type(),
+            ParseErrorType::UnknownType(name) if name == "Intt"
+        ));
+        assert_eq!(
+            engine.eval::<INT>("fn f(x: Dynamic) -> Dynamic { x } f(42)")?,
+            42
+        );
+    }
+
+    // Custom types registered with a name are known
+    #[cfg(not(feature = "no_object"))]
+    {
+        #[derive(Clone)]
+        struct TestStruct;
+
+        engine.register_type_with_name::<TestStruct>("TestStruct");
+        engine.register_fn("new_ts", || TestStruct);
+
+        assert!(engine.compile("let ts: TestStruct = new_ts();").is_ok());
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_function"))]
+fn test_type_checker_functions() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+    engine.set_type_checking(true);
+
+    assert_eq!(
+        engine.eval::<INT>("fn add(x: int, y: int) -> int { x + y } add(40, 2)")?,
+        42
+    );
+
+    assert!(matches!(
+        engine
+            .compile(r#"fn add(x: int, y: int) -> int { x + y } add(40, "2")"#)
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::MismatchedType(expected, actual) if expected == "int" && actual == "string"
+    ));
+    let err = engine
+        .compile(r#"fn f(x: string) -> int { x }"#)
+        .expect_err("should error");
+    assert!(matches!(
+        err.error_type(),
+        ParseErrorType::MismatchedType(expected, actual) if expected == "int" && actual == "string"
+    ));
+    assert_eq!(err.position(), Position::new(1, 26));
+
+    let err = engine
+        .compile(r#"fn f(x) -> int { if x { return "hello"; } 0 }"#)
+        .expect_err("should error");
+    assert!(matches!(
+        err.error_type(),
+        ParseErrorType::MismatchedType(_, actual) if actual == "string"
+    ));
+    assert_eq!(err.position(), Position::new(1, 32));
+    assert!(matches!(
+        engine
+            .compile(r#"fn f(x: bool) { abs(x) }"#)
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::FnArgTypesMismatch(s) if s == "abs (bool)"
+    ));
+
+    // Return types of script-defined functions are known
+    assert!(matches!(
+        engine
+            .compile(r#"fn f() -> string { "hello" } let x: int = f();"#)
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::MismatchedType(_, actual) if actual == "string"
+    ));
+
+    // Parameters without type annotations accept anything
+    assert!(engine.compile(r#"fn f(x) { x } f(1); f("hello")"#).is_ok());
+
+    Ok(())
+}
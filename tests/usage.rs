@@ -0,0 +1,221 @@
+use rhai::{Engine, EvalAltResult, Scope, INT};
+
+#[test]
+fn test_usage_operations() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    let ast = engine.compile("let x = 0; while x < 10 { x += 1; } x")?;
+    let (result, usage) = engine.eval_ast_with_usage::<INT>(&mut Scope::new(), &ast);
+
+    assert_eq!(result?, 10);
+    assert!(usage.operations > 10);
+    assert_eq!(usage.call_depth, 0);
+    assert_eq!(usage.modules, 0);
+
+    // Each run starts counting afresh
+    let (_, usage2) = engine.eval_ast_with_usage::<INT>(&mut Scope::new(), &ast);
+    assert_eq!(usage, usage2);
+
+    Ok(())
+}
+
+#[test]
+fn test_usage_eval_and_consume() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+    let script = "let x = 0; while x < 10 { x += 1; } x";
+
+    let (result, usage) = engine.eval_with_usage::<INT>(&mut Scope::new(), script);
+    assert_eq!(result?, 10);
+    assert!(usage.operations > 10);
+
+    let (result, usage) = engine.consume_with_usage(&mut Scope::new(), script);
+    result?;
+    assert!(usage.operations > 10);
+
+    let ast = engine.compile(script)?;
+    let (result, usage) = engine.consume_ast_with_usage(&mut Scope::new(), &ast);
+    result?;
+    assert_eq!(
+        usage,
+        engine.eval_ast_with_usage::<INT>(&mut Scope::new(), &ast).1
+    );
+
+    // Nothing is used by a script that fails to compile
+    let (result, usage) = engine.eval_with_usage::<INT>(&mut Scope::new(), "let x = ;");
+    assert!(matches!(
+        *result.expect_err("should error"),
+        EvalAltResult::ErrorParsing(_)
+    ));
+    assert_eq!(usage.operations, 0);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_function"))]
+fn test_usage_call_fn() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    let ast = engine.compile("fn fib(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }")?;
+
+    let (result, usage) =
+        engine.call_fn_with_usage::<_, INT>(&mut Scope::new(), &ast, "fib", (6 as INT,));
+    assert_eq!(result?, 8);
+    assert!(usage.operations > 0);
+    assert_eq!(usage.call_depth, 6);
+
+    // Usage is also reported when the call fails
+    let (result, usage) =
+        engine.call_fn_with_usage::<_, String>(&mut Scope::new(), &ast, "fib", (6 as INT,));
+    assert!(matches!(
+        *result.expect_err("should error"),
+        EvalAltResult::ErrorMismatchOutputType(_, _)
+    ));
+    assert_eq!(usage.call_depth, 6);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_function"))]
+fn test_usage_call_depth() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    let ast = engine.compile(
+        r#"
+            fn fib(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }
+            fib(6)
+        "#,
+    )?;
+    let (result, usage) = engine.eval_ast_with_usage::<INT>(&mut Scope::new(), &ast);
+
+    assert_eq!(result?, 8);
+    assert_eq!(usage.call_depth, 6);
+
+    // Calls made inside 'eval' are counted as well
+    let ast = engine.compile(r#"fn foo(x) { x + 1 } eval("foo(foo(41))")"#)?;
+    let (result, usage) = engine.eval_ast_with_usage::<INT>(&mut Scope::new(), &ast);
+
+    assert_eq!(result?, 43);
+    assert_eq!(usage.call_depth, 1);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_function"))]
+#[cfg(not(feature = "unchecked"))]
+fn test_usage_eval_recursion() {
+    let mut engine = Engine::new();
+    engine.set_max_call_levels(10);
+
+    // Recursing through 'eval' no longer resets the call depth
+    assert!(matches!(
+        *engine
+            .eval::<()>(r#"fn foo() { eval("foo()") } foo()"#)
+            .expect_err("should error"),
+        EvalAltResult::ErrorInFunctionCall(_, _, _)
+    ));
+}
+
+#[test]
+#[cfg(not(feature = "no_module"))]
+fn test_usage_modules() -> Result<(), Box<EvalAltResult>> {
+    use rhai::{module_resolvers::StaticModuleResolver, Module};
+
+    let mut module = Module::new();
+    module.set_var("answer", 42 as INT);
+
+    let mut resolver = StaticModuleResolver::new();
+    resolver.insert("hello".to_string(), module);
+
+    let mut engine = Engine::new();
+    engine.set_module_resolver(Some(resolver));
+
+    let ast = engine.compile(
+        r#"
+            import "hello" as h1;
+            import "hello" as h2;
+            h1::answer + h2::answer
+        "#,
+    )?;
+    let (result, usage) = engine.eval_ast_with_usage::<INT>(&mut Scope::new(), &ast);
+
+    assert_eq!(result?, 84);
+    assert_eq!(usage.modules, 2);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "unchecked"))]
+fn test_usage_on_error() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+    engine.set_max_operations(100);
+
+    let ast = engine.compile("loop {}")?;
+    let (result, usage) = engine.eval_ast_with_usage::<()>(&mut Scope::new(), &ast);
+
+    assert!(matches!(
+        *result.expect_err("should error"),
+        EvalAltResult::ErrorTooManyOperations(_)
+    ));
+    assert_eq!(usage.operations, 101);
+
+    // Usage is also reported on a type mismatch
+    let ast = engine.compile("42")?;
+    let (result, usage) = engine.eval_ast_with_usage::<String>(&mut Scope::new(), &ast);
+
+    assert!(matches!(
+        *result.expect_err("should error"),
+        EvalAltResult::ErrorMismatchOutputType(_, _)
+    ));
+    assert!(usage.operations > 0);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_module"))]
+#[cfg(not(feature = "no_std"))]
+fn test_usage_module_scripts() -> Result<(), Box<EvalAltResult>> {
+    use rhai::module_resolvers::FileModuleResolver;
+    use std::fs;
+
+    let dir = std::env::temp_dir().join(format!("rhai-test-usage-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("busy.rhai"),
+        "let x = 0; while x < 5000 { x += 1; } export x;",
+    )
+    .unwrap();
+
+    let mut engine = Engine::new();
+    engine.set_module_resolver(Some(FileModuleResolver::new_with_path(&dir)));
+
+    let ast = engine.compile(r#"import "busy" as m; m::x"#)?;
+
+    // The work done by the imported module is included
+    let (result, usage) = engine.eval_ast_with_usage::<INT>(&mut Scope::new(), &ast);
+    assert_eq!(result?, 5000);
+    assert!(usage.operations > 5000);
+    assert_eq!(usage.modules, 1);
+
+    // ... and counts towards the limits, even when the import fails
+    #[cfg(not(feature = "unchecked"))]
+    {
+        engine.set_max_operations(100);
+
+        let (result, usage) = engine.eval_ast_with_usage::<INT>(&mut Scope::new(), &ast);
+        assert!(matches!(
+            *result.expect_err("should error"),
+            EvalAltResult::ErrorTooManyOperations(_)
+        ));
+        assert_eq!(usage.operations, 101);
+        assert_eq!(usage.modules, 1);
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    Ok(())
+}
@@ -10,7 +10,7 @@ fn test_var_scope() -> Result<(), Box<EvalAltResult>> {
     engine.eval_with_scope::<()>(&mut scope, "x = x + 1; x = x + 2;")?;
     assert_eq!(engine.eval_with_scope::<INT>(&mut scope, "x")?, 12);
 
-    scope.set_value("x", 42 as INT);
+    scope.set_value("x", 42 as INT)?;
     assert_eq!(engine.eval_with_scope::<INT>(&mut scope, "x")?, 42);
 
     engine.eval_with_scope::<()>(&mut scope, "{let x = 3}")?;
@@ -19,6 +19,22 @@ fn test_var_scope() -> Result<(), Box<EvalAltResult>> {
     Ok(())
 }
 
+#[test]
+fn test_var_scope_set_constant() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+    let mut scope = Scope::new();
+
+    scope.push_constant("x", 42 as INT);
+
+    assert!(matches!(
+        *scope.set_value("x", 0 as INT).expect_err("should error"),
+        EvalAltResult::ErrorAssignmentToConstant(name, _) if name == "x"
+    ));
+    assert_eq!(engine.eval_with_scope::<INT>(&mut scope, "x")?, 42);
+
+    Ok(())
+}
+
 #[test]
 fn test_scope_eval() -> Result<(), Box<EvalAltResult>> {
     let engine = Engine::new();
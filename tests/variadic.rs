@@ -0,0 +1,193 @@
+#![cfg(not(feature = "no_index"))]
+use rhai::{Dynamic, Engine, EvalAltResult, RegisterDynamicFn, INT};
+
+#[test]
+#[cfg(not(feature = "no_function"))]
+fn test_rest_params() -> Result<(), Box<EvalAltResult>> {
+    use rhai::ParseErrorType;
+
+    let engine = Engine::new();
+
+    let script = r#"
+        fn log(level, ..args) {
+            let s = level + ":";
+            for arg in args { s += " " + arg; }
+            s
+        }
+    "#;
+
+    assert_eq!(
+        engine.eval::<String>(&format!("{} log(\"info\")", script))?,
+        "info:"
+    );
+    assert_eq!(
+        engine.eval::<String>(&format!("{} log(\"info\", 1)", script))?,
+        "info: 1"
+    );
+    assert_eq!(
+        engine.eval::<String>(&format!("{} log(\"warn\", 1, 2, \"x\")", script))?,
+        "warn: 1 2 x"
+    );
+
+    // Rest parameters after default values
+    let script = "fn f(a, b = 10, ..rest) { a + b + len(rest) }";
+
+    assert_eq!(engine.eval::<INT>(&format!("{} f(1)", script))?, 11);
+    assert_eq!(engine.eval::<INT>(&format!("{} f(1, 2)", script))?, 3);
+    assert_eq!(engine.eval::<INT>(&format!("{} f(1, 2, 3, 4)", script))?, 5);
+    assert_eq!(engine.eval::<INT>(&format!("{} f(1, b: 5)", script))?, 6);
+
+    // A function with a fixed number of parameters takes precedence
+    assert_eq!(
+        engine.eval::<INT>("fn f(..rest) { 0 } fn f(a, b) { 1 } f(1, 2) * 10 + f(1, 2, 3)")?,
+        10
+    );
+
+    #[cfg(not(feature = "no_object"))]
+    assert_eq!(
+        engine.eval::<INT>(
+            "fn sum(s, ..rest) { for x in rest { s += x; } s } let x = 1; x.sum(2, 3)"
+        )?,
+        6
+    );
+
+    assert!(matches!(
+        engine
+            .compile("fn f(..rest, a) { a }")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::MissingToken(_, _)
+    ));
+    assert!(matches!(
+        engine
+            .compile("fn f(a, ..a) { a }")
+            .expect_err("should error")
+            .error_type(),
+        ParseErrorType::FnDuplicatedParam(_, _)
+    ));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_function"))]
+fn test_spread_args() -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new();
+
+    let script = "fn add(a, b, c) { a * 100 + b * 10 + c }";
+
+    assert_eq!(
+        engine.eval::<INT>(&format!("{} let x = [1, 2, 3]; add(..x)", script))?,
+        123
+    );
+    assert_eq!(
+        engine.eval::<INT>(&format!("{} add(1, ..[2], 3)", script))?,
+        123
+    );
+    assert_eq!(
+        engine.eval::<INT>(&format!("{} add(..[], ..[1, 2], ..[3])", script))?,
+        123
+    );
+    assert_eq!(
+        engine.eval::<INT>("fn count(..rest) { len(rest) } let x = [1, 2]; count(0, ..x, ..x)")?,
+        5
+    );
+
+    #[cfg(not(feature = "no_object"))]
+    assert_eq!(
+        engine.eval::<INT>(&format!("{} let x = 1; x.add(..[2, 3])", script))?,
+        123
+    );
+
+    assert!(matches!(
+        *engine
+            .eval::<INT>(&format!("{} add(..42)", script))
+            .expect_err("should error"),
+        EvalAltResult::ErrorArrayMismatch(_)
+    ));
+    assert!(matches!(
+        *engine
+            .eval::<INT>(&format!("{} add(..[1, 2])", script))
+            .expect_err("should error"),
+        EvalAltResult::ErrorFunctionNotFound(_, _)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_spread_args_native() -> Result<(), Box<EvalAltResult>> {
+    let mut engine = Engine::new();
+
+    engine.register_dynamic_fn("pick", |a: INT, b: INT| -> Dynamic {
+        if a > b {
+            a.into()
+        } else {
+            b.into()
+        }
+    });
+
+    assert_eq!(engine.eval::<INT>("pick(..[3, 5])")?, 5);
+    assert_eq!(engine.eval::<INT>("let x = [7]; pick(..x, 2)")?, 7);
+    assert_eq!(engine.eval::<INT>("abs(..[-42])")?, 42);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_function"))]
+#[cfg(not(feature = "no_module"))]
+fn test_rest_params_module() -> Result<(), Box<EvalAltResult>> {
+    use rhai::{Module, Scope};
+
+    let engine = Engine::new();
+
+    let ast = engine.compile("fn count(a, ..rest) { 1 + len(rest) }")?;
+    let module = Module::eval_ast_as_new(Scope::new(), &ast, &engine)?;
+
+    let mut scope = Scope::new();
+    scope.push_module("m", module);
+
+    assert_eq!(engine.eval_with_scope::<INT>(&mut scope, "m::count(1)")?, 1);
+    assert_eq!(
+        engine.eval_with_scope::<INT>(&mut scope, "m::count(1, 2, 3, 4)")?,
+        4
+    );
+    assert_eq!(
+        engine.eval_with_scope::<INT>(&mut scope, "m::count(..[1, 2, 3])")?,
+        3
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "no_function"))]
+fn test_rest_params_reachable() -> Result<(), Box<EvalAltResult>> {
+    use rhai::{Scope, AST};
+
+    let engine = Engine::new();
+
+    let mut ast = engine.compile(
+        r"
+            fn count(..rest) { len(rest) }
+            fn add(a, b) { a + b }
+            fn unused() { 0 }
+            count(1, 2, 3) + add(..[1, 2])
+        ",
+    )?;
+
+    // Spread arguments keep all functions
+    ast.retain_reachable_functions(&[]);
+    assert_eq!(engine.eval_ast::<INT>(&ast)?, 6);
+
+    let bytes = ast.to_bytes();
+    let ast2 = AST::from_bytes(&bytes)?;
+    assert_eq!(engine.eval_ast::<INT>(&ast2)?, 6);
+    assert_eq!(ast2.to_bytes(), bytes);
+
+    let r: INT = engine.call_fn(&mut Scope::new(), &ast, "count", (1 as INT, 2 as INT))?;
+    assert_eq!(r, 2);
+
+    Ok(())
+}